@@ -0,0 +1,48 @@
+//! Encryption throughput for each cipher mode: `cargo bench --bench cipher`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use universal_primes::pmpt::{encrypt_with_params, CipherParams, DynamicSBox, KeyPair, MaskMode};
+
+fn cipher_modes(c: &mut Criterion) {
+    let mut rng = ChaCha20Rng::seed_from_u64(1);
+    let keys = KeyPair::generate(256, &mut rng);
+    let sbox = DynamicSBox::new(&mut rng);
+    let plaintext = "The quick brown fox jumps over the lazy dog";
+
+    let modes = [
+        ("single_round", CipherParams::single_round()),
+        ("gaussian_4_rounds", CipherParams::default()),
+        (
+            "keystream_4_rounds",
+            CipherParams {
+                mask: MaskMode::Keystream,
+                ..CipherParams::default()
+            },
+        ),
+    ];
+    let mut group = c.benchmark_group("encrypt");
+    for (name, params) in modes {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                encrypt_with_params(
+                    plaintext,
+                    &keys.public_key,
+                    &keys.private_key,
+                    &sbox,
+                    keys.pad_length,
+                    &keys.modulus,
+                    params,
+                    &mut rng,
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cipher_modes);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Records the git commit being built, for search manifests. Builds outside a checkout (or
+//! without git) simply leave it unset.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    if let Ok(output) = Command::new("git").args(["rev-parse", "HEAD"]).output() {
+        if output.status.success() {
+            let hash = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=UNIVERSAL_PRIMES_GIT_HASH={}", hash.trim());
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Optional number-theoretic columns for search hits.
+//!
+//! `qr_primes` lists the small primes q that are quadratic residues mod |N|, from the Jacobi
+//! symbol (q/|N|), which for prime |N| is the Legendre symbol. `cf_period` is the period of
+//! the continued fraction of √|N|, left empty above 64 bits or past `CF_PERIOD_LIMIT` steps
+//! since the period can grow like √N.
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Roots;
+use num_traits::ToPrimitive;
+
+use crate::modular::jacobi;
+
+/// Small primes tested as quadratic residues.
+pub const QR_PRIMES: [u64; 10] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29];
+
+/// Continued-fraction steps tried before giving up on the period.
+pub const CF_PERIOD_LIMIT: u64 = 1 << 20;
+
+/// Header fields appended to `search::CSV_HEADER` when annotating.
+pub const ANNOTATION_HEADER: &str = "qr_primes,cf_period";
+
+/// The primes in `primes` that are nonzero quadratic residues mod odd `n` (empty for even n).
+pub fn quadratic_residues(n: &BigUint, primes: &[u64]) -> Vec<u64> {
+    if !n.bit(0) || n <= &BigUint::from(1u32) {
+        return Vec::new();
+    }
+    primes
+        .iter()
+        .copied()
+        .filter(|&q| jacobi(&BigInt::from(q), n) == 1)
+        .collect()
+}
+
+/// Period of the continued fraction of √n: 0 for perfect squares, `None` when n exceeds
+/// 64 bits or the period is longer than `limit`.
+pub fn sqrt_cf_period(n: &BigUint, limit: u64) -> Option<u64> {
+    let n = n.to_u64()? as u128;
+    let a0 = n.sqrt();
+    if a0 * a0 == n {
+        return Some(0);
+    }
+    // √n = [a0; a1, a2, ...] via m_{k+1} = d_k a_k - m_k, d_{k+1} = (n - m_{k+1}²) / d_k;
+    // the period ends at the first a_k = 2a0
+    let (mut m, mut d, mut a) = (0u128, 1u128, a0);
+    for period in 1..=limit {
+        m = d * a - m;
+        d = (n - m * m) / d;
+        a = (a0 + m) / d;
+        if a == 2 * a0 {
+            return Some(period);
+        }
+    }
+    None
+}
+
+/// The annotation fields for |N|, formatted like the rest of a results row.
+pub fn annotation_fields(n: &BigUint) -> String {
+    let period = sqrt_cf_period(n, CF_PERIOD_LIMIT).map_or(String::new(), |p| p.to_string());
+    format!("{:?},{}", quadratic_residues(n, &QR_PRIMES), period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotations() {
+        // √7 = [2; 1, 1, 1, 4], √13 has period 5, √46 period 12
+        let period = |n: u32| sqrt_cf_period(&BigUint::from(n), CF_PERIOD_LIMIT);
+        assert_eq!(period(7), Some(4));
+        assert_eq!(period(13), Some(5));
+        assert_eq!(period(46), Some(12));
+        assert_eq!(period(49), Some(0));
+        assert_eq!(sqrt_cf_period(&BigUint::from(94u32), 3), None);
+
+        // Mod 23 the residues are 1, 2, 3, 4, 6, 8, 9, 12, 13, 16, 18
+        assert_eq!(quadratic_residues(&BigUint::from(23u32), &QR_PRIMES), vec![2, 3, 13, 29]);
+        assert_eq!(annotation_fields(&BigUint::from(23u32)), "[2, 3, 13, 29],4");
+    }
+}
@@ -0,0 +1,319 @@
+//! Binary floating point with arbitrary exponent and configurable precision.
+//!
+//! Hits of a few hundred bits no longer fit an f64, but the analytic code only needs their
+//! leading bits and magnitude. A `BigFloat` keeps a mantissa of at most `precision` bits
+//! and an unbounded binary exponent, and hands back f64 parts (or logarithms) that never
+//! overflow. `approx_log`, `log2_bits_exact` and `nth_root` cover the common cases directly
+//! on a `BigUint`.
+
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Div, Mul};
+
+/// Mantissa bits kept by `BigFloat::from`.
+pub const DEFAULT_PRECISION: u64 = 128;
+
+/// mantissa · 2^exponent, with the mantissa rounded to at most `precision` bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigFloat {
+    mantissa: BigUint,
+    exponent: i64,
+    precision: u64,
+}
+
+impl BigFloat {
+    /// `n` rounded to nearest (ties away from zero) at `precision` bits, at least 1.
+    pub fn from_biguint(n: &BigUint, precision: u64) -> Self {
+        BigFloat::normalized(n.clone(), 0, precision.max(1))
+    }
+
+    /// Round mantissa · 2^exponent to `precision` bits and strip trailing zero bits, so that
+    /// equal values have equal representations.
+    fn normalized(mut mantissa: BigUint, mut exponent: i64, precision: u64) -> Self {
+        let excess = mantissa.bits().saturating_sub(precision);
+        if excess > 0 {
+            let round_up = mantissa.bit(excess - 1);
+            mantissa >>= excess;
+            exponent += excess as i64;
+            if round_up {
+                mantissa += 1u32;
+                if mantissa.bits() > precision {
+                    mantissa >>= 1u32;
+                    exponent += 1;
+                }
+            }
+        }
+        match mantissa.trailing_zeros() {
+            Some(zeros) => {
+                mantissa >>= zeros;
+                exponent += zeros as i64;
+            }
+            None => exponent = 0,
+        }
+        BigFloat { mantissa, exponent, precision }
+    }
+
+    pub fn mantissa(&self) -> &BigUint {
+        &self.mantissa
+    }
+
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn precision(&self) -> u64 {
+        self.precision
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    /// (m, e) with the value equal to m·2^e and m in [0.5, 1), or (0, 0) for zero: the
+    /// leading 53 bits of the mantissa with the rest of the magnitude in the exponent.
+    pub fn to_f64_parts(&self) -> (f64, i64) {
+        let bits = self.mantissa.bits();
+        if bits == 0 {
+            return (0.0, 0);
+        }
+        let shift = bits.saturating_sub(64);
+        let top = (&self.mantissa >> shift).to_u64().expect("at most 64 bits");
+        // top < 2^64, so top / 2^(bits - shift) lies in [0.5, 1)
+        let m = top as f64 / 2f64.powi((bits - shift) as i32);
+        (m, self.exponent + bits as i64)
+    }
+
+    /// The nearest f64, infinite beyond f64::MAX and zero below the smallest subnormal.
+    pub fn to_f64(&self) -> f64 {
+        let (m, e) = self.to_f64_parts();
+        match e {
+            _ if m == 0.0 => 0.0,
+            e if e > 1024 => f64::INFINITY,
+            e if e < -1075 => 0.0,
+            // Two steps so that neither power of two overflows on its own
+            e => m * 2f64.powi((e / 2) as i32) * 2f64.powi((e - e / 2) as i32),
+        }
+    }
+
+    /// log2 of the value, -∞ for zero.
+    pub fn log2(&self) -> f64 {
+        let (m, e) = self.to_f64_parts();
+        m.log2() + e as f64
+    }
+
+    /// Natural logarithm of the value, -∞ for zero.
+    pub fn ln(&self) -> f64 {
+        self.log2() * std::f64::consts::LN_2
+    }
+
+    /// The value rounded to an integer.
+    pub fn to_biguint(&self) -> BigUint {
+        match self.exponent {
+            e if e >= 0 => &self.mantissa << e as u64,
+            e => {
+                let shift = e.unsigned_abs();
+                let half = self.mantissa.bit(shift - 1);
+                (&self.mantissa >> shift) + u32::from(half)
+            }
+        }
+    }
+}
+
+/// ln n to double precision for any n ≥ 1 (-∞ for 0), however many bits n has.
+pub fn approx_log(n: &BigUint) -> f64 {
+    BigFloat::from_biguint(n, 64).ln()
+}
+
+/// ⌊log2 n⌋ computed from the bit length, so exact at any size; `None` for 0.
+pub fn log2_bits_exact(n: &BigUint) -> Option<u64> {
+    n.bits().checked_sub(1)
+}
+
+/// ⌊n^(1/k)⌋.
+///
+/// # Panics
+/// If `k` is 0.
+pub fn nth_root(n: &BigUint, k: u32) -> BigUint {
+    assert!(k > 0, "zeroth root");
+    n.nth_root(k)
+}
+
+impl From<&BigUint> for BigFloat {
+    fn from(n: &BigUint) -> Self {
+        BigFloat::from_biguint(n, DEFAULT_PRECISION)
+    }
+}
+
+impl Mul for &BigFloat {
+    type Output = BigFloat;
+
+    /// Product rounded to the smaller of the two precisions.
+    fn mul(self, other: &BigFloat) -> BigFloat {
+        BigFloat::normalized(
+            &self.mantissa * &other.mantissa,
+            self.exponent + other.exponent,
+            self.precision.min(other.precision),
+        )
+    }
+}
+
+impl Div for &BigFloat {
+    type Output = BigFloat;
+
+    /// Quotient rounded to the smaller of the two precisions.
+    ///
+    /// # Panics
+    /// If `other` is zero.
+    fn div(self, other: &BigFloat) -> BigFloat {
+        assert!(!other.is_zero(), "BigFloat division by zero");
+        let precision = self.precision.min(other.precision);
+        // Two guard bits beyond the precision so that rounding the quotient is exact enough
+        let shift = (precision + 2 + other.mantissa.bits()).saturating_sub(self.mantissa.bits());
+        let quotient = (&self.mantissa << shift) / &other.mantissa;
+        BigFloat::normalized(quotient, self.exponent - other.exponent - shift as i64, precision)
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.is_zero(), other.is_zero()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            (false, false) => {}
+        }
+        let magnitude = |x: &BigFloat| x.exponent + x.mantissa.bits() as i64;
+        magnitude(self).cmp(&magnitude(other)).then_with(|| {
+            // Same magnitude: align the mantissas and compare them
+            let shift = self.exponent - other.exponent;
+            if shift >= 0 {
+                (&self.mantissa << shift as u64).cmp(&other.mantissa)
+            } else {
+                self.mantissa.cmp(&(&other.mantissa << shift.unsigned_abs()))
+            }
+        })
+    }
+}
+
+/// Scientific notation with `{:.N}` digits after the point (default 6), e.g. `1.234568e301`.
+impl fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0e0");
+        }
+        let digits = f.precision().unwrap_or(6) as i64 + 1;
+        let ten = BigUint::from(10u32);
+        // Decimal exponent from the logarithm, corrected below if it is off by one
+        let mut k = (self.log2() * std::f64::consts::LOG10_2).floor() as i64;
+        loop {
+            // round(value · 10^(digits - 1 - k)) as a ratio of integers
+            let scale = digits - 1 - k;
+            let mut numerator = self.mantissa.clone();
+            let mut denominator = BigUint::one();
+            if self.exponent >= 0 {
+                numerator <<= self.exponent as u64;
+            } else {
+                denominator <<= self.exponent.unsigned_abs();
+            }
+            if scale >= 0 {
+                numerator *= ten.pow(scale as u32);
+            } else {
+                denominator *= ten.pow(scale.unsigned_abs() as u32);
+            }
+            let scaled = (numerator * 2u32 + &denominator) / (denominator * 2u32);
+            let text = scaled.to_string();
+            match (text.len() as i64).cmp(&digits) {
+                Ordering::Greater => k += 1,
+                Ordering::Less => k -= 1,
+                Ordering::Equal => {
+                    let (lead, rest) = text.split_at(1);
+                    return if rest.is_empty() {
+                        write!(f, "{}e{}", lead, k)
+                    } else {
+                        write!(f, "{}.{}e{}", lead, rest, k)
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion() {
+        for n in [0u64, 1, 2, 3, 1000, (1 << 53) + 1, u64::MAX] {
+            let big = BigUint::from(n);
+            assert_eq!(BigFloat::from(&big).to_f64(), n as f64, "{}", n);
+            assert_eq!(BigFloat::from(&big).to_biguint(), big);
+        }
+        // Values past f64::MAX keep their leading bits and magnitude
+        let n = (BigUint::one() << 1100u32) * 3u32 + 12345u32;
+        let float = BigFloat::from_biguint(&n, 64);
+        assert_eq!(float.precision(), 64);
+        assert_eq!(float.to_f64(), f64::INFINITY);
+        assert_eq!((&float / &float).to_f64(), 1.0);
+        assert_eq!(float.to_f64_parts(), (0.75, 1102));
+        assert!((float.log2() - (1100.0 + 3f64.log2())).abs() < 1e-12);
+        assert!((float.ln() - float.log2() * std::f64::consts::LN_2).abs() < 1e-12);
+        assert_eq!(BigFloat::from(&n).to_biguint(), n >> 972u32 << 972u32);
+
+        // Rounding to nearest, carrying into a new bit
+        assert_eq!(BigFloat::from_biguint(&BigUint::from(0b1011u32), 3).to_biguint(), 12u32.into());
+        assert_eq!(BigFloat::from_biguint(&BigUint::from(0b1111u32), 3).to_biguint(), 16u32.into());
+        assert_eq!(BigFloat::from_biguint(&BigUint::from(0b1001u32), 3).to_biguint(), 10u32.into());
+    }
+
+    #[test]
+    fn test_logs_and_roots() {
+        assert_eq!(approx_log(&BigUint::zero()), f64::NEG_INFINITY);
+        assert_eq!(approx_log(&BigUint::one()), 0.0);
+        assert!((approx_log(&BigUint::from(1_000_000u32)) - 1e6f64.ln()).abs() < 1e-12);
+        let huge = BigUint::from(10u32).pow(5000);
+        assert!((approx_log(&huge) / 5000.0 - 10f64.ln()).abs() < 1e-14);
+
+        assert_eq!(log2_bits_exact(&BigUint::zero()), None);
+        assert_eq!(log2_bits_exact(&BigUint::one()), Some(0));
+        assert_eq!(log2_bits_exact(&BigUint::from(1023u32)), Some(9));
+        assert_eq!(log2_bits_exact(&BigUint::from(1024u32)), Some(10));
+        // 2^4000 - 1 rounds to 4000.0 as a float but its floor log2 is 3999
+        let below = (BigUint::one() << 4000u32) - 1u32;
+        assert_eq!(log2_bits_exact(&below), Some(3999));
+
+        assert_eq!(nth_root(&BigUint::from(1000u32), 3), BigUint::from(10u32));
+        assert_eq!(nth_root(&BigUint::from(999u32), 3), BigUint::from(9u32));
+        assert_eq!(nth_root(&huge, 1000), BigUint::from(100_000u32));
+        assert_eq!(nth_root(&(&huge - 1u32), 1000), BigUint::from(99_999u32));
+        assert_eq!(nth_root(&BigUint::from(7u32), 1), BigUint::from(7u32));
+    }
+
+    #[test]
+    fn test_arithmetic_and_display() {
+        let three = BigFloat::from(&BigUint::from(3u32));
+        let seven = BigFloat::from(&BigUint::from(7u32));
+        let quotient = &three / &seven;
+        assert!((quotient.to_f64() - 3.0 / 7.0).abs() < 1e-16);
+        assert_eq!((&quotient * &seven).to_biguint(), BigUint::from(3u32));
+        assert!(quotient < three && three < seven);
+        assert_eq!(three.cmp(&BigFloat::from_biguint(&BigUint::from(3u32), 8)), Ordering::Equal);
+
+        assert_eq!(three.to_string(), "3.000000e0");
+        assert_eq!(format!("{:.3}", quotient), "4.286e-1");
+        assert_eq!(format!("{:.0}", seven), "7e0");
+        assert_eq!(format!("{:.2}", BigFloat::from(&BigUint::from(999_999u32))), "1.00e6");
+        let huge = BigUint::from(10u32).pow(400) * 31_415_927u32;
+        assert_eq!(format!("{:.4}", BigFloat::from(&huge)), "3.1416e407");
+        assert_eq!(BigFloat::from(&BigUint::zero()).to_string(), "0e0");
+    }
+}
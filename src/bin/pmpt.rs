@@ -0,0 +1,140 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use tracing::Level;
+
+use std::io;
+
+use universal_primes::logging;
+use universal_primes::pmpt::*;
+use universal_primes::primality::PrimalityConfig;
+use universal_primes::prime_shamir::*;
+
+/// --- Main Function ---
+fn main() {
+    // Silent unless RUST_LOG asks for more
+    logging::init(Level::WARN, false);
+    let mut rng = ChaCha20Rng::from_entropy();
+
+    // Generate a large random prime
+    let secret_bits = 1024;
+    let secret = generate_large_prime(secret_bits, &mut rng);
+    println!("N: {}", secret);
+    let modulus_bits = secret_bits * 2;
+    let modulus = generate_large_prime(modulus_bits, &mut rng);
+    let shares_count = 6;
+    let threshold = 3;
+    let shares = shamir_split_shares(&secret, threshold, shares_count, &modulus);
+    // Calculate padding length based on modulus size
+    let pad_length = pad_length_for(&modulus); // Adjusted padding length
+    println!("Padding Length: {} bytes", pad_length);
+
+    // Create SpherePoints using DLP keys
+    let private_point = SpherePoint {
+        x: shares[0].1.clone(),
+        y: shares[1].1.clone(),
+        z: shares[2].1.clone(),
+    };
+    let public_point = SpherePoint {
+        x: shares[3].1.clone(),
+        y: shares[4].1.clone(),
+        z: shares[5].1.clone(),
+    };
+    verify_share_primality(&shares, &PrimalityConfig::default());
+    println!("Private Point: {:?}", private_point);
+    println!("Public Point: {:?}", public_point);
+    let ring_metadata = RingMetadata::generate(&public_point, &private_point, &modulus);
+    let ring_valid = ring_metadata.validate(&public_point, &private_point, &modulus);
+    let reconstructed_secret = shamir_reconstruct(&shares[..threshold], &modulus, &secret, threshold)
+        .expect("Shares do not determine the secret.");
+    println!("Public N Reconstucted: {}", reconstructed_secret);
+    if ring_valid {
+        println!("Ring metadata validation successful (key generation step).");
+    } else {
+        panic!("Ring metadata validation failed (key generation step).");
+    }
+
+    // Generate S-Box
+    let mut rng_sbox = ChaCha20Rng::from_entropy();
+    let sbox = DynamicSBox::new(&mut rng_sbox);
+
+    // --- PMPT-HMAC Integration ---
+    let pmpt_hmac = PmptHmac::new(
+        public_point.clone(),
+        private_point.clone(),
+        sbox.clone(),
+        pad_length,
+        modulus.clone(),
+    );
+
+    let data = b"Example data for PMPT-HMAC";
+    println!("Signing data: {:?}", String::from_utf8_lossy(data));
+
+    // Sign the data
+    let signature = pmpt_hmac.sign(data).expect("Signing failed");
+    println!("Generated Signature: {:?}", signature);
+
+    // Verify the signature
+    let is_valid = pmpt_hmac.verify(data, &signature).expect("Verification failed");
+    println!("Verification Result: {}", is_valid);
+
+    // --- PMPT Signature (verifiable with the public key alone) ---
+    let group = SignatureGroup::generate(512, &PrimalityConfig::default(), &mut rng);
+    let signing_key = SigningKey::new(group, &public_point, &private_point, &modulus);
+    let signature = signing_key.sign(data);
+    println!("Generated Signature: {:?}", signature);
+    let verifying_key = signing_key.verifying_key().clone();
+    println!("Public-key Verification Result: {}", verifying_key.verify(data, &signature));
+    // --- PMPT Encryption and Decryption ---
+    let mut plaintext = String::new();
+
+    println!("Enter your plaintext: ");
+
+    // Read input from the user
+    io::stdin()
+        .read_line(&mut plaintext)
+        .expect("Failed to read input");
+
+    // Remove the trailing newline from the input
+    let plaintext = plaintext.trim();
+
+    // Print the input back to the user
+    println!("Original Plaintext: {}", plaintext);
+
+    let ciphertext = encrypt(
+        plaintext,
+        &public_point,
+        &private_point,
+        &sbox,
+        pad_length,
+        &modulus,
+        &mut rng,
+    )
+    .expect("Encryption failed");
+
+    println!("Ciphertext: {:?}", ciphertext);
+    // Perform ring check on the ciphertext
+    let substituted_point = SpherePoint {
+        x: ciphertext.x_s.clone(),
+        y: ciphertext.y_s.clone(),
+        z: ciphertext.z_s.clone(),
+    };
+    let ring_metadata = RingMetadata::generate(&public_point, &substituted_point, &modulus);
+    let ring_valid = ring_metadata.validate(&public_point, &substituted_point, &modulus);
+    if ring_valid {
+        println!("Ring metadata validation successful (encryption step).");
+    } else {
+        panic!("Ring metadata validation failed (encryption step).");
+    }
+    let decrypted_plaintext = decrypt(
+        &ciphertext,
+        &public_point,
+        &private_point,
+        &sbox,
+        pad_length,
+        &modulus,
+    )
+    .expect("Decryption failed");
+    println!("Decrypted Plaintext: {}", decrypted_plaintext);
+    assert_eq!(plaintext, decrypted_plaintext);
+    println!("Encryption and decryption are consistent.");
+}
@@ -0,0 +1,35 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use tracing::Level;
+
+use universal_primes::logging;
+use universal_primes::primality::PrimalityConfig;
+use universal_primes::prime_shamir::*;
+
+fn main() {
+    // Silent unless RUST_LOG asks for more
+    logging::init(Level::WARN, false);
+    let mut rng = ChaCha20Rng::from_entropy();
+
+    let secret_bits = 512;
+    let secret = generate_large_prime(secret_bits, &mut rng);
+    let modulus_bits = secret_bits * 2;
+    let modulus = generate_large_prime(modulus_bits, &mut rng);
+    let threshold = 6;
+    let shares_count = 8;
+    let shares = shamir_split_shares(&secret, threshold, shares_count, &modulus);
+
+    println!("Original Secret (Prime): {}", secret);
+    println!("Shares:");
+    for (x, y) in &shares {
+        println!("x: {}, y: {}", x, y);
+    }
+    verify_share_primality(&shares, &PrimalityConfig::default());
+
+    let reconstructed_secret = shamir_reconstruct(&shares[..threshold], &modulus, &secret, threshold)
+        .expect("Shares do not determine the secret.");
+    println!("Reconstructed Secret: {}", reconstructed_secret);
+    assert_eq!(secret, reconstructed_secret);
+    println!("Reconstruction successful. The secret matches exactly.");
+}
+
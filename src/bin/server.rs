@@ -0,0 +1,134 @@
+//! REST service exposing primality, classification, and Shamir splitting over JSON.
+//!
+//! This binary owns the socket and the worker threads; the handlers and the per-client rate
+//! limiter are in `universal_primes::service`.
+
+use clap::Parser;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info, Level};
+
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+
+use universal_primes::classify::DEFAULT_ROUNDS;
+use universal_primes::logging;
+use universal_primes::service::{handle, ErrorResponse, HandlerResult, Limits, RateLimiter};
+
+/// Command-line arguments for the primality service.
+#[derive(Parser, Debug)]
+#[command(version, about = "Serve primality and classification checks over HTTP")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Worker threads handling requests
+    #[arg(long, default_value_t = 4)]
+    threads: usize,
+    /// Default Miller-Rabin rounds when a request does not specify any
+    #[arg(long, default_value_t = DEFAULT_ROUNDS)]
+    rounds: usize,
+    /// Upper bound on the rounds a request may ask for
+    #[arg(long, default_value_t = 128)]
+    max_rounds: usize,
+    /// Largest accepted input, in bits
+    #[arg(long, default_value_t = 8192)]
+    max_bits: u64,
+    /// Sustained requests per second allowed per client
+    #[arg(long, default_value_t = 10.0)]
+    rate: f64,
+    /// Requests a client may burst above the sustained rate
+    #[arg(long, default_value_t = 20.0)]
+    burst: f64,
+    /// Least severe log events to print to stderr: trace, debug, info, warn or error
+    #[arg(long, default_value = "info")]
+    log_level: Level,
+    /// Print log events as JSON lines
+    #[arg(long)]
+    log_json: bool,
+}
+
+impl Args {
+    fn limits(&self) -> Limits {
+        Limits {
+            rounds: self.rounds,
+            max_rounds: self.max_rounds,
+            max_bits: self.max_bits,
+        }
+    }
+}
+
+fn route(request: &mut Request, limits: &Limits) -> HandlerResult {
+    if *request.method() != Method::Post {
+        return Err((405, "only POST is supported".to_string()));
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(1 << 20)
+        .read_to_string(&mut body)
+        .map_err(|_| (400, "request body must be UTF-8".to_string()))?;
+
+    handle(request.url(), &body, limits)
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    let header = Header::from_bytes("Content-Type", "application/json").expect("static header");
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        error!("failed to send response: {}", e);
+    }
+}
+
+fn serve(server: &Server, limiter: &RateLimiter, limits: &Limits) {
+    loop {
+        let mut request = match server.recv() {
+            Ok(request) => request,
+            Err(e) => {
+                error!("failed to receive request: {}", e);
+                continue;
+            }
+        };
+
+        if let Some(client) = request.remote_addr().map(|a| a.ip()) {
+            if !limiter.allow(client) {
+                let error = ErrorResponse {
+                    error: "rate limit exceeded".to_string(),
+                };
+                respond(request, 429, serde_json::to_string(&error).unwrap_or_default());
+                continue;
+            }
+        }
+
+        match route(&mut request, limits) {
+            Ok(body) => respond(request, 200, body),
+            Err((status, error)) => {
+                let body = serde_json::to_string(&ErrorResponse { error }).unwrap_or_default();
+                respond(request, status, body);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    logging::init(args.log_level, args.log_json);
+    let server = Arc::new(Server::http(&args.addr).expect("Failed to bind server address"));
+    let limiter = Arc::new(RateLimiter::new(args.rate, args.burst));
+    info!("listening on http://{}", args.addr);
+
+    let workers: Vec<_> = (0..args.threads.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let limiter = Arc::clone(&limiter);
+            let limits = args.limits();
+            thread::spawn(move || serve(&server, &limiter, &limits))
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().expect("Server worker panicked");
+    }
+}
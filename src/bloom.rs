@@ -0,0 +1,204 @@
+//! Bloom filters over sets of hits too large to hold exactly.
+//!
+//! A value's bit positions come from double hashing a 128-bit SHAKE256 digest of its
+//! little-endian bytes, so filters are stable across runs and platforms and can be saved. A
+//! saved filter is the 8-byte magic `UPBLOOM2`, then the bit count and insertion count as
+//! little-endian u64s, the hash count as a little-endian u32, and the bit words.
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::domains;
+use crate::pmpt::shake256_parts;
+use crate::reader::{MalformedInput, StrictReader};
+
+const MAGIC: &[u8; 8] = b"UPBLOOM2";
+
+#[derive(Error, Debug)]
+pub enum BloomError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("not a Bloom filter file")]
+    BadMagic,
+    #[error("Bloom filter file is corrupt: {0}")]
+    Malformed(#[from] MalformedInput),
+}
+
+/// A Bloom filter of `BigUint`s: `contains` never misses an inserted value, and reports a
+/// value that was never inserted with a probability set when the filter is sized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    words: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    inserted: u64,
+}
+
+impl BloomFilter {
+    /// A filter with `num_bits` bits (at least one) probed `num_hashes` times per value.
+    pub fn new(num_bits: u64, num_hashes: u32) -> Self {
+        let num_bits = num_bits.max(1);
+        BloomFilter {
+            words: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes: num_hashes.max(1),
+            inserted: 0,
+        }
+    }
+
+    /// The smallest filter expected to hold `expected` values with the false-positive rate
+    /// `fp_rate`, which must be strictly between 0 and 1.
+    pub fn with_rate(expected: u64, fp_rate: f64) -> Self {
+        assert!(fp_rate > 0.0 && fp_rate < 1.0, "false-positive rate must be in (0, 1)");
+        let ln2 = std::f64::consts::LN_2;
+        let expected = expected.max(1) as f64;
+        let num_bits = (-expected * fp_rate.ln() / (ln2 * ln2)).ceil();
+        let num_hashes = (num_bits / expected * ln2).round().max(1.0);
+        BloomFilter::new(num_bits as u64, num_hashes as u32)
+    }
+
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Insertions so far, counting repeats.
+    pub fn len(&self) -> u64 {
+        self.inserted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0
+    }
+
+    /// The bit positions of `n`: h1 + i·h2 mod m for the two halves of its digest.
+    fn positions(&self, n: &BigUint) -> impl Iterator<Item = u64> {
+        let mut digest = [0u8; 16];
+        shake256_parts(domains::BLOOM, &[&n.to_bytes_le()], &mut digest);
+        let h1 = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        // An odd step never cycles early when the bit count is a power of two
+        let h2 = u64::from_le_bytes(digest[8..].try_into().unwrap()) | 1;
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    /// Add `n`, returning whether it may have been present already.
+    pub fn insert(&mut self, n: &BigUint) -> bool {
+        let mut present = true;
+        for bit in self.positions(n).collect::<Vec<_>>() {
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            present &= self.words[word] & mask != 0;
+            self.words[word] |= mask;
+        }
+        self.inserted += 1;
+        present
+    }
+
+    /// Whether `n` may have been inserted; `false` means it certainly was not.
+    pub fn contains(&self, n: &BigUint) -> bool {
+        self.positions(n).all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The false-positive rate expected at the current fill, (1 - e^(-kn/m))^k.
+    pub fn false_positive_rate(&self) -> f64 {
+        let k = self.num_hashes as f64;
+        (1.0 - (-k * self.inserted as f64 / self.num_bits as f64).exp()).powf(k)
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&self.num_bits.to_le_bytes())?;
+        out.write_all(&self.inserted.to_le_bytes())?;
+        out.write_all(&self.num_hashes.to_le_bytes())?;
+        for word in &self.words {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> Result<Self, BloomError> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic).map_err(|_| BloomError::BadMagic)?;
+        if &magic != MAGIC {
+            return Err(BloomError::BadMagic);
+        }
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let mut reader = StrictReader::new(&bytes);
+        let num_bits = reader.u64_le("bit count")?;
+        let inserted = reader.u64_le("insertion count")?;
+        let num_hashes = reader.u32_le("hash count")?;
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(MalformedInput::Invalid("filter size").into());
+        }
+        let words = reader.length("bit words", num_bits.div_ceil(64), reader.remaining() / 8)?;
+        let words = reader.take("bit words", 8 * words)?;
+        reader.finish()?;
+        let words = words.chunks_exact(8).map(|w| u64::from_le_bytes(w.try_into().unwrap()));
+        Ok(BloomFilter { words: words.collect(), num_bits, num_hashes, inserted })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BloomError> {
+        BloomFilter::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+impl<'a> Extend<&'a BigUint> for BloomFilter {
+    fn extend<I: IntoIterator<Item = &'a BigUint>>(&mut self, values: I) {
+        for n in values {
+            self.insert(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_rate(2000, 0.01);
+        assert_eq!((filter.num_bits(), filter.num_hashes()), (19171, 7));
+        let inserted: Vec<BigUint> =
+            (0..2000u32).map(|i| BigUint::from(i) * 2u32 + 1u32).collect();
+        filter.extend(&inserted);
+        assert_eq!(filter.len(), 2000);
+        assert!(inserted.iter().all(|n| filter.contains(n)));
+        assert!((filter.false_positive_rate() - 0.01).abs() < 0.001);
+
+        // Even numbers were never inserted; about 1% of them show up anyway
+        let false_positives =
+            (0..20_000u32).filter(|&i| filter.contains(&(BigUint::from(i) * 2u32))).count();
+        assert!((100..300).contains(&false_positives), "{}", false_positives);
+        assert!(filter.insert(&BigUint::from(3u32)));
+
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 28 + 300 * 8);
+        assert_eq!(BloomFilter::read_from(&mut bytes.as_slice()).unwrap(), filter);
+        assert!(matches!(
+            BloomFilter::read_from(&mut &bytes[..bytes.len() - 1]),
+            Err(BloomError::Malformed(MalformedInput::TooLong { field: "bit words", .. }))
+        ));
+        let pool = &mut &b"UPPOOL01"[..];
+        assert!(matches!(BloomFilter::read_from(pool), Err(BloomError::BadMagic)));
+        assert!(matches!(
+            BloomFilter::read_from(&mut &bytes[..20]),
+            Err(BloomError::Malformed(MalformedInput::Truncated { missing: 4, .. }))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Chebyshev's θ and ψ, and the standard prime sums restricted to the hits.
+//!
+//! For the primes θ(x) = Σ ln p ~ x, Σ 1/p ~ ln ln x + M and Σ ln p / p ~ ln x (Mertens).
+//! Computing the same sums over the hits alone shows how thin the universal primes are
+//! among all primes up to the same bound.
+
+use num_bigint::BigUint;
+use serde::Serialize;
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::bigfloat::approx_log;
+
+/// θ(x) = Σ ln p over the primes p ≤ x.
+pub fn chebyshev_theta(x: u64) -> f64 {
+    primal::Primes::all().take_while(|&p| p as u64 <= x).map(|p| (p as f64).ln()).sum()
+}
+
+/// ψ(x) = Σ ln p over the prime powers p^k ≤ x.
+pub fn chebyshev_psi(x: u64) -> f64 {
+    primal::Primes::all()
+        .take_while(|&p| p as u64 <= x)
+        .map(|p| {
+            let p = p as u64;
+            let (mut power, mut k) = (Some(p), 0u32);
+            while let Some(q) = power.filter(|&q| q <= x) {
+                k += 1;
+                power = q.checked_mul(p);
+            }
+            k as f64 * (p as f64).ln()
+        })
+        .sum()
+}
+
+/// Sums over the hits h ≤ `bound`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HitSums {
+    pub bound: BigUint,
+    /// Number of hits, the analogue of π(x)
+    pub count: u64,
+    /// Σ ln h, the analogue of θ(x)
+    pub theta: f64,
+    /// Σ 1/h
+    pub reciprocal_sum: f64,
+    /// Σ ln h / h
+    pub mertens_sum: f64,
+}
+
+impl HitSums {
+    fn empty(bound: BigUint) -> Self {
+        HitSums { bound, count: 0, theta: 0.0, reciprocal_sum: 0.0, mertens_sum: 0.0 }
+    }
+
+    fn add(&mut self, hit: &BigUint) {
+        let log = approx_log(hit);
+        // 1/h from log h, since h itself may not fit a double
+        let reciprocal = (-log).exp();
+        self.count += 1;
+        self.theta += log;
+        self.reciprocal_sum += reciprocal;
+        self.mertens_sum += log * reciprocal;
+    }
+}
+
+/// The sums over the hits up to `bound`.
+pub fn hit_sums(hits: &BTreeSet<BigUint>, bound: &BigUint) -> HitSums {
+    let mut sums = HitSums::empty(bound.clone());
+    for hit in hits.range(..=bound).filter(|h| h.bits() > 1) {
+        sums.add(hit);
+    }
+    sums
+}
+
+/// Σ ln h over the powers h^k ≤ `bound` of the hits, the analogue of ψ(x).
+pub fn hit_psi(hits: &BTreeSet<BigUint>, bound: &BigUint) -> f64 {
+    hits.range(..=bound)
+        .filter(|h| h.bits() > 1)
+        .map(|hit| {
+            let mut k = 1u32;
+            let mut power = hit * hit;
+            while power <= *bound {
+                k += 1;
+                power *= hit;
+            }
+            k as f64 * approx_log(hit)
+        })
+        .fold(0.0, |total, term| total + term)
+}
+
+/// Cumulative sums at every bit length from the smallest hit's to the largest's: row b
+/// covers the hits below 2^b.
+pub fn hit_partial_sums(hits: &BTreeSet<BigUint>) -> Vec<HitSums> {
+    let mut pending = hits.iter().filter(|h| h.bits() > 1).peekable();
+    let (Some(smallest), Some(largest)) = (pending.peek(), hits.last()) else {
+        return Vec::new();
+    };
+    let mut rows = Vec::new();
+    let mut sums = HitSums::empty(BigUint::from(1u32));
+    for bits in smallest.bits()..=largest.bits() {
+        let bound = (BigUint::from(1u32) << bits) - 1u32;
+        while let Some(hit) = pending.next_if(|h| **h <= bound) {
+            sums.add(hit);
+        }
+        sums.bound = bound;
+        rows.push(sums.clone());
+    }
+    rows
+}
+
+pub const CHEBYSHEV_CSV_HEADER: &str = "bits,count,theta,psi,reciprocal_sum,mertens_sum";
+
+/// `hit_partial_sums` as CSV, one row per bit length with ψ alongside.
+pub fn write_partial_sums<W: Write>(hits: &BTreeSet<BigUint>, out: &mut W) -> io::Result<()> {
+    writeln!(out, "{}", CHEBYSHEV_CSV_HEADER)?;
+    for row in hit_partial_sums(hits) {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            row.bound.bits(),
+            row.count,
+            row.theta,
+            hit_psi(hits, &row.bound),
+            row.reciprocal_sum,
+            row.mertens_sum
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chebyshev_functions() {
+        assert_eq!(chebyshev_theta(1), 0.0);
+        assert!((chebyshev_theta(100) - 83.72839).abs() < 1e-4);
+        assert!((chebyshev_psi(100) - 94.04531).abs() < 1e-4);
+        // ψ(x) - θ(x) = θ(√x) + θ(∛x) + ...
+        let x = 1_000_000u64;
+        let expected: f64 =
+            (2..20).map(|k| chebyshev_theta((x as f64).powf(1.0 / k as f64) as u64)).sum();
+        assert!((chebyshev_psi(x) - chebyshev_theta(x) - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hit_sums() {
+        let primes: BTreeSet<BigUint> =
+            primal::Primes::all().take_while(|&p| p < 10_000).map(BigUint::from).collect();
+        let bound = BigUint::from(10_000u32);
+        let sums = hit_sums(&primes, &bound);
+        assert_eq!(sums.count, 1229);
+        assert!((sums.theta - chebyshev_theta(10_000)).abs() < 1e-6);
+        assert!((hit_psi(&primes, &bound) - chebyshev_psi(10_000)).abs() < 1e-6);
+        let reciprocal: f64 = primal::Primes::all().take(1229).map(|p| 1.0 / p as f64).sum();
+        assert!((sums.reciprocal_sum - reciprocal).abs() < 1e-9);
+        // Mertens: Σ ln p / p = ln x + O(1)
+        assert!((sums.mertens_sum - (10_000f64.ln() - 1.3325)).abs() < 0.1);
+
+        let rows = hit_partial_sums(&primes);
+        assert_eq!(rows.len(), 13);
+        assert_eq!(rows.iter().map(|r| r.count).take(5).collect::<Vec<_>>(), [2, 4, 6, 11, 18]);
+        assert_eq!(rows.last().unwrap().count, 1229);
+        let mut csv = Vec::new();
+        write_partial_sums(&primes, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with(CHEBYSHEV_CSV_HEADER));
+        assert_eq!(csv.lines().count(), 14);
+        assert!(hit_partial_sums(&BTreeSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,549 @@
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::One;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::decompose::two_square_decomposition;
+use crate::factor::factorize_within;
+use crate::parse::parse_biguint;
+use crate::modular::jacobi;
+use crate::primality::{is_bpsw_prime, proth_decomposition, PrimalityConfig};
+
+pub use crate::primality::DEFAULT_ROUNDS;
+
+pub fn classify_prime<R: Rng + ?Sized>(p: &BigUint, rng: &mut R) -> Vec<&'static str> {
+    classify_prime_with_config(p, &PrimalityConfig::default(), rng)
+}
+
+/// Classify `p` using `rounds` Miller-Rabin witnesses for every primality check.
+pub fn classify_prime_with_rounds<R: Rng + ?Sized>(
+    p: &BigUint,
+    rounds: usize,
+    rng: &mut R,
+) -> Vec<&'static str> {
+    classify_prime_with_config(p, &PrimalityConfig::with_rounds(rounds), rng)
+}
+
+/// Classify `p`, testing primality of p and its neighbours as `config` prescribes.
+pub fn classify_prime_with_config<R: Rng + ?Sized>(
+    p: &BigUint,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> Vec<&'static str> {
+    classify_with(p, None, config, rng)
+}
+
+/// `classify_prime_with_config` for a `p` whose primality the caller has already tested, so
+/// it is not tested again and the "Prime" tag follows `is_prime`.
+pub fn classify_with_verdict<R: Rng + ?Sized>(
+    p: &BigUint,
+    is_prime: bool,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> Vec<&'static str> {
+    classify_with(p, Some(is_prime), config, rng)
+}
+
+fn classify_with<R: Rng + ?Sized>(
+    p: &BigUint,
+    is_prime: Option<bool>,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> Vec<&'static str> {
+    let mut classifications = Vec::new();
+
+    // Check if it's a Germain prime
+    if is_germain_prime(p, config, rng) {
+        classifications.push("Germain");
+    }
+    // Check if it's a Safe prime
+    if is_safe_prime(p, config, rng) {
+        classifications.push("Safe");
+    }
+    // Check if it's a Prime (basic primality check)
+    if is_prime.unwrap_or_else(|| config.is_prime(p, rng)) {
+        classifications.push("Prime");
+        if is_primorial_prime(p) {
+            classifications.push("Primorial");
+        }
+        if is_factorial_prime(p) {
+            classifications.push("Factorial");
+        }
+        if proth_decomposition(p).is_some() {
+            classifications.push("Proth");
+        }
+        if is_cullen(p) {
+            classifications.push("Cullen");
+        }
+        if is_woodall(p) {
+            classifications.push("Woodall");
+        }
+        if is_gaussian_prime(p) {
+            classifications.push("Gaussian");
+        }
+        if is_eisenstein_prime(p) {
+            classifications.push("Eisenstein");
+        }
+    }
+
+    classifications
+}
+
+/// `classify_prime_with_config`, plus `pseudoprime_tags` when `n` turns out composite.
+pub fn classify_with_pseudoprimes<R: Rng + ?Sized>(
+    n: &BigUint,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> Vec<&'static str> {
+    let mut classifications = classify_prime_with_config(n, config, rng);
+    if !classifications.contains(&"Prime") {
+        classifications.extend(pseudoprime_tags(n));
+    }
+    classifications
+}
+
+/// Pollard rho steps `is_carmichael` may spend factoring before it gives up.
+pub const CARMICHAEL_RHO_BUDGET: u64 = 1 << 20;
+
+/// Tags for composites that masquerade as primes: base-2 Fermat and Euler pseudoprimes,
+/// and Carmichael numbers. Empty for primes and ordinary composites.
+///
+/// Carmichael is only tagged once `n` is known to be one, so a pseudoprime too hard to factor
+/// within `CARMICHAEL_RHO_BUDGET` goes without it.
+pub fn pseudoprime_tags(n: &BigUint) -> Vec<&'static str> {
+    let mut tags = Vec::new();
+    if n < &BigUint::from(4u32) || is_bpsw_prime(n) {
+        return tags;
+    }
+    let two = BigUint::from(2u32);
+    if !fermat_condition(n, &two) {
+        return tags;
+    }
+    tags.push("FermatPsp2");
+    if euler_condition(n, &two) {
+        tags.push("EulerPsp2");
+    }
+    if korselt_condition(n) == Some(true) {
+        tags.push("Carmichael");
+    }
+    tags
+}
+
+/// Composite `n` with `base^(n-1) ≡ 1 (mod n)`.
+pub fn is_fermat_pseudoprime(n: &BigUint, base: &BigUint) -> bool {
+    n >= &BigUint::from(4u32) && !is_bpsw_prime(n) && fermat_condition(n, base)
+}
+
+fn fermat_condition(n: &BigUint, base: &BigUint) -> bool {
+    base.modpow(&(n - BigUint::one()), n) == BigUint::one()
+}
+
+/// Odd composite `n` with `base^((n-1)/2) ≡ (base/n) (mod n)`.
+pub fn is_euler_pseudoprime(n: &BigUint, base: &BigUint) -> bool {
+    n >= &BigUint::from(4u32) && !is_bpsw_prime(n) && euler_condition(n, base)
+}
+
+fn euler_condition(n: &BigUint, base: &BigUint) -> bool {
+    if n.is_even() {
+        return false;
+    }
+    let symbol = jacobi(&BigInt::from(base.clone()), n);
+    let power = base.modpow(&((n - BigUint::one()) >> 1), n);
+    match symbol {
+        1 => power == BigUint::one(),
+        -1 => power == n - BigUint::one(),
+        _ => false,
+    }
+}
+
+/// Carmichael number by Korselt's criterion: composite, squarefree, and `p - 1 | n - 1`
+/// for every prime `p | n`. `None` if `n` could not be factored within
+/// `CARMICHAEL_RHO_BUDGET` rho steps.
+pub fn is_carmichael(n: &BigUint) -> Option<bool> {
+    if n < &BigUint::from(4u32) || is_bpsw_prime(n) {
+        return Some(false);
+    }
+    korselt_condition(n)
+}
+
+fn korselt_condition(n: &BigUint) -> Option<bool> {
+    if n < &BigUint::from(561u32) || n.is_even() {
+        return Some(false);
+    }
+    // A Carmichael number passes the Fermat test to every base coprime to it, so a failing
+    // small base settles most inputs without factoring them
+    let fails = |base: u32| {
+        let base = BigUint::from(base);
+        n.gcd(&base).is_one() && !fermat_condition(n, &base)
+    };
+    if [3, 5, 7, 11, 13].into_iter().any(fails) {
+        return Some(false);
+    }
+    let n_minus_one = n - BigUint::one();
+    let factors = factorize_within(n, CARMICHAEL_RHO_BUDGET)?;
+    let korselt = factors
+        .iter()
+        .all(|(p, e)| *e == 1 && (&n_minus_one % (p - BigUint::one())) == BigUint::ZERO);
+    Some(factors.len() >= 2 && korselt)
+}
+
+pub fn is_germain_prime<R: Rng + ?Sized>(p: &BigUint, config: &PrimalityConfig, rng: &mut R) -> bool {
+    let two = BigUint::from(2u32);
+    let q = p * &two + BigUint::one();
+    config.is_prime(&q, rng)
+}
+
+/// How many times p → 2p + 1 stays prime from prime `p`: its Sophie Germain (first-kind
+/// Cunningham) chain depth, 0 unless `p` is a Germain prime. 2 → 5 → 11 → 23 → 47 has depth 4.
+pub fn germain_chain_depth<R: Rng + ?Sized>(
+    p: &BigUint,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> u32 {
+    let mut depth = 0;
+    let mut q = p * 2u32 + 1u32;
+    while config.is_prime(&q, rng) {
+        depth += 1;
+        q = q * 2u32 + 1u32;
+    }
+    depth
+}
+
+pub fn is_safe_prime<R: Rng + ?Sized>(p: &BigUint, config: &PrimalityConfig, rng: &mut R) -> bool {
+    let two = BigUint::from(2u32);
+    if p <= &two {
+        return false;
+    }
+    let q = (p - BigUint::one()) / &two;
+    config.is_prime(&q, rng)
+}
+
+/// Whether `n` is one away from a running product of `factors` (starting from the empty
+/// product 1). The factors must be at least 2, and products are only formed until they pass
+/// n + 1.
+fn is_adjacent_to_product<I: Iterator<Item = u64>>(n: &BigUint, factors: I) -> bool {
+    let limit = n + BigUint::one();
+    let mut product = BigUint::one();
+    for factor in std::iter::once(1).chain(factors) {
+        product *= factor;
+        if product > limit {
+            break;
+        }
+        if &product + BigUint::one() == *n || product == limit {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether `n` is a primorial ± 1, q# ± 1 with q# the product of the primes up to q (1 for
+/// the empty product). Only meaningful as a tag when `n` is prime.
+pub fn is_primorial_prime(n: &BigUint) -> bool {
+    is_adjacent_to_product(n, (2u64..).filter(|&q| primal::is_prime(q)))
+}
+
+/// Whether `n` is a factorial ± 1, k! ± 1. Only meaningful as a tag when `n` is prime.
+pub fn is_factorial_prime(n: &BigUint) -> bool {
+    is_adjacent_to_product(n, 2u64..)
+}
+
+/// Whether m = k·2^k for some k ≥ 1. Writing m = odd·2^t, k must be odd·2^j with
+/// j + k = t, so only j < t need be tried.
+fn is_cullen_form(m: &BigUint) -> bool {
+    let Some(t) = m.trailing_zeros() else {
+        return false;
+    };
+    let odd = m >> t;
+    (0..t).any(|j| {
+        let k = &odd << j;
+        k.bits() <= 64 && k.iter_u64_digits().next().unwrap_or(0) + j == t
+    })
+}
+
+/// Whether `n` is a Cullen number k·2^k + 1.
+pub fn is_cullen(n: &BigUint) -> bool {
+    n > &BigUint::one() && is_cullen_form(&(n - 1u32))
+}
+
+/// Whether `n` is a Woodall number k·2^k - 1.
+pub fn is_woodall(n: &BigUint) -> bool {
+    is_cullen_form(&(n + 1u32))
+}
+
+/// Whether the rational prime `p` stays prime in the Gaussian integers Z[i], which happens
+/// exactly when p ≡ 3 (mod 4). Every other prime is a sum of two squares.
+pub fn is_gaussian_prime(p: &BigUint) -> bool {
+    p % 4u32 == BigUint::from(3u32)
+}
+
+/// Whether the rational prime `p` stays prime in the Eisenstein integers Z[ω], which happens
+/// exactly when p ≡ 2 (mod 3). Every other prime is of the form a² - ab + b².
+pub fn is_eisenstein_prime(p: &BigUint) -> bool {
+    p % 3u32 == BigUint::from(2u32)
+}
+
+/// For a prime `p` that splits or ramifies in Z[i], the (a, b) with a ≥ b > 0 and
+/// p = a² + b² = (a + bi)(a - bi); `None` when p ≡ 3 (mod 4) or p is not prime.
+pub fn gaussian_factorization(p: &BigUint) -> Option<(BigUint, BigUint)> {
+    two_square_decomposition(p)
+}
+
+/// Which field of a CSV line holds the number to classify.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnSelector {
+    /// Zero-based field index; the input has no header
+    Index(usize),
+    /// Header name; the first line is treated as a header
+    Name(String),
+}
+
+impl FromStr for ColumnSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse::<usize>() {
+            Ok(index) => ColumnSelector::Index(index),
+            Err(_) => ColumnSelector::Name(s.to_string()),
+        })
+    }
+}
+
+/// Counts from a `classify_lines` run.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClassifySummary {
+    pub classified: usize,
+    pub invalid: usize,
+}
+
+/// Lines classified in parallel per batch; bounds memory on huge inputs.
+const CLASSIFY_BATCH: usize = 4096;
+
+/// Classify one number per line (or one CSV column) from `input` in parallel, writing each
+/// line back out with a `classifications` field appended.
+///
+/// Tags are joined with `;` so the output stays valid CSV, and composites get
+/// `pseudoprime_tags` only if `pseudoprimes` is set. Line `i` draws its witnesses from
+/// ChaCha20 stream `i` of `seed`, so results do not depend on thread scheduling.
+pub fn classify_lines<B: BufRead, W: Write>(
+    input: B,
+    out: &mut W,
+    column: Option<&ColumnSelector>,
+    config: &PrimalityConfig,
+    pseudoprimes: bool,
+    seed: u64,
+) -> io::Result<ClassifySummary> {
+    let mut lines = input.lines().enumerate().peekable();
+    let mut summary = ClassifySummary::default();
+
+    let index = match column {
+        None => {
+            writeln!(out, "n,classifications")?;
+            None
+        }
+        Some(ColumnSelector::Index(index)) => Some(*index),
+        Some(ColumnSelector::Name(name)) => {
+            let header = match lines.next() {
+                Some((_, line)) => line?,
+                None => return Ok(summary),
+            };
+            let index = header.split(',').position(|field| field.trim() == name);
+            let index = index.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("no column named '{}'", name))
+            })?;
+            writeln!(out, "{},classifications", header)?;
+            Some(index)
+        }
+    };
+
+    while lines.peek().is_some() {
+        let batch = lines
+            .by_ref()
+            .take(CLASSIFY_BATCH)
+            .map(|(i, line)| line.map(|line| (i, line)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let results: Vec<(String, Option<Vec<&'static str>>)> = batch
+            .into_par_iter()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let field = match index {
+                    Some(index) => line.split(',').nth(index).unwrap_or(""),
+                    None => line.as_str(),
+                };
+                let tags = parse_biguint(field).ok().map(|n| {
+                    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                    rng.set_stream(i as u64);
+                    if pseudoprimes {
+                        classify_with_pseudoprimes(&n, config, &mut rng)
+                    } else {
+                        classify_prime_with_config(&n, config, &mut rng)
+                    }
+                });
+                (line, tags)
+            })
+            .collect();
+
+        for (line, tags) in results {
+            match tags {
+                Some(tags) => {
+                    writeln!(out, "{},{}", line, tags.join(";"))?;
+                    summary.classified += 1;
+                }
+                None => {
+                    writeln!(out, "{},", line)?;
+                    summary.invalid += 1;
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudoprime_tags() {
+        assert_eq!(pseudoprime_tags(&BigUint::from(341u32)), vec!["FermatPsp2"]);
+        assert_eq!(
+            pseudoprime_tags(&BigUint::from(561u32)),
+            vec!["FermatPsp2", "EulerPsp2", "Carmichael"]
+        );
+        assert_eq!(is_carmichael(&BigUint::from(41041u32)), Some(true));
+        assert_eq!(is_carmichael(&BigUint::from(341u32)), Some(false));
+        assert_eq!(is_carmichael(&BigUint::from(563u32)), Some(false));
+        assert!(pseudoprime_tags(&BigUint::from(48883u32)).is_empty());
+        assert!(pseudoprime_tags(&BigUint::from(339u32)).is_empty());
+
+        // Composites are only tagged on request
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let config = PrimalityConfig::default();
+        assert!(classify_prime_with_config(&BigUint::from(1105u32), &config, &mut rng).is_empty());
+        let tags = classify_with_pseudoprimes(&BigUint::from(1105u32), &config, &mut rng);
+        assert_eq!(tags, vec!["FermatPsp2", "EulerPsp2", "Carmichael"]);
+
+        // Cipolla's pseudoprime (4^127 - 1)/3 has two 127-bit prime factors, out of rho's reach
+        let cipolla = ((BigUint::one() << 254) - 1u32) / 3u32;
+        assert_eq!(pseudoprime_tags(&cipolla), vec!["FermatPsp2"]);
+        assert_eq!(is_carmichael(&cipolla), Some(false));
+    }
+
+    #[test]
+    fn test_primorial_and_factorial_primes() {
+        let tagged = |is_tagged: fn(&BigUint) -> bool| -> Vec<u32> {
+            (2u32..50_000)
+                .filter(|&n| is_tagged(&BigUint::from(n)) && is_bpsw_prime(&BigUint::from(n)))
+                .collect()
+        };
+        // OEIS A228486 (p# ± 1) and A088054 (k! ± 1), below 50000
+        assert_eq!(tagged(is_primorial_prime), [2, 3, 5, 7, 29, 31, 211, 2309, 2311, 30029]);
+        assert_eq!(tagged(is_factorial_prime), [2, 3, 5, 7, 23, 719, 5039]);
+        // 31# + 1 = 200560490131
+        let primes = [2u32, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+        let p = primes.iter().product::<BigUint>() + 1u32;
+        assert!(is_bpsw_prime(&p) && is_primorial_prime(&p) && !is_factorial_prime(&p));
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        assert_eq!(
+            classify_prime(&BigUint::from(211u32), &mut rng),
+            ["Prime", "Primorial", "Gaussian"]
+        );
+
+        // A verdict passed in is trusted rather than tested again
+        let config = PrimalityConfig::default();
+        let p = BigUint::from(211u32);
+        let tags = classify_with_verdict(&p, true, &config, &mut rng);
+        assert_eq!(tags, classify_prime_with_config(&p, &config, &mut rng));
+        assert_eq!(classify_with_verdict(&p, false, &config, &mut rng), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_germain_chain_depth() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let config = PrimalityConfig::default();
+        let mut depth = |p: u32| germain_chain_depth(&BigUint::from(p), &config, &mut rng);
+        // 89 starts the chain 89, 179, 359, 719, 1439, 2879 (OEIS A005602)
+        assert_eq!([depth(2), depth(3), depth(7), depth(89), depth(179)], [4, 1, 0, 5, 4]);
+        let germain: Vec<u32> = (2u32..100)
+            .filter(|&p| primal::is_prime(p as u64))
+            .filter(|&p| depth(p) > 0)
+            .collect();
+        // OEIS A005384
+        assert_eq!(germain, [2, 3, 5, 11, 23, 29, 41, 53, 83, 89]);
+    }
+
+    #[test]
+    fn test_cullen_and_woodall() {
+        let up_to = |is_tagged: fn(&BigUint) -> bool| -> Vec<u32> {
+            (0u32..3000).filter(|&n| is_tagged(&BigUint::from(n))).collect()
+        };
+        // OEIS A002064 and A003261
+        assert_eq!(up_to(is_cullen), [3, 9, 25, 65, 161, 385, 897, 2049]);
+        assert_eq!(up_to(is_woodall), [1, 7, 23, 63, 159, 383, 895, 2047]);
+        // 141·2^141 + 1, the first Cullen prime past 3
+        let cullen = (BigUint::from(141u32) << 141u32) + 1u32;
+        assert!(is_cullen(&cullen) && is_bpsw_prime(&cullen));
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        assert_eq!(
+            classify_prime(&BigUint::from(383u32), &mut rng),
+            ["Safe", "Prime", "Woodall", "Gaussian", "Eisenstein"]
+        );
+        assert_eq!(classify_prime(&BigUint::from(13u32), &mut rng), ["Prime", "Proth"]);
+    }
+
+    #[test]
+    fn test_gaussian_and_eisenstein_primes() {
+        let primes: Vec<u32> = (2u32..1000).filter(|&n| primal::is_prime(n as u64)).collect();
+        for &p in &primes {
+            let n = BigUint::from(p);
+            match gaussian_factorization(&n) {
+                Some((a, b)) => {
+                    assert!(!is_gaussian_prime(&n) && a >= b);
+                    assert_eq!(&a * &a + &b * &b, n);
+                }
+                None => assert!(is_gaussian_prime(&n), "{} should split", p),
+            }
+        }
+        assert_eq!(gaussian_factorization(&BigUint::from(2u32)), Some((1u32.into(), 1u32.into())));
+        assert_eq!(gaussian_factorization(&BigUint::from(97u32)), Some((9u32.into(), 4u32.into())));
+        assert_eq!(gaussian_factorization(&BigUint::from(25u32)), None);
+        // Mersenne primes are ≡ 3 (mod 4) and stay inert; Fermat primes are 2^(2^k) + 1
+        let mersenne = (BigUint::one() << 127u32) - 1u32;
+        assert!(is_gaussian_prime(&mersenne) && gaussian_factorization(&mersenne).is_none());
+        assert_eq!(
+            gaussian_factorization(&BigUint::from(65537u32)),
+            Some((256u32.into(), 1u32.into()))
+        );
+        let eisenstein: Vec<u32> = primes
+            .iter()
+            .copied()
+            .filter(|&p| is_eisenstein_prime(&BigUint::from(p)))
+            .take(6)
+            .collect();
+        assert_eq!(eisenstein, [2, 5, 11, 17, 23, 29]);
+    }
+
+    #[test]
+    fn test_classify_lines_by_column_name() {
+        let input = "id,value\na,23\nb,not-a-number\nc,2^7-1\n";
+        let mut out = Vec::new();
+        let column = ColumnSelector::Name("value".to_string());
+        let config = PrimalityConfig::default();
+        let summary =
+            classify_lines(input.as_bytes(), &mut out, Some(&column), &config, false, 1).unwrap();
+        assert_eq!(summary, ClassifySummary { classified: 2, invalid: 1 });
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "id,value,classifications\na,23,Germain;Safe;Prime;Factorial;Woodall;Gaussian;Eisenstein\n",
+                "b,not-a-number,\nc,2^7-1,Prime;Gaussian\n"
+            )
+        );
+    }
+}
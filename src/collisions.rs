@@ -0,0 +1,132 @@
+//! Values of N reached by more than one (x, y, z) in the pool.
+//!
+//! A `RepresentationIndex` maps every N over pool³ to the tuples that produce it, in one
+//! pass over the pool. Its multiplicities are the pool-restricted representation numbers
+//! r(N), and the N with r(N) > 1 are the collisions.
+
+use num_bigint::BigInt;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::form::QuadraticForm;
+use crate::pool::CandidatePool;
+use crate::search::evaluate_from;
+
+/// The tuple indices (x-major, as in `search`) representing each value of N.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepresentationIndex {
+    representations: HashMap<BigInt, Vec<u64>>,
+    pool_len: u64,
+}
+
+impl RepresentationIndex {
+    /// Evaluate `form` on all of `pool`³ and index the tuples by value.
+    pub fn build<P: CandidatePool + ?Sized>(form: &QuadraticForm, pool: &P) -> Self {
+        let mut representations: HashMap<BigInt, Vec<u64>> = HashMap::new();
+        for (index, _, _, _, n) in evaluate_from(form, pool, 0) {
+            representations.entry(n).or_default().push(index);
+        }
+        RepresentationIndex { representations, pool_len: pool.len() as u64 }
+    }
+
+    /// Number of distinct values of N.
+    pub fn len(&self) -> usize {
+        self.representations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.representations.is_empty()
+    }
+
+    /// r(N): how many tuples in the pool represent `n`.
+    pub fn count(&self, n: &BigInt) -> usize {
+        self.representations.get(n).map_or(0, Vec::len)
+    }
+
+    /// The indices of the tuples representing `n`, in increasing order.
+    pub fn representations(&self, n: &BigInt) -> &[u64] {
+        self.representations.get(n).map_or(&[], Vec::as_slice)
+    }
+
+    /// Pool indices (i, j, k) of the x, y and z of tuple `index`.
+    pub fn tuple(&self, index: u64) -> (usize, usize, usize) {
+        let len = self.pool_len;
+        ((index / (len * len)) as usize, (index / len % len) as usize, (index % len) as usize)
+    }
+
+    /// The values with more than one representation, in increasing order of N.
+    pub fn collisions(&self) -> Vec<(&BigInt, &[u64])> {
+        let mut collisions: Vec<(&BigInt, &[u64])> = self
+            .representations
+            .iter()
+            .filter(|(_, tuples)| tuples.len() > 1)
+            .map(|(n, tuples)| (n, tuples.as_slice()))
+            .collect();
+        collisions.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        collisions
+    }
+}
+
+pub const COLLISIONS_CSV_HEADER: &str = "n,representations,tuples";
+
+/// Write each collision as `n,r(n),[(x, y, z), ...]`, in increasing order of N.
+pub fn write_collisions<W: Write, P: CandidatePool + ?Sized>(
+    index: &RepresentationIndex,
+    pool: &P,
+    out: &mut W,
+) -> io::Result<()> {
+    writeln!(out, "{}", COLLISIONS_CSV_HEADER)?;
+    for (n, tuples) in index.collisions() {
+        let tuples: Vec<String> = tuples
+            .iter()
+            .map(|&t| {
+                let (i, j, k) = index.tuple(t);
+                format!("({}, {}, {})", pool.get(i), pool.get(j), pool.get(k))
+            })
+            .collect();
+        writeln!(out, "{},{},[{}]", n, tuples.len(), tuples.join(", "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{default_pool, evaluate_all, signed_pool};
+
+    #[test]
+    fn test_collisions() {
+        // x² + y² + z² is symmetric, so permutations of a tuple collide
+        let form = QuadraticForm::new([1, 0, 1, 0, 0, 1, 0]);
+        let pool: Vec<BigInt> = [1, 2, 3, 5].into_iter().map(BigInt::from).collect();
+        let index = RepresentationIndex::build(&form, &pool);
+        assert_eq!(index.count(&BigInt::from(3)), 1);
+        assert_eq!(index.count(&BigInt::from(14)), 6);
+        assert_eq!(index.count(&BigInt::from(4)), 0);
+        // 27 = 1 + 1 + 25 = 9 + 9 + 9
+        assert_eq!(index.count(&BigInt::from(27)), 4);
+        assert_eq!(index.representations(&BigInt::from(27)), [3, 12, 42, 48]);
+        assert_eq!(index.tuple(42), (2, 2, 2));
+        assert_eq!(index.representations(&BigInt::from(4)), [] as [u64; 0]);
+        // Each value is counted once per tuple representing it
+        let total: usize = evaluate_all(&form, &pool).map(|(_, _, _, n)| index.count(&n)).sum();
+        let squares: usize = index.representations.values().map(|t| t.len() * t.len()).sum();
+        assert_eq!(total, squares);
+
+        let mut out = Vec::new();
+        write_collisions(&index, &pool, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(COLLISIONS_CSV_HEADER));
+        assert!(out.contains("\n27,4,[(1, 1, 5), (1, 5, 1), (3, 3, 3), (5, 1, 1)]\n"));
+        let values: Vec<BigInt> =
+            out.lines().skip(1).map(|l| l.split(',').next().unwrap().parse().unwrap()).collect();
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+
+        // Over signed primes (x, y, z) and (-x, -y, -z) always give the same N
+        let pool = signed_pool(&default_pool()[..4]);
+        let index = RepresentationIndex::build(&QuadraticForm::universal(), &pool);
+        assert_eq!(index.collisions().len(), index.len());
+        assert!(index.collisions().iter().all(|(_, tuples)| tuples.len() % 2 == 0));
+    }
+}
@@ -0,0 +1,255 @@
+//! Search settings read from a TOML file, for `search --config`.
+//!
+//! The `[pools]` table gives x, y and z their own candidates, e.g.
+//!
+//! ```toml
+//! [pools]
+//! x = { class = "safe", below = 5000 }
+//! y = { class = "germain", below = 5000 }
+//! z = { list = [3, 5, 7, 11] }
+//! ```
+//!
+//! A pool is `"default"` (the built-in primes, also what a missing variable gets),
+//! `"signed"` (those and their negations), `{ list = [...] }`, `{ file = "..." }` for a
+//! file written by the pool command, or `{ class = ..., below = ..., above = ... }` for the
+//! primes strictly between the bounds that are `prime`, `safe` or `germain`.
+//!
+//! A top-level `constraints` list restricts the tuples searched, e.g.
+//!
+//! ```toml
+//! constraints = ["x < y < z", "gcd(x, y) == 1", "x % 4 == 3"]
+//! ```
+//!
+//! in the syntax of `constraint::TupleConstraints`.
+
+use num_bigint::{BigInt, BigUint};
+use rand::Rng;
+use serde::Deserialize;
+use thiserror::Error;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::classify::{is_germain_prime, is_safe_prime};
+use crate::constraint::{ConstraintError, TupleConstraints};
+use crate::pool::{MappedPool, PoolError, VariablePools};
+use crate::primality::PrimalityConfig;
+use crate::search::{default_pool, signed_pool};
+
+/// Largest `below` bound of a class pool, which is sieved in memory.
+pub const MAX_CLASS_BOUND: u64 = 1 << 32;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error("{}: {1}", .0.display())]
+    Pool(PathBuf, PoolError),
+    #[error("unknown pool '{0}', expected default or signed")]
+    UnknownPool(String),
+    #[error("unknown prime class '{0}', expected prime, safe or germain")]
+    UnknownClass(String),
+    #[error("class pools must lie below {}, not {0}", MAX_CLASS_BOUND)]
+    BoundTooLarge(u64),
+    #[error("the {0} pool is empty")]
+    EmptyPool(char),
+    #[error(transparent)]
+    Constraint(#[from] ConstraintError),
+}
+
+/// Where one variable's candidates come from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum PoolSpec {
+    Named(String),
+    List { list: Vec<i64> },
+    File { file: PathBuf },
+    Class {
+        class: String,
+        below: u64,
+        #[serde(default)]
+        above: u64,
+    },
+}
+
+impl Default for PoolSpec {
+    fn default() -> Self {
+        PoolSpec::Named("default".to_string())
+    }
+}
+
+impl fmt::Display for PoolSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolSpec::Named(name) => write!(f, "{}", name),
+            PoolSpec::List { list } => {
+                let entries: Vec<String> = list.iter().map(i64::to_string).collect();
+                write!(f, "[{}]", entries.join(", "))
+            }
+            PoolSpec::File { file } => write!(f, "{}", file.display()),
+            PoolSpec::Class { class, below, above } => {
+                write!(f, "{} primes in ({}, {})", class, above, below)
+            }
+        }
+    }
+}
+
+impl PoolSpec {
+    /// The candidates, with class membership decided as `primality` prescribes.
+    pub fn build<R: Rng + ?Sized>(
+        &self,
+        primality: &PrimalityConfig,
+        rng: &mut R,
+    ) -> Result<Vec<BigInt>, ConfigError> {
+        match self {
+            PoolSpec::Named(name) => match name.as_str() {
+                "default" => Ok(default_pool()),
+                "signed" => Ok(signed_pool(&default_pool())),
+                _ => Err(ConfigError::UnknownPool(name.clone())),
+            },
+            PoolSpec::List { list } => Ok(list.iter().map(|&v| BigInt::from(v)).collect()),
+            PoolSpec::File { file } => {
+                let pool = MappedPool::open(file).map_err(|e| ConfigError::Pool(file.clone(), e))?;
+                Ok(pool.iter().map(BigInt::from).collect())
+            }
+            PoolSpec::Class { class, below, above } => {
+                if *below > MAX_CLASS_BOUND {
+                    return Err(ConfigError::BoundTooLarge(*below));
+                }
+                let keep: fn(&BigUint, &PrimalityConfig, &mut R) -> bool =
+                    match class.to_ascii_lowercase().as_str() {
+                        "prime" => |_, _, _| true,
+                        "safe" => is_safe_prime,
+                        "germain" => is_germain_prime,
+                        _ => return Err(ConfigError::UnknownClass(class.clone())),
+                    };
+                let sieve = primal::Sieve::new(*below as usize);
+                Ok(sieve
+                    .primes_from(0)
+                    .map(|p| p as u64)
+                    .take_while(|&p| p < *below)
+                    .filter(|&p| p > *above && keep(&BigUint::from(p), primality, rng))
+                    .map(BigInt::from)
+                    .collect())
+            }
+        }
+    }
+}
+
+/// The `[pools]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolsConfig {
+    #[serde(default)]
+    pub x: PoolSpec,
+    #[serde(default)]
+    pub y: PoolSpec,
+    #[serde(default)]
+    pub z: PoolSpec,
+}
+
+impl PoolsConfig {
+    pub fn build<R: Rng + ?Sized>(
+        &self,
+        primality: &PrimalityConfig,
+        rng: &mut R,
+    ) -> Result<VariablePools, ConfigError> {
+        let mut pools = Vec::with_capacity(3);
+        for (variable, spec) in ['x', 'y', 'z'].into_iter().zip([&self.x, &self.y, &self.z]) {
+            let pool = spec.build(primality, rng)?;
+            if pool.is_empty() {
+                return Err(ConfigError::EmptyPool(variable));
+            }
+            pools.push(pool);
+        }
+        let [x, y, z]: [Vec<BigInt>; 3] = pools.try_into().expect("three pools");
+        Ok(VariablePools { x, y, z })
+    }
+}
+
+impl fmt::Display for PoolsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x: {}; y: {}; z: {}", self.x, self.y, self.z)
+    }
+}
+
+/// A search configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchFile {
+    #[serde(default)]
+    pub constraints: Vec<String>,
+    pub pools: Option<PoolsConfig>,
+}
+
+impl SearchFile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// The parsed `constraints` list.
+    pub fn constraints(&self) -> Result<TupleConstraints, ConfigError> {
+        Ok(TupleConstraints::parse(&self.constraints)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_variable_pools_from_toml() {
+        let file: SearchFile = toml::from_str(
+            r#"
+            [pools]
+            x = { class = "safe", below = 100 }
+            y = { class = "Germain", below = 30, above = 3 }
+            z = { list = [-2, 9] }
+            "#,
+        )
+        .unwrap();
+        let pools = file.pools.unwrap();
+        assert_eq!(
+            pools.to_string(),
+            "x: safe primes in (0, 100); y: Germain primes in (3, 30); z: [-2, 9]"
+        );
+        let mut rng = ChaCha20Rng::seed_from_u64(98);
+        let built = pools.build(&PrimalityConfig::default(), &mut rng).unwrap();
+        let values = |pool: &[BigInt]| -> Vec<i64> {
+            pool.iter().map(|v| i64::try_from(v).unwrap()).collect()
+        };
+        assert_eq!(values(&built.x), vec![5, 7, 11, 23, 47, 59, 83]);
+        assert_eq!(values(&built.y), vec![5, 11, 23, 29]);
+        assert_eq!(values(&built.z), vec![-2, 9]);
+
+        let defaults: SearchFile = toml::from_str("[pools]\nz = \"signed\"\n").unwrap();
+        let built = defaults.pools.unwrap().build(&PrimalityConfig::default(), &mut rng).unwrap();
+        assert_eq!(built.x, default_pool());
+        assert_eq!(built.z.len(), 2 * default_pool().len());
+
+        let constrained: SearchFile =
+            toml::from_str("constraints = [\"x < y\", \"gcd(y, z) == 1\"]\n").unwrap();
+        assert_eq!(constrained.pools, None);
+        assert_eq!(constrained.constraints().unwrap().sources().len(), 2);
+        let reads_n: SearchFile = toml::from_str("constraints = [\"n > 1\"]\n").unwrap();
+        assert!(matches!(reads_n.constraints(), Err(ConfigError::Constraint(_))));
+
+        let unknown = toml::from_str::<SearchFile>("[pools]\nw = \"default\"\n");
+        assert!(unknown.is_err());
+        let class = PoolSpec::Class { class: "twin".to_string(), below: 10, above: 0 };
+        assert!(matches!(
+            class.build(&PrimalityConfig::default(), &mut rng),
+            Err(ConfigError::UnknownClass(_))
+        ));
+        let empty = PoolsConfig { x: PoolSpec::List { list: vec![] }, ..Default::default() };
+        assert!(matches!(
+            empty.build(&PrimalityConfig::default(), &mut rng),
+            Err(ConfigError::EmptyPool('x'))
+        ));
+    }
+}
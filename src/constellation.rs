@@ -0,0 +1,138 @@
+//! Prime constellations (k-tuples) among the hits of a search.
+//!
+//! A pattern is a list of offsets from 0, such as (0, 2) for twin primes or (0, 2, 6) for
+//! one form of prime triplet. It is admissible when for every prime p its offsets miss at
+//! least one residue class mod p; inadmissible patterns such as (0, 2, 4) can only occur
+//! finitely often, so they are rejected up front.
+
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PatternError {
+    #[error("invalid pattern '{0}', expected a name like twin or offsets like 0,2,6")]
+    Invalid(String),
+    #[error("pattern offsets must start at 0 and increase strictly")]
+    NotIncreasing,
+    #[error("pattern {0} is not admissible: it covers every residue mod {1}")]
+    Inadmissible(Pattern, u64),
+}
+
+/// Offsets of a prime constellation, starting at 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    offsets: Vec<u64>,
+}
+
+/// Named patterns accepted by `Pattern::from_str`.
+pub const NAMED_PATTERNS: [(&str, &[u64]); 7] = [
+    ("twin", &[0, 2]),
+    ("cousin", &[0, 4]),
+    ("sexy", &[0, 6]),
+    ("triplet", &[0, 2, 6]),
+    ("triplet2", &[0, 4, 6]),
+    ("quadruplet", &[0, 2, 6, 8]),
+    ("quintuplet", &[0, 2, 6, 8, 12]),
+];
+
+impl Pattern {
+    /// Check the offsets are increasing from 0 and admissible.
+    pub fn new(offsets: Vec<u64>) -> Result<Self, PatternError> {
+        if offsets.first() != Some(&0) || offsets.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(PatternError::NotIncreasing);
+        }
+        let pattern = Pattern { offsets };
+        // Only primes p ≤ k can have every residue class covered by k offsets
+        let k = pattern.offsets.len() as u64;
+        for p in (2..=k).filter(|&p| (2..p).all(|d| p % d != 0)) {
+            let mut covered = vec![false; p as usize];
+            for &offset in &pattern.offsets {
+                covered[(offset % p) as usize] = true;
+            }
+            if covered.iter().all(|&c| c) {
+                return Err(PatternError::Inadmissible(pattern, p));
+            }
+        }
+        Ok(pattern)
+    }
+
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offsets: Vec<String> = self.offsets.iter().map(|o| o.to_string()).collect();
+        write!(f, "({})", offsets.join(", "))
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((_, offsets)) = NAMED_PATTERNS.iter().find(|(name, _)| *name == s) {
+            return Pattern::new(offsets.to_vec());
+        }
+        let offsets = s
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .map(|o| o.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| PatternError::Invalid(s.to_string()))?;
+        Pattern::new(offsets)
+    }
+}
+
+/// Every occurrence of one pattern, by its smallest member.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstellationReport {
+    pub pattern: Pattern,
+    pub starts: Vec<BigUint>,
+}
+
+/// Find every n in `hits` with n + offset in `hits` for all of the pattern's offsets.
+pub fn find_constellations(hits: &BTreeSet<BigUint>, pattern: &Pattern) -> ConstellationReport {
+    let starts = hits
+        .iter()
+        .filter(|&n| {
+            pattern.offsets[1..]
+                .iter()
+                .all(|&offset| hits.contains(&(n + offset)))
+        })
+        .cloned()
+        .collect();
+    ConstellationReport {
+        pattern: pattern.clone(),
+        starts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!("triplet".parse::<Pattern>().unwrap().offsets(), &[0, 2, 6]);
+        assert_eq!("0,4".parse::<Pattern>().unwrap().to_string(), "(0, 4)");
+        assert!(matches!("0,2,4".parse::<Pattern>(), Err(PatternError::Inadmissible(_, 3))));
+        assert_eq!("2,4".parse::<Pattern>(), Err(PatternError::NotIncreasing));
+
+        let hits: BTreeSet<BigUint> = [5u32, 7, 11, 13, 17, 19, 23, 37, 41, 43]
+            .into_iter()
+            .map(BigUint::from)
+            .collect();
+        let twins = find_constellations(&hits, &"twin".parse().unwrap());
+        assert_eq!(twins.starts, [5u32, 11, 17, 41].map(BigUint::from));
+        let triplets = find_constellations(&hits, &"triplet".parse().unwrap());
+        assert_eq!(triplets.starts, [5u32, 11, 17].map(BigUint::from));
+    }
+}
@@ -0,0 +1,101 @@
+//! Declarative constraints on the input tuple, such as `x < y < z`, `x ≠ y`,
+//! `gcd(x, y) == 1` or `x % 4 == 3`, so a structured sub-search needs no code changes.
+//!
+//! Each constraint is a filter expression (see `filter`) that reads only x, y and z, which lets
+//! the search check it before computing N: a tuple that fails one is skipped outright.
+
+use num_bigint::BigInt;
+use thiserror::Error;
+
+use std::fmt;
+
+use crate::filter::{Filter, FilterError};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ConstraintError {
+    #[error("constraint '{0}': {1}")]
+    Parse(String, FilterError),
+    #[error("constraint '{0}' reads n or tags, which are not known before N is computed")]
+    ReadsRow(String),
+}
+
+/// The constraints of a search; a tuple is searched only if it meets all of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TupleConstraints {
+    sources: Vec<String>,
+    filters: Vec<Filter>,
+}
+
+impl TupleConstraints {
+    /// Parse each constraint, rejecting any that reads more than x, y and z.
+    pub fn parse<S: AsRef<str>>(constraints: &[S]) -> Result<Self, ConstraintError> {
+        let mut parsed = TupleConstraints::default();
+        for source in constraints {
+            let source = source.as_ref().trim();
+            let filter: Filter =
+                source.parse().map_err(|e| ConstraintError::Parse(source.to_string(), e))?;
+            if !filter.reads_only_tuple() {
+                return Err(ConstraintError::ReadsRow(source.to_string()));
+            }
+            parsed.sources.push(source.to_string());
+            parsed.filters.push(filter);
+        }
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// The constraints as written.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Whether (x, y, z) meets every constraint.
+    pub fn admits(&self, x: &BigInt, y: &BigInt, z: &BigInt) -> bool {
+        self.filters.iter().all(|filter| filter.matches_tuple(x, y, z))
+    }
+}
+
+impl fmt::Display for TupleConstraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.sources.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuple_constraints() {
+        let constraints =
+            TupleConstraints::parse(&["x < y < z", "x ≠ 5", "gcd(y, z) == 1", " x % 4 == 3"])
+                .unwrap();
+        assert_eq!(constraints.to_string(), "x < y < z; x ≠ 5; gcd(y, z) == 1; x % 4 == 3");
+        let admits = |x: i64, y: i64, z: i64| {
+            constraints.admits(&BigInt::from(x), &BigInt::from(y), &BigInt::from(z))
+        };
+        assert!(admits(3, 5, 7));
+        assert!(admits(-1, 2, 9));
+        assert!(!admits(3, 7, 5));
+        assert!(!admits(7, 9, 15));
+        assert!(!admits(11, 13, 13));
+        assert!(TupleConstraints::parse::<&str>(&[]).unwrap().admits(
+            &BigInt::from(1),
+            &BigInt::from(1),
+            &BigInt::from(1)
+        ));
+
+        assert!(matches!(
+            TupleConstraints::parse(&["n > 100"]),
+            Err(ConstraintError::ReadsRow(_))
+        ));
+        assert!(matches!(
+            TupleConstraints::parse(&["tags.contains(Safe)"]),
+            Err(ConstraintError::ReadsRow(_))
+        ));
+        assert!(matches!(TupleConstraints::parse(&["x <"]), Err(ConstraintError::Parse(..))));
+    }
+}
@@ -0,0 +1,316 @@
+//! Live view of a running search: throughput, hits per classification, the (x, y, z)
+//! frontier and the most recent hits, plus the run's extreme prime hits when a `TopHits` is
+//! attached.
+//!
+//! `HitTap` wraps the search's output and keeps a `DashboardState` up to date from the rows
+//! passing through it, so the search itself needs no hooks. With the `tui` feature,
+//! `Dashboard` draws that state in an inline ratatui viewport at each checkpoint; being
+//! inline, whatever was last drawn stays readable if the process is killed.
+
+use num_bigint::BigInt;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::pool::TupleSpace;
+use crate::results::parse_row;
+use crate::search::{SearchProgress, TupleOrder, TupleSchedule};
+use crate::top::TopHits;
+
+/// Hits kept for the scrolling list.
+pub const RECENT_HITS: usize = 8;
+
+/// What the dashboard shows, accumulated over a run.
+#[derive(Debug, Clone)]
+pub struct DashboardState {
+    pub started: Instant,
+    /// Tuples already done when this run started (non-zero after a resume)
+    pub start_tuples: u64,
+    pub total_tuples: u64,
+    pub progress: SearchProgress,
+    /// Rows seen in this run, by tag of N
+    pub tag_counts: BTreeMap<String, u64>,
+    /// Rows seen in this run
+    pub rows: u64,
+    /// The next (x, y, z) to be evaluated, if any remain
+    pub frontier: Option<(BigInt, BigInt, BigInt)>,
+    /// (x, y, z, N) of the latest rows, newest last
+    pub recent: VecDeque<(BigInt, BigInt, BigInt, BigInt)>,
+    /// Extreme prime hits of this run, if asked for
+    pub top: Option<TopHits>,
+    /// Order the search visits tuples in, for the frontier
+    pub order: TupleOrder,
+    /// Built from the pool at the first update
+    schedule: Option<TupleSchedule>,
+}
+
+impl DashboardState {
+    pub fn new(start: SearchProgress, total_tuples: u64) -> Self {
+        DashboardState {
+            started: Instant::now(),
+            start_tuples: start.tuples,
+            total_tuples,
+            progress: start,
+            tag_counts: BTreeMap::new(),
+            rows: 0,
+            frontier: None,
+            recent: VecDeque::with_capacity(RECENT_HITS),
+            top: None,
+            order: TupleOrder::XMajor,
+            schedule: None,
+        }
+    }
+
+    pub fn order(mut self, order: TupleOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Also keep the extreme prime hits in `top`.
+    pub fn track_top(mut self, top: TopHits) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// Count a results row; lines that are not rows (the header) are ignored.
+    pub fn record_line(&mut self, line: &str) {
+        let Ok(row) = parse_row(line) else { return };
+        self.rows += 1;
+        for tag in &row.classifications_n {
+            *self.tag_counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        if let Some(top) = self.top.as_mut().filter(|_| row.is_prime_hit()) {
+            top.record(row.n.magnitude());
+        }
+        if self.recent.len() == RECENT_HITS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((row.x, row.y, row.z, row.n));
+    }
+
+    /// Take the search's progress and work out the frontier in `pool`.
+    pub fn update<P: TupleSpace + ?Sized>(&mut self, progress: &SearchProgress, pool: &P) {
+        self.progress = *progress;
+        let position = progress.tuples;
+        self.frontier = (position < self.total_tuples).then(|| {
+            let schedule =
+                self.schedule.get_or_insert_with(|| TupleSchedule::new(pool, self.order));
+            let (x, y, z) = schedule.indices(position);
+            let value = |variable, index| pool.value(variable, index).into_owned();
+            (value(0, x), value(1, y), value(2, z))
+        });
+    }
+
+    /// Tuples per second over this run.
+    pub fn throughput(&self) -> f64 {
+        let elapsed = self.started.elapsed().max(Duration::from_millis(1));
+        (self.progress.tuples - self.start_tuples) as f64 / elapsed.as_secs_f64()
+    }
+
+    /// Fraction of this run's tuples that gave a row.
+    pub fn hit_rate(&self) -> f64 {
+        let tuples = self.progress.tuples - self.start_tuples;
+        if tuples == 0 {
+            0.0
+        } else {
+            self.rows as f64 / tuples as f64
+        }
+    }
+
+    /// Fraction of all tuples done.
+    pub fn completion(&self) -> f64 {
+        if self.total_tuples == 0 {
+            1.0
+        } else {
+            self.progress.tuples as f64 / self.total_tuples as f64
+        }
+    }
+}
+
+/// A writer that passes everything through to `inner` and records each complete line in a
+/// `DashboardState`.
+pub struct HitTap<W> {
+    inner: W,
+    pending: Vec<u8>,
+    pub state: DashboardState,
+}
+
+impl<W: Write> HitTap<W> {
+    pub fn new(inner: W, state: DashboardState) -> Self {
+        HitTap {
+            inner,
+            pending: Vec::new(),
+            state,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for HitTap<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.pending.extend_from_slice(&buf[..written]);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.state.record_line(&String::from_utf8_lossy(&line));
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "tui")]
+pub use self::terminal::Dashboard;
+
+#[cfg(feature = "tui")]
+mod terminal {
+    use ratatui::backend::CrosstermBackend;
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::style::{Color, Style};
+    use ratatui::text::Line;
+    use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph};
+    use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+
+    use std::io::{self, Stdout};
+
+    use super::{DashboardState, RECENT_HITS};
+
+    /// Terminal rows taken by the dashboard.
+    const HEIGHT: u16 = RECENT_HITS as u16 + 8;
+
+    /// The dashboard, drawn inline below the cursor.
+    pub struct Dashboard {
+        terminal: Terminal<CrosstermBackend<Stdout>>,
+    }
+
+    impl Dashboard {
+        pub fn new() -> io::Result<Self> {
+            let terminal = Terminal::with_options(
+                CrosstermBackend::new(io::stdout()),
+                TerminalOptions {
+                    viewport: Viewport::Inline(HEIGHT),
+                },
+            )?;
+            Ok(Dashboard { terminal })
+        }
+
+        pub fn draw(&mut self, state: &DashboardState) -> io::Result<()> {
+            self.terminal.draw(|frame| render(frame, state))?;
+            Ok(())
+        }
+    }
+
+    pub(super) fn render(frame: &mut Frame, state: &DashboardState) {
+        let [gauge, body] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+        let [stats, recent] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(body);
+
+        let label = format!(
+            "{} of {} tuples, {} hits",
+            state.progress.tuples, state.total_tuples, state.progress.hits
+        );
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("Search"))
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(state.completion().clamp(0.0, 1.0))
+                .label(label),
+            gauge,
+        );
+
+        let mut lines = vec![
+            Line::from(format!("{:.0} tuples/s", state.throughput())),
+            Line::from(format!("hit rate {:.4}%", 100.0 * state.hit_rate())),
+            Line::from(match &state.frontier {
+                Some((x, y, z)) => format!("next ({}, {}, {})", x, y, z),
+                None => "all tuples done".to_string(),
+            }),
+        ];
+        for (tag, count) in &state.tag_counts {
+            lines.push(Line::from(format!(
+                "{:<12} {:>8} {:>6.2}%",
+                tag,
+                count,
+                100.0 * *count as f64 / state.rows.max(1) as f64
+            )));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Rates")),
+            stats,
+        );
+
+        let items: Vec<ListItem> = state
+            .recent
+            .iter()
+            .rev()
+            .map(|(x, y, z, n)| ListItem::new(format!("F({}, {}, {}) = {}", x, y, z, n)))
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::default().borders(Borders::ALL).title("Recent hits")),
+            recent,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::QuadraticForm;
+    use crate::results::read_hits;
+    use crate::search::{default_pool, search, SearchConfig};
+
+    #[test]
+    fn test_hit_tap_tracks_rows() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..4].to_vec();
+        let mut tap = HitTap::new(Vec::new(), DashboardState::new(SearchProgress::default(), 64));
+        let hits = search(&form, &pool, &mut tap, &SearchConfig::new(3)).unwrap();
+        let written = String::from_utf8(tap.inner_mut().clone()).unwrap();
+        let state = &tap.state;
+        assert_eq!(state.rows, hits as u64);
+        assert!(state.tag_counts["Prime"] > 0 && state.tag_counts["Prime"] <= state.rows);
+        assert_eq!(state.recent.len(), RECENT_HITS.min(hits));
+        let last = written.lines().last().unwrap();
+        assert!(last.starts_with(&format!("{},", state.recent.back().unwrap().0)));
+
+        let mut state = state.clone();
+        state.update(&SearchProgress { tuples: 17, hits: 0 }, &pool);
+        assert_eq!(state.frontier, Some((pool[1].clone(), pool[0].clone(), pool[1].clone())));
+        state.update(&SearchProgress { tuples: 64, hits: 0 }, &pool);
+        assert_eq!(state.frontier, None);
+        assert_eq!(state.completion(), 1.0);
+
+        let state = DashboardState::new(SearchProgress::default(), 64).track_top(TopHits::new(2));
+        let mut tap = HitTap::new(Vec::new(), state);
+        search(&form, &pool, &mut tap, &SearchConfig::new(3)).unwrap();
+        let hits = read_hits(written.as_bytes()).unwrap();
+        let expected: Vec<_> = hits.iter().rev().take(2).cloned().collect();
+        assert_eq!(tap.state.top.as_ref().unwrap().largest(), expected);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_dashboard_renders() {
+        use ratatui::backend::TestBackend;
+
+        let pool = default_pool()[..4].to_vec();
+        let mut state = DashboardState::new(SearchProgress::default(), 64);
+        state.record_line("3,5,7,4409,[\"Prime\"],[],[],[],1e-12");
+        state.update(&SearchProgress { tuples: 32, hits: 1 }, &pool);
+        let mut terminal = ratatui::Terminal::new(TestBackend::new(100, 16)).unwrap();
+        terminal.draw(|frame| terminal::render(frame, &state)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let screen: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("32 of 64 tuples, 1 hits"));
+        assert!(screen.contains("F(3, 5, 7) = 4409"));
+        assert!(screen.contains("next (7, 3, 3)"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Sums of squares: primes as a² + b² (and more generally x² + d·y²) and integers as
+//! a² + b² + c².
+//!
+//! Fermat: an odd prime is a sum of two squares exactly when p ≡ 1 (mod 4). Legendre: n is a
+//! sum of three squares exactly when it is not of the form 4^a(8b + 7).
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::modular::sqrt_mod;
+use crate::primality::is_bpsw_prime;
+
+/// The (x, y) with x, y ≥ 0 and x² + d·y² = p, for a prime `p` and d ≥ 1; `None` when
+/// there is none, including when p is not prime.
+///
+/// Cornacchia's algorithm: take a square root r of -d mod p with p/2 < r < p, run Euclid's
+/// algorithm on p and r until the remainder x drops below √p, and accept if (p - x²)/d is a
+/// square. Solutions with p prime are unique, so `None` is definitive.
+pub fn cornacchia(d: u64, p: &BigUint) -> Option<(BigUint, BigUint)> {
+    if d == 0 {
+        return None;
+    }
+    if p == &BigUint::from(2u32) {
+        return match d {
+            1 => Some((BigUint::one(), BigUint::one())),
+            2 => Some((BigUint::zero(), BigUint::one())),
+            _ => None,
+        };
+    }
+    // Tonelli–Shanks may not terminate for a composite modulus
+    if !p.bit(0) || !is_bpsw_prime(p) {
+        return None;
+    }
+    let minus_d = (p - (d % p)) % p;
+    if minus_d.is_zero() {
+        // d ≡ 0 (mod p): only p = d·1² with x = 0
+        return (p == &BigUint::from(d)).then(|| (BigUint::zero(), BigUint::one()));
+    }
+    let root = sqrt_mod(&minus_d, p)?;
+    let (mut r0, mut r1) = (p.clone(), p - root);
+    while &r1 * &r1 > *p {
+        let r2 = &r0 % &r1;
+        r0 = r1;
+        r1 = r2;
+    }
+    let rest = p - &r1 * &r1;
+    if !(&rest % d).is_zero() {
+        return None;
+    }
+    let y2 = rest / d;
+    let y = y2.sqrt();
+    (&y * &y == y2).then_some((r1, y))
+}
+
+/// The (a, b) with a ≥ b ≥ 0 and p = a² + b², for a prime `p` that is 2 or ≡ 1 (mod 4);
+/// `None` otherwise, including when p is not prime.
+pub fn two_square_decomposition(p: &BigUint) -> Option<(BigUint, BigUint)> {
+    let (a, b) = cornacchia(1, p)?;
+    Some(if a >= b { (a, b) } else { (b, a) })
+}
+
+/// Whether `n` is a sum of three squares, i.e. not of the form 4^a(8b + 7).
+pub fn is_sum_of_three_squares(n: &BigUint) -> bool {
+    let Some(twos) = n.trailing_zeros() else {
+        return true;
+    };
+    let odd_part = n >> (twos - twos % 2);
+    odd_part % 8u32 != BigUint::from(7u32)
+}
+
+/// a² + b² = m with a ≥ b when that is quick to find: m zero, a square, a prime, or twice a
+/// prime (using 2(a² + b²) = (a + b)² + (a - b)²).
+fn easy_two_squares(m: &BigUint) -> Option<(BigUint, BigUint)> {
+    let root = m.sqrt();
+    if &root * &root == *m {
+        return Some((root, BigUint::zero()));
+    }
+    if let Some(squares) = two_square_decomposition(m) {
+        return Some(squares);
+    }
+    if m.bit(0) {
+        return None;
+    }
+    let (a, b) = two_square_decomposition(&(m >> 1u32))?;
+    Some((&a + &b, a - b))
+}
+
+/// The (a, b, c) with a ≥ b ≥ c ≥ 0 and n = a² + b² + c², or `None` when n = 4^a(8b + 7).
+///
+/// Factors of 4 are taken out and the result scaled back up. For the rest, c runs down from
+/// √n until n - c² is a square, a prime or twice a prime, which happens after a handful of
+/// steps in practice.
+pub fn three_square_decomposition(n: &BigUint) -> Option<(BigUint, BigUint, BigUint)> {
+    if !is_sum_of_three_squares(n) {
+        return None;
+    }
+    let Some(twos) = n.trailing_zeros() else {
+        return Some((BigUint::zero(), BigUint::zero(), BigUint::zero()));
+    };
+    let scale = twos / 2;
+    let m = n >> (2 * scale);
+    let mut c = m.sqrt();
+    loop {
+        if let Some((a, b)) = easy_two_squares(&(&m - &c * &c)) {
+            let mut squares = [a << scale, b << scale, c << scale];
+            squares.sort_unstable_by(|x, y| y.cmp(x));
+            let [a, b, c] = squares;
+            return Some((a, b, c));
+        }
+        if c.is_zero() {
+            return None;
+        }
+        c -= 1u32;
+    }
+}
+
+/// Header fields appended to `search::CSV_HEADER` with the sums-of-squares columns.
+pub const SQUARES_HEADER: &str = "two_squares,three_squares";
+
+/// The sums-of-squares fields for a prime |N|, formatted like the rest of a results row;
+/// each is empty when |N| has no such decomposition.
+pub fn squares_fields(p: &BigUint) -> String {
+    let two = two_square_decomposition(p).map_or(String::new(), |(a, b)| format!("{:?}", [a, b]));
+    let three =
+        three_square_decomposition(p).map_or(String::new(), |(a, b, c)| format!("{:?}", [a, b, c]));
+    format!("{},{}", two, three)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_integer::Roots;
+
+    #[test]
+    fn test_sums_of_squares() {
+        for n in 0u32..5000 {
+            let big = BigUint::from(n);
+            let brute = (0..=n.sqrt()).any(|a| (0..=a).any(|b| {
+                let rest = n as i64 - (a * a + b * b) as i64;
+                rest >= 0 && (rest as u32).sqrt().pow(2) == rest as u32
+            }));
+            assert_eq!(is_sum_of_three_squares(&big), brute, "{}", n);
+            match three_square_decomposition(&big) {
+                Some((a, b, c)) => {
+                    assert!(a >= b && b >= c);
+                    assert_eq!(&a * &a + &b * &b + &c * &c, big);
+                }
+                None => assert!(!brute, "{} has no decomposition found", n),
+            }
+            if primal::is_prime(n as u64) {
+                let two = two_square_decomposition(&big);
+                assert_eq!(two.is_some(), n == 2 || n % 4 == 1, "{}", n);
+                if let Some((a, b)) = two {
+                    assert_eq!(&a * &a + &b * &b, big);
+                }
+            }
+        }
+        assert_eq!(two_square_decomposition(&BigUint::from(25u32)), None);
+        // 2^127 - 1 ≡ 7 (mod 8), so it needs four squares
+        let mersenne = (BigUint::one() << 127u32) - 1u32;
+        assert!(three_square_decomposition(&mersenne).is_none());
+        let prime = (0u32..)
+            .map(|k| (BigUint::one() << 127u32) + 4 * k + 1u32)
+            .find(is_bpsw_prime)
+            .unwrap();
+        let (a, b) = two_square_decomposition(&prime).unwrap();
+        assert_eq!(&a * &a + &b * &b, prime);
+        assert_eq!(squares_fields(&BigUint::from(13u32)), "[3, 2],[3, 2, 0]");
+        assert_eq!(squares_fields(&BigUint::from(11u32)), ",[3, 1, 1]");
+        assert_eq!(squares_fields(&BigUint::from(7u32)), ",");
+    }
+
+    #[test]
+    fn test_cornacchia() {
+        for d in 1u64..=12 {
+            for p in (2u64..2000).filter(|&p| primal::is_prime(p)) {
+                let brute = (0..=p.sqrt()).any(|y| {
+                    p >= d * y * y && (p - d * y * y).sqrt().pow(2) == p - d * y * y
+                });
+                match cornacchia(d, &BigUint::from(p)) {
+                    Some((x, y)) => assert_eq!(&x * &x + &y * &y * d, BigUint::from(p)),
+                    None => assert!(!brute, "x² + {}y² = {} was missed", d, p),
+                }
+            }
+        }
+        // x² + 2y² takes every prime ≡ 1, 3 (mod 8)
+        let p = (0u64..)
+            .map(|k| BigUint::from(10u32).pow(30) + 8 * k + 1u32)
+            .find(is_bpsw_prime)
+            .unwrap();
+        let (x, y) = cornacchia(2, &p).unwrap();
+        assert_eq!(&x * &x + &y * &y * 2u32, p);
+        assert_eq!(cornacchia(5, &BigUint::from(3u32)), None);
+        assert_eq!(cornacchia(1, &BigUint::from(65u32)), None);
+        assert_eq!(cornacchia(0, &BigUint::from(5u32)), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Differences between two results files, whether from the same form or not.
+//!
+//! Rows are keyed by |N|, so hits that several tuples produce count once. A value's tags are
+//! the union of its rows' N tags, which lets a value recorded as a prime hit in one run and
+//! as a tagged pseudoprime in the other show up as a classification change. Densities are
+//! compared per bit length as each run's share of its own distinct prime hits, so runs of
+//! different sizes can still be lined up.
+
+use num_bigint::BigUint;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::results::ResultRow;
+
+/// A value present in both runs whose N tags differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    pub n: BigUint,
+    pub before: BTreeSet<String>,
+    pub after: BTreeSet<String>,
+}
+
+/// Distinct prime hits of each run with a given bit length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DensityBand {
+    pub bits: u64,
+    pub a: usize,
+    pub b: usize,
+}
+
+/// How run B differs from run A.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultsDiff {
+    /// Distinct prime hits of A and of B
+    pub hits_a: usize,
+    pub hits_b: usize,
+    /// Prime hits of A missing from B, ascending
+    pub only_a: Vec<BigUint>,
+    /// Prime hits of B missing from A, ascending
+    pub only_b: Vec<BigUint>,
+    pub common: usize,
+    /// Values in both runs, ascending, whose tags changed from A to B
+    pub changes: Vec<TagChange>,
+    /// One band per bit length holding a prime hit of either run, ascending
+    pub bands: Vec<DensityBand>,
+}
+
+/// The N tags of each distinct |N|.
+fn tags_by_value(rows: &[ResultRow]) -> BTreeMap<BigUint, BTreeSet<String>> {
+    let mut values: BTreeMap<BigUint, BTreeSet<String>> = BTreeMap::new();
+    for row in rows {
+        let tags = values.entry(row.n.magnitude().clone()).or_default();
+        tags.extend(row.classifications_n.iter().cloned());
+    }
+    values
+}
+
+fn prime_hits(values: &BTreeMap<BigUint, BTreeSet<String>>) -> BTreeSet<&BigUint> {
+    values.iter().filter(|(_, tags)| tags.contains("Prime")).map(|(n, _)| n).collect()
+}
+
+impl ResultsDiff {
+    /// Compare the rows of run A with those of run B.
+    pub fn new(a: &[ResultRow], b: &[ResultRow]) -> Self {
+        let (values_a, values_b) = (tags_by_value(a), tags_by_value(b));
+        let (hits_a, hits_b) = (prime_hits(&values_a), prime_hits(&values_b));
+
+        let changes = values_a
+            .iter()
+            .filter_map(|(n, before)| {
+                let after = values_b.get(n)?;
+                (before != after).then(|| TagChange {
+                    n: n.clone(),
+                    before: before.clone(),
+                    after: after.clone(),
+                })
+            })
+            .collect();
+
+        let mut counts: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+        for n in &hits_a {
+            counts.entry(n.bits()).or_default().0 += 1;
+        }
+        for n in &hits_b {
+            counts.entry(n.bits()).or_default().1 += 1;
+        }
+
+        ResultsDiff {
+            hits_a: hits_a.len(),
+            hits_b: hits_b.len(),
+            only_a: hits_a.difference(&hits_b).map(|&n| n.clone()).collect(),
+            only_b: hits_b.difference(&hits_a).map(|&n| n.clone()).collect(),
+            common: hits_a.intersection(&hits_b).count(),
+            changes,
+            bands: counts.into_iter().map(|(bits, (a, b))| DensityBand { bits, a, b }).collect(),
+        }
+    }
+}
+
+impl DensityBand {
+    /// This band's share of A's and of B's distinct prime hits, 0 for an empty run.
+    pub fn shares(&self, diff: &ResultsDiff) -> (f64, f64) {
+        let share = |count: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64
+            }
+        };
+        (share(self.a, diff.hits_a), share(self.b, diff.hits_b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::parse_row;
+
+    #[test]
+    fn test_results_diff() {
+        let rows = |lines: &[&str]| -> Vec<ResultRow> {
+            lines.iter().map(|line| parse_row(line).unwrap()).collect()
+        };
+        let a = rows(&[
+            "2,2,3,17,Prime,Prime,Prime,Prime",
+            "3,2,2,-17,Prime,Prime,Prime,Prime",
+            "2,3,5,41,Prime,Prime,Prime,Prime",
+            "3,3,3,561,Carmichael,Prime,Prime,Prime",
+            "2,2,7,71,Prime,Prime,Prime,Prime",
+        ]);
+        let b = rows(&[
+            "2,2,3,17,Safe;Prime,Prime,Prime,Prime",
+            "3,3,3,561,Carmichael,Prime,Prime,Prime",
+            "2,2,7,71,Prime,Prime,Prime,Prime",
+            "5,5,5,227,Prime,Prime,Prime,Prime",
+        ]);
+        let diff = ResultsDiff::new(&a, &b);
+        assert_eq!((diff.hits_a, diff.hits_b, diff.common), (3, 3, 2));
+        assert_eq!(diff.only_a, vec![BigUint::from(41u32)]);
+        assert_eq!(diff.only_b, vec![BigUint::from(227u32)]);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].n, BigUint::from(17u32));
+        assert!(diff.changes[0].after.contains("Safe"));
+
+        // 17 has 5 bits, 41 and 71 have 6 and 7, 227 has 8
+        let bands: Vec<(u64, usize, usize)> =
+            diff.bands.iter().map(|band| (band.bits, band.a, band.b)).collect();
+        assert_eq!(bands, vec![(5, 1, 1), (6, 1, 0), (7, 1, 1), (8, 0, 1)]);
+        assert_eq!(diff.bands[1].shares(&diff), (1.0 / 3.0, 0.0));
+        assert_eq!(ResultsDiff::new(&a, &a).changes, vec![]);
+    }
+}
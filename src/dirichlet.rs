@@ -0,0 +1,160 @@
+//! Dirichlet series Σ a(n)·n^(-s) over a finite support, built from hit sets.
+//!
+//! With a(n) the indicator of the hits, the series is a truncated "zeta function of the
+//! hits". Its prime-supported part is compared with the Euler product Π (1 - p^(-s))^(-1)
+//! over the prime hits: log of the product is Σ_p Σ_k p^(-ks)/k, so the difference from
+//! Σ_p p^(-s) is the prime-power correction, which stays small for Re s > 1/2.
+
+use num_bigint::BigUint;
+use num_complex::Complex;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bigfloat::approx_log;
+use crate::primality::is_bpsw_prime;
+
+/// A Dirichlet series with finitely many nonzero coefficients.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DirichletSeries {
+    coefficients: BTreeMap<BigUint, f64>,
+}
+
+/// n^(-s) for n ≥ 1, through ln n so that n need not fit a double.
+fn power(n: &BigUint, s: Complex<f64>) -> Complex<f64> {
+    (-s * approx_log(n)).exp()
+}
+
+impl DirichletSeries {
+    pub fn new() -> Self {
+        DirichletSeries::default()
+    }
+
+    /// The series with a(n) = 1 for each hit n ≥ 1 and 0 elsewhere.
+    pub fn indicator(hits: &BTreeSet<BigUint>) -> Self {
+        hits.iter().fold(DirichletSeries::new(), |series, n| series.coefficient(n.clone(), 1.0))
+    }
+
+    /// Set a(n), replacing any earlier value; a(0) is ignored since 0^(-s) is undefined.
+    pub fn coefficient(mut self, n: BigUint, a: f64) -> Self {
+        if n.bits() > 0 {
+            self.coefficients.insert(n, a);
+        }
+        self
+    }
+
+    /// Keep only the n with `keep(n)`.
+    pub fn restrict<F: Fn(&BigUint) -> bool>(mut self, keep: F) -> Self {
+        self.coefficients.retain(|n, _| keep(n));
+        self
+    }
+
+    /// a(n), zero off the support.
+    pub fn get(&self, n: &BigUint) -> f64 {
+        self.coefficients.get(n).copied().unwrap_or(0.0)
+    }
+
+    /// Number of nonzero coefficients.
+    pub fn len(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// Σ a(n)·n^(-s) over the whole support.
+    pub fn evaluate(&self, s: Complex<f64>) -> Complex<f64> {
+        self.partial_sum(s, None)
+    }
+
+    /// Σ a(n)·n^(-s) over n ≤ `bound`, or the whole support for `None`.
+    pub fn partial_sum(&self, s: Complex<f64>, bound: Option<&BigUint>) -> Complex<f64> {
+        let terms: Box<dyn Iterator<Item = (&BigUint, &f64)>> = match bound {
+            Some(bound) => Box::new(self.coefficients.range(..=bound)),
+            None => Box::new(self.coefficients.iter()),
+        };
+        terms.fold(Complex::new(0.0, 0.0), |sum, (n, a)| sum + power(n, s) * a)
+    }
+
+    /// The primes in the support.
+    pub fn primes(&self) -> impl Iterator<Item = &BigUint> {
+        self.coefficients.keys().filter(|n| is_bpsw_prime(n))
+    }
+
+    /// Compare the prime-supported part of the series with the Euler product over those
+    /// primes at `s`.
+    pub fn euler_comparison(&self, s: Complex<f64>) -> EulerComparison {
+        let mut prime_sum = Complex::new(0.0, 0.0);
+        let mut log_product = Complex::new(0.0, 0.0);
+        for p in self.primes() {
+            let term = power(p, s);
+            prime_sum += term * self.get(p);
+            log_product -= (Complex::new(1.0, 0.0) - term).ln();
+        }
+        EulerComparison {
+            prime_sum,
+            euler_product: log_product.exp(),
+            prime_power_correction: log_product - prime_sum,
+        }
+    }
+}
+
+/// The prime-supported part of a series against the Euler product over the same primes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EulerComparison {
+    /// Σ a(p)·p^(-s) over the primes in the support
+    pub prime_sum: Complex<f64>,
+    /// Π (1 - p^(-s))^(-1) over the same primes
+    pub euler_product: Complex<f64>,
+    /// log of the product minus the prime sum: Σ_p Σ_(k≥2) p^(-ks)/k for an indicator
+    pub prime_power_correction: Complex<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeta::riemann_zeta;
+
+    #[test]
+    fn test_dirichlet_series() {
+        let s = Complex::new(2.0, 0.0);
+        let integers: BTreeSet<BigUint> = (1u32..=100_000).map(BigUint::from).collect();
+        let series = DirichletSeries::indicator(&integers);
+        assert_eq!(series.len(), 100_000);
+        // ζ(2) minus a tail of about 1/N
+        assert!((series.evaluate(s) - riemann_zeta(s)).norm() < 1.1e-5);
+        let bound = BigUint::from(10u32);
+        let head: f64 = (1..=10).map(|n| 1.0 / (n * n) as f64).sum();
+        assert!((series.partial_sum(s, Some(&bound)).re - head).abs() < 1e-12);
+
+        let odd = series.clone().restrict(|n| n.bit(0));
+        assert_eq!(odd.get(&BigUint::from(4u32)), 0.0);
+        assert_eq!(odd.get(&BigUint::from(5u32)), 1.0);
+        let weighted = DirichletSeries::new()
+            .coefficient(BigUint::from(2u32), 3.0)
+            .coefficient(BigUint::from(0u32), 1.0);
+        assert_eq!(weighted.len(), 1);
+        assert!((weighted.evaluate(Complex::new(1.0, 0.0)).re - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_euler_product() {
+        let primes: BTreeSet<BigUint> =
+            primal::Primes::all().take_while(|&p| p < 100_000).map(BigUint::from).collect();
+        let series = DirichletSeries::indicator(&primes);
+        for s in [Complex::new(2.0, 0.0), Complex::new(3.0, 5.0), Complex::new(1.5, -20.0)] {
+            let comparison = series.euler_comparison(s);
+            // Over all primes below 10^5 the product is ζ(s) up to the tail of primes
+            assert!((comparison.euler_product - riemann_zeta(s)).norm() < 1e-3, "{}", s);
+            assert!((comparison.prime_sum - series.evaluate(s)).norm() < 1e-12);
+            let correction = primal::Primes::all()
+                .take_while(|&p| p < 100_000)
+                .flat_map(|p| (2..60).map(move |k| (k as f64, (p as f64).ln())))
+                .map(|(k, log)| (-s * k * log).exp() / k)
+                .fold(Complex::new(0.0, 0.0), |total, term| total + term);
+            assert!((comparison.prime_power_correction - correction).norm() < 1e-9, "{}", s);
+        }
+        let empty = DirichletSeries::new().euler_comparison(Complex::new(2.0, 0.0));
+        assert_eq!(empty.euler_product, Complex::new(1.0, 0.0));
+    }
+}
@@ -0,0 +1,95 @@
+//! Domain-separation tags for every hash the crate computes.
+//!
+//! Each tag is `universal-primes/v1/` followed by the use, and is absorbed length-prefixed
+//! before anything else, so no input to one use can hash to the same state as an input to
+//! another: a transcript entry cannot double as a signing nonce, nor a Bloom filter probe as
+//! a key fingerprint. A change to what a use hashes gets a new version rather than reusing
+//! the tag. RSA-OAEP takes its tag as the OAEP label. The one untagged hash is the SHA3-256
+//! of a secret recorded in share files, which is there to be checked with ordinary tools.
+
+use sha3::digest::Update;
+
+/// Common prefix of every tag, naming the crate and the tag version.
+pub const PREFIX: &str = "universal-primes/v1/";
+
+pub const BLOOM: &[u8] = b"universal-primes/v1/bloom";
+pub const JWK_THUMBPRINT: &[u8] = b"universal-primes/v1/jwk-thumbprint";
+pub const MNEMONIC_CHECKSUM: &[u8] = b"universal-primes/v1/mnemonic-checksum";
+/// Digest of the data a PMPT-HMAC tag or a detached signature covers
+pub const PMPT_MESSAGE: &[u8] = b"universal-primes/v1/pmpt-message";
+/// HKDF salt for the sub-keys of a private point
+pub const PMPT_KDF: &[u8] = b"universal-primes/v1/pmpt-kdf";
+pub const PMPT_ENCRYPTION_KEY: &[u8] = b"universal-primes/v1/pmpt-encryption-key";
+pub const PMPT_KEY_COMMITMENT: &[u8] = b"universal-primes/v1/pmpt-key-commitment";
+/// Seed of a ciphertext's round keys, from the noise sub-key and the message nonce
+pub const PMPT_ROUND_KEYS: &[u8] = b"universal-primes/v1/pmpt-round-keys";
+pub const PMPT_SIGNATURE: &[u8] = b"universal-primes/v1/pmpt-signature";
+pub const PMPT_SIGNATURE_NONCE: &[u8] = b"universal-primes/v1/pmpt-signature-nonce";
+pub const PMPT_SIGNING_KEY: &[u8] = b"universal-primes/v1/pmpt-signing-key";
+pub const PMPT_VERIFYING_KEY: &[u8] = b"universal-primes/v1/pmpt-verifying-key";
+pub const SESSION_KEYS: &[u8] = b"universal-primes/v1/session-keys";
+pub const SESSION_NONCE: &[u8] = b"universal-primes/v1/session-nonce";
+pub const SESSION_STREAM: &[u8] = b"universal-primes/v1/session-stream";
+pub const SESSION_TAG: &[u8] = b"universal-primes/v1/session-tag";
+pub const SESSION_TRANSCRIPT: &[u8] = b"universal-primes/v1/session-transcript";
+pub const SHARE_MAC: &[u8] = b"universal-primes/v1/share-mac";
+pub const TRANSCRIPT_ENTRY: &[u8] = b"universal-primes/v1/transcript-entry";
+pub const TRANSCRIPT_DATA: &[u8] = b"universal-primes/v1/transcript-data";
+/// The OAEP label when a key-encryption key is wrapped with RSA
+pub const WRAP_RSA_OAEP: &[u8] = b"universal-primes/v1/wrap-rsa-oaep";
+pub const WRAP_STREAM: &[u8] = b"universal-primes/v1/wrap-stream";
+pub const WRAP_TAG: &[u8] = b"universal-primes/v1/wrap-tag";
+pub const WRAP_X25519: &[u8] = b"universal-primes/v1/wrap-x25519";
+
+/// Every tag above.
+pub const ALL: &[&[u8]] = &[
+    BLOOM,
+    JWK_THUMBPRINT,
+    MNEMONIC_CHECKSUM,
+    PMPT_MESSAGE,
+    PMPT_KDF,
+    PMPT_ENCRYPTION_KEY,
+    PMPT_KEY_COMMITMENT,
+    PMPT_ROUND_KEYS,
+    PMPT_SIGNATURE,
+    PMPT_SIGNATURE_NONCE,
+    PMPT_SIGNING_KEY,
+    PMPT_VERIFYING_KEY,
+    SESSION_KEYS,
+    SESSION_NONCE,
+    SESSION_STREAM,
+    SESSION_TAG,
+    SESSION_TRANSCRIPT,
+    SHARE_MAC,
+    TRANSCRIPT_ENTRY,
+    TRANSCRIPT_DATA,
+    WRAP_RSA_OAEP,
+    WRAP_STREAM,
+    WRAP_TAG,
+    WRAP_X25519,
+];
+
+/// A fresh hasher that has absorbed `domain`, length-prefixed.
+pub fn tagged<H: Default + Update>(domain: &[u8]) -> H {
+    let mut hasher = H::default();
+    hasher.update(&(domain.len() as u64).to_be_bytes());
+    hasher.update(domain);
+    hasher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha3::{Digest, Sha3_256};
+
+    #[test]
+    fn test_domains_distinct() {
+        for (i, domain) in ALL.iter().enumerate() {
+            assert!(domain.starts_with(PREFIX.as_bytes()));
+            assert!(ALL[..i].iter().all(|other| other != domain));
+        }
+        let digest = |domain| tagged::<Sha3_256>(domain).chain_update(b"data").finalize();
+        assert_ne!(digest(BLOOM), digest(SHARE_MAC));
+        assert_ne!(digest(BLOOM), Sha3_256::digest(b"data"));
+    }
+}
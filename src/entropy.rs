@@ -0,0 +1,110 @@
+//! Pluggable entropy for key generation, S-boxes and Shamir coefficients.
+//!
+//! Implement `EntropySource` for a hardware TRNG, or use `OsEntropy` (the operating system
+//! via getrandom) or `DeterministicEntropy` (a seeded stream for tests). `EntropyRng` adapts
+//! any source to the `Rng` the rest of the crate takes.
+
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("entropy source failed: {0}")]
+pub struct EntropyError(pub String);
+
+/// A source of cryptographically secure random bytes.
+pub trait EntropySource {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<(), EntropyError>;
+}
+
+/// The operating system's generator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<(), EntropyError> {
+        OsRng
+            .try_fill_bytes(dest)
+            .map_err(|e| EntropyError(e.to_string()))
+    }
+}
+
+/// A ChaCha20 stream from a fixed seed, for reproducible tests. Not for real keys.
+#[derive(Debug, Clone)]
+pub struct DeterministicEntropy(ChaCha20Rng);
+
+impl DeterministicEntropy {
+    pub fn new(seed: u64) -> Self {
+        DeterministicEntropy(ChaCha20Rng::seed_from_u64(seed))
+    }
+}
+
+impl EntropySource for DeterministicEntropy {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<(), EntropyError> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// An `EntropySource` used as an `Rng`.
+///
+/// `RngCore` cannot report errors outside `try_fill_bytes`, so the infallible methods panic
+/// if the source fails; a TRNG that can fail health checks should be checked before use.
+pub struct EntropyRng<'a, E: EntropySource + ?Sized>(pub &'a mut E);
+
+impl<E: EntropySource + ?Sized> RngCore for EntropyRng<'_, E> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill(dest).expect("entropy source failed");
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill(dest).map_err(rand::Error::new)
+    }
+}
+
+impl<E: EntropySource + ?Sized> CryptoRng for EntropyRng<'_, E> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmpt::{DynamicSBox, KeyPair};
+    use crate::primality::PrimalityConfig;
+
+    #[test]
+    fn test_entropy_sources() {
+        // The same seed gives the same keys and S-box, Shamir coefficients included
+        let generate = |seed| {
+            let mut entropy = DeterministicEntropy::new(seed);
+            let config = PrimalityConfig::default();
+            let keys = KeyPair::generate_with_entropy(64, &config, &mut entropy);
+            (keys.private_key, DynamicSBox::from_entropy(&mut entropy))
+        };
+        assert_eq!(generate(24), generate(24));
+        assert_ne!(generate(24).0, generate(25).0);
+
+        let mut bytes = [0u8; 32];
+        OsEntropy.fill(&mut bytes).unwrap();
+        assert_ne!(bytes, [0u8; 32]);
+
+        struct Failing;
+        impl EntropySource for Failing {
+            fn fill(&mut self, _: &mut [u8]) -> Result<(), EntropyError> {
+                Err(EntropyError("health check failed".to_string()))
+            }
+        }
+        assert!(EntropyRng(&mut Failing).try_fill_bytes(&mut bytes).is_err());
+    }
+}
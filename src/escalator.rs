@@ -0,0 +1,294 @@
+//! Universality of positive-definite integral quadratic forms via the 290 theorem.
+//!
+//! Bhargava and Hanke showed that a positive-definite integer-valued form represents every
+//! positive integer as soon as it represents the 29 critical integers below, so universality
+//! reduces to a finite search for short vectors.
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive};
+use thiserror::Error;
+
+use std::str::FromStr;
+
+use crate::form::QuadraticForm;
+
+/// The critical integers of the 290 theorem.
+pub const CRITICAL_INTEGERS: [u64; 29] = [
+    1, 2, 3, 5, 6, 7, 10, 13, 14, 15, 17, 19, 21, 22, 23, 26, 29, 30, 31, 34, 35, 37, 42, 58, 93,
+    110, 145, 203, 290,
+];
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum IntegralFormError {
+    #[error("Gram matrix must be square and non-empty")]
+    NotSquare,
+    #[error("Gram matrix must be symmetric")]
+    NotSymmetric,
+    #[error("Gram matrix diagonal must be even for an integer-valued form")]
+    OddDiagonal,
+    #[error("form is not positive definite")]
+    NotPositiveDefinite,
+    #[error("coefficient does not fit in 64 bits")]
+    Overflow,
+    #[error("invalid Gram matrix '{0}', expected rows like 2,1;1,2")]
+    Invalid(String),
+}
+
+/// Integer-valued form Q(x) = ½·xᵀAx given by its Gram matrix A (symmetric, even diagonal).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegralForm {
+    gram: Vec<Vec<i64>>,
+}
+
+impl IntegralForm {
+    pub fn from_gram(gram: Vec<Vec<i64>>) -> Result<Self, IntegralFormError> {
+        let n = gram.len();
+        if n == 0 || gram.iter().any(|row| row.len() != n) {
+            return Err(IntegralFormError::NotSquare);
+        }
+        for (i, row) in gram.iter().enumerate() {
+            if row[i] % 2 != 0 {
+                return Err(IntegralFormError::OddDiagonal);
+            }
+            if (0..i).any(|j| row[j] != gram[j][i]) {
+                return Err(IntegralFormError::NotSymmetric);
+            }
+        }
+        Ok(IntegralForm { gram })
+    }
+
+    /// The diagonal form c₁x₁² + ... + cₙxₙ². Its Gram diagonal is 2cᵢ, so each cᵢ must lie
+    /// within ±`i64::MAX / 2`.
+    pub fn diagonal(coefficients: &[i64]) -> Self {
+        let n = coefficients.len();
+        let gram = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| if i == j { 2 * coefficients[i] } else { 0 })
+                    .collect()
+            })
+            .collect();
+        IntegralForm { gram }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.gram.len()
+    }
+
+    pub fn gram(&self) -> &[Vec<i64>] {
+        &self.gram
+    }
+
+    pub fn evaluate(&self, x: &[i64]) -> i128 {
+        let n = self.dimension();
+        let mut twice = 0i128;
+        for i in 0..n {
+            for j in 0..n {
+                twice += self.gram[i][j] as i128 * x[i] as i128 * x[j] as i128;
+            }
+        }
+        twice / 2
+    }
+
+    /// Sylvester's criterion on the leading principal minors, computed exactly by Bareiss
+    /// elimination. The minors are products of up to n entries, so they are kept as `BigInt`.
+    pub fn is_positive_definite(&self) -> bool {
+        let n = self.dimension();
+        let mut m: Vec<Vec<BigInt>> = self
+            .gram
+            .iter()
+            .map(|row| row.iter().map(|&v| BigInt::from(v)).collect())
+            .collect();
+        let mut previous = BigInt::one();
+        for k in 0..n {
+            // After k Bareiss steps m[k][k] is the (k+1)-th leading principal minor
+            if !m[k][k].is_positive() {
+                return false;
+            }
+            for i in (k + 1)..n {
+                for j in (k + 1)..n {
+                    m[i][j] = (&m[i][j] * &m[k][k] - &m[i][k] * &m[k][j]) / &previous;
+                }
+            }
+            previous = m[k][k].clone();
+        }
+        true
+    }
+
+    /// A vector x with Q(x) = `target`, found by Fincke–Pohst enumeration of the ellipsoid
+    /// Q(x) ≤ target. The form must be positive definite.
+    pub fn represent(&self, target: u64) -> Option<Vec<i64>> {
+        let n = self.dimension();
+        let q = self.cholesky();
+        let mut x = vec![0i64; n];
+        if self.search(&q, n - 1, &mut x, target as f64, target) {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Coefficients q with Q(x) = Σᵢ qᵢᵢ (xᵢ + Σ_{j>i} qᵢⱼ xⱼ)² (Cohen, Algorithm 2.7.6).
+    fn cholesky(&self) -> Vec<Vec<f64>> {
+        let n = self.dimension();
+        let mut q: Vec<Vec<f64>> = self
+            .gram
+            .iter()
+            .map(|row| row.iter().map(|&v| v as f64 / 2.0).collect())
+            .collect();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                q[j][i] = q[i][j];
+                q[i][j] /= q[i][i];
+            }
+            for k in (i + 1)..n {
+                for l in k..n {
+                    q[k][l] -= q[k][i] * q[i][l];
+                }
+            }
+        }
+        q
+    }
+
+    fn search(&self, q: &[Vec<f64>], i: usize, x: &mut [i64], remaining: f64, target: u64) -> bool {
+        // Slack so rounding in the Cholesky coefficients never prunes a genuine solution
+        const EPSILON: f64 = 1e-6;
+        let n = self.dimension();
+        let center: f64 = -((i + 1)..n).map(|j| q[i][j] * x[j] as f64).sum::<f64>();
+        let radius = ((remaining + EPSILON) / q[i][i]).sqrt();
+        let lo = (center - radius).ceil() as i64;
+        let hi = (center + radius).floor() as i64;
+        for xi in lo..=hi {
+            let term = q[i][i] * (xi as f64 - center).powi(2);
+            if term > remaining + EPSILON {
+                continue;
+            }
+            x[i] = xi;
+            if i == 0 {
+                if self.evaluate(x) == target as i128 {
+                    return true;
+                }
+            } else if self.search(q, i - 1, x, remaining - term, target) {
+                return true;
+            }
+        }
+        x[i] = 0;
+        false
+    }
+}
+
+impl TryFrom<&QuadraticForm> for IntegralForm {
+    type Error = IntegralFormError;
+
+    /// The homogeneous part of a search form; the constant g is dropped.
+    fn try_from(form: &QuadraticForm) -> Result<Self, Self::Error> {
+        let [a, b, c, d, e, f, _] = form.coefficients();
+        let v = |c: &num_bigint::BigInt| c.to_i64().ok_or(IntegralFormError::Overflow);
+        let (a, b, c, d, e, f) = (v(a)?, v(b)?, v(c)?, v(d)?, v(e)?, v(f)?);
+        let double = |c: i64| c.checked_mul(2).ok_or(IntegralFormError::Overflow);
+        IntegralForm::from_gram(vec![
+            vec![double(a)?, b, d],
+            vec![b, double(c)?, e],
+            vec![d, e, double(f)?],
+        ])
+    }
+}
+
+impl FromStr for IntegralForm {
+    type Err = IntegralFormError;
+
+    /// Parse a Gram matrix written row by row, e.g. "2,1;1,2" for x² + xy + y².
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let gram = s
+            .split(';')
+            .map(|row| {
+                row.split(',')
+                    .map(|v| v.trim().parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| IntegralFormError::Invalid(s.to_string()))?;
+        IntegralForm::from_gram(gram)
+    }
+}
+
+/// Outcome of the 290 check: each critical integer with a representing vector, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscalatorReport {
+    pub representations: Vec<(u64, Option<Vec<i64>>)>,
+}
+
+impl EscalatorReport {
+    /// Critical integers the form fails to represent.
+    pub fn missing(&self) -> Vec<u64> {
+        self.representations
+            .iter()
+            .filter(|(_, x)| x.is_none())
+            .map(|(t, _)| *t)
+            .collect()
+    }
+
+    /// Whether the form represents every positive integer.
+    pub fn is_universal(&self) -> bool {
+        self.representations.iter().all(|(_, x)| x.is_some())
+    }
+}
+
+/// Test the 29 critical integers against a positive-definite form.
+///
+/// Enumeration cost grows like 290^(n/2), so forms in many variables with large
+/// coefficients can take a while when a critical integer is genuinely missing.
+pub fn check_290(form: &IntegralForm) -> Result<EscalatorReport, IntegralFormError> {
+    if !form.is_positive_definite() {
+        return Err(IntegralFormError::NotPositiveDefinite);
+    }
+    let representations = CRITICAL_INTEGERS
+        .iter()
+        .map(|&t| (t, form.represent(t)))
+        .collect();
+    Ok(EscalatorReport { representations })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagonal_forms() {
+        // Lagrange's four-square theorem
+        let four_squares = check_290(&IntegralForm::diagonal(&[1, 1, 1, 1])).unwrap();
+        assert!(four_squares.is_universal());
+        // 4^a(8b + 7) is never a sum of three squares
+        let three_squares = check_290(&IntegralForm::diagonal(&[1, 1, 1])).unwrap();
+        assert_eq!(three_squares.missing(), vec![7, 15, 23, 31]);
+        // Ramanujan listed x² + 2y² + 5z² + 5w² as universal, but it misses 15
+        let ramanujan = check_290(&IntegralForm::diagonal(&[1, 2, 5, 5])).unwrap();
+        assert_eq!(ramanujan.missing(), vec![15]);
+        for (t, x) in &ramanujan.representations {
+            if let Some(x) = x {
+                assert_eq!(IntegralForm::diagonal(&[1, 2, 5, 5]).evaluate(x), *t as i128);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gram_forms() {
+        // x² + xy + y²
+        let form: IntegralForm = "2,1;1,2".parse().unwrap();
+        assert_eq!(form.evaluate(&[1, 1]), 3);
+        assert!(form.is_positive_definite());
+        assert_eq!("2,3;3,2".parse::<IntegralForm>().map(|f| f.is_positive_definite()), Ok(false));
+        assert_eq!("1,0;0,2".parse::<IntegralForm>(), Err(IntegralFormError::OddDiagonal));
+        assert_eq!(
+            check_290(&IntegralForm::diagonal(&[1, -1])),
+            Err(IntegralFormError::NotPositiveDefinite)
+        );
+        let universal = IntegralForm::try_from(&QuadraticForm::universal()).unwrap();
+        assert!(universal.is_positive_definite());
+
+        // Minors of large entries leave i128
+        let large = IntegralForm::diagonal(&[3_000_000_000_000_000_000; 4]);
+        assert!(large.is_positive_definite());
+        assert_eq!(check_290(&large).map(|report| report.is_universal()), Ok(false));
+    }
+}
@@ -0,0 +1,220 @@
+//! Exporting hits in formats other tools read.
+
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::results::ResultRow;
+
+/// Write the distinct hit values as an OEIS b-file: one `index value` pair per line in
+/// increasing order, numbered from `offset`, after `#` comment lines from `comments`.
+///
+/// At most `limit` terms are written if given; OEIS asks for b-files of no more than 10000
+/// terms. Returns the number of terms written.
+pub fn write_bfile<W: Write>(
+    hits: &BTreeSet<BigUint>,
+    out: &mut W,
+    offset: u64,
+    limit: Option<usize>,
+    comments: &[String],
+) -> io::Result<usize> {
+    for comment in comments {
+        writeln!(out, "# {}", comment)?;
+    }
+    let terms = hits.iter().take(limit.unwrap_or(usize::MAX));
+    let mut written = 0;
+    for (index, value) in (offset..).zip(terms) {
+        writeln!(out, "{} {}", index, value)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid graph format '{0}', expected dot or graphml")]
+pub struct GraphFormatError(pub String);
+
+/// Serialization used by `write_graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// GraphML XML, read by Gephi, yEd, networkx and igraph
+    GraphMl,
+}
+
+impl FromStr for GraphFormat {
+    type Err = GraphFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            _ => Err(GraphFormatError(s.to_string())),
+        }
+    }
+}
+
+/// How a value occurs in the results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeRole {
+    /// Drawn from the pool as x, y or z
+    pub input: bool,
+    /// Emitted as |N| of a hit
+    pub hit: bool,
+}
+
+impl NodeRole {
+    fn kind(&self) -> &'static str {
+        match (self.input, self.hit) {
+            (true, true) => "reentrant",
+            (false, true) => "hit",
+            _ => "input",
+        }
+    }
+}
+
+/// Primes as nodes (by absolute value) with an edge from each of x, y, z to |N| for every
+/// prime hit, labelled with the variable. Values that are both inputs and hits are the
+/// ones that re-enter the pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrimeGraph {
+    pub nodes: BTreeMap<BigUint, NodeRole>,
+    /// (input, hit, variable), without repeats
+    pub edges: BTreeSet<(BigUint, BigUint, char)>,
+}
+
+impl PrimeGraph {
+    pub fn from_rows(rows: &[ResultRow]) -> Self {
+        let mut graph = PrimeGraph::default();
+        for row in rows {
+            let n = row.n.magnitude();
+            graph.nodes.entry(n.clone()).or_default().hit = true;
+            for (variable, value) in [('x', &row.x), ('y', &row.y), ('z', &row.z)] {
+                let value = value.magnitude();
+                graph.nodes.entry(value.clone()).or_default().input = true;
+                graph.edges.insert((value.clone(), n.clone(), variable));
+            }
+        }
+        graph
+    }
+
+    /// Values that are both pool inputs and hits.
+    pub fn reentrant(&self) -> impl Iterator<Item = &BigUint> {
+        self.nodes.iter().filter(|(_, role)| role.input && role.hit).map(|(n, _)| n)
+    }
+}
+
+/// Write the relationship graph of the prime-hit `rows` in `format`.
+pub fn write_graph<W: Write>(
+    rows: &[ResultRow],
+    format: GraphFormat,
+    out: &mut W,
+) -> io::Result<()> {
+    let graph = PrimeGraph::from_rows(rows);
+    match format {
+        GraphFormat::Dot => {
+            writeln!(out, "digraph universal_primes {{")?;
+            for (n, role) in &graph.nodes {
+                let style = match role.kind() {
+                    "reentrant" => ", style=filled, fillcolor=gold",
+                    "hit" => ", shape=box",
+                    _ => "",
+                };
+                writeln!(out, "  \"{}\" [kind={}{}];", n, role.kind(), style)?;
+            }
+            for (input, hit, variable) in &graph.edges {
+                writeln!(out, "  \"{}\" -> \"{}\" [label={}];", input, hit, variable)?;
+            }
+            writeln!(out, "}}")
+        }
+        GraphFormat::GraphMl => {
+            writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(out, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+            writeln!(out, r#"  <key id="kind" for="node" attr.name="kind" attr.type="string"/>"#)?;
+            writeln!(
+                out,
+                r#"  <key id="variable" for="edge" attr.name="variable" attr.type="string"/>"#
+            )?;
+            writeln!(out, r#"  <graph id="universal_primes" edgedefault="directed">"#)?;
+            for (n, role) in &graph.nodes {
+                writeln!(
+                    out,
+                    r#"    <node id="{}"><data key="kind">{}</data></node>"#,
+                    n,
+                    role.kind()
+                )?;
+            }
+            for (input, hit, variable) in &graph.edges {
+                writeln!(
+                    out,
+                    r#"    <edge source="{}" target="{}"><data key="variable">{}</data></edge>"#,
+                    input, hit, variable
+                )?;
+            }
+            writeln!(out, "  </graph>")?;
+            writeln!(out, "</graphml>")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bfile() {
+        let hits: BTreeSet<BigUint> = [5851u32, 1117, 34267].into_iter().map(BigUint::from).collect();
+        let mut out = Vec::new();
+        let comments = vec!["Primes of the form F(x, y, z)".to_string()];
+        assert_eq!(write_bfile(&hits, &mut out, 1, Some(2), &comments).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# Primes of the form F(x, y, z)\n1 1117\n2 5851\n"
+        );
+    }
+
+    #[test]
+    fn test_graph() {
+        let row = |x: i64, y: i64, z: i64, n: i64| ResultRow {
+            x: x.into(),
+            y: y.into(),
+            z: z.into(),
+            n: n.into(),
+            classifications_n: vec!["Prime".to_string()],
+            classifications_x: vec![],
+            classifications_y: vec![],
+            classifications_z: vec![],
+            error_bound: None,
+        };
+        // 7 is both an input and (in the second row) a hit
+        let rows = [row(3, 3, 7, 5851), row(2, -2, 3, -7)];
+        let graph = PrimeGraph::from_rows(&rows);
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.edges.len(), 6);
+        assert_eq!(graph.reentrant().collect::<Vec<_>>(), [&BigUint::from(7u32)]);
+
+        let mut dot = Vec::new();
+        write_graph(&rows, GraphFormat::Dot, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph universal_primes {\n"));
+        assert!(dot.contains("  \"7\" [kind=reentrant, style=filled, fillcolor=gold];\n"));
+        assert!(dot.contains("  \"3\" -> \"5851\" [label=x];\n"));
+        assert!(dot.contains("  \"2\" -> \"7\" [label=y];\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let mut graphml = Vec::new();
+        write_graph(&rows, GraphFormat::GraphMl, &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert!(graphml.contains(r#"<node id="5851"><data key="kind">hit</data></node>"#));
+        assert!(graphml.contains(
+            r#"<edge source="7" target="5851"><data key="variable">z</data></edge>"#
+        ));
+        assert_eq!(graphml.matches("<edge ").count(), 6);
+        assert_eq!("graphml".parse(), Ok(GraphFormat::GraphMl));
+        assert!("gexf".parse::<GraphFormat>().is_err());
+    }
+}
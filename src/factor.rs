@@ -0,0 +1,171 @@
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::primality::is_bpsw_prime;
+
+/// Bound for the trial-division pass before Pollard's rho takes over.
+const TRIAL_DIVISION_BOUND: u32 = 1000;
+
+/// Prime factorization of `n` as sorted `(prime, exponent)` pairs; empty for 0 and 1.
+///
+/// Trial division strips small factors, then Brent's variant of Pollard's rho splits the rest.
+/// Running time grows with the second-largest prime factor, so this is meant for the
+/// moderate sizes the classifiers deal with rather than cryptographic moduli.
+pub fn factorize(n: &BigUint) -> Vec<(BigUint, u32)> {
+    factorize_within(n, u64::MAX).expect("an unlimited budget always factors")
+}
+
+/// `factorize`, giving up with `None` once Pollard's rho has taken `budget` steps. Use it
+/// where `n` comes from outside and an unlucky one must not stall the caller.
+pub fn factorize_within(n: &BigUint, budget: u64) -> Option<Vec<(BigUint, u32)>> {
+    let mut factors = Vec::new();
+    if n.is_zero() {
+        return Some(factors);
+    }
+
+    let mut rest = n.clone();
+    for p in 2..TRIAL_DIVISION_BOUND {
+        if p > 2 && p % 2 == 0 {
+            continue;
+        }
+        let mut exponent = 0;
+        while (&rest % p).is_zero() {
+            rest /= p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            factors.push((BigUint::from(p), exponent));
+        }
+        if rest.is_one() {
+            return Some(factors);
+        }
+    }
+
+    let mut primes = Vec::new();
+    let mut budget = budget;
+    split_into_primes(rest, &mut primes, &mut budget)?;
+    primes.sort();
+    for p in primes {
+        match factors.last_mut() {
+            Some((last, exponent)) if *last == p => *exponent += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    Some(factors)
+}
+
+fn split_into_primes(n: BigUint, primes: &mut Vec<BigUint>, budget: &mut u64) -> Option<()> {
+    if n.is_one() {
+        return Some(());
+    }
+    if is_bpsw_prime(&n) {
+        primes.push(n);
+        return Some(());
+    }
+    let divisor = pollard_brent(&n, budget)?;
+    let cofactor = &n / &divisor;
+    split_into_primes(divisor, primes, budget)?;
+    split_into_primes(cofactor, primes, budget)
+}
+
+/// A nontrivial divisor of the odd composite `n` via Brent's cycle detection, or `None` once
+/// `budget` steps are spent. Every step taken is deducted from `budget`.
+fn pollard_brent(n: &BigUint, budget: &mut u64) -> Option<BigUint> {
+    let one = BigUint::one();
+    // Perfect squares defeat rho's gcd step in some cycles, so peel them off directly
+    let root = n.sqrt();
+    if &(&root * &root) == n {
+        return Some(root);
+    }
+
+    let batch = 128u64;
+    let mut c = BigUint::one();
+    loop {
+        let f = |x: &BigUint| (x * x + &c) % n;
+        let mut y = BigUint::from(2u32);
+        let mut r = 1u64;
+        let mut q = BigUint::one();
+        let mut g = BigUint::one();
+        let mut x = y.clone();
+        let mut ys = y.clone();
+
+        while g.is_one() {
+            // Each round walks 2r steps at most: r to advance y, r more comparing
+            *budget = budget.checked_sub(2 * r)?;
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+            let mut k = 0u64;
+            while k < r && g.is_one() {
+                ys = y.clone();
+                for _ in 0..batch.min(r - k) {
+                    y = f(&y);
+                    let diff = if x > y { &x - &y } else { &y - &x };
+                    q = (q * diff) % n;
+                }
+                g = q.gcd(n);
+                k += batch;
+            }
+            r *= 2;
+        }
+
+        if &g == n {
+            // The batched product overshot; retrace one step at a time
+            loop {
+                ys = f(&ys);
+                let diff = if x > ys { &x - &ys } else { &ys - &x };
+                g = diff.gcd(n);
+                if g != one {
+                    break;
+                }
+            }
+        }
+        if &g != n {
+            return Some(g);
+        }
+        c += 1u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(factors: &[(BigUint, u32)]) -> BigUint {
+        factors
+            .iter()
+            .fold(BigUint::one(), |acc, (p, e)| acc * p.pow(*e))
+    }
+
+    #[test]
+    fn test_factorize_small() {
+        assert!(factorize(&BigUint::one()).is_empty());
+        let factors = factorize(&BigUint::from(360u32));
+        let expected: Vec<(BigUint, u32)> =
+            vec![(2u32.into(), 3), (3u32.into(), 2), (5u32.into(), 1)];
+        assert_eq!(factors, expected);
+    }
+
+    #[test]
+    fn test_factorize_with_rho() {
+        // 2^67 - 1 = 193707721 * 761838257287
+        let n = (BigUint::one() << 67) - BigUint::one();
+        let factors = factorize(&n);
+        assert_eq!(
+            factors,
+            vec![
+                (BigUint::from(193707721u64), 1),
+                (BigUint::from(761838257287u64), 1)
+            ]
+        );
+        let square = BigUint::from(1000003u64).pow(2) * 7u32;
+        assert_eq!(product(&factorize(&square)), square);
+
+        // Rho needs about 2^14 steps for the smaller factor, so a tighter budget gives up
+        assert_eq!(factorize_within(&n, 1 << 10), None);
+        assert_eq!(factorize_within(&n, 1 << 20), Some(factors));
+        assert_eq!(factorize_within(&BigUint::from(360u32), 0).map(|f| f.len()), Some(3));
+    }
+}
@@ -0,0 +1,554 @@
+//! Row filters such as `n.bits() > 40 && tags.contains(Safe)`, applied to hits before they are
+//! written.
+//!
+//! Numbers are `x`, `y`, `z`, `n` and integer literals, combined with `+ - * / %` and the
+//! methods `.bits()` and `.abs()`. Tag lists are `tags` (for N) and `x_tags`, `y_tags`,
+//! `z_tags`, with `.contains(Tag)` and `.len()`. Conditions compare numbers with
+//! `< <= > >= == !=` (or `≤ ≥ ≠`), which chain so `x < y < z` means `x < y && y < z`, and
+//! combine with `&&`, `||`, `!` and parentheses; `gcd(a, b)` is the non-negative greatest
+//! common divisor. Expressions are type checked when parsed, so evaluating a parsed filter
+//! cannot fail.
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, Zero};
+use thiserror::Error;
+
+use std::str::FromStr;
+
+use crate::results::ResultRow;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FilterError {
+    #[error("unexpected '{0}' in filter")]
+    UnexpectedToken(String),
+    #[error("unexpected end of filter")]
+    UnexpectedEnd,
+    #[error("unknown name '{0}' in filter")]
+    UnknownName(String),
+    #[error("expected {expected} in filter, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+/// A column of a results row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    X,
+    Y,
+    Z,
+    N,
+}
+
+impl Column {
+    fn value(self, row: &ResultRow) -> &BigInt {
+        match self {
+            Column::X => &row.x,
+            Column::Y => &row.y,
+            Column::Z => &row.z,
+            Column::N => &row.n,
+        }
+    }
+
+    fn tags(self, row: &ResultRow) -> &[String] {
+        match self {
+            Column::X => &row.classifications_x,
+            Column::Y => &row.classifications_y,
+            Column::Z => &row.classifications_z,
+            Column::N => &row.classifications_n,
+        }
+    }
+}
+
+/// What a filter is evaluated on: a results row, or for a tuple constraint just x, y and z.
+trait Operands {
+    fn value(&self, column: Column) -> &BigInt;
+    fn tags(&self, column: Column) -> &[String];
+}
+
+impl Operands for ResultRow {
+    fn value(&self, column: Column) -> &BigInt {
+        column.value(self)
+    }
+
+    fn tags(&self, column: Column) -> &[String] {
+        column.tags(self)
+    }
+}
+
+/// An input tuple, for filters that pass `Filter::reads_only_tuple`.
+struct Tuple<'a>([&'a BigInt; 3]);
+
+impl Operands for Tuple<'_> {
+    fn value(&self, column: Column) -> &BigInt {
+        match column {
+            Column::X => self.0[0],
+            Column::Y => self.0[1],
+            Column::Z => self.0[2],
+            Column::N => unreachable!("tuple filters never read n"),
+        }
+    }
+
+    fn tags(&self, _: Column) -> &[String] {
+        unreachable!("tuple filters never read tags")
+    }
+}
+
+/// Integer-valued filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Literal(BigInt),
+    Value(Column),
+    TagCount(Column),
+    Bits(Box<Expr>),
+    Abs(Box<Expr>),
+    Neg(Box<Expr>),
+    Arithmetic(char, Box<Expr>, Box<Expr>),
+    Gcd(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Division and remainder by zero evaluate to 0 rather than failing mid-search.
+    pub fn evaluate(&self, row: &ResultRow) -> BigInt {
+        self.evaluate_on(row)
+    }
+
+    fn evaluate_on<O: Operands + ?Sized>(&self, row: &O) -> BigInt {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Value(column) => row.value(*column).clone(),
+            Expr::TagCount(column) => BigInt::from(row.tags(*column).len()),
+            Expr::Bits(e) => BigInt::from(e.evaluate_on(row).bits()),
+            Expr::Abs(e) => e.evaluate_on(row).abs(),
+            Expr::Neg(e) => -e.evaluate_on(row),
+            Expr::Gcd(lhs, rhs) => lhs.evaluate_on(row).gcd(&rhs.evaluate_on(row)),
+            Expr::Arithmetic(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate_on(row), rhs.evaluate_on(row));
+                match op {
+                    '+' => lhs + rhs,
+                    '-' => lhs - rhs,
+                    '*' => lhs * rhs,
+                    _ if rhs.is_zero() => BigInt::zero(),
+                    '/' => lhs.div_floor(&rhs),
+                    _ => lhs.mod_floor(&rhs),
+                }
+            }
+        }
+    }
+
+    /// Whether this reads nothing but x, y and z.
+    pub fn reads_only_tuple(&self) -> bool {
+        match self {
+            Expr::Literal(_) => true,
+            Expr::Value(column) => *column != Column::N,
+            Expr::TagCount(_) => false,
+            Expr::Bits(e) | Expr::Abs(e) | Expr::Neg(e) => e.reads_only_tuple(),
+            Expr::Arithmetic(_, lhs, rhs) | Expr::Gcd(lhs, rhs) => {
+                lhs.reads_only_tuple() && rhs.reads_only_tuple()
+            }
+        }
+    }
+}
+
+/// Boolean filter expression; see the module documentation for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Constant(bool),
+    Compare(&'static str, Expr, Expr),
+    Contains(Column, String),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    pub fn matches(&self, row: &ResultRow) -> bool {
+        self.matches_on(row)
+    }
+
+    /// Whether the tuple (x, y, z) matches, for a filter that passes `reads_only_tuple`.
+    pub fn matches_tuple(&self, x: &BigInt, y: &BigInt, z: &BigInt) -> bool {
+        self.matches_on(&Tuple([x, y, z]))
+    }
+
+    /// Whether this reads nothing but x, y and z, so it can be checked before N is known.
+    pub fn reads_only_tuple(&self) -> bool {
+        match self {
+            Filter::Constant(_) => true,
+            Filter::Compare(_, lhs, rhs) => lhs.reads_only_tuple() && rhs.reads_only_tuple(),
+            Filter::Contains(..) => false,
+            Filter::Not(f) => f.reads_only_tuple(),
+            Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => {
+                lhs.reads_only_tuple() && rhs.reads_only_tuple()
+            }
+        }
+    }
+
+    fn matches_on<O: Operands + ?Sized>(&self, row: &O) -> bool {
+        match self {
+            Filter::Constant(b) => *b,
+            Filter::Compare(op, lhs, rhs) => {
+                let ordering = lhs.evaluate_on(row).cmp(&rhs.evaluate_on(row));
+                match *op {
+                    "<" => ordering.is_lt(),
+                    "<=" => ordering.is_le(),
+                    ">" => ordering.is_gt(),
+                    ">=" => ordering.is_ge(),
+                    "==" => ordering.is_eq(),
+                    _ => ordering.is_ne(),
+                }
+            }
+            Filter::Contains(column, tag) => row.tags(*column).iter().any(|t| t == tag),
+            Filter::Not(f) => !f.matches_on(row),
+            Filter::And(lhs, rhs) => lhs.matches_on(row) && rhs.matches_on(row),
+            Filter::Or(lhs, rhs) => lhs.matches_on(row) || rhs.matches_on(row),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = FilterParser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let filter = parser.condition()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(filter),
+            Some(token) => Err(FilterError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(BigInt),
+    Name(String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+/// Operators, longest first so `<=` is not read as `<`.
+const OPERATORS: [&str; 18] = [
+    "&&", "||", "<=", ">=", "==", "!=", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", ".", ",",
+];
+
+/// Mathematical spellings of comparison operators.
+const ALIASES: [(&str, &str); 3] = [("≤", "<="), ("≥", ">="), ("≠", "!=")];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..len].parse().unwrap()));
+            len
+        } else if c.is_alphabetic() || c == '_' || c == '"' {
+            // Tag names may be quoted
+            let name = rest.trim_start_matches('"');
+            let len = name
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(name.len());
+            if len == 0 {
+                return Err(FilterError::UnexpectedToken(c.to_string()));
+            }
+            tokens.push(Token::Name(name[..len].to_string()));
+            let quotes = rest.len() - name.len();
+            quotes + len + usize::from(quotes > 0 && name[len..].starts_with('"'))
+        } else if let Some((alias, op)) = ALIASES.iter().find(|(a, _)| rest.starts_with(a)) {
+            tokens.push(Token::Op(op));
+            alias.len()
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| FilterError::UnexpectedToken(c.to_string()))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// A parsed operand, typed so mismatches are caught before any row is seen.
+enum Operand {
+    Number(Expr),
+    Condition(Filter),
+    Tags(Column),
+}
+
+impl Operand {
+    fn kind(&self) -> &'static str {
+        match self {
+            Operand::Number(_) => "a number",
+            Operand::Condition(_) => "a condition",
+            Operand::Tags(_) => "a tag list",
+        }
+    }
+
+    fn number(self) -> Result<Expr, FilterError> {
+        match self {
+            Operand::Number(e) => Ok(e),
+            other => Err(FilterError::TypeMismatch {
+                expected: "a number",
+                found: other.kind(),
+            }),
+        }
+    }
+
+    fn condition(self) -> Result<Filter, FilterError> {
+        match self {
+            Operand::Condition(f) => Ok(f),
+            other => Err(FilterError::TypeMismatch {
+                expected: "a condition",
+                found: other.kind(),
+            }),
+        }
+    }
+}
+
+/// Recursive-descent parser over the token stream.
+struct FilterParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl FilterParser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), FilterError> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(found)) if *found == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(FilterError::UnexpectedToken(token.to_string())),
+            None => Err(FilterError::UnexpectedEnd),
+        }
+    }
+
+    // condition := conjunction ('||' conjunction)*
+    fn condition(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.conjunction()?;
+        while self.peek_op() == Some("||") {
+            self.pos += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.conjunction()?));
+        }
+        Ok(filter)
+    }
+
+    // conjunction := negation ('&&' negation)*
+    fn conjunction(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.negation()?;
+        while self.peek_op() == Some("&&") {
+            self.pos += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.negation()?));
+        }
+        Ok(filter)
+    }
+
+    // negation := '!' negation | comparison
+    fn negation(&mut self) -> Result<Filter, FilterError> {
+        if self.peek_op() == Some("!") {
+            self.pos += 1;
+            return Ok(Filter::Not(Box::new(self.negation()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison_op(&self) -> Option<&'static str> {
+        self.peek_op().filter(|op| matches!(*op, "<" | "<=" | ">" | ">=" | "==" | "!="))
+    }
+
+    // comparison := sum (('<' | '<=' | '>' | '>=' | '==' | '!=') sum)*
+    fn comparison(&mut self) -> Result<Filter, FilterError> {
+        let lhs = self.sum()?;
+        if self.comparison_op().is_none() {
+            return lhs.condition();
+        }
+        // A chain compares each neighbouring pair
+        let mut lhs = lhs.number()?;
+        let mut chain: Option<Filter> = None;
+        while let Some(op) = self.comparison_op() {
+            self.pos += 1;
+            let rhs = self.sum()?.number()?;
+            let compare = Filter::Compare(op, lhs, rhs.clone());
+            chain = Some(match chain {
+                Some(chain) => Filter::And(Box::new(chain), Box::new(compare)),
+                None => compare,
+            });
+            lhs = rhs;
+        }
+        Ok(chain.expect("at least one comparison"))
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Operand, FilterError> {
+        let mut lhs = self.product()?;
+        while let Some(op @ ("+" | "-")) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.product()?.number()?;
+            lhs = Operand::Number(arithmetic(op, lhs.number()?, rhs));
+        }
+        Ok(lhs)
+    }
+
+    // product := unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<Operand, FilterError> {
+        let mut lhs = self.unary()?;
+        while let Some(op @ ("*" | "/" | "%")) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.unary()?.number()?;
+            lhs = Operand::Number(arithmetic(op, lhs.number()?, rhs));
+        }
+        Ok(lhs)
+    }
+
+    // unary := '-' unary | postfix
+    fn unary(&mut self) -> Result<Operand, FilterError> {
+        if self.peek_op() == Some("-") {
+            self.pos += 1;
+            let operand = self.unary()?.number()?;
+            return Ok(Operand::Number(Expr::Neg(Box::new(operand))));
+        }
+        self.postfix()
+    }
+
+    // postfix := atom ('.' name '(' name? ')')*
+    fn postfix(&mut self) -> Result<Operand, FilterError> {
+        let mut operand = self.atom()?;
+        while self.peek_op() == Some(".") {
+            self.pos += 1;
+            let method = self.name()?;
+            self.expect_op("(")?;
+            operand = match (method.as_str(), operand) {
+                ("contains", Operand::Tags(column)) => {
+                    Operand::Condition(Filter::Contains(column, self.name()?))
+                }
+                ("len", Operand::Tags(column)) => Operand::Number(Expr::TagCount(column)),
+                ("bits", Operand::Number(e)) => Operand::Number(Expr::Bits(Box::new(e))),
+                ("abs", Operand::Number(e)) => Operand::Number(Expr::Abs(Box::new(e))),
+                _ => return Err(FilterError::UnknownName(method)),
+            };
+            self.expect_op(")")?;
+        }
+        Ok(operand)
+    }
+
+    // atom := number | name | 'gcd' '(' sum ',' sum ')' | '(' condition-or-sum ')'
+    fn atom(&mut self) -> Result<Operand, FilterError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(FilterError::UnexpectedEnd)?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Operand::Number(Expr::Literal(n))),
+            Token::Name(name) => match name.as_str() {
+                "x" => Ok(Operand::Number(Expr::Value(Column::X))),
+                "y" => Ok(Operand::Number(Expr::Value(Column::Y))),
+                "z" => Ok(Operand::Number(Expr::Value(Column::Z))),
+                "n" => Ok(Operand::Number(Expr::Value(Column::N))),
+                "tags" | "n_tags" => Ok(Operand::Tags(Column::N)),
+                "x_tags" => Ok(Operand::Tags(Column::X)),
+                "y_tags" => Ok(Operand::Tags(Column::Y)),
+                "z_tags" => Ok(Operand::Tags(Column::Z)),
+                "true" => Ok(Operand::Condition(Filter::Constant(true))),
+                "false" => Ok(Operand::Condition(Filter::Constant(false))),
+                "gcd" => {
+                    self.expect_op("(")?;
+                    let lhs = self.sum()?.number()?;
+                    self.expect_op(",")?;
+                    let rhs = self.sum()?.number()?;
+                    self.expect_op(")")?;
+                    Ok(Operand::Number(Expr::Gcd(Box::new(lhs), Box::new(rhs))))
+                }
+                _ => Err(FilterError::UnknownName(name)),
+            },
+            Token::Op("(") => {
+                // Parentheses may hold either a condition or an arithmetic expression
+                let start = self.pos;
+                let operand = match self.condition() {
+                    Ok(filter) => Operand::Condition(filter),
+                    Err(_) => {
+                        self.pos = start;
+                        self.sum()?
+                    }
+                };
+                self.expect_op(")")?;
+                Ok(operand)
+            }
+            token => Err(FilterError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, FilterError> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                Ok(name.clone())
+            }
+            Some(token) => Err(FilterError::UnexpectedToken(token.to_string())),
+            None => Err(FilterError::UnexpectedEnd),
+        }
+    }
+}
+
+fn arithmetic(op: &str, lhs: Expr, rhs: Expr) -> Expr {
+    let op = op.chars().next().unwrap();
+    Expr::Arithmetic(op, Box::new(lhs), Box::new(rhs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::parse_row;
+
+    #[test]
+    fn test_filters() {
+        let row = parse_row(
+            r#"3,3,83,589533,["Prime"],["Germain", "Prime"],["Germain", "Prime"],["Germain", "Safe", "Prime"],0e0"#,
+        )
+        .unwrap();
+        let matches = |filter: &str| filter.parse::<Filter>().unwrap().matches(&row);
+        assert!(matches("n.bits() > 19 && z_tags.contains(Safe)"));
+        assert!(!matches("tags.contains(\"Safe\")"));
+        assert!(matches("(n - 1) % 4 == 0 || !(x == y)"));
+        assert!(matches("z_tags.len() == 3 && -x < 0"));
+        assert!(matches("(x + y) * 2 == 12"));
+        assert!(matches!(
+            "n && x".parse::<Filter>(),
+            Err(FilterError::TypeMismatch { expected: "a condition", .. })
+        ));
+        assert!(matches!("n >".parse::<Filter>(), Err(FilterError::UnexpectedEnd)));
+        assert!(matches!("w > 1".parse::<Filter>(), Err(FilterError::UnknownName(_))));
+
+        assert!(matches("3 ≤ x ≤ y ≠ z"));
+        assert!(!matches("x < y < z"));
+        assert!(matches("gcd(x * 2, z - 1) == 2 && gcd(-4, 6) == 2"));
+        let tuple: Filter = "x < y < z && gcd(x, y) == 1 && x % 4 == 3".parse().unwrap();
+        assert!(tuple.reads_only_tuple());
+        let (x, y, z) = (BigInt::from(3), BigInt::from(5), BigInt::from(7));
+        assert!(tuple.matches_tuple(&x, &y, &z));
+        assert!(!tuple.matches_tuple(&y, &x, &z));
+        assert!(!"x < n".parse::<Filter>().unwrap().reads_only_tuple());
+        assert!(!"x_tags.len() > 0".parse::<Filter>().unwrap().reads_only_tuple());
+    }
+}
@@ -0,0 +1,107 @@
+use num_bigint::{BigInt, BigUint};
+use num_traits::Signed;
+use thiserror::Error;
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("expected seven comma-separated coefficients a,b,c,d,e,f,g, got '{0}'")]
+pub struct FormParseError(pub String);
+
+/// Ternary form a·x² + b·xy + c·y² + d·xz + e·yz + f·z² + g evaluated by the search.
+///
+/// Coefficients are signed; forms whose coefficients are all non-negative can also be
+/// evaluated on unsigned inputs through `evaluate_unsigned`, which skips sign handling.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuadraticForm {
+    pub a: BigInt,
+    pub b: BigInt,
+    pub c: BigInt,
+    pub d: BigInt,
+    pub e: BigInt,
+    pub f: BigInt,
+    pub g: BigInt,
+}
+
+impl QuadraticForm {
+    pub fn new(coefficients: [i64; 7]) -> Self {
+        let [a, b, c, d, e, f, g] = coefficients.map(BigInt::from);
+        QuadraticForm { a, b, c, d, e, f, g }
+    }
+
+    /// The form the universal prime search was built around: (5, 7, 11, 23, 47, 83, 107).
+    pub fn universal() -> Self {
+        QuadraticForm::new([5, 7, 11, 23, 47, 83, 107])
+    }
+
+    pub fn evaluate(&self, x: &BigInt, y: &BigInt, z: &BigInt) -> BigInt {
+        &self.a * x * x
+            + &self.b * x * y
+            + &self.c * y * y
+            + &self.d * x * z
+            + &self.e * y * z
+            + &self.f * z * z
+            + &self.g
+    }
+
+    /// Whether every coefficient is non-negative, so `evaluate_unsigned` applies.
+    pub fn is_nonnegative(&self) -> bool {
+        self.coefficients().iter().all(|c| !c.is_negative())
+    }
+
+    /// Unsigned fast path of `evaluate` for non-negative forms and inputs.
+    ///
+    /// Works on the coefficient magnitudes, so the result is only meaningful when
+    /// `is_nonnegative()` holds.
+    pub fn evaluate_unsigned(&self, x: &BigUint, y: &BigUint, z: &BigUint) -> BigUint {
+        debug_assert!(self.is_nonnegative());
+        self.a.magnitude() * x * x
+            + self.b.magnitude() * x * y
+            + self.c.magnitude() * y * y
+            + self.d.magnitude() * x * z
+            + self.e.magnitude() * y * z
+            + self.f.magnitude() * z * z
+            + self.g.magnitude()
+    }
+
+    /// Coefficients in (a, b, c, d, e, f, g) order.
+    pub fn coefficients(&self) -> [&BigInt; 7] {
+        [&self.a, &self.b, &self.c, &self.d, &self.e, &self.f, &self.g]
+    }
+}
+
+impl Default for QuadraticForm {
+    fn default() -> Self {
+        QuadraticForm::universal()
+    }
+}
+
+impl fmt::Display for QuadraticForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms = ["x^2", "xy", "y^2", "xz", "yz", "z^2", ""];
+        write!(f, "{}{}", self.a, terms[0])?;
+        for (coefficient, term) in self.coefficients().iter().zip(terms).skip(1) {
+            let sign = if coefficient.is_negative() { '-' } else { '+' };
+            write!(f, " {} {}{}", sign, coefficient.magnitude(), term)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for QuadraticForm {
+    type Err = FormParseError;
+
+    /// Parse "a,b,c,d,e,f,g", e.g. "5,7,11,23,47,83,107" or "1,0,1,0,0,-1,0".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coefficients = s
+            .split(',')
+            .map(|c| c.trim().parse::<BigInt>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| FormParseError(s.to_string()))?;
+        let [a, b, c, d, e, f, g]: [BigInt; 7] = coefficients
+            .try_into()
+            .map_err(|_| FormParseError(s.to_string()))?;
+        Ok(QuadraticForm { a, b, c, d, e, f, g })
+    }
+}
@@ -0,0 +1,1096 @@
+//! Local (p-adic) analysis of the search's quadratic forms.
+//!
+//! Counting solutions of F(x, y, z) ≡ n modulo p^k gives the local representation densities
+//! that heuristic hit-count predictions are built from. For a ternary form the density at p^k
+//! is #solutions / p^(2k); by Hensel's lemma it stabilises once k exceeds the p-adic valuation
+//! of the discriminant, which the reports flag.
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::factor::factorize;
+use crate::form::QuadraticForm;
+use crate::modular::{jacobi, mod_inverse};
+use crate::represent::is_positive_definite;
+
+/// Largest modulus for which solutions are enumerated exhaustively (m³ evaluations).
+pub const MAX_LOCAL_MODULUS: u64 = 512;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LocalPrimeError {
+    #[error("invalid prime '{0}'")]
+    Invalid(String),
+    #[error("{0} is not a prime")]
+    NotPrime(u64),
+    #[error("{0} is above the largest local modulus {}", MAX_LOCAL_MODULUS)]
+    TooLarge(u64),
+}
+
+/// Parse a prime to analyse locally: at least 2, prime and at most `MAX_LOCAL_MODULUS`.
+pub fn parse_local_prime(input: &str) -> Result<u64, LocalPrimeError> {
+    let p: u64 = input.trim().parse().map_err(|_| LocalPrimeError::Invalid(input.to_string()))?;
+    if !primal::is_prime(p) {
+        return Err(LocalPrimeError::NotPrime(p));
+    }
+    if p > MAX_LOCAL_MODULUS {
+        return Err(LocalPrimeError::TooLarge(p));
+    }
+    Ok(p)
+}
+
+/// Representation data for one prime power p^k.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalReport {
+    pub prime: u64,
+    pub exponent: u32,
+    /// Solution counts of F(x, y, z) ≡ n (mod p^k), indexed by n
+    pub counts: Vec<u64>,
+    /// Every residue class mod p^k is represented
+    pub universal: bool,
+    /// The homogeneous part represents zero nontrivially over Q_p
+    pub isotropic: bool,
+    /// Densities of residues prime to p match those at p^(k-1), so lifting has stabilised
+    pub stable: bool,
+}
+
+impl LocalReport {
+    pub fn modulus(&self) -> u64 {
+        self.prime.pow(self.exponent)
+    }
+
+    /// Local density δ(n) = #{F ≡ n mod p^k} / p^(2k).
+    pub fn density(&self, n: u64) -> f64 {
+        let m = self.modulus();
+        self.counts[(n % m) as usize] as f64 / (m * m) as f64
+    }
+
+    /// Share of (x, y, z) tuples whose value is not divisible by p.
+    pub fn unit_fraction(&self) -> f64 {
+        let m = self.modulus();
+        let divisible: u64 = self
+            .counts
+            .iter()
+            .step_by(self.prime as usize)
+            .sum();
+        1.0 - divisible as f64 / (m * m * m) as f64
+    }
+}
+
+fn reduce(c: &BigInt, m: u64) -> u64 {
+    c.mod_floor(&BigInt::from(m)).to_u64().unwrap_or(0)
+}
+
+/// Solution counts of F(x, y, z) ≡ n (mod m) for every residue n.
+pub fn representation_counts(form: &QuadraticForm, m: u64) -> Vec<u64> {
+    assert!(
+        (1..=MAX_LOCAL_MODULUS).contains(&m),
+        "modulus must be in 1..={}",
+        MAX_LOCAL_MODULUS
+    );
+    let [a, b, c, d, e, f, g] = form.coefficients().map(|c| reduce(c, m));
+    let mut counts = vec![0u64; m as usize];
+    for x in 0..m {
+        let x_part = (a * x % m * x + g) % m;
+        for y in 0..m {
+            let xy_part = (x_part + b * x % m * y + c * y % m * y) % m;
+            for z in 0..m {
+                let value = (xy_part + d * x % m * z + e * y % m * z + f * z % m * z) % m;
+                counts[value as usize] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Local reports for p, p², ..., p^max_exponent (stopping early past `MAX_LOCAL_MODULUS`).
+pub fn local_reports(form: &QuadraticForm, p: u64, max_exponent: u32) -> Vec<LocalReport> {
+    let isotropic = is_isotropic_at(form, p);
+    let mut reports: Vec<LocalReport> = Vec::new();
+    for k in 1..=max_exponent {
+        let m = p.pow(k);
+        if m > MAX_LOCAL_MODULUS {
+            break;
+        }
+        let counts = representation_counts(form, m);
+        let stable = match reports.last() {
+            Some(previous) => {
+                let previous_m = previous.counts.len();
+                (0..m as usize)
+                    .filter(|n| n % p as usize != 0)
+                    .all(|n| counts[n] == previous.counts[n % previous_m] * p * p)
+            }
+            None => false,
+        };
+        reports.push(LocalReport {
+            prime: p,
+            exponent: k,
+            universal: counts.iter().all(|&c| c > 0),
+            counts,
+            isotropic,
+            stable,
+        });
+    }
+    reports
+}
+
+/// Heuristic correction factor ∏ (unit fraction at p) / (1 - 1/p) over `primes`,
+/// comparing how often F avoids small divisors against a random integer. Entries that are
+/// not primes of at most `MAX_LOCAL_MODULUS` are skipped.
+pub fn local_density_factor(form: &QuadraticForm, primes: &[u64]) -> f64 {
+    primes
+        .iter()
+        .filter(|&&p| p <= MAX_LOCAL_MODULUS && primal::is_prime(p))
+        .map(|&p| {
+            let counts = representation_counts(form, p);
+            let report = LocalReport {
+                prime: p,
+                exponent: 1,
+                universal: false,
+                counts,
+                isotropic: false,
+                stable: false,
+            };
+            report.unit_fraction() / (1.0 - 1.0 / p as f64)
+        })
+        .product()
+}
+
+/// Whether the homogeneous part of `form` represents zero nontrivially over Q_p.
+///
+/// Diagonalises the Gram matrix of 2Q over Q and applies the rank-3 criterion
+/// (-1, -d)_p = ∏_{i<j} (a_i, a_j)_p. Degenerate forms are always isotropic.
+pub fn is_isotropic_at(form: &QuadraticForm, p: u64) -> bool {
+    let diagonal = match diagonalize(form) {
+        Some(diagonal) => diagonal,
+        None => return true,
+    };
+    let d = &diagonal[0] * &diagonal[1] * &diagonal[2];
+    let mut epsilon = 1;
+    for i in 0..3 {
+        for j in (i + 1)..3 {
+            epsilon *= hilbert_symbol(&diagonal[i], &diagonal[j], p);
+        }
+    }
+    hilbert_symbol(&BigInt::from(-1), &-d, p) == epsilon
+}
+
+/// Square-class representatives of a rational diagonalisation of the Gram matrix of 2Q,
+/// or `None` when the form is degenerate.
+pub(crate) fn diagonalize(form: &QuadraticForm) -> Option<[BigInt; 3]> {
+    let (a, b, c, d, e, f) = (
+        form.a.clone(),
+        form.b.clone(),
+        form.c.clone(),
+        form.d.clone(),
+        form.e.clone(),
+        form.f.clone(),
+    );
+    // Entries are fractions (numerator, denominator) with positive denominators
+    let one = BigInt::one();
+    let mut m: Vec<Vec<(BigInt, BigInt)>> = vec![
+        vec![(&a * 2, one.clone()), (b.clone(), one.clone()), (d.clone(), one.clone())],
+        vec![(b, one.clone()), (&c * 2, one.clone()), (e.clone(), one.clone())],
+        vec![(d, one.clone()), (e, one.clone()), (&f * 2, one.clone())],
+    ];
+
+    let mut diagonal = Vec::with_capacity(3);
+    for i in 0..3 {
+        if m[i][i].0.is_zero() {
+            if let Some(j) = ((i + 1)..3).find(|&j| !m[j][j].0.is_zero()) {
+                m.swap(i, j);
+                for row in m.iter_mut() {
+                    row.swap(i, j);
+                }
+            } else if let Some(j) = ((i + 1)..3).find(|&j| !m[i][j].0.is_zero()) {
+                // Replace basis vector i by e_i + e_j, making the pivot 2·m[i][j]
+                let row_j = m[j].clone();
+                for (entry, other) in m[i].iter_mut().zip(row_j.iter()) {
+                    *entry = frac_add(entry, other);
+                }
+                for row in m.iter_mut() {
+                    row[i] = frac_add(&row[i], &row[j]);
+                }
+            } else {
+                return None;
+            }
+        }
+        let pivot = m[i][i].clone();
+        for j in (i + 1)..3 {
+            let factor = frac_div(&m[j][i], &pivot);
+            let row_i = m[i].clone();
+            for (entry, other) in m[j].iter_mut().zip(row_i.iter()) {
+                *entry = frac_sub(entry, &frac_mul(&factor, other));
+            }
+            for row in m.iter_mut() {
+                let delta = frac_mul(&factor, &row[i]);
+                row[j] = frac_sub(&row[j], &delta);
+            }
+        }
+        // num/den lies in the same square class as num·den
+        diagonal.push(&pivot.0 * &pivot.1);
+    }
+    Some([diagonal[0].clone(), diagonal[1].clone(), diagonal[2].clone()])
+}
+
+fn frac_normalize(num: BigInt, den: BigInt) -> (BigInt, BigInt) {
+    let g = num.gcd(&den);
+    let (mut num, mut den) = if g.is_zero() { (num, den) } else { (num / &g, den / &g) };
+    if den.is_negative() {
+        num = -num;
+        den = -den;
+    }
+    (num, den)
+}
+
+fn frac_add(x: &(BigInt, BigInt), y: &(BigInt, BigInt)) -> (BigInt, BigInt) {
+    frac_normalize(&x.0 * &y.1 + &y.0 * &x.1, &x.1 * &y.1)
+}
+
+fn frac_sub(x: &(BigInt, BigInt), y: &(BigInt, BigInt)) -> (BigInt, BigInt) {
+    frac_normalize(&x.0 * &y.1 - &y.0 * &x.1, &x.1 * &y.1)
+}
+
+fn frac_mul(x: &(BigInt, BigInt), y: &(BigInt, BigInt)) -> (BigInt, BigInt) {
+    frac_normalize(&x.0 * &y.0, &x.1 * &y.1)
+}
+
+fn frac_div(x: &(BigInt, BigInt), y: &(BigInt, BigInt)) -> (BigInt, BigInt) {
+    frac_normalize(&x.0 * &y.1, &x.1 * &y.0)
+}
+
+/// Split nonzero `a` into (p-adic valuation, unit part).
+fn split_valuation(a: &BigInt, p: u64) -> (u32, BigInt) {
+    // Either would divide forever
+    assert!(p >= 2 && !a.is_zero(), "valuation of {} at {} is undefined", a, p);
+    let mut unit = a.clone();
+    let mut valuation = 0;
+    while (&unit % p).is_zero() {
+        unit /= p;
+        valuation += 1;
+    }
+    (valuation, unit)
+}
+
+/// Hilbert symbol (a, b)_p for nonzero integers.
+pub fn hilbert_symbol(a: &BigInt, b: &BigInt, p: u64) -> i32 {
+    let (alpha, u) = split_valuation(a, p);
+    let (beta, v) = split_valuation(b, p);
+    if p == 2 {
+        let epsilon = |u: &BigInt| -> u32 {
+            if u.mod_floor(&BigInt::from(4)).is_one() {
+                0
+            } else {
+                1
+            }
+        };
+        let omega = |u: &BigInt| -> u32 {
+            match u.mod_floor(&BigInt::from(8)).to_u32() {
+                Some(1) | Some(7) => 0,
+                _ => 1,
+            }
+        };
+        let exponent = epsilon(&u) * epsilon(&v) + alpha * omega(&v) + beta * omega(&u);
+        return if exponent % 2 == 0 { 1 } else { -1 };
+    }
+
+    let p_big = BigUint::from(p);
+    let mut symbol = if (alpha * beta) % 2 == 1 && p % 4 == 3 { -1 } else { 1 };
+    if beta % 2 == 1 {
+        symbol *= jacobi(&u, &p_big);
+    }
+    if alpha % 2 == 1 {
+        symbol *= jacobi(&v, &p_big);
+    }
+    symbol
+}
+
+/// Half the determinant of the Gram matrix [[2a, b, d], [b, 2c, e], [d, e, 2f]] of the
+/// homogeneous part: the discriminant of a ternary form, an integer since that matrix is even.
+/// It is 4 for x² + y² + z² and zero exactly for degenerate forms.
+pub fn discriminant(form: &QuadraticForm) -> BigInt {
+    let [a, b, c, d, e, f, _] = form.coefficients();
+    let (a2, c2, f2) = (a * 2u32, c * 2u32, f * 2u32);
+    let determinant =
+        &a2 * (&c2 * &f2 - e * e) - b * (b * &f2 - e * d) + d * (b * e - &c2 * d);
+    determinant / 2u32
+}
+
+/// Discriminant b² - 4ac of the binary form a·u² + b·uv + c·v².
+pub fn binary_discriminant(a: &BigInt, b: &BigInt, c: &BigInt) -> BigInt {
+    b * b - a * c * 4u32
+}
+
+/// The binary forms obtained by setting one variable to zero, named by the two that remain:
+/// (a, b, c) in x, y; (a, d, f) in x, z; (c, e, f) in y, z.
+pub fn binary_subforms(form: &QuadraticForm) -> [(&'static str, [BigInt; 3]); 3] {
+    let [a, b, c, d, e, f, _] = form.coefficients().map(|c| c.clone());
+    [
+        ("xy", [a.clone(), b, c.clone()]),
+        ("xz", [a, d, f.clone()]),
+        ("yz", [c, e, f]),
+    ]
+}
+
+/// Largest |Δ| for which `class_number` enumerates reduced forms (about |Δ| steps).
+pub const MAX_CLASS_NUMBER_DISCRIMINANT: u64 = 100_000_000;
+
+/// The class number h(Δ) of primitive positive-definite binary forms of negative discriminant
+/// Δ ≡ 0, 1 (mod 4), by counting reduced forms |b| ≤ a ≤ c (with b ≥ 0 when |b| = a or
+/// a = c). `None` for Δ ≥ 0, for Δ ≡ 2, 3 (mod 4), or past `MAX_CLASS_NUMBER_DISCRIMINANT`.
+pub fn class_number(discriminant: &BigInt) -> Option<u64> {
+    if !discriminant.is_negative() {
+        return None;
+    }
+    let delta = discriminant.magnitude().to_u64()?;
+    if delta > MAX_CLASS_NUMBER_DISCRIMINANT || delta % 4 == 1 || delta % 4 == 2 {
+        return None;
+    }
+    let delta = delta as i64;
+    let mut count = 0;
+    // Reduced forms have 3a² ≤ |Δ|, and b ≡ Δ (mod 2)
+    let mut a = 1i64;
+    while 3 * a * a <= delta {
+        for b in (-a + 1..=a).filter(|b| (b - delta).rem_euclid(2) == 0) {
+            let ac = b * b + delta;
+            if ac % (4 * a) != 0 {
+                continue;
+            }
+            let c = ac / (4 * a);
+            if c < a || (b < 0 && a == c) {
+                continue;
+            }
+            if a.gcd(&b).gcd(&c) == 1 {
+                count += 1;
+            }
+        }
+        a += 1;
+    }
+    Some(count)
+}
+
+/// Arithmetic invariants of a search form, for normalising hit densities across forms.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormInvariants {
+    /// `discriminant` of the ternary form
+    pub discriminant: String,
+    pub binary: Vec<BinaryInvariants>,
+}
+
+/// Discriminant and class number of one binary sub-form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryInvariants {
+    /// The variables left, e.g. "xy" for z = 0
+    pub variables: String,
+    pub discriminant: String,
+    /// Absent for indefinite sub-forms and past `MAX_CLASS_NUMBER_DISCRIMINANT`
+    pub class_number: Option<u64>,
+}
+
+impl FormInvariants {
+    pub fn of(form: &QuadraticForm) -> Self {
+        let binary = binary_subforms(form)
+            .into_iter()
+            .map(|(variables, [a, b, c])| {
+                let discriminant = binary_discriminant(&a, &b, &c);
+                BinaryInvariants {
+                    variables: variables.to_string(),
+                    class_number: class_number(&discriminant),
+                    discriminant: discriminant.to_string(),
+                }
+            })
+            .collect();
+        FormInvariants {
+            discriminant: discriminant(form).to_string(),
+            binary,
+        }
+    }
+}
+
+/// Homogeneous coefficients (a, b, c, d, e, f) as machine integers.
+type Coefficients = [i64; 6];
+
+fn q(g: &Coefficients, v: [i64; 3]) -> i128 {
+    let [a, b, c, d, e, f] = g.map(i128::from);
+    let [x, y, z] = v.map(i128::from);
+    a * x * x + b * x * y + c * y * y + d * x * z + e * y * z + f * z * z
+}
+
+/// The bilinear form Q(u + v) - Q(u) - Q(v), whose values on a basis are b, d and e.
+fn polar(g: &Coefficients, u: [i64; 3], v: [i64; 3]) -> i128 {
+    q(g, [u[0] + v[0], u[1] + v[1], u[2] + v[2]]) - q(g, u) - q(g, v)
+}
+
+/// Coefficients of Q in the basis `basis`, or `None` if they leave i64.
+fn in_basis(g: &Coefficients, basis: &[[i64; 3]; 3]) -> Option<Coefficients> {
+    let [u, v, w] = *basis;
+    let values = [q(g, u), polar(g, u, v), q(g, v), polar(g, u, w), polar(g, v, w), q(g, w)];
+    let mut coefficients = [0i64; 6];
+    for (coefficient, value) in coefficients.iter_mut().zip(values) {
+        *coefficient = i64::try_from(value).ok()?;
+    }
+    Some(coefficients)
+}
+
+fn determinant(basis: &[[i64; 3]; 3]) -> i128 {
+    let [u, v, w] = basis.map(|r| r.map(i128::from));
+    u[0] * (v[1] * w[2] - v[2] * w[1]) - u[1] * (v[0] * w[2] - v[2] * w[0])
+        + u[2] * (v[0] * w[1] - v[1] * w[0])
+}
+
+fn cross(u: [i64; 3], v: [i64; 3]) -> [i128; 3] {
+    let [u, v] = [u, v].map(|r| r.map(i128::from));
+    [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]]
+}
+
+/// Minkowski-reduce: shorten the basis vectors against each other, and each against the sums
+/// ±eᵢ ± eⱼ of the other two, until no step lowers any Q(eᵢ). In three dimensions that is a
+/// Minkowski-reduced basis, so Q(e₁) ≤ Q(e₂) ≤ Q(e₃) are the successive minima and every
+/// vector attaining one has coordinates in {-1, 0, 1}.
+fn minkowski_reduce(g: &Coefficients) -> Option<Coefficients> {
+    let mut basis = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+    loop {
+        basis.sort_by_key(|&v| q(g, v));
+        let mut changed = false;
+        for j in 0..3 {
+            for i in 0..3 {
+                let (norm, cross) = (q(g, basis[i]), polar(g, basis[i], basis[j]));
+                if i == j || norm == 0 {
+                    continue;
+                }
+                let k = i64::try_from((2 * cross + 2 * norm).div_euclid(4 * norm)).ok()?;
+                let shorter = [0, 1, 2].map(|t| basis[j][t] - k * basis[i][t]);
+                if k != 0 && q(g, shorter) < q(g, basis[j]) {
+                    basis[j] = shorter;
+                    changed = true;
+                }
+            }
+        }
+        for j in 0..3 {
+            let (i, k) = ((j + 1) % 3, (j + 2) % 3);
+            for (s, t) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let shorter = [0, 1, 2].map(|n| basis[j][n] + s * basis[i][n] + t * basis[k][n]);
+                if q(g, shorter) < q(g, basis[j]) {
+                    basis[j] = shorter;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return in_basis(g, &basis);
+        }
+    }
+}
+
+/// Coordinates searched around a Minkowski-reduced basis for vectors attaining the successive
+/// minima, one more than the bound of 1 that holds in three dimensions.
+const MINIMAL_REACH: i64 = 2;
+
+/// A canonical representative of the GL₃(Z) class of a positive-definite form: Minkowski
+/// reduced, with the successive minima λ₁ ≤ λ₂ ≤ λ₃ as a, c, f, and among all bases attaining
+/// them the one with the smallest |b|, |d|, |e| (then non-negative signs first). The constant g
+/// is carried over.
+///
+/// Two forms are equivalent exactly when their representatives agree. `None` for forms that
+/// are not positive definite or whose coefficients do not fit in i64.
+pub fn reduce_form(form: &QuadraticForm) -> Option<QuadraticForm> {
+    if !is_positive_definite(form) {
+        return None;
+    }
+    let [a, b, c, d, e, f, _] = form.coefficients().map(|c| c.to_i64());
+    let g = minkowski_reduce(&[a?, b?, c?, d?, e?, f?])?;
+
+    // The vectors that can attain a minimum lie in a small box of the reduced basis, so the
+    // work no longer grows with the coefficients
+    let bound = q(&g, [0, 0, 1]);
+    let mut vectors = Vec::new();
+    for x in -MINIMAL_REACH..=MINIMAL_REACH {
+        for y in -MINIMAL_REACH..=MINIMAL_REACH {
+            for z in -MINIMAL_REACH..=MINIMAL_REACH {
+                let norm = q(&g, [x, y, z]);
+                if norm > 0 && norm <= bound {
+                    vectors.push((norm, [x, y, z]));
+                }
+            }
+        }
+    }
+    vectors.sort_unstable();
+
+    // The successive minima: the norms at which the vectors seen so far first span 1, 2, 3
+    // dimensions
+    let mut minima = Vec::with_capacity(3);
+    let mut span: Vec<[i64; 3]> = Vec::with_capacity(3);
+    for &(norm, v) in &vectors {
+        let independent = match span.len() {
+            0 => true,
+            1 => cross(span[0], v) != [0, 0, 0],
+            _ => determinant(&[span[0], span[1], v]) != 0,
+        };
+        if independent {
+            span.push(v);
+            minima.push(norm);
+            if minima.len() == 3 {
+                break;
+            }
+        }
+    }
+    let with_norm = |norm: i128| -> Vec<[i64; 3]> {
+        vectors.iter().filter(|(n, _)| *n == norm).map(|&(_, v)| v).collect()
+    };
+    let (first, second, third) = (with_norm(minima[0]), with_norm(minima[1]), with_norm(minima[2]));
+    // Smallest off-diagonal magnitudes first, then non-negative signs
+    let key = |[_, b, _, d, e, _]: Coefficients| (b.abs(), d.abs(), e.abs(), -b, -d, -e);
+    let mut best: Option<Coefficients> = None;
+    for &u in &first {
+        for &v in &second {
+            for &w in &third {
+                let basis = [u, v, w];
+                if determinant(&basis).abs() != 1 {
+                    continue;
+                }
+                let candidate = in_basis(&g, &basis)?;
+                if best.is_none_or(|best| key(candidate) < key(best)) {
+                    best = Some(candidate);
+                }
+            }
+        }
+    }
+    let [a, b, c, d, e, f] = best?.map(BigInt::from);
+    Some(QuadraticForm { a, b, c, d, e, f, g: form.g.clone() })
+}
+
+/// Whether two positive-definite forms are GL₃(Z)-equivalent (with equal constants), i.e.
+/// take the same values with the same multiplicities. `None` if either cannot be reduced.
+pub fn are_equivalent(first: &QuadraticForm, second: &QuadraticForm) -> Option<bool> {
+    Some(reduce_form(first)? == reduce_form(second)?)
+}
+
+/// One block of a Jordan decomposition over Z_p of the Hessian M = [[2a, b, d], [b, 2c, e],
+/// [d, e, 2f]], entries being p-adic integers kept as fractions with denominators prime to p.
+#[derive(Debug, Clone, PartialEq)]
+enum JordanBlock {
+    /// p^scale·u for a unit u
+    Single { scale: u32, entry: (BigInt, BigInt) },
+    /// p^scale·[[2α, β], [β, 2γ]] with β a unit; only needed at p = 2
+    Pair { scale: u32, entries: [(BigInt, BigInt); 3] },
+}
+
+fn valuation(x: &(BigInt, BigInt), p: u64) -> u32 {
+    split_valuation(&x.0, p).0
+}
+
+/// A p-adic integer mod `modulus`, a power of p.
+fn residue(x: &(BigInt, BigInt), modulus: u64) -> u64 {
+    let m = BigUint::from(modulus);
+    let num = x.0.mod_floor(&BigInt::from(modulus)).magnitude().clone();
+    let inverse = mod_inverse(x.1.magnitude(), &m).expect("denominators are prime to p");
+    (num * inverse % m).to_u64().expect("reduced below the modulus")
+}
+
+/// Split the Hessian of a nondegenerate form into Jordan blocks over Z_p, or `None` if the
+/// form is degenerate.
+///
+/// Pivots on an entry of least valuation. A diagonal one gives a 1×1 block; failing that,
+/// at odd p, e_i + e_j brings an off-diagonal one to the diagonal, and at p = 2 the pair
+/// (e_i, e_j) gives a 2×2 block. Either way the elimination factors are p-adic integers.
+fn jordan_blocks(form: &QuadraticForm, p: u64) -> Option<Vec<JordanBlock>> {
+    let one = BigInt::one();
+    let entry = |x: &BigInt| (x.clone(), one.clone());
+    let [a, b, c, d, e, f, _] = form.coefficients();
+    let mut m = vec![
+        vec![entry(&(a * 2)), entry(b), entry(d)],
+        vec![entry(b), entry(&(c * 2)), entry(e)],
+        vec![entry(d), entry(e), entry(&(f * 2))],
+    ];
+    let swap = |m: &mut Vec<Vec<(BigInt, BigInt)>>, i: usize, j: usize| {
+        m.swap(i, j);
+        for row in m.iter_mut() {
+            row.swap(i, j);
+        }
+    };
+
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    while start < 3 {
+        let least = (start..3)
+            .flat_map(|i| (start..3).map(move |j| (i, j)))
+            .filter(|&(i, j)| !m[i][j].0.is_zero())
+            .min_by_key(|&(i, j)| (valuation(&m[i][j], p), i != j))?;
+        let (i, j) = least;
+        if i != j && p != 2 {
+            // Replace e_i by e_i + e_j: the new diagonal entry m_ii + 2m_ij + m_jj keeps the
+            // least valuation since p is odd
+            let row_j = m[j].clone();
+            for (x, y) in m[i].iter_mut().zip(row_j.iter()) {
+                *x = frac_add(x, y);
+            }
+            for row in m.iter_mut() {
+                row[i] = frac_add(&row[i], &row[j]);
+            }
+        }
+        if i == j || p != 2 {
+            swap(&mut m, start, i);
+            let pivot = m[start][start].clone();
+            for k in start + 1..3 {
+                let factor = frac_div(&m[k][start], &pivot);
+                let row = m[start].clone();
+                for (x, y) in m[k].iter_mut().zip(row.iter()) {
+                    *x = frac_sub(x, &frac_mul(&factor, y));
+                }
+                for row in m.iter_mut() {
+                    let delta = frac_mul(&factor, &row[start]);
+                    row[k] = frac_sub(&row[k], &delta);
+                }
+            }
+            blocks.push(JordanBlock::Single {
+                scale: valuation(&pivot, p),
+                entry: pivot,
+            });
+            start += 1;
+        } else {
+            let (i, j) = (i.min(j), i.max(j));
+            swap(&mut m, start, i);
+            swap(&mut m, start + 1, j);
+            let (s, t) = (start, start + 1);
+            let det = frac_sub(&frac_mul(&m[s][s], &m[t][t]), &frac_mul(&m[s][t], &m[s][t]));
+            if let Some(k) = (t + 1..3).next() {
+                // [f_s, f_t] = [m_ks, m_kt] · B⁻¹ with B⁻¹ = adj(B) / det(B)
+                let f_s = frac_div(
+                    &frac_sub(&frac_mul(&m[k][s], &m[t][t]), &frac_mul(&m[k][t], &m[s][t])),
+                    &det,
+                );
+                let f_t = frac_div(
+                    &frac_sub(&frac_mul(&m[k][t], &m[s][s]), &frac_mul(&m[k][s], &m[s][t])),
+                    &det,
+                );
+                let (row_s, row_t) = (m[s].clone(), m[t].clone());
+                for ((x, y), z) in m[k].iter_mut().zip(row_s.iter()).zip(row_t.iter()) {
+                    *x = frac_sub(&frac_sub(x, &frac_mul(&f_s, y)), &frac_mul(&f_t, z));
+                }
+                for row in m.iter_mut() {
+                    let delta = frac_add(&frac_mul(&f_s, &row[s]), &frac_mul(&f_t, &row[t]));
+                    row[k] = frac_sub(&row[k], &delta);
+                }
+            }
+            blocks.push(JordanBlock::Pair {
+                scale: valuation(&m[s][t], p),
+                entries: [m[s][s].clone(), m[s][t].clone(), m[t][t].clone()],
+            });
+            start += 2;
+        }
+    }
+    Some(blocks)
+}
+
+/// The constituent of scale p^scale in a p-adic genus symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenusConstituent {
+    pub scale: u32,
+    pub dimension: usize,
+    /// Legendre symbol of the unit part of the determinant at odd p; at p = 2, +1 when it is
+    /// ±1 (mod 8) and -1 when it is ±3
+    pub sign: i32,
+    /// At p = 2, the oddity (trace of the unit parts mod 8) of an odd (type I) constituent;
+    /// `None` for even (type II) ones and at odd p
+    pub oddity: Option<u32>,
+}
+
+/// The p-adic symbol of the even lattice with Gram matrix the Hessian of a form, in
+/// Conway–Sloane notation: "1^+2 3^-1" at odd p, with an oddity subscript ("2^+1_3") on
+/// odd constituents at p = 2.
+///
+/// At p = 2 the symbol is that of the Jordan decomposition found, not the canonical one
+/// under Conway and Sloane's sign walking and oddity fusion, so two 2-adic symbols can differ
+/// while describing the same genus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenusSymbol {
+    pub prime: u64,
+    pub constituents: Vec<GenusConstituent>,
+}
+
+impl fmt::Display for GenusSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, constituent) in self.constituents.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            let sign = if constituent.sign > 0 { '+' } else { '-' };
+            let scale = BigUint::from(self.prime).pow(constituent.scale);
+            write!(f, "{}^{}{}", scale, sign, constituent.dimension)?;
+            if let Some(oddity) = constituent.oddity {
+                write!(f, "_{}", oddity)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The p-adic genus symbol of `form`, or `None` if it is degenerate. Constituents of
+/// dimension zero are omitted.
+pub fn genus_symbol(form: &QuadraticForm, p: u64) -> Option<GenusSymbol> {
+    let mut constituents: Vec<GenusConstituent> = Vec::new();
+    let mut scales: Vec<u32> = Vec::new();
+    let blocks = jordan_blocks(form, p)?;
+    for block in &blocks {
+        let scale = match block {
+            JordanBlock::Single { scale, .. } | JordanBlock::Pair { scale, .. } => *scale,
+        };
+        if !scales.contains(&scale) {
+            scales.push(scale);
+        }
+    }
+    scales.sort_unstable();
+    let unit = |x: &(BigInt, BigInt), scale: u32| {
+        (x.0.clone() / BigInt::from(p).pow(scale), x.1.clone())
+    };
+    for scale in scales {
+        let mut dimension = 0;
+        let mut determinant = (BigInt::one(), BigInt::one());
+        let mut trace = 0u64;
+        let mut odd = false;
+        for block in &blocks {
+            match block {
+                JordanBlock::Single { scale: s, entry } if *s == scale => {
+                    dimension += 1;
+                    let u = unit(entry, scale);
+                    if p == 2 {
+                        trace += residue(&u, 8);
+                        odd = true;
+                    }
+                    determinant = frac_mul(&determinant, &u);
+                }
+                JordanBlock::Pair { scale: s, entries: [x, y, z] } if *s == scale => {
+                    dimension += 2;
+                    let det = frac_sub(&frac_mul(x, z), &frac_mul(y, y));
+                    determinant = frac_mul(&determinant, &unit(&det, 2 * scale));
+                }
+                _ => {}
+            }
+        }
+        let sign = if p == 2 {
+            match residue(&determinant, 8) {
+                1 | 7 => 1,
+                _ => -1,
+            }
+        } else {
+            jacobi(&BigInt::from(residue(&determinant, p)), &BigUint::from(p))
+        };
+        constituents.push(GenusConstituent {
+            scale,
+            dimension,
+            sign,
+            oddity: odd.then_some((trace % 8) as u32),
+        });
+    }
+    Some(GenusSymbol { prime: p, constituents })
+}
+
+/// The primes dividing 2·discriminant, where the genus symbol carries information; empty for
+/// degenerate forms.
+pub fn genus_primes(form: &QuadraticForm) -> Vec<u64> {
+    let twice = (discriminant(form) * 2u32).magnitude().clone();
+    factorize(&twice)
+        .into_iter()
+        .filter_map(|(p, _)| p.to_u64())
+        .collect()
+}
+
+/// Largest modulus p^k at which `local_density` counts solutions; the count takes p^(2k)
+/// steps.
+pub const MAX_DENSITY_MODULUS: u64 = 1 << 13;
+
+/// Distribution of the values of one Jordan block of Q = ½·xᵀMx mod `modulus`.
+fn block_values(block: &JordanBlock, modulus: u64) -> Vec<u64> {
+    let mut counts = vec![0u64; modulus as usize];
+    let halve = |x: &(BigInt, BigInt)| frac_div(x, &(BigInt::from(2), BigInt::one()));
+    let wide = |x: u64| x as u128;
+    match block {
+        JordanBlock::Single { entry, .. } => {
+            let coefficient = wide(residue(&halve(entry), modulus));
+            for x in 0..wide(modulus) {
+                counts[(coefficient * x % wide(modulus) * x % wide(modulus)) as usize] += 1;
+            }
+        }
+        JordanBlock::Pair { entries: [x2, xy, y2], .. } => {
+            let [alpha, beta, gamma] = [halve(x2), xy.clone(), halve(y2)]
+                .map(|c| wide(residue(&c, modulus)));
+            let m = wide(modulus);
+            for x in 0..m {
+                let x_part = alpha * x % m * x % m;
+                for y in 0..m {
+                    counts[((x_part + beta * x % m * y + gamma * y % m * y) % m) as usize] += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// The local density α_p(n) = lim #{x mod p^k : Q(x) ≡ n} / p^(2k) of the homogeneous part
+/// of a nondegenerate form, for n ≥ 1.
+///
+/// Counts over the Jordan decomposition at p^k with k = v_p(n) + v_p(discriminant) + 1 (+3 at
+/// p = 2), past which the ratio no longer changes. `None` if the form is degenerate or that
+/// modulus exceeds `MAX_DENSITY_MODULUS`.
+pub fn local_density(form: &QuadraticForm, n: u64, p: u64) -> Option<f64> {
+    let disc = discriminant(form);
+    if n == 0 || disc.is_zero() {
+        return None;
+    }
+    let exponent = split_valuation(&BigInt::from(n), p).0
+        + split_valuation(&disc, p).0
+        + if p == 2 { 4 } else { 1 };
+    let modulus = p.checked_pow(exponent).filter(|&m| m <= MAX_DENSITY_MODULUS)?;
+    let blocks = jordan_blocks(form, p)?;
+    // Convolve the blocks' value distributions, keeping only the total ≡ n for the last
+    let (last, rest) = blocks.split_last()?;
+    let mut totals = vec![0u64; modulus as usize];
+    totals[0] = 1;
+    for block in rest {
+        let values = block_values(block, modulus);
+        let mut next = vec![0u64; modulus as usize];
+        for (r, &count) in totals.iter().enumerate().filter(|(_, &c)| c > 0) {
+            for (s, &other) in values.iter().enumerate().filter(|(_, &c)| c > 0) {
+                next[(r + s) % modulus as usize] += count * other;
+            }
+        }
+        totals = next;
+    }
+    let values = block_values(last, modulus);
+    let target = (n % modulus) as usize;
+    let solutions: u64 = (0..modulus as usize)
+        .map(|r| totals[r] * values[(target + modulus as usize - r) % modulus as usize])
+        .sum();
+    Some(solutions as f64 / (modulus * modulus) as f64)
+}
+
+/// Default `prime_bound` for `siegel_representation_average`.
+pub const SIEGEL_PRIME_BOUND: u64 = 100_000;
+
+/// Siegel's genus average of the number of representations of n ≥ 1 by the homogeneous part
+/// of a positive-definite form, α_∞(n)·∏_p α_p(n) with α_∞(n) = 4π·√n / √discriminant.
+///
+/// Densities at the primes dividing 2·discriminant·n are counted exactly; elsewhere
+/// α_p(n) = 1 + (-n·discriminant / p) / p, multiplied up to `prime_bound`. That product
+/// converges slowly (like an L-series at 1), so a bound of 10^5 gives about two digits.
+/// `None` if the form is not positive definite or a density is out of reach.
+pub fn siegel_representation_average(
+    form: &QuadraticForm,
+    n: u64,
+    prime_bound: u64,
+) -> Option<f64> {
+    if !is_positive_definite(form) || n == 0 {
+        return None;
+    }
+    let disc = discriminant(form);
+    let mut special = genus_primes(form);
+    special.extend(factorize(&BigUint::from(n)).into_iter().filter_map(|(p, _)| p.to_u64()));
+    let mut product = 1.0;
+    for &p in special.iter().collect::<BTreeSet<_>>() {
+        product *= local_density(form, n, p)?;
+    }
+    let character = -BigInt::from(n) * &disc;
+    for p in (3..=prime_bound).filter(|&p| primal::is_prime(p) && !special.contains(&p)) {
+        product *= 1.0 + f64::from(jacobi(&character, &BigUint::from(p))) / p as f64;
+    }
+    let infinite = 4.0 * std::f64::consts::PI * (n as f64).sqrt() / disc.to_f64()?.sqrt();
+    Some(infinite * product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_counts_cover_all_tuples() {
+        let form = QuadraticForm::universal();
+        for m in [2u64, 3, 4, 9, 25] {
+            let counts = representation_counts(&form, m);
+            assert_eq!(counts.iter().sum::<u64>(), m * m * m);
+        }
+        assert_eq!(parse_local_prime(" 509"), Ok(509));
+        for (input, error) in [
+            ("0", LocalPrimeError::NotPrime(0)),
+            ("1", LocalPrimeError::NotPrime(1)),
+            ("9", LocalPrimeError::NotPrime(9)),
+            ("521", LocalPrimeError::TooLarge(521)),
+            ("x", LocalPrimeError::Invalid("x".to_string())),
+        ] {
+            assert_eq!(parse_local_prime(input), Err(error));
+        }
+        // Unusable entries are skipped rather than enumerated
+        assert_eq!(
+            local_density_factor(&form, &[0, 1, 3, 4, 521]),
+            local_density_factor(&form, &[3])
+        );
+    }
+
+    #[test]
+    fn test_sum_of_three_squares_is_anisotropic_only_at_two() {
+        // x² + y² + z² is anisotropic exactly over Q_2 (and R)
+        let form = QuadraticForm::new([1, 0, 1, 0, 0, 1, 0]);
+        assert!(!is_isotropic_at(&form, 2));
+        for p in [3u64, 5, 7, 11] {
+            assert!(is_isotropic_at(&form, p), "p = {}", p);
+        }
+        // xy + z² contains a hyperbolic plane, so it is isotropic everywhere
+        let hyperbolic = QuadraticForm::new([0, 1, 0, 0, 0, 1, 0]);
+        assert!(is_isotropic_at(&hyperbolic, 2));
+    }
+
+    #[test]
+    fn test_hilbert_symbols() {
+        let h = |a: i64, b: i64, p| hilbert_symbol(&BigInt::from(a), &BigInt::from(b), p);
+        assert_eq!(h(-1, -1, 2), -1);
+        assert_eq!(h(-1, -1, 3), 1);
+        assert_eq!(h(2, 3, 3), -1);
+        assert_eq!(h(5, 5, 5), 1);
+        assert_eq!(h(3, 3, 3), -1);
+    }
+
+    #[test]
+    fn test_discriminants_and_class_numbers() {
+        assert_eq!(discriminant(&QuadraticForm::new([1, 0, 1, 0, 0, 1, 0])), BigInt::from(4));
+        assert!(discriminant(&QuadraticForm::new([1, 2, 1, 0, 0, 0, 0])).is_zero());
+        // Class numbers of imaginary quadratic orders, h(-163) = 1 being the last
+        let h = |d: i64| class_number(&BigInt::from(d));
+        assert_eq!(h(-3), Some(1));
+        assert_eq!(h(-4), Some(1));
+        assert_eq!(h(-20), Some(2));
+        assert_eq!(h(-23), Some(3));
+        assert_eq!(h(-47), Some(5));
+        assert_eq!(h(-163), Some(1));
+        assert_eq!(h(-140), Some(6));
+        assert_eq!(h(-5), None);
+        assert_eq!(h(5), None);
+
+        let invariants = FormInvariants::of(&QuadraticForm::universal());
+        let discriminants: Vec<&str> =
+            invariants.binary.iter().map(|b| b.discriminant.as_str()).collect();
+        assert_eq!(discriminants, ["-171", "-1131", "-1443"]);
+        assert!(invariants.binary.iter().all(|b| b.class_number.is_some()));
+    }
+
+    #[test]
+    fn test_reduction_and_equivalence() {
+        let basis = [[1, 1, 0], [0, 1, 1], [1, 1, 1]];
+        let [a, b, c, d, e, f] = in_basis(&[1, 0, 1, 0, 0, 1], &basis).unwrap();
+        let cube = QuadraticForm::new([a, b, c, d, e, f, 0]);
+        assert_eq!(reduce_form(&cube), Some(QuadraticForm::new([1, 0, 1, 0, 0, 1, 0])));
+
+        // The universal form under a few unimodular changes of basis
+        let universal = QuadraticForm::universal();
+        let g: Coefficients = [5, 7, 11, 23, 47, 83];
+        for basis in [
+            [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            [[0, 0, 1], [1, 0, 0], [0, 1, 0]],
+            [[1, 2, 0], [0, 1, 0], [-3, 1, 1]],
+            [[-1, 0, 0], [5, 1, -2], [2, 0, -1]],
+        ] {
+            assert_eq!(determinant(&basis).abs(), 1);
+            let [a, b, c, d, e, f] = in_basis(&g, &basis).unwrap();
+            let moved = QuadraticForm::new([a, b, c, d, e, f, 107]);
+            assert_eq!(are_equivalent(&moved, &universal), Some(true), "{}", moved);
+        }
+        let reduced = reduce_form(&universal).unwrap();
+        assert_eq!(discriminant(&reduced), discriminant(&universal));
+        assert!(reduced.a <= reduced.c && reduced.c <= reduced.f);
+
+        // Same discriminant 20, different minima
+        let first = QuadraticForm::new([1, 0, 1, 0, 0, 5, 0]);
+        let second = QuadraticForm::new([1, 0, 2, 0, 2, 3, 0]);
+        assert_eq!(discriminant(&first), discriminant(&second));
+        assert_eq!(are_equivalent(&first, &second), Some(false));
+        assert_eq!(are_equivalent(&first, &QuadraticForm::new([1, 0, 1, 0, 0, 5, 1])), Some(false));
+        assert_eq!(reduce_form(&QuadraticForm::new([1, 0, -1, 0, 0, 1, 0])), None);
+
+        // Large coefficients cost no more than small ones
+        let stretched = in_basis(&[1, 0, 1, 0, 0, 1_000_000_000_000], &basis).unwrap();
+        let [a, b, c, d, e, f] = stretched;
+        assert_eq!(
+            reduce_form(&QuadraticForm::new([a, b, c, d, e, f, 0])),
+            Some(QuadraticForm::new([1, 0, 1, 0, 0, 1_000_000_000_000, 0]))
+        );
+        let mut rng = ChaCha20Rng::seed_from_u64(1409);
+        for _ in 0..200 {
+            let g: Coefficients = [
+                rng.gen_range(1..=6),
+                rng.gen_range(-1..=1),
+                rng.gen_range(1..=6),
+                rng.gen_range(-1..=1),
+                rng.gen_range(-1..=1),
+                rng.gen_range(1..=300),
+            ];
+            let form = QuadraticForm::new([g[0], g[1], g[2], g[3], g[4], g[5], 0]);
+            let Some(reduced) = reduce_form(&form) else { continue };
+            let mut moved = [[1i64, 0, 0], [0, 1, 0], [0, 0, 1]];
+            for _ in 0..4 {
+                let (i, j) = (rng.gen_range(0..3), rng.gen_range(0..3));
+                let k = if i == j { 0 } else { rng.gen_range(-3..=3) };
+                moved[i] = [0, 1, 2].map(|n| moved[i][n] + k * moved[j][n]);
+            }
+            let [a, b, c, d, e, f] = in_basis(&g, &moved).unwrap();
+            let moved = QuadraticForm::new([a, b, c, d, e, f, 0]);
+            assert_eq!(reduce_form(&moved), Some(reduced), "{}", form);
+        }
+    }
+
+    #[test]
+    fn test_genus_symbols() {
+        let squares = QuadraticForm::new([1, 0, 1, 0, 0, 1, 0]);
+        assert_eq!(genus_primes(&squares), [2]);
+        assert_eq!(genus_symbol(&squares, 2).unwrap().to_string(), "2^+3_3");
+        // Of the Hessian 2I, so the determinant 8 is a non-residue mod 3
+        assert_eq!(genus_symbol(&squares, 3).unwrap().to_string(), "1^-3");
+
+        // x² + xy + y² + z²: an even 2×2 block at 2, and 3 | discriminant
+        let hexagonal = QuadraticForm::new([1, 1, 1, 0, 0, 1, 0]);
+        assert_eq!(genus_primes(&hexagonal), [2, 3]);
+        assert_eq!(genus_symbol(&hexagonal, 2).unwrap().to_string(), "1^-2 2^+1_1");
+        assert_eq!(genus_symbol(&hexagonal, 3).unwrap().to_string(), "1^+2 3^-1");
+
+        let universal = QuadraticForm::universal();
+        for p in genus_primes(&universal) {
+            let symbol = genus_symbol(&universal, p).unwrap();
+            let dimension: usize = symbol.constituents.iter().map(|c| c.dimension).sum();
+            let valuation: u32 =
+                symbol.constituents.iter().map(|c| c.scale * c.dimension as u32).sum();
+            assert_eq!(dimension, 3);
+            // det M = 2·discriminant
+            assert_eq!(valuation, split_valuation(&(discriminant(&universal) * 2), p).0);
+        }
+        assert!(genus_symbol(&QuadraticForm::new([1, 2, 1, 0, 0, 0, 0]), 2).is_none());
+    }
+
+    #[test]
+    fn test_local_densities() {
+        // The Jordan-block count matches brute force, one power of p further up too
+        let counted = |form: &QuadraticForm, n: u64, m: u64| {
+            let homogeneous = QuadraticForm { g: BigInt::zero(), ..form.clone() };
+            representation_counts(&homogeneous, m)[(n % m) as usize] as f64 / (m * m) as f64
+        };
+        let universal = QuadraticForm::universal();
+        for n in [1u64, 2, 3, 7, 18] {
+            assert_eq!(local_density(&universal, n, 3), Some(counted(&universal, n, 243)));
+            assert_eq!(local_density(&universal, n, 17), Some(counted(&universal, n, 289)));
+        }
+        let squares = QuadraticForm::new([1, 0, 1, 0, 0, 1, 0]);
+        for n in [1u64, 3, 7] {
+            assert_eq!(local_density(&squares, n, 2), Some(counted(&squares, n, 128)));
+        }
+        assert_eq!(local_density(&squares, 7, 2), Some(0.0));
+
+        // x² + y² + z² is alone in its genus, so Siegel's average is r₃(n)
+        for (n, r3) in [(1u64, 6.0), (2, 12.0), (3, 8.0), (5, 24.0), (14, 48.0)] {
+            let average = siegel_representation_average(&squares, n, SIEGEL_PRIME_BOUND).unwrap();
+            assert!((average / r3 - 1.0).abs() < 0.02, "r3({}) = {} vs {}", n, r3, average);
+        }
+    }
+}
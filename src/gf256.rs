@@ -0,0 +1,168 @@
+//! Shamir secret sharing of byte strings over GF(2^8), one independent polynomial per byte.
+//!
+//! The field is GF(2)[x] / (x^8 + x^4 + x^3 + x + 1), the AES field, with multiplication by
+//! log and exp tables over the generator x + 1. Shares are as long as the secret and carry
+//! an index from 1 to 255; any `threshold` of them recover it, fewer reveal nothing.
+
+use rand::Rng;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Gf256Error {
+    #[error("need 2 <= threshold <= shares <= 255, got threshold {threshold} of {shares}")]
+    InvalidThreshold { threshold: usize, shares: usize },
+    #[error("no shares to combine")]
+    Empty,
+    #[error("share index 0 is reserved for the secret")]
+    ZeroIndex,
+    #[error("share {0} appears more than once")]
+    DuplicateIndex(u8),
+    #[error("shares have different lengths")]
+    LengthMismatch,
+}
+
+/// exp[i] = (x + 1)^i for i in 0..510, doubled up so products of logs need no reduction.
+const EXP: [u8; 510] = {
+    let mut exp = [0u8; 510];
+    let mut value = 1u8;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = value;
+        exp[i + 255] = value;
+        // value · (x + 1) = value·x + value
+        let shifted = (value << 1) ^ if value & 0x80 != 0 { 0x1b } else { 0 };
+        value ^= shifted;
+        i += 1;
+    }
+    exp
+};
+
+/// log[a] for nonzero a, the inverse of `EXP`; log[0] is unused.
+const LOG: [u8; 256] = {
+    let mut log = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        log[EXP[i] as usize] = i as u8;
+        i += 1;
+    }
+    log
+};
+
+/// a·b in GF(2^8).
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+/// a⁻¹ in GF(2^8); panics for 0.
+pub fn inv(a: u8) -> u8 {
+    assert_ne!(a, 0, "0 has no inverse");
+    EXP[255 - LOG[a as usize] as usize]
+}
+
+/// One share of a byte string: the sharing polynomials evaluated at `index`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteShare {
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+/// Split `secret` into `shares` shares, indexed 1 to `shares`, any `threshold` of which
+/// recover it.
+pub fn split<R: Rng + ?Sized>(
+    secret: &[u8],
+    threshold: usize,
+    shares: usize,
+    rng: &mut R,
+) -> Result<Vec<ByteShare>, Gf256Error> {
+    if threshold < 2 || threshold > shares || shares > 255 {
+        return Err(Gf256Error::InvalidThreshold { threshold, shares });
+    }
+    let mut result: Vec<ByteShare> = (1..=shares as u8)
+        .map(|index| ByteShare { index, data: Vec::with_capacity(secret.len()) })
+        .collect();
+    let mut coefficients = vec![0u8; threshold];
+    for &byte in secret {
+        coefficients[0] = byte;
+        rng.fill(&mut coefficients[1..]);
+        for share in &mut result {
+            // Horner's rule from the leading coefficient down
+            let y = coefficients.iter().rev().fold(0, |y, &c| mul(y, share.index) ^ c);
+            share.data.push(y);
+        }
+    }
+    Ok(result)
+}
+
+/// Recover the secret from `shares` by Lagrange interpolation at 0.
+///
+/// Any number of distinct shares can be combined, but with fewer than the threshold the
+/// result is unrelated to the secret; the caller has to know or check it.
+pub fn combine(shares: &[ByteShare]) -> Result<Vec<u8>, Gf256Error> {
+    let first = shares.first().ok_or(Gf256Error::Empty)?;
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err(Gf256Error::ZeroIndex);
+        }
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(Gf256Error::DuplicateIndex(share.index));
+        }
+        if share.data.len() != first.data.len() {
+            return Err(Gf256Error::LengthMismatch);
+        }
+    }
+    // Basis values at 0: ∏ x_j / (x_j - x_i), with subtraction being xor
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares.iter().filter(|s| s.index != share.index).fold(1, |acc, s| {
+                mul(acc, mul(s.index, inv(s.index ^ share.index)))
+            })
+        })
+        .collect();
+    Ok((0..first.data.len())
+        .map(|i| shares.iter().zip(&basis).fold(0, |acc, (s, &b)| acc ^ mul(s.data[i], b)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_field_and_sharing() {
+        // The AES field: 0x53 and 0xca are inverses, and 0x57 · 0x83 = 0xc1
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(inv(0x53), 0xca);
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inv(a)), 1);
+        }
+
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let secret = b"any three of five".to_vec();
+        let shares = split(&secret, 3, 5, &mut rng).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|s| s.data.len() == secret.len() && s.data != secret));
+        for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<ByteShare> = picked.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine(&subset).unwrap(), secret);
+        }
+        assert_eq!(combine(&shares).unwrap(), secret);
+        assert_ne!(combine(&shares[..2]).unwrap(), secret);
+
+        assert_eq!(
+            split(&secret, 4, 3, &mut rng),
+            Err(Gf256Error::InvalidThreshold { threshold: 4, shares: 3 })
+        );
+        assert_eq!(combine(&[]), Err(Gf256Error::Empty));
+        let repeated = [shares[1].clone(), shares[1].clone()];
+        assert_eq!(combine(&repeated), Err(Gf256Error::DuplicateIndex(2)));
+        let mut short = shares[..3].to_vec();
+        short[2].data.pop();
+        assert_eq!(combine(&short), Err(Gf256Error::LengthMismatch));
+    }
+}
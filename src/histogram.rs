@@ -0,0 +1,199 @@
+//! Plot-ready histograms of hit magnitudes.
+//!
+//! Hits are binned by bit length or on a log10 scale. Bins run contiguously from the smallest
+//! to the largest hit, empty ones included, and are written as CSV or JSON (or, with the
+//! `plotters` feature, an SVG bar chart) so a distribution plot needs no separate scripting.
+
+use num_bigint::BigUint;
+use serde::Serialize;
+use thiserror::Error;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::bigfloat::approx_log;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid binning '{0}', expected bits, log10 or log10:WIDTH")]
+pub struct BinningError(pub String);
+
+/// How hit values are assigned to bins.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "scale", rename_all = "lowercase")]
+pub enum Binning {
+    /// One bin per bit length: N in [2^(b-1), 2^b)
+    Bits,
+    /// Bins of `width` decades: N in [10^(k·width), 10^((k+1)·width))
+    Log10 { width: f64 },
+}
+
+impl FromStr for Binning {
+    type Err = BinningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "bits" => Ok(Binning::Bits),
+            "log10" => Ok(Binning::Log10 { width: 1.0 }),
+            other => other
+                .strip_prefix("log10:")
+                .and_then(|width| width.parse::<f64>().ok())
+                .filter(|width| width.is_finite() && *width > 0.0)
+                .map(|width| Binning::Log10 { width })
+                .ok_or_else(|| BinningError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Binning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binning::Bits => write!(f, "bit length"),
+            Binning::Log10 { width } => write!(f, "log10 (width {})", width),
+        }
+    }
+}
+
+impl Binning {
+    /// Index of the bin holding `n` (which must be positive).
+    fn bin(&self, n: &BigUint) -> i64 {
+        match self {
+            Binning::Bits => n.bits() as i64,
+            Binning::Log10 { width } => {
+                (approx_log(n) * std::f64::consts::LOG10_E / width).floor() as i64
+            }
+        }
+    }
+
+    /// Bounds of bin `index` on the log scale of this binning.
+    fn bounds(&self, index: i64) -> (f64, f64) {
+        match self {
+            Binning::Bits => ((index - 1) as f64, index as f64),
+            Binning::Log10 { width } => (index as f64 * width, (index + 1) as f64 * width),
+        }
+    }
+}
+
+/// One bar: the hits with log2 N (for bit-length bins) or log10 N in [lower, upper).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    pub binning: Binning,
+    pub bins: Vec<Bin>,
+}
+
+pub const HISTOGRAM_CSV_HEADER: &str = "lower,upper,count";
+
+impl Histogram {
+    /// Bin `values`; zeros have no magnitude on a log scale and are skipped.
+    pub fn new<'a, I: IntoIterator<Item = &'a BigUint>>(values: I, binning: Binning) -> Self {
+        let indices: Vec<i64> = values
+            .into_iter()
+            .filter(|n| n.bits() > 0)
+            .map(|n| binning.bin(n))
+            .collect();
+        let (Some(&first), Some(&last)) = (indices.iter().min(), indices.iter().max()) else {
+            return Histogram { binning, bins: Vec::new() };
+        };
+        let mut counts = vec![0u64; (last - first + 1) as usize];
+        for index in &indices {
+            counts[(index - first) as usize] += 1;
+        }
+        let bins = counts
+            .into_iter()
+            .zip(first..)
+            .map(|(count, index)| {
+                let (lower, upper) = binning.bounds(index);
+                Bin { lower, upper, count }
+            })
+            .collect();
+        Histogram { binning, bins }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.bins.iter().map(|bin| bin.count).sum()
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", HISTOGRAM_CSV_HEADER)?;
+        for bin in &self.bins {
+            writeln!(out, "{},{},{}", bin.lower, bin.upper, bin.count)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("histograms always serialize")
+    }
+
+    /// Draw the histogram as an SVG bar chart at `path`.
+    #[cfg(feature = "plotters")]
+    pub fn write_svg(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        use plotters::prelude::*;
+
+        let root = SVGBackend::new(path, (800, 500)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let lower = self.bins.first().map_or(0.0, |bin| bin.lower);
+        let upper = self.bins.last().map_or(1.0, |bin| bin.upper);
+        let tallest = self.bins.iter().map(|bin| bin.count).max().unwrap_or(0);
+        let x_label = match self.binning {
+            Binning::Bits => "log2 N",
+            Binning::Log10 { .. } => "log10 N",
+        };
+        let mut chart = ChartBuilder::on(&root)
+            .caption(format!("Hits by {}", self.binning), ("sans-serif", 24))
+            .margin(16)
+            .x_label_area_size(40)
+            .y_label_area_size(56)
+            .build_cartesian_2d(lower..upper, 0u64..tallest + 1)?;
+        chart
+            .configure_mesh()
+            .x_desc(x_label)
+            .y_desc("hits")
+            .disable_x_mesh()
+            .draw()?;
+        chart.draw_series(self.bins.iter().map(|bin| {
+            Rectangle::new([(bin.lower, 0), (bin.upper, bin.count)], BLUE.mix(0.6).filled())
+        }))?;
+        root.present()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_bins() {
+        let values: Vec<BigUint> = [0u32, 2, 3, 5, 7, 11, 13, 29, 31, 997]
+            .into_iter()
+            .map(BigUint::from)
+            .collect();
+        let histogram = Histogram::new(&values, Binning::Bits);
+        let counts: Vec<u64> = histogram.bins.iter().map(|bin| bin.count).collect();
+        // Bit lengths 2 (2, 3), 3 (5, 7), 4 (11, 13), 5 (29, 31), then empty bins up to 10
+        assert_eq!(counts, [2, 2, 2, 2, 0, 0, 0, 0, 1]);
+        assert_eq!((histogram.bins[0].lower, histogram.bins[0].upper), (1.0, 2.0));
+        assert_eq!(histogram.total(), 9);
+
+        let decades = Histogram::new(&values, "log10".parse().unwrap());
+        let counts: Vec<u64> = decades.bins.iter().map(|bin| bin.count).collect();
+        assert_eq!(counts, [4, 4, 1]);
+        let big = BigUint::from(10u32).pow(300) * 3u32;
+        assert_eq!(Binning::Log10 { width: 0.5 }.bin(&big), 600);
+
+        let mut csv = Vec::new();
+        decades.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "lower,upper,count\n0,1,4\n1,2,4\n2,3,1\n");
+        assert!(decades.to_json().contains(r#""scale": "log10""#));
+        assert!("log10:0".parse::<Binning>().is_err());
+        assert!(Histogram::new(&[], Binning::Bits).bins.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! JWK-style JSON for PMPT public keys, so they can sit in JSON-based key stores.
+//!
+//! Big integers are unpadded base64url of their big-endian bytes, as in RFC 7517:
+//!
+//! ```text
+//! {"kty":"PMPT","x":"...","y":"...","z":"...","n":"...","pad":16,"rounds":4,"mask":"gaussian"}
+//! ```
+//!
+//! `n` is the modulus and `pad` the pad length; `rounds` and `mask` are the `CipherParams`
+//! senders should encrypt with.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+use thiserror::Error;
+
+use crate::domains;
+use crate::pmpt::{pad_length_for, CipherParams, KeyPair, MaskMode, SpherePoint};
+
+/// Key type of PMPT JWKs.
+pub const KTY: &str = "PMPT";
+
+#[derive(Error, Debug)]
+pub enum JwkError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("key type {0:?} is not PMPT")]
+    WrongKeyType(String),
+    #[error("member {0} is not unpadded base64url")]
+    InvalidBase64(&'static str),
+    #[error("unknown mask mode {0:?}")]
+    UnknownMask(String),
+    #[error("pad length {pad} is shorter than the {minimum}-byte modulus")]
+    PadTooShort { pad: usize, minimum: usize },
+    #[error("coordinate {0} is not reduced mod n")]
+    CoordinateOutOfRange(&'static str),
+}
+
+/// The JSON members of a PMPT JWK.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub x: String,
+    pub y: String,
+    pub z: String,
+    pub n: String,
+    pub pad: usize,
+    pub rounds: u32,
+    pub mask: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+/// A PMPT public key with the cipher parameters it expects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKey {
+    pub point: SpherePoint,
+    pub modulus: BigUint,
+    pub pad_length: usize,
+    pub params: CipherParams,
+}
+
+fn encode(n: &BigUint) -> String {
+    URL_SAFE_NO_PAD.encode(n.to_bytes_be())
+}
+
+fn decode(member: &'static str, value: &str) -> Result<BigUint, JwkError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| JwkError::InvalidBase64(member))?;
+    Ok(BigUint::from_bytes_be(&bytes))
+}
+
+pub(crate) fn mask_name(mask: MaskMode) -> &'static str {
+    match mask {
+        MaskMode::Gaussian => "gaussian",
+        MaskMode::Keystream => "keystream",
+    }
+}
+
+impl PublicKey {
+    /// The public half of `keys`, advertising `params`.
+    pub fn from_key_pair(keys: &KeyPair, params: CipherParams) -> Self {
+        PublicKey {
+            point: keys.public_key.clone(),
+            modulus: keys.modulus.clone(),
+            pad_length: keys.pad_length,
+            params,
+        }
+    }
+
+    /// JWK members for this key, with `kid` set to its thumbprint.
+    pub fn to_jwk(&self) -> Jwk {
+        let mut jwk = Jwk {
+            kty: KTY.to_string(),
+            x: encode(&self.point.x),
+            y: encode(&self.point.y),
+            z: encode(&self.point.z),
+            n: encode(&self.modulus),
+            pad: self.pad_length,
+            rounds: self.params.rounds,
+            mask: mask_name(self.params.mask).to_string(),
+            kid: None,
+        };
+        jwk.kid = Some(jwk.thumbprint());
+        jwk
+    }
+
+    /// Validate and import JWK members. A `kid` is informational and not checked.
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, JwkError> {
+        if jwk.kty != KTY {
+            return Err(JwkError::WrongKeyType(jwk.kty.clone()));
+        }
+        let modulus = decode("n", &jwk.n)?;
+        let minimum = pad_length_for(&modulus);
+        if jwk.pad < minimum {
+            return Err(JwkError::PadTooShort { pad: jwk.pad, minimum });
+        }
+        let coordinate = |member: &'static str, value: &str| {
+            let value = decode(member, value)?;
+            if value >= modulus {
+                return Err(JwkError::CoordinateOutOfRange(member));
+            }
+            Ok(value)
+        };
+        let point = SpherePoint::new(
+            coordinate("x", &jwk.x)?,
+            coordinate("y", &jwk.y)?,
+            coordinate("z", &jwk.z)?,
+        );
+        let mask = match jwk.mask.as_str() {
+            "gaussian" => MaskMode::Gaussian,
+            "keystream" => MaskMode::Keystream,
+            other => return Err(JwkError::UnknownMask(other.to_string())),
+        };
+        Ok(PublicKey {
+            point,
+            modulus,
+            pad_length: jwk.pad,
+            params: CipherParams {
+                rounds: jwk.rounds,
+                mask,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.to_jwk()).expect("JWK members always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, JwkError> {
+        PublicKey::from_jwk(&serde_json::from_str(json)?)
+    }
+}
+
+impl Jwk {
+    /// Thumbprint in the manner of RFC 7638: SHAKE256 (256 bits) of the key members in
+    /// lexicographic order with no whitespace, base64url encoded. `kid` is excluded.
+    pub fn thumbprint(&self) -> String {
+        let canonical = format!(
+            r#"{{"kty":"{}","mask":"{}","n":"{}","pad":{},"rounds":{},"x":"{}","y":"{}","z":"{}"}}"#,
+            self.kty, self.mask, self.n, self.pad, self.rounds, self.x, self.y, self.z
+        );
+        let mut hasher: Shake256 = domains::tagged(domains::JWK_THUMBPRINT);
+        hasher.update(canonical.as_bytes());
+        let mut digest = [0u8; 32];
+        hasher.finalize_xof().read(&mut digest);
+        URL_SAFE_NO_PAD.encode(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_jwk_round_trip() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(23);
+        let keys = KeyPair::generate(64, &mut rng);
+        let key = PublicKey::from_key_pair(&keys, CipherParams::default());
+        let json = key.to_json();
+        assert!(json.starts_with(r#"{"kty":"PMPT","x":""#));
+        assert_eq!(PublicKey::from_json(&json).unwrap(), key);
+
+        let mut jwk = key.to_jwk();
+        assert_eq!(jwk.kid, Some(jwk.thumbprint()));
+        jwk.pad -= 1;
+        assert_ne!(Some(jwk.thumbprint()), jwk.kid);
+        assert!(matches!(PublicKey::from_jwk(&jwk), Err(JwkError::PadTooShort { .. })));
+
+        let mut jwk = key.to_jwk();
+        jwk.x = encode(&keys.modulus);
+        assert!(matches!(
+            PublicKey::from_jwk(&jwk),
+            Err(JwkError::CoordinateOutOfRange("x"))
+        ));
+        jwk.kty = "RSA".to_string();
+        assert!(matches!(PublicKey::from_jwk(&jwk), Err(JwkError::WrongKeyType(_))));
+        assert!(matches!(PublicKey::from_json("{}"), Err(JwkError::Json(_))));
+    }
+}
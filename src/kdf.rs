@@ -0,0 +1,81 @@
+//! Sub-keys of a private sphere point, so no two uses of the point share a seed.
+//!
+//! HKDF-SHA3-256 extracts a pseudorandom key from the point's coordinates, each prefixed
+//! with its length, salted with `domains::PMPT_KDF`; expanding it under a use's label gives
+//! that use's 32-byte sub-key. Learning the cipher's noise stream therefore says nothing
+//! about the S-box or the MAC noise, and each seeds its own ChaCha20 stream.
+
+use hkdf::Hkdf;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use sha3::Sha3_256;
+
+use crate::domains;
+use crate::pmpt::SpherePoint;
+
+/// What a sub-key is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubKey {
+    /// Noise and round keys of the cipher
+    EncNoise,
+    /// A key-bound S-box, see `DynamicSBox::derive`
+    SBox,
+    /// Noise of PMPT-HMAC tags
+    Mac,
+}
+
+impl SubKey {
+    pub const ALL: [SubKey; 3] = [SubKey::EncNoise, SubKey::SBox, SubKey::Mac];
+
+    /// The HKDF info string.
+    pub fn label(self) -> &'static [u8] {
+        match self {
+            SubKey::EncNoise => b"enc-noise",
+            SubKey::SBox => b"sbox",
+            SubKey::Mac => b"mac",
+        }
+    }
+}
+
+/// The 32-byte sub-key of `private_key` for `use_`.
+pub fn derive(private_key: &SpherePoint, use_: SubKey) -> [u8; 32] {
+    let mut ikm = Vec::new();
+    for coordinate in [&private_key.x, &private_key.y, &private_key.z] {
+        let bytes = coordinate.to_bytes_be();
+        ikm.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+        ikm.extend_from_slice(&bytes);
+    }
+    let mut okm = [0u8; 32];
+    Hkdf::<Sha3_256>::new(Some(domains::PMPT_KDF), &ikm)
+        .expand(use_.label(), &mut okm)
+        .expect("32 bytes is within HKDF's output limit");
+    okm
+}
+
+/// A ChaCha20 stream seeded with the sub-key of `private_key` for `use_`.
+pub fn rng(private_key: &SpherePoint, use_: SubKey) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed(derive(private_key, use_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_subkeys_independent() {
+        let point = |x: u32, y: u32, z: u32| {
+            SpherePoint::new(BigUint::from(x), BigUint::from(y), BigUint::from(z))
+        };
+        let key = point(3, 5, 7);
+        let subkeys = SubKey::ALL.map(|use_| derive(&key, use_));
+        assert_ne!(subkeys[0], subkeys[1]);
+        assert_ne!(subkeys[1], subkeys[2]);
+        assert_ne!(subkeys[0], subkeys[2]);
+        assert_eq!(derive(&key, SubKey::Mac), subkeys[2]);
+        assert_ne!(derive(&point(3, 5, 8), SubKey::Mac), subkeys[2]);
+        // Lengths are bound in, so coordinates cannot trade bytes
+        let joined = derive(&point(0x0305, 7, 9), SubKey::SBox);
+        assert_ne!(derive(&point(3, 0x0507, 9), SubKey::SBox), joined);
+    }
+}
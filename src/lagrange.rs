@@ -0,0 +1,113 @@
+//! Lagrange interpolation of polynomials over the integers mod m, as used to recover Shamir
+//! secrets from shares.
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use thiserror::Error;
+
+use crate::modular::{batch_inverse, ModularError};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum InterpolationError {
+    #[error("no points to interpolate")]
+    Empty,
+    #[error("x = {0} appears more than once modulo the modulus")]
+    DuplicatePoint(BigUint),
+    #[error(transparent)]
+    NotInvertible(#[from] ModularError),
+}
+
+/// The value at `x` of the unique polynomial of degree < `points.len()` through `points`,
+/// mod `modulus`.
+///
+/// Coordinates may be any size; they are reduced mod `modulus` first, so points whose x
+/// agree mod m count as duplicates. Differences x - xⱼ are taken over the signed integers and
+/// normalized into [0, m). With a composite modulus the differences between points must also
+/// be invertible, which is reported as `NotInvertible` rather than giving a wrong answer.
+pub fn interpolate_at(
+    x: &BigUint,
+    points: &[(BigUint, BigUint)],
+    modulus: &BigUint,
+) -> Result<BigUint, InterpolationError> {
+    if points.is_empty() {
+        return Err(InterpolationError::Empty);
+    }
+    let m = BigInt::from(modulus.clone());
+    let reduce = |v: &BigUint| BigInt::from(v % modulus);
+    let xs: Vec<BigInt> = points.iter().map(|(xi, _)| reduce(xi)).collect();
+    for (i, xi) in xs.iter().enumerate() {
+        if xs[..i].contains(xi) {
+            return Err(InterpolationError::DuplicatePoint(points[i].0.clone()));
+        }
+    }
+
+    let x = reduce(x);
+    let mut numerators = Vec::with_capacity(xs.len());
+    let mut denominators = Vec::with_capacity(xs.len());
+    for (i, xi) in xs.iter().enumerate() {
+        let mut numerator = BigInt::one();
+        let mut denominator = BigInt::one();
+        for (j, xj) in xs.iter().enumerate() {
+            if i != j {
+                numerator = (numerator * (&x - xj)).mod_floor(&m);
+                denominator = (denominator * (xi - xj)).mod_floor(&m);
+            }
+        }
+        numerators.push(numerator);
+        denominators.push(denominator.magnitude().clone());
+    }
+    // All the denominators are inverted at the cost of one inversion
+    let inverses = batch_inverse(&denominators, modulus)?;
+    let mut value = BigInt::zero();
+    for ((numerator, inverse), (_, yi)) in numerators.into_iter().zip(inverses).zip(points) {
+        value = (value + numerator * BigInt::from(inverse) * reduce(yi)).mod_floor(&m);
+    }
+    Ok(value.magnitude().clone())
+}
+
+/// The constant term of the polynomial through `points`: the secret of a Shamir sharing.
+pub fn interpolate_at_zero(
+    points: &[(BigUint, BigUint)],
+    modulus: &BigUint,
+) -> Result<BigUint, InterpolationError> {
+    interpolate_at(&BigUint::zero(), points, modulus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolation() {
+        // f(x) = 5 + 3x + 2x² mod 97
+        let modulus = BigUint::from(97u32);
+        let f = |x: &BigUint| (x * x * 2u32 + x * 3u32 + 5u32) % &modulus;
+        let points: Vec<(BigUint, BigUint)> = [1u32, 4, 9]
+            .into_iter()
+            .map(|x| (BigUint::from(x), f(&BigUint::from(x))))
+            .collect();
+        assert_eq!(interpolate_at_zero(&points, &modulus), Ok(BigUint::from(5u32)));
+        for x in 0u32..200 {
+            let x = BigUint::from(x);
+            assert_eq!(interpolate_at(&x, &points, &modulus), Ok(f(&x)));
+        }
+        // Evaluation points past the modulus (and far past usize) wrap around correctly
+        let large = BigUint::from(97u32).pow(5) + 4u32;
+        let wrapped = [(large.clone(), f(&large)), points[0].clone(), points[2].clone()];
+        assert_eq!(interpolate_at_zero(&wrapped, &modulus), Ok(BigUint::from(5u32)));
+
+        assert_eq!(interpolate_at_zero(&[], &modulus), Err(InterpolationError::Empty));
+        let repeated = [points[1].clone(), (large.clone(), f(&large))];
+        assert_eq!(
+            interpolate_at_zero(&repeated, &modulus),
+            Err(InterpolationError::DuplicatePoint(large))
+        );
+        // 1 and 4 differ by 3, which has no inverse mod 15
+        let composite = BigUint::from(15u32);
+        assert!(matches!(
+            interpolate_at_zero(&points[..2], &composite),
+            Err(InterpolationError::NotInvertible(_))
+        ));
+    }
+}
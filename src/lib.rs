@@ -0,0 +1,63 @@
+//! Universal prime search and classification, plus the PMPT and prime-Shamir toolkits.
+
+pub mod annotate;
+pub mod bigfloat;
+pub mod bloom;
+pub mod chebyshev;
+pub mod classify;
+pub mod collisions;
+pub mod config;
+pub mod constellation;
+pub mod constraint;
+pub mod dashboard;
+pub mod decompose;
+pub mod diff;
+pub mod dirichlet;
+pub mod domains;
+pub mod entropy;
+pub mod escalator;
+pub mod export;
+pub mod factor;
+pub mod filter;
+pub mod form;
+pub mod form_analysis;
+pub mod gf256;
+pub mod histogram;
+pub mod jwk;
+pub mod kdf;
+pub mod lagrange;
+pub mod logging;
+pub mod merge;
+pub mod mnemonic;
+pub mod modular;
+pub mod output;
+pub mod parse;
+pub mod pmpt;
+pub mod pool;
+pub mod prime_shamir;
+pub mod primality;
+pub mod reader;
+pub mod represent;
+pub mod residues;
+pub mod results;
+pub mod search;
+pub mod service;
+pub mod session;
+pub mod share_file;
+pub mod sieve;
+pub mod sweep;
+pub mod threshold_mac;
+pub mod top;
+pub mod transcript;
+pub mod trial;
+pub mod vectors;
+pub mod verify;
+pub mod wheel;
+pub mod wrap;
+pub mod zeta;
+
+#[cfg(feature = "async")]
+pub mod pipeline;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
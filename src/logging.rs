@@ -0,0 +1,23 @@
+//! Logging setup shared by the binaries.
+//!
+//! Library code emits `tracing` spans and events (and the older `log` records, which are
+//! forwarded); a binary calls `init` once to print them to stderr, as text or JSON lines.
+
+use std::io::{self, IsTerminal};
+
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+/// Print events at `level` and above to stderr, one JSON object per line if `json`.
+///
+/// `RUST_LOG`, when set, takes precedence over `level` (e.g. `RUST_LOG=universal_primes=debug`).
+/// Calling this again, or after another subscriber was installed, does nothing.
+pub fn init(level: Level, json: bool) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(level.as_str().to_lowercase()));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal());
+    let _ = if json { builder.json().try_init() } else { builder.try_init() };
+}
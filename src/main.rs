@@ -1,171 +1,1641 @@
-use num_bigint::*;
-use num_traits::*;
-use rand::Rng;
+use clap::{Parser, Subcommand};
+use num_bigint::BigUint;
+use num_complex::Complex;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
 
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::Level;
 
-fn classify_prime(p: &BigUint) -> Vec<&'static str> {
-    let mut classifications = Vec::new();
+use universal_primes::bloom::BloomFilter;
+use universal_primes::chebyshev::write_partial_sums;
+use universal_primes::collisions::{write_collisions, RepresentationIndex};
+use universal_primes::classify::{
+    classify_lines, classify_prime_with_config, classify_with_pseudoprimes, ColumnSelector,
+};
+use universal_primes::parse::{parse_biguint, parse_duration};
+use universal_primes::primality::{
+    is_prime_with_witness, next_prime, prev_prime, random_prime_in_range, random_safe_prime,
+    PrimalityConfig, PrimalityResult, DEFAULT_ROUNDS,
+};
+use universal_primes::config::SearchFile;
+use universal_primes::constraint::TupleConstraints;
+use universal_primes::constellation::{find_constellations, Pattern};
+#[cfg(feature = "tui")]
+use universal_primes::dashboard::Dashboard;
+use universal_primes::dashboard::{DashboardState, HitTap};
+use universal_primes::diff::ResultsDiff;
+use universal_primes::dirichlet::DirichletSeries;
+use universal_primes::entropy::{EntropyRng, OsEntropy};
+use universal_primes::escalator::{check_290, IntegralForm};
+use universal_primes::export::{write_bfile, write_graph, GraphFormat, PrimeGraph};
+use universal_primes::filter::Filter;
+use universal_primes::form::QuadraticForm;
+use universal_primes::form_analysis::{
+    genus_primes, genus_symbol, local_density, local_density_factor, local_reports,
+    parse_local_prime, siegel_representation_average, FormInvariants, SIEGEL_PRIME_BOUND,
+};
+use universal_primes::histogram::{Binning, Histogram};
+use universal_primes::logging;
+use universal_primes::merge::{merge_results, MergeOrder};
+use universal_primes::represent::represent;
+use universal_primes::residues::{default_moduli, ResidueProfile};
+use universal_primes::results::{read_hits, read_prime_rows, read_rows};
+use universal_primes::modular::{multiplicative_order, primitive_root};
+#[cfg(feature = "async")]
+use universal_primes::pipeline::{search_async, PipelineConfig};
+use universal_primes::pool::{sieve_pool, CandidatePool, MappedPool, TupleSpace};
+use universal_primes::output::{recover, AppendWriter, Checkpoint, Manifest, SyncPolicy};
+use universal_primes::search::{
+    closure_search, default_pool, estimate, sample_density, search_from, signed_pool,
+    thread_pool_builder, Sampler, SearchConfig, SearchProgress, StopSignal, TupleOrder,
+};
+use universal_primes::share_file::{ShareFile, SharePolicy};
+use universal_primes::sieve::{self, PrimeBitmap, SieveBackend, MAX_LIMIT};
+use universal_primes::sweep::{
+    sweep_distinct_forms, sweep_forms, write_sweep, CoefficientBounds, CoefficientRange,
+};
+use universal_primes::top::TopHits;
+use universal_primes::vectors::{TestVectors, DEFAULT_SEED};
+use universal_primes::verify::{verify_results, VERIFY_ROUNDS};
+use universal_primes::zeta::{compare_spacings, riemann_zeta, zeta_zeros};
 
-    // Check if it's a Germain prime
-    if is_germain_prime(p) {
-        classifications.push("Germain");
+/// Command-line arguments for the universal prime search.
+#[derive(Parser, Debug)]
+#[command(version, about = "Search for universal primes of the quadratic form")]
+struct Args {
+    /// Seed for all randomness (Miller-Rabin witnesses, sampling) so a run can be reproduced exactly
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// Miller-Rabin rounds per primality test [default: 20, or 64 for verify]
+    #[arg(long, global = true)]
+    rounds: Option<usize>,
+
+    /// Skip BPSW and rely on the Miller-Rabin rounds alone
+    #[arg(long, global = true)]
+    no_bpsw: bool,
+
+    /// Trial-divide by the primes below this bound before the probabilistic tests; 0 skips it
+    #[arg(long, global = true, default_value_t = 0)]
+    trial_division_bound: u32,
+
+    /// Sieve the primes up to this bound first and answer primality below it by lookup
+    #[arg(long, global = true, value_parser = clap::value_parser!(u64).range(..=MAX_LIMIT))]
+    sieve_limit: Option<u64>,
+
+    /// Sieve used for --sieve-limit: eratosthenes or atkin
+    #[arg(long, global = true, default_value = "eratosthenes")]
+    sieve: SieveBackend,
+
+    /// Worker threads for parallel work [default: one per CPU]
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Run worker threads at the lowest scheduling priority so other work comes first
+    #[arg(long, global = true)]
+    nice: bool,
+
+    /// Least severe log events to print to stderr: trace, debug, info, warn or error
+    /// (RUST_LOG overrides this)
+    #[arg(long, global = true, default_value = "warn")]
+    log_level: Level,
+
+    /// Print log events as JSON lines
+    #[arg(long, global = true)]
+    log_json: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Parser, Debug)]
+struct SearchArgs {
+    /// Form coefficients a,b,c,d,e,f,g (negative values allowed)
+    #[arg(long, default_value = "5,7,11,23,47,83,107", allow_hyphen_values = true)]
+    form: QuadraticForm,
+    /// Also draw x, y, z from the negated pool primes
+    #[arg(long, conflicts_with = "pool")]
+    signed: bool,
+    /// Draw x, y, z from a pool file written by the pool command instead of the built-in primes
+    #[arg(long)]
+    pool: Option<PathBuf>,
+    /// TOML file of further settings: a [pools] table giving x, y and z their own candidates
+    /// (safe or Germain primes below a bound, a list, or a pool file), and a constraints list
+    /// such as ["x < y < z", "gcd(x, y) == 1"] restricting the tuples searched
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Where to write the results
+    #[arg(long, default_value = "universal_primes_index.csv")]
+    output: PathBuf,
+    /// Continue from the checkpoint next to the output instead of starting over
+    #[arg(long)]
+    resume: bool,
+    /// When to fsync result rows: never, always, or every N rows
+    #[arg(long, default_value = "never")]
+    sync: SyncPolicy,
+    /// Also write composite N that are base-2 pseudoprimes, tagged (e.g. Carmichael) instead
+    /// of Prime
+    #[arg(long)]
+    pseudoprimes: bool,
+    /// Stop cleanly once this many rows have been written (resumable with --resume)
+    #[arg(long)]
+    max_hits: Option<u64>,
+    /// Stop cleanly after roughly this long, e.g. 30m or 2h (resumable with --resume)
+    #[arg(long, value_parser = parse_duration)]
+    time_budget: Option<Duration>,
+    /// Only write rows matching an expression, e.g. "n.bits() > 40 && tags.contains(Safe)"
+    #[arg(long, value_parser = |s: &str| s.parse::<Filter>().map(|_| s.to_string()))]
+    filter: Option<String>,
+    /// Add qr_primes (small primes that are quadratic residues mod |N|) and cf_period (period of the continued fraction of sqrt|N|) columns
+    #[arg(long)]
+    annotate: bool,
+    /// Add two_squares (a, b with a^2 + b^2 = |N|) and three_squares columns for prime hits
+    #[arg(long)]
+    squares: bool,
+    /// Add an r column: how many (x, y, z) in the pool give the same N, from an index built
+    /// in one pass over the pool before the search
+    #[arg(long)]
+    representations: bool,
+    /// Add a germain_depth column for prime hits: how many times p -> 2p + 1 stays prime from
+    /// p = |N|, so 0 for non-Germain primes and 2 or more for Cunningham chains
+    #[arg(long)]
+    germain_depth: bool,
+    /// Only project the runtime and output size from a random sample of tuples, writing nothing
+    #[arg(long, conflicts_with = "resume")]
+    estimate: bool,
+    /// Instead of searching exhaustively, estimate the hit density from this many sampled
+    /// tuples, with a 95% confidence interval; writes nothing
+    #[arg(long, conflicts_with_all = ["resume", "estimate"])]
+    sample: Option<u64>,
+    /// How --sample picks tuples: uniform or halton (low-discrepancy)
+    #[arg(long, default_value = "uniform", requires = "sample")]
+    sampler: Sampler,
+    /// Feed each generation's new prime values of N back into the pool and search again, for
+    /// up to this many generations; rows get a generation column (not resumable)
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["resume", "estimate", "sample", "representations", "order"]
+    )]
+    closure_depth: Option<u32>,
+    /// After a closure search, print how this prime was derived from the pool (repeatable)
+    #[arg(long, requires = "closure_depth", value_parser = parse_biguint)]
+    lineage_of: Vec<BigUint>,
+    /// Run generation, testing and writing as separate async stages joined by bounded
+    /// channels, so a slow disk and busy CPUs do not wait on each other (not resumable)
+    #[cfg(feature = "async")]
+    #[arg(
+        long,
+        conflicts_with_all = ["resume", "estimate", "sample", "closure_depth", "top", "order"]
+    )]
+    pipeline: bool,
+    /// Visit tuples in xyz (x-major) order or in bands of the largest input, smallest first,
+    /// so hits come roughly in increasing N and --max-hits keeps the smallest
+    #[arg(long, default_value = "xyz")]
+    order: TupleOrder,
+    /// Report the K largest distinct prime hits of this run at the end
+    #[arg(long, value_name = "K", conflicts_with_all = ["estimate", "sample", "closure_depth"])]
+    top: Option<usize>,
+    /// With --top, also report the K smallest prime hits greater than this
+    #[arg(long, requires = "top", value_parser = parse_biguint)]
+    smallest_above: Option<BigUint>,
+    /// Show a live dashboard of throughput, hit rates and recent hits
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    tui: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the universal prime search (the default when no subcommand is given)
+    Search(SearchArgs),
+    /// Print the classification tags of each number
+    Classify {
+        /// Numbers as decimal, 0x-hex, base64:..., or expressions like 2^127-1
+        #[arg(required_unless_present = "input", value_parser = parse_biguint)]
+        numbers: Vec<BigUint>,
+        /// Classify one number per line of this file ("-" for stdin) in parallel
+        #[arg(long, conflicts_with = "numbers")]
+        input: Option<PathBuf>,
+        /// CSV column holding the number, by zero-based index or header name
+        #[arg(long, requires = "input")]
+        column: Option<ColumnSelector>,
+        /// Where to write the augmented lines (defaults to stdout)
+        #[arg(long, requires = "input")]
+        output: Option<PathBuf>,
+        /// Also tag composites that are base-2 pseudoprimes (e.g. Carmichael)
+        #[arg(long)]
+        pseudoprimes: bool,
+    },
+    /// Test whether a number is a probable prime and report the error bound
+    IsPrime {
+        /// Number as decimal, 0x-hex, base64:..., or an expression like 2^127-1
+        #[arg(value_parser = parse_biguint)]
+        n: BigUint,
+        /// Print the witness of compositeness (and any factor it exposes)
+        #[arg(long)]
+        witness: bool,
+    },
+    /// Print the smallest prime greater than a number
+    NextPrime {
+        #[arg(value_parser = parse_biguint)]
+        n: BigUint,
+    },
+    /// Print the largest prime less than a number
+    PrevPrime {
+        #[arg(value_parser = parse_biguint)]
+        n: BigUint,
+    },
+    /// Sweep quadratic-form coefficients and rank forms by hit density over a fixed pool
+    Sweep {
+        /// Range for a (x^2), as v, lo..hi or lo..=hi (negative bounds allowed)
+        #[arg(long, default_value = "5", allow_hyphen_values = true)]
+        a: CoefficientRange,
+        /// Range for b (xy)
+        #[arg(long, default_value = "7", allow_hyphen_values = true)]
+        b: CoefficientRange,
+        /// Range for c (y^2)
+        #[arg(long, default_value = "11", allow_hyphen_values = true)]
+        c: CoefficientRange,
+        /// Range for d (xz)
+        #[arg(long, default_value = "23", allow_hyphen_values = true)]
+        d: CoefficientRange,
+        /// Range for e (yz)
+        #[arg(long, default_value = "47", allow_hyphen_values = true)]
+        e: CoefficientRange,
+        /// Range for f (z^2)
+        #[arg(long, default_value = "83", allow_hyphen_values = true)]
+        f: CoefficientRange,
+        /// Range for the constant g
+        #[arg(long, default_value = "107", allow_hyphen_values = true)]
+        g: CoefficientRange,
+        /// Use only the first N primes of the default pool for (x, y, z)
+        #[arg(long)]
+        pool_size: Option<usize>,
+        /// Where to write the full ranking as CSV
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// How many of the most productive forms to print
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Skip forms equivalent under GL3(Z) to one earlier in the sweep (positive-definite forms only)
+        #[arg(long)]
+        distinct: bool,
+    },
+    /// Report local representation densities of a form modulo small prime powers
+    Local {
+        /// Form coefficients a,b,c,d,e,f,g
+        #[arg(long, default_value = "5,7,11,23,47,83,107", allow_hyphen_values = true)]
+        form: QuadraticForm,
+        /// Primes to analyse, at most 512
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "2,3,5,7,11,13",
+            value_parser = parse_local_prime
+        )]
+        primes: Vec<u64>,
+        /// Highest power of each prime to lift to
+        #[arg(long, default_value_t = 3)]
+        max_exponent: u32,
+        /// Also print the local densities at N and Siegel's genus average of its
+        /// representation count by the homogeneous part
+        #[arg(long)]
+        siegel: Option<u64>,
+    },
+    /// Re-check a results file: recompute N, re-test primality and reclassify every row
+    Verify {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Form the results were generated with [default: from the results' manifest]
+        #[arg(long, allow_hyphen_values = true)]
+        form: Option<QuadraticForm>,
+    },
+    /// Compare two results files: prime hits unique to each, changed classifications and
+    /// the hit density per bit length
+    Diff {
+        /// Results CSV of run A
+        a: PathBuf,
+        /// Results CSV of run B
+        b: PathBuf,
+        /// List every unique hit and classification change, not just the counts
+        #[arg(long)]
+        list: bool,
+    },
+    /// Combine partial results files into one, dropping repeated tuples and checking that
+    /// their manifests agree
+    Merge {
+        /// Results CSVs to combine
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Sort rows by xyz (the tuple) or n
+        #[arg(long, default_value = "xyz")]
+        sort: MergeOrder,
+        /// Where to write the merged results, with their manifest (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Report prime constellations (twin, cousin, sexy, triplets, ...) among the hits of a results file
+    Constellations {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Pattern name (twin, cousin, sexy, triplet, triplet2, quadruplet, quintuplet) or offsets like 0,2,6
+        #[arg(long = "pattern", default_values = ["twin", "cousin", "sexy", "triplet", "triplet2"])]
+        patterns: Vec<Pattern>,
+        /// List every constellation, not just the counts
+        #[arg(long)]
+        list: bool,
+    },
+    /// Profile hit values modulo small moduli against the uniform distribution over units
+    Residues {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Modulus to profile (repeatable) [default: 3, 4, 5, 8 and the form's coefficients]
+        #[arg(long = "modulus", value_parser = clap::value_parser!(u64).range(2..))]
+        moduli: Vec<u64>,
+        /// Form the results were generated with [default: from the results' manifest]
+        #[arg(long, allow_hyphen_values = true)]
+        form: Option<QuadraticForm>,
+    },
+    /// Export the sorted distinct prime hits as an OEIS b-file
+    Bfile {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Index of the first term
+        #[arg(long, default_value_t = 1)]
+        offset: u64,
+        /// Write at most this many terms (OEIS accepts up to 10000)
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Form the results were generated with, for the header comment [default: from the
+        /// results' manifest]
+        #[arg(long, allow_hyphen_values = true)]
+        form: Option<QuadraticForm>,
+        /// Where to write the b-file (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Save a Bloom filter of the distinct prime hits, for cheap membership tests
+    Bloom {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Where to write the filter
+        #[arg(long)]
+        output: PathBuf,
+        /// Chance that a value never inserted is reported as present
+        #[arg(long, default_value_t = 0.001, value_parser = |s: &str| {
+            let rate: f64 = s.parse().map_err(|_| "expected a number")?;
+            (rate > 0.0 && rate < 1.0).then_some(rate).ok_or("must be between 0 and 1")
+        })]
+        fp_rate: f64,
+    },
+    /// Check numbers against a filter written by the bloom command
+    BloomCheck {
+        /// Filter file
+        filter: PathBuf,
+        #[arg(required = true, value_parser = parse_biguint)]
+        numbers: Vec<BigUint>,
+    },
+    /// Graph of which pool primes produce which hits, highlighting hits that re-enter the pool
+    Graph {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Output format: dot or graphml
+        #[arg(long, default_value = "dot")]
+        format: GraphFormat,
+        /// Where to write the graph (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Histogram of the distinct prime hits by magnitude, as CSV or JSON
+    Histogram {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Bin by bits (bit length), log10 (decades) or log10:WIDTH
+        #[arg(long, default_value = "bits")]
+        binning: Binning,
+        /// Write JSON instead of CSV
+        #[arg(long)]
+        json: bool,
+        /// Where to write the histogram (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Also draw the histogram as an SVG bar chart here
+        #[cfg(feature = "plotters")]
+        #[arg(long)]
+        svg: Option<PathBuf>,
+    },
+    /// Chebyshev θ and ψ, Σ 1/h and Σ ln h / h over the prime hits, per bit length, as CSV
+    Chebyshev {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Where to write the sums (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Evaluate Σ n^(-s) over the prime hits and compare it with their Euler product
+    Dirichlet {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Real part of s
+        #[arg(long, default_value_t = 2.0)]
+        re: f64,
+        /// Imaginary part of s
+        #[arg(long, default_value_t = 0.0)]
+        im: f64,
+    },
+    /// Order statistics of a base modulo each prime hit: how often it is a primitive root
+    Orders {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Base whose multiplicative order is computed
+        #[arg(long, default_value = "2", value_parser = parse_biguint)]
+        base: BigUint,
+    },
+    /// Compare the spacing of zeta zeros with the gaps between prime hits (KS distances)
+    Spacings {
+        /// Results CSV written by the search
+        path: PathBuf,
+        /// Locate zeros with ordinates from here...
+        #[arg(long, default_value_t = 10.0)]
+        from: f64,
+        /// ...up to here
+        #[arg(long, default_value_t = 1000.0)]
+        to: f64,
+    },
+    /// Print the least primitive root modulo n
+    PrimitiveRoot {
+        #[arg(value_parser = parse_biguint)]
+        n: BigUint,
+    },
+    /// List every N that more than one (x, y, z) in the pool represents, with the tuples
+    Collisions {
+        /// Form coefficients a,b,c,d,e,f,g
+        #[arg(long, default_value = "5,7,11,23,47,83,107", allow_hyphen_values = true)]
+        form: QuadraticForm,
+        /// Also draw x, y, z from the negated pool primes
+        #[arg(long, conflicts_with = "pool")]
+        signed: bool,
+        /// Pool file written by the pool command instead of the built-in primes
+        #[arg(long)]
+        pool: Option<PathBuf>,
+        /// Where to write the collisions (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Find integers (x, y, z) with F(x, y, z) = n
+    Represent {
+        #[arg(value_parser = parse_biguint)]
+        n: BigUint,
+        /// Form coefficients a,b,c,d,e,f,g
+        #[arg(long, default_value = "5,7,11,23,47,83,107", allow_hyphen_values = true)]
+        form: QuadraticForm,
+    },
+    /// Check whether a positive-definite form is universal using the 290 theorem
+    Check290 {
+        /// Diagonal coefficients, e.g. 1,2,5,5
+        #[arg(
+            long,
+            value_delimiter = ',',
+            allow_hyphen_values = true,
+            required_unless_present = "gram",
+            value_parser = clap::value_parser!(i64).range(i64::MIN / 2..=i64::MAX / 2)
+        )]
+        diagonal: Vec<i64>,
+        /// Gram matrix rows (even diagonal), e.g. "2,1;1,2" for x^2 + xy + y^2
+        #[arg(long, conflicts_with = "diagonal", allow_hyphen_values = true)]
+        gram: Option<IntegralForm>,
+    },
+    /// Print primes drawn uniformly from [lo, hi)
+    RandomPrime {
+        #[arg(value_parser = parse_biguint)]
+        lo: BigUint,
+        #[arg(value_parser = parse_biguint)]
+        hi: BigUint,
+        /// How many primes to draw
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Print random safe primes p = 2q + 1 of the given bit length
+    SafePrime {
+        bits: u64,
+        /// How many primes to draw
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Sieve the primes in [lo, hi] into a compact pool file for search --pool
+    Pool {
+        lo: u64,
+        hi: u64,
+        /// Where to write the pool
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Write known-answer test vectors for the cipher, HMAC, Shamir sharing and
+    /// classification as JSON, from --seed or the published seed
+    Genvectors {
+        /// Where to write the vectors (default: stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Check an existing vectors file against this build instead of writing one
+        #[arg(long, conflicts_with = "output")]
+        check: Option<PathBuf>,
+    },
+    /// Split files into Shamir shares and combine them again
+    Shamir {
+        #[command(subcommand)]
+        command: ShamirCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ShamirCommand {
+    /// Split a file into share files named <file>.share<i>, any --t of which recover it
+    SplitFile {
+        path: PathBuf,
+        /// Shares needed to recover the file
+        #[arg(long = "t")]
+        threshold: usize,
+        /// Shares to write
+        #[arg(long = "n")]
+        shares: usize,
+        /// Directory for the share files (default: next to the file)
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        /// What the secret is, recorded in every share
+        #[arg(long)]
+        label: Option<String>,
+        /// Holder of each share in turn; give none or one per share
+        #[arg(long = "custodian")]
+        custodians: Vec<String>,
+        /// Refuse to combine the shares after this long, e.g. 90d
+        #[arg(long, value_parser = parse_duration)]
+        expires_in: Option<Duration>,
+        /// Who has to come together to recover the secret, in words
+        #[arg(long)]
+        quorum: Option<String>,
+    },
+    /// Recover a file from its share files, checking it against the recorded SHA3-256
+    Combine {
+        #[arg(required = true)]
+        shares: Vec<PathBuf>,
+        /// Where to write the recovered file (default: stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Args {
+    /// Primality settings from the global flags, with `default_rounds` if --rounds is absent.
+    fn primality(&self, default_rounds: usize) -> PrimalityConfig {
+        PrimalityConfig {
+            rounds: self.rounds.unwrap_or(default_rounds),
+            use_bpsw: !self.no_bpsw,
+            trial_division_bound: self.trial_division_bound,
+            constant_time: false,
+        }
     }
-    // Check if it's a Safe prime
-    if is_safe_prime(p) {
-        classifications.push("Safe");
+}
+
+/// `form` if given, else the form recorded in the manifest next to `path`, else the default.
+fn form_for(path: &Path, form: Option<QuadraticForm>) -> QuadraticForm {
+    if let Some(form) = form {
+        return form;
     }
-    // Check if it's a Prime (basic primality check)
-    if is_prime(p, 20) {
-        classifications.push("Prime");
+    let manifest_path = Manifest::path_for(path);
+    match Manifest::load(&manifest_path).expect("Failed to read manifest.") {
+        Some(manifest) => manifest.form().unwrap_or_else(|e| {
+            eprintln!("{}: {}", manifest_path.display(), e);
+            std::process::exit(1);
+        }),
+        None => QuadraticForm::universal(),
     }
-
-    classifications
 }
 
-fn is_germain_prime(p: &BigUint) -> bool {
-    let two = BigUint::from(2u32);
-    let q = p * &two + BigUint::one();
-    is_prime(&q, 20)
+/// A signal raised by the first Ctrl-C, so the search can stop at its next checkpoint with
+/// everything flushed; a second Ctrl-C exits at once.
+fn stop_on_interrupt() -> StopSignal {
+    let signal = StopSignal::new();
+    let handler_signal = signal.clone();
+    ctrlc::set_handler(move || {
+        if handler_signal.is_stopped() {
+            std::process::exit(130);
+        }
+        handler_signal.stop();
+        eprintln!("Interrupted; stopping at the next checkpoint (press Ctrl-C again to abort)");
+    })
+    .expect("Failed to install the Ctrl-C handler.");
+    signal
 }
 
-fn is_safe_prime(p: &BigUint) -> bool {
-    let two = BigUint::from(2u32);
-    if p <= &two {
-        return false;
+/// Tuples evaluated by `search --estimate`.
+const ESTIMATE_SAMPLES: u64 = 2000;
+
+/// The search settings given on the command line, with the config file's constraints.
+fn search_config(
+    args: &SearchArgs,
+    seed: u64,
+    primality: PrimalityConfig,
+    constraints: &TupleConstraints,
+) -> SearchConfig {
+    let mut config = SearchConfig::new(seed).primality(primality).constraints(constraints.clone());
+    if let Some(max_hits) = args.max_hits {
+        config = config.stop_after_hits(max_hits);
     }
-    let q = (p - BigUint::one()) / &two;
-    is_prime(&q, 20)
+    if let Some(budget) = args.time_budget {
+        config = config.stop_after(budget);
+    }
+    if let Some(filter) = &args.filter {
+        config = config.filter(filter.parse().expect("validated when parsing arguments"));
+    }
+    config
+        .annotate(args.annotate)
+        .squares(args.squares)
+        .germain_depth(args.germain_depth)
+        .pseudoprimes(args.pseudoprimes)
+        .order(args.order)
 }
 
-fn is_prime(n: &BigUint, k: usize) -> bool {
-    if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
-        return true;
+fn run_search(args: &SearchArgs, seed: u64, primality: PrimalityConfig) {
+    let (primes, pool): (Arc<dyn CandidatePool + Send>, String) = match &args.pool {
+        Some(path) => (
+            Arc::new(MappedPool::open(path).expect("Failed to open pool file.")),
+            path.display().to_string(),
+        ),
+        None if args.signed => (
+            Arc::new(signed_pool(&default_pool())),
+            "default signed".to_string(),
+        ),
+        None => (Arc::new(default_pool()), "default".to_string()),
+    };
+    let file = args.config.as_ref().map_or_else(SearchFile::default, |path| {
+        SearchFile::load(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let constraints = file.constraints().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let (space, pool, pool_size): (Arc<dyn TupleSpace + Send>, String, usize) = match &file.pools {
+        Some(config) => {
+            let single_pool = args.pool.is_some() || args.signed;
+            if single_pool || args.closure_depth.is_some() || args.representations {
+                eprintln!(
+                    "Per-variable pools cannot be combined with --pool, --signed, \
+                     --closure-depth or --representations"
+                );
+                std::process::exit(1);
+            }
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            let pools = config.build(&primality, &mut rng).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let size = pools.dims().iter().sum();
+            (Arc::new(pools), config.to_string(), size)
+        }
+        None => (Arc::new(primes.clone()), pool, primes.len()),
+    };
+    let manifest_path = Manifest::path_for(&args.output);
+    let mut manifest = Manifest::new(seed, &args.form, &pool, pool_size, &primality);
+    manifest.filter = args.filter.clone();
+    manifest.constraints = constraints.sources().to_vec();
+    manifest.annotate = args.annotate;
+    manifest.pseudoprimes = args.pseudoprimes;
+    manifest.squares = args.squares;
+    manifest.representations = args.representations;
+    manifest.germain_depth = args.germain_depth;
+
+    if args.estimate {
+        let config = search_config(args, seed, primality, &constraints);
+        let estimate = estimate(&args.form, &*space, &config, ESTIMATE_SAMPLES);
+        let [x, y, z] = space.dims();
+        println!("Search space: {} tuples ({} x {} x {} candidates)", estimate.tuples, x, y, z);
+        println!(
+            "Sampled {} tuples: {:?} each, {:.2}% hits",
+            estimate.sampled,
+            estimate.per_tuple(),
+            100.0 * estimate.hit_rate()
+        );
+        println!(
+            "Projected: {} hits in {:.1?} on {} threads, {} bytes of output",
+            estimate.projected_hits(),
+            estimate.projected_runtime(),
+            estimate.threads,
+            estimate.projected_output_bytes()
+        );
+        return;
     }
-    if n < &BigUint::from(2u32) || n % BigUint::from(2u32) == BigUint::zero() {
-        return false;
+    if let Some(samples) = args.sample {
+        let config = search_config(args, seed, primality, &constraints);
+        let estimate = sample_density(&args.form, &*space, &config, samples, args.sampler);
+        let (low, high) = estimate.confidence_interval(1.96);
+        println!(
+            "{} of {} sampled tuples are hits: density {:.4}% (95% CI {:.4}% to {:.4}%)",
+            estimate.hits,
+            estimate.samples,
+            100.0 * estimate.density(),
+            100.0 * low,
+            100.0 * high
+        );
+        println!(
+            "Projected hits over all {} tuples: {:.0} ({:.0} to {:.0})",
+            estimate.tuples,
+            estimate.projected_hits(),
+            low * estimate.tuples as f64,
+            high * estimate.tuples as f64
+        );
+        return;
     }
-
-    let mut d = n - BigUint::one();
-    let mut s = 0usize;
-    while &d % BigUint::from(2u32) == BigUint::zero() {
-        d /= BigUint::from(2u32);
-        s += 1;
+    if let Some(depth) = args.closure_depth {
+        let interrupt = stop_on_interrupt();
+        let config = search_config(args, seed, primality, &constraints).stop_on(interrupt.clone());
+        println!("Seed: {}", seed);
+        manifest.closure_depth = Some(depth);
+        manifest.save(&manifest_path).expect("Failed to write manifest.");
+        let mut writer =
+            BufWriter::new(File::create(&args.output).expect("Failed to create output file."));
+        let report = closure_search(&args.form, &*primes, &mut writer, &config, depth)
+            .expect("Failed to write to CSV file.");
+        writer.flush().expect("Failed to write to CSV file.");
+        for generation in &report.generations {
+            println!(
+                "Generation {}: {} tuples from a pool of {}, {} hits, {} new primes",
+                generation.generation,
+                generation.tuples,
+                generation.pool_len,
+                generation.hits,
+                generation.new_primes
+            );
+        }
+        if report.stopped {
+            println!("Stopped after {} hits", report.hits());
+        } else if report.reached_fixpoint() {
+            println!("Reached a fixpoint: no new primes in the last generation");
+        }
+        for n in &args.lineage_of {
+            match report.lineage_of(n) {
+                Some(lineage) => print!("{}", lineage),
+                None => println!("{} was not reached", n),
+            }
+        }
+        println!("Data has been saved to {}", args.output.display());
+        return;
+    }
+    #[cfg(feature = "async")]
+    if args.pipeline {
+        let interrupt = stop_on_interrupt();
+        let config = search_config(args, seed, primality, &constraints).stop_on(interrupt.clone());
+        println!("Seed: {}", seed);
+        manifest.save(&manifest_path).expect("Failed to write manifest.");
+        let total = space.tuples();
+        let runtime = tokio::runtime::Runtime::new().expect("Failed to start the async runtime.");
+        let progress = runtime
+            .block_on(async {
+                let file = tokio::fs::File::create(&args.output).await?;
+                let mut out = tokio::io::BufWriter::new(file);
+                let pipeline = PipelineConfig::new();
+                let start = SearchProgress::default();
+                search_async(&args.form, space, &mut out, &config, &pipeline, start).await
+            })
+            .expect("Failed to write to CSV file.");
+        if progress.tuples < total {
+            println!(
+                "Stopped after {} of {} tuples with {} hits",
+                progress.tuples, total, progress.hits
+            );
+        }
+        println!("Data has been saved to {}", args.output.display());
+        return;
     }
 
-    let mut rng = rand::thread_rng();
-    'witness_loop: for _ in 0..k {
-        let a = rng.gen_biguint_range(&BigUint::from(2u32), &(n - BigUint::one()));
-        let mut x = a.modpow(&d, n);
-        if x == BigUint::one() || x == n - BigUint::one() {
-            continue;
+    let output = &args.output;
+    let checkpoint_path = Checkpoint::path_for(output);
+    let form_key = Checkpoint::form_key(&args.form);
+    let mut seed = seed;
+    let mut start = SearchProgress::default();
+    let mut keep_manifest = false;
+    let checkpoint = if args.resume {
+        Checkpoint::load(&checkpoint_path).expect("Failed to read checkpoint.")
+    } else {
+        None
+    };
+    match checkpoint {
+        Some(checkpoint) => {
+            if checkpoint.form != form_key
+                || checkpoint.pool_size != pool_size
+                || checkpoint.order != args.order
+            {
+                eprintln!(
+                    "{} was written for a different form, pool or tuple order",
+                    checkpoint_path.display()
+                );
+                std::process::exit(1);
+            }
+            // The rows to come must match those already written, which the manifest describes
+            let existing = Manifest::load(&manifest_path).expect("Failed to read manifest.");
+            if let Some(existing) = &existing {
+                let mismatch = existing.row_mismatch(&manifest).or_else(|| {
+                    let pools = (existing.pool.clone(), manifest.pool.clone());
+                    (pools.0 != pools.1).then_some(("pool", pools.0, pools.1))
+                });
+                if let Some((field, written, requested)) = mismatch {
+                    eprintln!(
+                        "{} has {} '{}', but this run asks for '{}'",
+                        manifest_path.display(),
+                        field,
+                        written,
+                        requested
+                    );
+                    std::process::exit(1);
+                }
+            }
+            keep_manifest = existing.is_some();
+            // Rows written after the checkpoint are regenerated, so roll the output back to it
+            let discarded = recover(output, Some(checkpoint.output_len)).unwrap_or_else(|e| {
+                eprintln!("{}: {}", output.display(), e);
+                std::process::exit(1);
+            });
+            seed = checkpoint.seed;
+            start = checkpoint.progress;
+            println!(
+                "Resuming after {} tuples and {} hits ({} bytes past the checkpoint discarded)",
+                start.tuples, start.hits, discarded
+            );
         }
-        for _ in 0..s - 1 {
-            x = x.modpow(&BigUint::from(2u32), n);
-            if x == n - BigUint::one() {
-                continue 'witness_loop;
+        None => {
+            if args.resume {
+                println!("No checkpoint at {}; starting over", checkpoint_path.display());
             }
+            File::create(output).expect("Failed to create output file.");
         }
-        return false;
     }
-    true
-}
+    println!("Seed: {}", seed);
+
+    if !keep_manifest {
+        manifest.seed = seed;
+        manifest.save(&manifest_path).expect("Failed to write manifest.");
+    }
+
+    let total = space.tuples();
+    let writer = AppendWriter::open(output, args.sync).expect("Failed to open output file.");
+    let mut state = DashboardState::new(start, total).order(args.order);
+    if let Some(k) = args.top {
+        let mut top = TopHits::new(k);
+        if let Some(bound) = &args.smallest_above {
+            top = top.smallest_above(bound.clone());
+        }
+        state = state.track_top(top);
+    }
+    let mut writer = HitTap::new(writer, state);
+    #[cfg(feature = "tui")]
+    let mut dashboard = match args.tui.then(Dashboard::new) {
+        Some(Err(e)) => {
+            eprintln!("Running without the dashboard: {}", e);
+            None
+        }
+        dashboard => dashboard.and_then(Result::ok),
+    };
+    let interrupt = stop_on_interrupt();
+    let mut config = search_config(args, seed, primality, &constraints).stop_on(interrupt.clone());
+    if args.representations {
+        let index = RepresentationIndex::build(&args.form, &*primes);
+        eprintln!("Indexed {} distinct values of N", index.len());
+        config = config.representations(Arc::new(index));
+    }
+    let progress = search_from(&args.form, &*space, &mut writer, &config, start, |progress, writer| {
+        writer.state.update(progress, &*space);
+        #[cfg(feature = "tui")]
+        if let Some(dashboard) = &mut dashboard {
+            dashboard.draw(&writer.state)?;
+        }
+        // The checkpoint must never point past rows that are not yet durable
+        let writer = writer.inner_mut();
+        writer.sync()?;
+        Checkpoint {
+            seed,
+            form: form_key.clone(),
+            pool_size,
+            order: args.order,
+            progress: *progress,
+            output_len: writer.position(),
+        }
+        .save(&checkpoint_path)
+    })
+    .expect("Failed to write to CSV file.");
 
-fn compute_n(x: &BigUint, y: &BigUint, z: &BigUint) -> BigUint {
-    let a = BigUint::from(5u32);
-    let b = BigUint::from(7u32);
-    let c = BigUint::from(11u32);
-    let d = BigUint::from(23u32);
-    let e = BigUint::from(47u32);
-    let f = BigUint::from(83u32);
-    let g = BigUint::from(107u32);
-
-    &a * x * x
-        + &b * x * y
-        + &c * y * y
-        + &d * x * z
-        + &e * y * z
-        + &f * z * z
-        + &g
+    if interrupt.is_stopped() {
+        println!("Interrupted; the checkpoint is up to date");
+    }
+    if let Some(top) = &writer.state.top {
+        let join = |hits: Vec<BigUint>| -> String {
+            hits.iter().map(BigUint::to_string).collect::<Vec<_>>().join(", ")
+        };
+        println!("{} largest prime hits of this run: {}", top.k(), join(top.largest()));
+        if let Some(bound) = top.bound() {
+            println!("{} smallest above {}: {}", top.k(), bound, join(top.smallest()));
+        }
+    }
+    if progress.tuples < total {
+        println!(
+            "Stopped after {} of {} tuples with {} hits; run again with --resume to continue",
+            progress.tuples, total, progress.hits
+        );
+    }
+    println!("Data has been saved to {}", output.display());
 }
 
 fn main() {
-    // Define the first few known primes
-    let primes = vec![
-    BigUint::from(3u32),
-    BigUint::from(5u32),
-    BigUint::from(7u32),
-    BigUint::from(11u32),
-    BigUint::from(13u32),
-    BigUint::from(23u32),
-    BigUint::from(47u32),
-    BigUint::from(83u32),
-    BigUint::from(107u32),
-    BigUint::from(167u32),
-    BigUint::from(227u32),
-    BigUint::from(359u32),
-    BigUint::from(383u32),
-    BigUint::from(467u32),
-    BigUint::from(479u32),
-    BigUint::from(503u32),
-    BigUint::from(563u32),
-    BigUint::from(587u32),
-    BigUint::from(719u32),
-    BigUint::from(839u32),
-    BigUint::from(863u32),
-    BigUint::from(887u32),
-    BigUint::from(983u32),
-    BigUint::from(1019u32),
-    BigUint::from(1187u32),
-    BigUint::from(1283u32),
-    BigUint::from(1307u32),
-    BigUint::from(1319u32),
-    BigUint::from(1367u32),
-    BigUint::from(1439u32),
-    BigUint::from(1487u32),
-    BigUint::from(1523u32),
-    BigUint::from(1619u32),
-    BigUint::from(1823u32),
-    BigUint::from(1907u32),
-];
-
-
-    // Create output file and write header
-    let output_file = "universal_primes_index.csv";
-    let mut file = File::create(output_file).expect("Failed to create output file.");
-    writeln!(
-        file,
-        "x,y,z,n,classifications_n,classifications_x,classifications_y,classifications_z"
-    )
-    .expect("Failed to write header.");
-
-    // Iterate through all combinations of (x, y, z)
-    for x in &primes {
-        for y in &primes {
-            for z in &primes {
-                let n = compute_n(x, y, z);
-
-                let classifications_n = classify_prime(&n);
-
-                // Proceed only if N is prime
-                if classifications_n.contains(&"Prime") {
-                    let classifications_x = classify_prime(x);
-                    let classifications_y = classify_prime(y);
-                    let classifications_z = classify_prime(z);
-
-                    // Write to CSV file
-                    writeln!(
-                        file,
-                        "{},{},{},{},{:?},{:?},{:?},{:?}",
-                        x, y, z, n, classifications_n, classifications_x, classifications_y, classifications_z
-                    )
-                    .expect("Failed to write to CSV file.");
-                }
-            }
-        }
-    }
-
-    println!("Data has been saved to {}", output_file);
+    let args = Args::parse();
+    logging::init(args.log_level, args.log_json);
+
+    // Seed every random choice from one ChaCha20 stream so runs are reproducible
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+
+    // Search, classify, verify and sweep all run on the global pool
+    thread_pool_builder(args.threads, args.nice)
+        .build_global()
+        .expect("Failed to start the thread pool.");
+
+    if let Some(limit) = args.sieve_limit {
+        let bitmap = PrimeBitmap::new(limit, args.sieve);
+        eprintln!("Sieved {} primes up to {}", bitmap.count(), limit);
+        sieve::install(bitmap).expect("the sieve is installed once");
+    }
+
+    let primality = args.primality(DEFAULT_ROUNDS);
+    let args_rounds = args.rounds;
+    let args_seed = args.seed;
+    let command = args
+        .command
+        .unwrap_or_else(|| Command::Search(SearchArgs::parse_from(["search"])));
+    match command {
+        Command::Search(search_args) => run_search(&search_args, seed, primality),
+        Command::Classify {
+            input: Some(input),
+            column,
+            output,
+            pseudoprimes,
+            ..
+        } => {
+            let reader: Box<dyn BufRead> = if input.as_os_str() == "-" {
+                Box::new(io::stdin().lock())
+            } else {
+                Box::new(BufReader::new(File::open(&input).expect("Failed to open input file.")))
+            };
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            let column = column.as_ref();
+            let summary =
+                classify_lines(reader, &mut writer, column, &primality, pseudoprimes, seed)
+                    .expect("Failed to classify input.");
+            writer.flush().expect("Failed to write output.");
+            eprintln!(
+                "Classified {} numbers ({} invalid lines)",
+                summary.classified, summary.invalid
+            );
+        }
+        Command::Classify { numbers, pseudoprimes, .. } => {
+            for n in &numbers {
+                let classifications = if pseudoprimes {
+                    classify_with_pseudoprimes(n, &primality, &mut rng)
+                } else {
+                    classify_prime_with_config(n, &primality, &mut rng)
+                };
+                println!("{}: {:?}", n, classifications);
+            }
+        }
+        Command::IsPrime { n, witness: true } => {
+            match is_prime_with_witness(&n, primality.rounds, &mut rng) {
+                PrimalityResult::Composite { witness, factor } => {
+                    print!("false (witness {}", witness);
+                    if let Some(factor) = factor {
+                        print!(", factor {}", factor);
+                    }
+                    println!(")");
+                }
+                PrimalityResult::ProbablyPrime { rounds } => {
+                    println!("true (survived {} rounds)", rounds);
+                }
+            }
+        }
+        Command::IsPrime { n, .. } => {
+            let result = primality.test(&n, &mut rng);
+            println!("{} (error bound {:e})", result.is_prime, result.error_bound);
+        }
+        Command::NextPrime { n } => println!("{}", next_prime(&n)),
+        Command::PrevPrime { n } => match prev_prime(&n) {
+            Some(p) => println!("{}", p),
+            None => {
+                eprintln!("There is no prime below {}", n);
+                std::process::exit(1);
+            }
+        },
+        Command::Sweep {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            g,
+            pool_size,
+            output,
+            top,
+            distinct,
+        } => {
+            let bounds = CoefficientBounds {
+                ranges: [a, b, c, d, e, f, g],
+            };
+            let form_count = match bounds.form_count() {
+                Ok(count) => count,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let mut pool = default_pool();
+            pool.truncate(pool_size.unwrap_or(pool.len()));
+            println!(
+                "Sweeping {} forms over {} tuples each",
+                form_count,
+                pool.len().pow(3)
+            );
+
+            let results = if distinct {
+                let (results, skipped) =
+                    sweep_distinct_forms(&bounds, &pool).expect("form count checked above");
+                println!("Skipped {} forms equivalent to an earlier one", skipped);
+                results
+            } else {
+                sweep_forms(&bounds, &pool).expect("form count checked above")
+            };
+            for result in results.iter().take(top) {
+                println!(
+                    "{:.6}  {}  ({} hits)",
+                    result.density(),
+                    result.form,
+                    result.hits
+                );
+            }
+            if let Some(path) = output {
+                let mut writer =
+                    BufWriter::new(File::create(&path).expect("Failed to create output file."));
+                write_sweep(&results, &mut writer).expect("Failed to write sweep results.");
+                writer.flush().expect("Failed to write sweep results.");
+                println!("Sweep results have been saved to {}", path.display());
+            }
+        }
+        Command::Local {
+            form,
+            primes,
+            max_exponent,
+            siegel,
+        } => {
+            println!("Form: {}", form);
+            let invariants = FormInvariants::of(&form);
+            println!("Discriminant: {}", invariants.discriminant);
+            for binary in &invariants.binary {
+                let class_number = binary.class_number.map_or("-".to_string(), |h| h.to_string());
+                println!(
+                    "Binary sub-form in {}: discriminant {}, class number {}",
+                    binary.variables, binary.discriminant, class_number
+                );
+            }
+            for p in genus_primes(&form) {
+                if let Some(symbol) = genus_symbol(&form, p) {
+                    println!("Genus symbol at {}: {}", p, symbol);
+                }
+            }
+            for &p in &primes {
+                for report in local_reports(&form, p, max_exponent) {
+                    let represented = report.counts.iter().filter(|&&c| c > 0).count();
+                    println!(
+                        "mod {}^{}: {}/{} residues represented{}, unit fraction {:.6}, {}{}",
+                        p,
+                        report.exponent,
+                        represented,
+                        report.modulus(),
+                        if report.universal { " (universal)" } else { "" },
+                        report.unit_fraction(),
+                        if report.isotropic { "isotropic" } else { "anisotropic" },
+                        if report.stable { ", stable" } else { "" }
+                    );
+                }
+            }
+            println!(
+                "Local density factor over {:?}: {:.6}",
+                primes,
+                local_density_factor(&form, &primes)
+            );
+            if let Some(n) = siegel {
+                for &p in &primes {
+                    match local_density(&form, n, p) {
+                        Some(density) => println!("alpha_{}({}) = {:.6}", p, n, density),
+                        None => println!("alpha_{}({}) is out of reach", p, n),
+                    }
+                }
+                match siegel_representation_average(&form, n, SIEGEL_PRIME_BOUND) {
+                    Some(average) => println!("Siegel average of r({}): {:.3}", n, average),
+                    None => println!("No Siegel average for {}", n),
+                }
+            }
+        }
+        Command::Verify { path, form } => {
+            let form = form_for(&path, form);
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let verify_primality = PrimalityConfig {
+                rounds: args_rounds.unwrap_or(VERIFY_ROUNDS),
+                ..primality
+            };
+            let summary =
+                verify_results(reader, &form, &verify_primality, seed)
+                    .expect("Failed to read results file.");
+            for (line, failure) in &summary.failures {
+                println!("line {}: {}", line, failure);
+            }
+            println!(
+                "Verified {} rows: {} failed",
+                summary.checked,
+                summary.failures.len()
+            );
+            if !summary.failures.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Command::Diff { a, b, list } => {
+            let read = |path: &Path| {
+                let reader =
+                    BufReader::new(File::open(path).expect("Failed to open results file."));
+                read_rows(reader).expect("Failed to read results file.")
+            };
+            let (form_a, form_b) = (form_for(&a, None), form_for(&b, None));
+            let diff = ResultsDiff::new(&read(&a), &read(&b));
+            println!("A: {} ({}), {} distinct prime hits", a.display(), form_a, diff.hits_a);
+            println!("B: {} ({}), {} distinct prime hits", b.display(), form_b, diff.hits_b);
+            println!(
+                "{} in both, {} only in A, {} only in B",
+                diff.common,
+                diff.only_a.len(),
+                diff.only_b.len()
+            );
+            if list {
+                for n in &diff.only_a {
+                    println!("  < {}", n);
+                }
+                for n in &diff.only_b {
+                    println!("  > {}", n);
+                }
+            }
+            println!("{} classification changes", diff.changes.len());
+            if list {
+                let join = |tags: &BTreeSet<String>| -> String {
+                    tags.iter().cloned().collect::<Vec<_>>().join(";")
+                };
+                for change in &diff.changes {
+                    println!("  {}: {} -> {}", change.n, join(&change.before), join(&change.after));
+                }
+            }
+            println!("bits,hits_a,hits_b,share_a,share_b,delta");
+            for band in &diff.bands {
+                let (share_a, share_b) = band.shares(&diff);
+                println!(
+                    "{},{},{},{:.4},{:.4},{:+.4}",
+                    band.bits,
+                    band.a,
+                    band.b,
+                    share_a,
+                    share_b,
+                    share_b - share_a
+                );
+            }
+        }
+        Command::Merge { paths, sort, output } => {
+            let merged = merge_results(&paths, sort).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let mut writer: Box<dyn Write> = match &output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            merged.write_to(&mut writer).expect("Failed to write merged results.");
+            writer.flush().expect("Failed to write merged results.");
+            if let (Some(path), Some(manifest)) = (&output, &merged.manifest) {
+                manifest.save(&Manifest::path_for(path)).expect("Failed to write manifest.");
+            }
+            eprintln!(
+                "Merged {} rows from {} files ({} repeated rows dropped)",
+                merged.lines.len(),
+                paths.len(),
+                merged.duplicates
+            );
+        }
+        Command::Constellations { path, patterns, list } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            println!("{} distinct prime hits", hits.len());
+            for pattern in &patterns {
+                let report = find_constellations(&hits, pattern);
+                println!("{}: {} constellations", report.pattern, report.starts.len());
+                if list {
+                    for start in &report.starts {
+                        let members: Vec<String> =
+                            pattern.offsets().iter().map(|o| (start + o).to_string()).collect();
+                        println!("  {}", members.join(", "));
+                    }
+                }
+            }
+        }
+        Command::Residues { path, moduli, form } => {
+            let form = form_for(&path, form);
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let moduli = if moduli.is_empty() { default_moduli(&form) } else { moduli };
+            println!("{} distinct prime hits", hits.len());
+            for modulus in moduli {
+                let profile = ResidueProfile::new(&hits, modulus);
+                let (statistic, degrees) = profile.chi_square();
+                println!(
+                    "mod {}: chi-square {:.3} ({} df), p = {:.4e}, {} hits in non-unit classes",
+                    modulus,
+                    statistic,
+                    degrees,
+                    profile.p_value(),
+                    profile.non_units()
+                );
+                let counts: Vec<String> = profile
+                    .units()
+                    .iter()
+                    .map(|&r| format!("{}:{}", r, profile.counts[r as usize]))
+                    .collect();
+                println!("  {}", counts.join(" "));
+            }
+        }
+        Command::Bfile {
+            path,
+            offset,
+            limit,
+            form,
+            output,
+        } => {
+            let form = form_for(&path, form);
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            let comments = vec![
+                format!("Distinct primes |N| with N = {} and x, y, z from the search pool", form),
+                format!("Source: {}", path.display()),
+            ];
+            let written = write_bfile(&hits, &mut writer, offset, limit, &comments)
+                .expect("Failed to write b-file.");
+            writer.flush().expect("Failed to write b-file.");
+            eprintln!("Wrote {} of {} terms", written, hits.len());
+        }
+        Command::Bloom { path, output, fp_rate } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let mut filter = BloomFilter::with_rate(hits.len() as u64, fp_rate);
+            filter.extend(&hits);
+            filter.save(&output).expect("Failed to write filter.");
+            eprintln!(
+                "{} hits in {} bits with {} hashes ({} bytes)",
+                filter.len(),
+                filter.num_bits(),
+                filter.num_hashes(),
+                filter.num_bits().div_ceil(64) * 8
+            );
+        }
+        Command::BloomCheck { filter, numbers } => {
+            let filter = BloomFilter::load(&filter).unwrap_or_else(|e| {
+                eprintln!("{}: {}", filter.display(), e);
+                std::process::exit(1);
+            });
+            for n in numbers {
+                let verdict = if filter.contains(&n) { "possibly present" } else { "absent" };
+                println!("{}: {}", n, verdict);
+            }
+        }
+        Command::Graph {
+            path,
+            format,
+            output,
+        } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let rows = read_prime_rows(reader).expect("Failed to read results file.");
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            write_graph(&rows, format, &mut writer).expect("Failed to write graph.");
+            writer.flush().expect("Failed to write graph.");
+            let graph = PrimeGraph::from_rows(&rows);
+            eprintln!(
+                "{} nodes, {} edges, {} hits re-enter the pool",
+                graph.nodes.len(),
+                graph.edges.len(),
+                graph.reentrant().count()
+            );
+        }
+        Command::Histogram {
+            path,
+            binning,
+            json,
+            output,
+            #[cfg(feature = "plotters")]
+            svg,
+        } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let histogram = Histogram::new(&hits, binning);
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            if json {
+                writeln!(writer, "{}", histogram.to_json()).expect("Failed to write histogram.");
+            } else {
+                histogram.write_csv(&mut writer).expect("Failed to write histogram.");
+            }
+            writer.flush().expect("Failed to write histogram.");
+            #[cfg(feature = "plotters")]
+            if let Some(svg) = svg {
+                histogram.write_svg(&svg).expect("Failed to draw histogram.");
+                eprintln!("Chart has been saved to {}", svg.display());
+            }
+            eprintln!("Binned {} hits into {} bins", histogram.total(), histogram.bins.len());
+        }
+        Command::Chebyshev { path, output } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            write_partial_sums(&hits, &mut writer).expect("Failed to write sums.");
+            writer.flush().expect("Failed to write sums.");
+        }
+        Command::Dirichlet { path, re, im } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let series = DirichletSeries::indicator(&hits);
+            let s = Complex::new(re, im);
+            let comparison = series.euler_comparison(s);
+            println!("s = {}, {} hits", s, series.len());
+            println!("Σ n^-s over the hits:        {}", series.evaluate(s));
+            println!("Euler product over the hits: {}", comparison.euler_product);
+            println!("prime-power correction:      {}", comparison.prime_power_correction);
+            println!("ζ(s):                        {}", riemann_zeta(s));
+        }
+        Command::Orders { path, base } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            // Index (N - 1) / ord of the base; index 1 means it is a primitive root
+            let indices: Vec<Option<BigUint>> = hits
+                .par_iter()
+                .map(|n| multiplicative_order(&base, n).map(|order| (n - 1u32) / order))
+                .collect();
+            let mut histogram = std::collections::BTreeMap::new();
+            for index in indices.iter().flatten() {
+                *histogram.entry(index.clone()).or_insert(0u64) += 1;
+            }
+            let tested: u64 = histogram.values().sum();
+            let primitive = histogram.get(&BigUint::from(1u32)).copied().unwrap_or(0);
+            println!(
+                "{} is a primitive root modulo {} of {} prime hits ({:.2}%; Artin's constant is 37.40%)",
+                base,
+                primitive,
+                tested,
+                100.0 * primitive as f64 / tested.max(1) as f64
+            );
+            for (index, count) in histogram.iter().take(12) {
+                println!("  index {}: {}", index, count);
+            }
+        }
+        Command::Spacings { path, from, to } => {
+            let reader = BufReader::new(File::open(&path).expect("Failed to open results file."));
+            let hits = read_hits(reader).expect("Failed to read results file.");
+            let zeros = zeta_zeros(from, to, 0.05);
+            let comparison = compare_spacings(&zeros, &hits);
+            println!(
+                "{} zero spacings in [{}, {}], {} hit gaps",
+                comparison.zero_spacings, from, to, comparison.hit_gaps
+            );
+            println!("KS distance, zeros vs hits: {:.4}", comparison.zeros_vs_hits);
+            println!("KS distance, zeros vs GUE:  {:.4}", comparison.zeros_vs_gue);
+            println!("KS distance, hits vs GUE:   {:.4}", comparison.hits_vs_gue);
+        }
+        Command::PrimitiveRoot { n } => match primitive_root(&n) {
+            Some(g) => println!("{}", g),
+            None => {
+                eprintln!("There is no primitive root modulo {}", n);
+                std::process::exit(1);
+            }
+        },
+        Command::Collisions { form, signed, pool, output } => {
+            let pool: Box<dyn CandidatePool> = match &pool {
+                Some(path) => Box::new(MappedPool::open(path).expect("Failed to open pool file.")),
+                None if signed => Box::new(signed_pool(&default_pool())),
+                None => Box::new(default_pool()),
+            };
+            let index = RepresentationIndex::build(&form, &*pool);
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).expect("Failed to create output file."),
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            write_collisions(&index, &*pool, &mut writer).expect("Failed to write collisions.");
+            writer.flush().expect("Failed to write collisions.");
+            eprintln!(
+                "{} of {} values of N have more than one representation",
+                index.collisions().len(),
+                index.len()
+            );
+        }
+        Command::Represent { n, form } => match represent(&form, &n) {
+            Some((x, y, z)) => println!("{} = F({}, {}, {})", n, x, y, z),
+            None => {
+                eprintln!("{} is not represented by {}", n, form);
+                std::process::exit(1);
+            }
+        },
+        Command::Check290 { diagonal, gram } => {
+            let form = gram.unwrap_or_else(|| IntegralForm::diagonal(&diagonal));
+            let report = match check_290(&form) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Cannot check form: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            for (t, x) in &report.representations {
+                match x {
+                    Some(x) => println!("{}: {:?}", t, x),
+                    None => println!("{}: not represented", t),
+                }
+            }
+            if report.is_universal() {
+                println!("Universal: represents every positive integer");
+            } else {
+                println!("Not universal: misses {:?}", report.missing());
+            }
+        }
+        Command::RandomPrime { lo, hi, count } => {
+            for _ in 0..count {
+                match random_prime_in_range(&lo, &hi, &mut rng) {
+                    Some(p) => println!("{}", p),
+                    None => {
+                        eprintln!("There is no prime in [{}, {})", lo, hi);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Command::SafePrime { bits, count } => {
+            if bits < 5 {
+                eprintln!("Safe primes need at least 5 bits");
+                std::process::exit(1);
+            }
+            for _ in 0..count {
+                println!("{}", random_safe_prime(bits, &primality, &mut rng));
+            }
+        }
+        Command::Pool { lo, hi, output } => {
+            let count = sieve_pool(&output, lo, hi).expect("Failed to write pool file.");
+            println!("Wrote {} primes to {}", count, output.display());
+        }
+        Command::Genvectors { check: Some(path), .. } => {
+            let json = std::fs::read_to_string(&path).expect("Failed to read vectors file.");
+            let vectors = TestVectors::from_json(&json).expect("Failed to parse vectors file.");
+            match vectors.check() {
+                Ok(()) => println!("All vectors match"),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Genvectors { output, .. } => {
+            let vectors = TestVectors::generate(args_seed.unwrap_or(DEFAULT_SEED));
+            match output {
+                Some(path) => std::fs::write(&path, vectors.to_json() + "\n")
+                    .expect("Failed to write vectors file."),
+                None => println!("{}", vectors.to_json()),
+            }
+        }
+        Command::Shamir { command } => run_shamir(command),
+    }
+}
+
+/// Seconds since the Unix epoch by the system clock.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
 }
 
+fn run_shamir(command: ShamirCommand) {
+    match command {
+        ShamirCommand::SplitFile {
+            path,
+            threshold,
+            shares,
+            output_dir,
+            label,
+            custodians,
+            expires_in,
+            quorum,
+        } => {
+            let secret = std::fs::read(&path).expect("Failed to read secret file.");
+            let now = unix_now();
+            let policy = SharePolicy {
+                label,
+                created: Some(now),
+                expires: expires_in.map(|duration| now + duration.as_secs()),
+                quorum,
+            };
+            let rng = &mut EntropyRng(&mut OsEntropy);
+            let split =
+                ShareFile::split_with_policy(&secret, threshold, shares, &policy, &custodians, rng);
+            let files = split.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let name = path.file_name().expect("Secret path names no file.").to_string_lossy();
+            let dir = output_dir.unwrap_or_else(|| path.with_file_name(""));
+            for file in &files {
+                let share_path = dir.join(format!("{}.share{}", name, file.index));
+                file.save(&share_path).expect("Failed to write share file.");
+                println!("{}", share_path.display());
+            }
+            eprintln!(
+                "Split {} bytes into {} shares, any {} of which recover it",
+                secret.len(),
+                shares,
+                threshold
+            );
+        }
+        ShamirCommand::Combine { shares, output } => {
+            let files: Vec<ShareFile> = shares
+                .iter()
+                .map(|path| {
+                    ShareFile::load(path).unwrap_or_else(|e| {
+                        eprintln!("{}: {}", path.display(), e);
+                        std::process::exit(1);
+                    })
+                })
+                .collect();
+            let now = unix_now();
+            let secret = ShareFile::combine_at(&files, now).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            match output {
+                Some(path) => std::fs::write(path, &secret).expect("Failed to write output file."),
+                None => io::stdout().lock().write_all(&secret).expect("Failed to write output."),
+            }
+            eprintln!("Recovered {} bytes; SHA3-256 and share MACs verified", secret.len());
+            let policy = &files[0].policy;
+            if let Some(label) = &policy.label {
+                eprintln!("Label: {}", label);
+            }
+            if let Some(quorum) = &policy.quorum {
+                eprintln!("Quorum: {}", quorum);
+            }
+            let custodians: Vec<&str> =
+                files.iter().filter_map(|file| file.custodian.as_deref()).collect();
+            if !custodians.is_empty() {
+                eprintln!("Custodians: {}", custodians.join(", "));
+            }
+            if let Some(expires) = policy.expires {
+                eprintln!("Shares expire at {} ({}s from now)", expires, expires - now);
+            }
+        }
+    }
+}
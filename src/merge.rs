@@ -0,0 +1,249 @@
+//! Combining partial results files, e.g. from searches sharded by hand, into one.
+//!
+//! Rows are kept verbatim, annotation columns included, deduplicated by (x, y, z) and sorted
+//! by the tuple or by N. The inputs must share a header and, where they have manifests,
+//! agree on every setting that changes what a row says: the form, the primality settings,
+//! the filter, the tuple constraints and the optional columns. Seeds and pools may differ
+//! between shards.
+
+use num_bigint::BigInt;
+use thiserror::Error;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::output::Manifest;
+use crate::results::{is_header, parse_row, ResultsError};
+use crate::search::CSV_HEADER;
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{}: line {line}: {error}", .path.display())]
+    Row { path: PathBuf, line: usize, error: ResultsError },
+    #[error("{}: header '{found}' differs from '{expected}'", .path.display())]
+    HeaderMismatch { path: PathBuf, expected: String, found: String },
+    #[error("{}: manifest has {field} '{found}', expected '{expected}'", .path.display())]
+    ManifestMismatch { path: PathBuf, field: &'static str, expected: String, found: String },
+    #[error("{}: no manifest, unlike the other inputs", .0.display())]
+    MissingManifest(PathBuf),
+    #[error("{tuple} gives N = {first} in one input and {second} in another")]
+    Conflict { tuple: String, first: BigInt, second: BigInt },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid sort order '{0}', expected xyz or n")]
+pub struct MergeOrderError(pub String);
+
+/// How merged rows are sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOrder {
+    /// By (x, y, z)
+    Tuple,
+    /// By N, then (x, y, z)
+    N,
+}
+
+impl FromStr for MergeOrder {
+    type Err = MergeOrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "xyz" => Ok(MergeOrder::Tuple),
+            "n" => Ok(MergeOrder::N),
+            _ => Err(MergeOrderError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for MergeOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeOrder::Tuple => write!(f, "xyz"),
+            MergeOrder::N => write!(f, "n"),
+        }
+    }
+}
+
+/// The merged rows of several results files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedResults {
+    pub header: String,
+    /// Data lines in the requested order, without line endings
+    pub lines: Vec<String>,
+    /// The first input's manifest, with the pools of the others if they differ; `None` if
+    /// the inputs have no manifests
+    pub manifest: Option<Manifest>,
+    /// Rows dropped because an earlier input already had their tuple
+    pub duplicates: usize,
+}
+
+impl MergedResults {
+    /// Write the header and the rows as a results file.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", self.header)?;
+        for line in &self.lines {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Merge the results files at `paths`, keeping the first row of each tuple.
+pub fn merge_results(paths: &[PathBuf], order: MergeOrder) -> Result<MergedResults, MergeError> {
+    let mut header: Option<String> = None;
+    let mut manifests: Vec<(Option<Manifest>, &Path)> = Vec::new();
+    let mut rows: BTreeMap<(BigInt, BigInt, BigInt), (BigInt, String)> = BTreeMap::new();
+    let mut duplicates = 0;
+
+    for path in paths {
+        manifests.push((Manifest::load(&Manifest::path_for(path))?, path));
+        let reader = BufReader::new(File::open(path)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.trim().is_empty() {
+                continue;
+            }
+            if is_header(line) {
+                match &header {
+                    Some(expected) if expected != line => {
+                        return Err(MergeError::HeaderMismatch {
+                            path: path.clone(),
+                            expected: expected.clone(),
+                            found: line.to_string(),
+                        })
+                    }
+                    Some(_) => {}
+                    None => header = Some(line.to_string()),
+                }
+                continue;
+            }
+            let row = parse_row(line).map_err(|error| MergeError::Row {
+                path: path.clone(),
+                line: i + 1,
+                error,
+            })?;
+            match rows.get(&(row.x.clone(), row.y.clone(), row.z.clone())) {
+                Some((n, _)) if *n != row.n => {
+                    return Err(MergeError::Conflict {
+                        tuple: format!("({}, {}, {})", row.x, row.y, row.z),
+                        first: n.clone(),
+                        second: row.n,
+                    })
+                }
+                Some(_) => duplicates += 1,
+                None => {
+                    rows.insert((row.x, row.y, row.z), (row.n, line.to_string()));
+                }
+            }
+        }
+    }
+
+    let manifest = match &manifests[..] {
+        _ if manifests.iter().all(|(manifest, _)| manifest.is_none()) => None,
+        [] => None,
+        [(first, first_path), rest @ ..] => {
+            let first =
+                first.as_ref().ok_or_else(|| MergeError::MissingManifest(first_path.into()))?;
+            let mut merged = first.clone();
+            for (manifest, path) in rest {
+                let manifest =
+                    manifest.as_ref().ok_or_else(|| MergeError::MissingManifest(path.into()))?;
+                if let Some((field, expected, found)) = first.row_mismatch(manifest) {
+                    let path = path.into();
+                    return Err(MergeError::ManifestMismatch { path, field, expected, found });
+                }
+                if manifest.pool != first.pool {
+                    merged.pool = format!("{} + {}", merged.pool, manifest.pool);
+                    merged.pool_size += manifest.pool_size;
+                }
+            }
+            Some(merged)
+        }
+    };
+
+    let mut rows: Vec<_> = rows.into_iter().collect();
+    if order == MergeOrder::N {
+        rows.sort_by(|(tuple_a, (n_a, _)), (tuple_b, (n_b, _))| {
+            n_a.cmp(n_b).then_with(|| tuple_a.cmp(tuple_b))
+        });
+    }
+    Ok(MergedResults {
+        header: header.unwrap_or_else(|| CSV_HEADER.to_string()),
+        lines: rows.into_iter().map(|(_, (_, line))| line).collect(),
+        manifest,
+        duplicates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form::QuadraticForm;
+    use crate::primality::PrimalityConfig;
+
+    fn scratch(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("universal-primes-merge-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        let _ = std::fs::remove_file(Manifest::path_for(&path));
+        path
+    }
+
+    #[test]
+    fn test_merge_results() {
+        let a = scratch(
+            "a.csv",
+            &format!("{}\n1,9,9,20,[],[],[],[]\n2,2,2,17,[],[],[],[]\n", CSV_HEADER),
+        );
+        let b = scratch(
+            "b.csv",
+            &format!("{}\n2,2,2,17,[],[],[],[]\n2,2,3,40,[],[],[],[]\n", CSV_HEADER),
+        );
+        let merged = merge_results(&[a.clone(), b.clone()], MergeOrder::Tuple).unwrap();
+        assert_eq!(merged.duplicates, 1);
+        assert_eq!(merged.manifest, None);
+        let tuples: Vec<&str> = merged.lines.iter().map(|line| &line[..5]).collect();
+        assert_eq!(tuples, vec!["1,9,9", "2,2,2", "2,2,3"]);
+        let by_n = merge_results(&[a.clone(), b.clone()], MergeOrder::N).unwrap();
+        let tuples: Vec<&str> = by_n.lines.iter().map(|line| &line[..5]).collect();
+        assert_eq!(tuples, vec!["2,2,2", "1,9,9", "2,2,3"]);
+
+        let mut out = Vec::new();
+        merged.write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 4);
+
+        // A tuple giving two different values means the inputs searched different forms
+        let c = scratch("c.csv", "2,2,2,41,[],[],[],[]\n");
+        assert!(matches!(
+            merge_results(&[a.clone(), c], MergeOrder::Tuple),
+            Err(MergeError::Conflict { .. })
+        ));
+
+        let form = QuadraticForm::universal();
+        let manifest = Manifest::new(1, &form, "default", 35, &PrimalityConfig::default());
+        manifest.save(&Manifest::path_for(&a)).unwrap();
+        assert!(matches!(
+            merge_results(&[a.clone(), b.clone()], MergeOrder::Tuple),
+            Err(MergeError::MissingManifest(_))
+        ));
+        let mut shard = Manifest::new(2, &form, "shard.pool", 10, &PrimalityConfig::default());
+        shard.save(&Manifest::path_for(&b)).unwrap();
+        let merged = merge_results(&[a.clone(), b.clone()], MergeOrder::Tuple).unwrap();
+        let merged = merged.manifest.unwrap();
+        assert_eq!(merged.seed, 1);
+        assert_eq!((merged.pool.as_str(), merged.pool_size), ("default + shard.pool", 45));
+        shard.rounds += 1;
+        shard.save(&Manifest::path_for(&b)).unwrap();
+        assert!(matches!(
+            merge_results(&[a, b], MergeOrder::Tuple),
+            Err(MergeError::ManifestMismatch { field: "rounds", .. })
+        ));
+    }
+}
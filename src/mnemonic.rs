@@ -0,0 +1,301 @@
+//! Word mnemonics for shares, so they can be written down and typed back in by hand.
+//!
+//! As in BIP39, each word of a 2^b-word list carries b bits. The encoded frame is the payload
+//! length as a big-endian u16, the payload, and the first four bytes of a SHA3-256 of both,
+//! padded with zero bits to whole words; a misspelt, dropped or swapped word fails the
+//! checksum rather than decoding to a different share. The default list is BIP39's English
+//! one (11 bits a word); any list whose length is a power of two works.
+
+use num_bigint::BigUint;
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::domains;
+use crate::gf256::ByteShare;
+use crate::reader::{MalformedInput, StrictReader};
+
+/// Checksum bytes appended to each frame.
+const CHECKSUM_BYTES: usize = 4;
+
+/// SHA3-256 of a frame's length and payload, the first `CHECKSUM_BYTES` of which are kept.
+fn checksum(framed: &[u8]) -> [u8; 32] {
+    let hasher: Sha3_256 = domains::tagged(domains::MNEMONIC_CHECKSUM);
+    hasher.chain_update(framed).finalize().into()
+}
+
+#[derive(Error, Debug)]
+pub enum MnemonicError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("word list has {0} words, not a power of two between 2 and 65536")]
+    WordListSize(usize),
+    #[error("word list has an empty, spaced or repeated word {0:?}")]
+    BadWord(String),
+    #[error("share is {bytes} bytes, more than the limit of {max}")]
+    TooLarge { bytes: usize, max: usize },
+    #[error("{0:?} is not in the word list")]
+    UnknownWord(String),
+    #[error("mnemonic has the wrong number of words")]
+    Length,
+    #[error("mnemonic checksum does not match; a word is wrong or missing")]
+    Checksum,
+    #[error("mnemonic does not hold a share: {0}")]
+    Malformed(#[from] MalformedInput),
+}
+
+/// A list of 2^b distinct words, each standing for b bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordList {
+    words: Vec<String>,
+    index: HashMap<String, u16>,
+    bits: u32,
+}
+
+impl WordList {
+    /// Words in order; the list must have a power-of-two length between 2 and 65536 and its
+    /// words must be distinct, nonempty and free of whitespace.
+    pub fn new(words: Vec<String>) -> Result<Self, MnemonicError> {
+        if !(2..=1 << 16).contains(&words.len()) || !words.len().is_power_of_two() {
+            return Err(MnemonicError::WordListSize(words.len()));
+        }
+        let mut index = HashMap::with_capacity(words.len());
+        for (i, word) in words.iter().enumerate() {
+            let bad = word.is_empty() || word.contains(char::is_whitespace);
+            if bad || index.insert(word.clone(), i as u16).is_some() {
+                return Err(MnemonicError::BadWord(word.clone()));
+            }
+        }
+        let bits = words.len().trailing_zeros();
+        Ok(WordList { words, index, bits })
+    }
+
+    /// BIP39's English list of 2048 words.
+    pub fn english() -> Self {
+        let words = bip39::Language::English.word_list().iter().map(|w| w.to_string());
+        WordList::new(words.collect()).expect("the BIP39 list is valid")
+    }
+
+    /// A list from a file of one word per line; blank lines are skipped.
+    pub fn load(path: &Path) -> Result<Self, MnemonicError> {
+        let text = fs::read_to_string(path)?;
+        let words = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        WordList::new(words.map(String::from).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Bits carried by each word.
+    pub fn bits_per_word(&self) -> u32 {
+        self.bits
+    }
+}
+
+impl Default for WordList {
+    fn default() -> Self {
+        WordList::english()
+    }
+}
+
+/// Encodes and decodes mnemonics over a word list, refusing payloads over a size limit.
+#[derive(Debug, Clone)]
+pub struct MnemonicCodec {
+    words: WordList,
+    max_bytes: usize,
+}
+
+impl MnemonicCodec {
+    /// The English list, with payloads of up to 256 bytes (a share of a 2048-bit modulus).
+    pub fn new() -> Self {
+        MnemonicCodec {
+            words: WordList::english(),
+            max_bytes: 256,
+        }
+    }
+
+    pub fn word_list(mut self, words: WordList) -> Self {
+        self.words = words;
+        self
+    }
+
+    /// The largest payload to encode or accept, at most 65535 bytes.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes.min(u16::MAX as usize);
+        self
+    }
+
+    /// Words needed for a payload of `bytes` bytes.
+    pub fn words_for(&self, bytes: usize) -> usize {
+        let bits = 8 * (2 + bytes + CHECKSUM_BYTES);
+        bits.div_ceil(self.words.bits as usize)
+    }
+
+    /// The mnemonic of `payload`, as words separated by single spaces.
+    pub fn encode(&self, payload: &[u8]) -> Result<String, MnemonicError> {
+        if payload.len() > self.max_bytes {
+            return Err(MnemonicError::TooLarge { bytes: payload.len(), max: self.max_bytes });
+        }
+        let mut frame = (payload.len() as u16).to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        let checksum = checksum(&frame);
+        frame.extend_from_slice(&checksum[..CHECKSUM_BYTES]);
+
+        let bits = self.words.bits;
+        let mut words = Vec::with_capacity(self.words_for(payload.len()));
+        let (mut buffer, mut buffered) = (0u32, 0u32);
+        for &byte in &frame {
+            buffer = (buffer << 8) | byte as u32;
+            buffered += 8;
+            while buffered >= bits {
+                buffered -= bits;
+                words.push(self.words.words[(buffer >> buffered) as usize].as_str());
+                buffer &= (1 << buffered) - 1;
+            }
+        }
+        if buffered > 0 {
+            words.push(self.words.words[(buffer << (bits - buffered)) as usize].as_str());
+        }
+        Ok(words.join(" "))
+    }
+
+    /// The payload of `mnemonic`, which may be split by any whitespace and in any case.
+    pub fn decode(&self, mnemonic: &str) -> Result<Vec<u8>, MnemonicError> {
+        let bits = self.words.bits;
+        let mut frame = Vec::new();
+        let (mut buffer, mut buffered) = (0u32, 0u32);
+        for word in mnemonic.split_whitespace() {
+            let value = self
+                .words
+                .index
+                .get(word)
+                .or_else(|| self.words.index.get(&word.to_lowercase()))
+                .ok_or_else(|| MnemonicError::UnknownWord(word.to_string()))?;
+            buffer = (buffer << bits) | *value as u32;
+            buffered += bits;
+            while buffered >= 8 {
+                buffered -= 8;
+                frame.push((buffer >> buffered) as u8);
+                buffer &= (1 << buffered) - 1;
+            }
+        }
+        if frame.len() < 2 + CHECKSUM_BYTES {
+            return Err(MnemonicError::Length);
+        }
+        let length = u16::from_be_bytes([frame[0], frame[1]]) as usize;
+        if length > self.max_bytes {
+            return Err(MnemonicError::TooLarge { bytes: length, max: self.max_bytes });
+        }
+        // The words must cover the frame exactly, with only zero padding after it
+        let end = 2 + length + CHECKSUM_BYTES;
+        let words = mnemonic.split_whitespace().count();
+        if frame.len() < end || words != self.words_for(length) {
+            return Err(MnemonicError::Length);
+        }
+        let checksum = checksum(&frame[..2 + length]);
+        let padding_clear = buffer == 0 && frame[end..].iter().all(|&byte| byte == 0);
+        if frame[2 + length..end] != checksum[..CHECKSUM_BYTES] || !padding_clear {
+            return Err(MnemonicError::Checksum);
+        }
+        frame.truncate(2 + length);
+        Ok(frame.split_off(2))
+    }
+
+    /// The mnemonic of a prime-Shamir share: its x-coordinate as a big-endian u16, then y.
+    pub fn encode_prime_share(&self, share: &(usize, BigUint)) -> Result<String, MnemonicError> {
+        let x = u16::try_from(share.0).map_err(|_| MalformedInput::Invalid("share index"))?;
+        let mut payload = x.to_be_bytes().to_vec();
+        payload.extend_from_slice(&share.1.to_bytes_be());
+        self.encode(&payload)
+    }
+
+    pub fn decode_prime_share(&self, mnemonic: &str) -> Result<(usize, BigUint), MnemonicError> {
+        let payload = self.decode(mnemonic)?;
+        let mut reader = StrictReader::new(&payload);
+        let x = reader.u16_be("share index")? as usize;
+        let y = reader.rest();
+        if y.is_empty() {
+            return Err(MalformedInput::Truncated { field: "share value", missing: 1 }.into());
+        }
+        Ok((x, BigUint::from_bytes_be(y)))
+    }
+
+    /// The mnemonic of a GF(256) share: its index byte, then its data.
+    pub fn encode_byte_share(&self, share: &ByteShare) -> Result<String, MnemonicError> {
+        let mut payload = vec![share.index];
+        payload.extend_from_slice(&share.data);
+        self.encode(&payload)
+    }
+
+    pub fn decode_byte_share(&self, mnemonic: &str) -> Result<ByteShare, MnemonicError> {
+        let payload = self.decode(mnemonic)?;
+        let mut reader = StrictReader::new(&payload);
+        let index = reader.u8("share index")?;
+        if index == 0 {
+            return Err(MalformedInput::Invalid("share index").into());
+        }
+        Ok(ByteShare { index, data: reader.rest().to_vec() })
+    }
+}
+
+impl Default for MnemonicCodec {
+    fn default() -> Self {
+        MnemonicCodec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mnemonics() {
+        let codec = MnemonicCodec::new();
+        assert_eq!(codec.words_for(0), 5);
+        assert_eq!(codec.encode(&[]).unwrap().split(' ').count(), 5);
+        let share = (3, (BigUint::from(1u32) << 127u32) - 1u32);
+        let words = codec.encode_prime_share(&share).unwrap();
+        assert_eq!(words.split(' ').count(), codec.words_for(18));
+        assert_eq!(codec.decode_prime_share(&words).unwrap(), share);
+        let shouted = format!("  {}\n", words.to_uppercase().replace(' ', "\n"));
+        assert_eq!(codec.decode_prime_share(&shouted).unwrap(), share);
+
+        // Changing, dropping or swapping a word is caught
+        let mut list: Vec<&str> = words.split(' ').collect();
+        let original = list[4];
+        list[4] = if original == "zoo" { "abandon" } else { "zoo" };
+        assert!(matches!(codec.decode(&list.join(" ")), Err(MnemonicError::Checksum)));
+        list[4] = original;
+        let last = list.len() - 1;
+        list.swap(2, last);
+        assert_ne!(list[2], list[last]);
+        assert!(codec.decode(&list.join(" ")).is_err());
+        assert!(matches!(codec.decode(&list[..last].join(" ")), Err(MnemonicError::Length)));
+        assert!(matches!(codec.decode("abandon zzz"), Err(MnemonicError::UnknownWord(_))));
+
+        let byte_share = ByteShare { index: 2, data: b"paper".to_vec() };
+        let small = WordList::new((0..16).map(|i| format!("w{}", i)).collect()).unwrap();
+        let codec = MnemonicCodec::new().word_list(small).max_bytes(8);
+        let words = codec.encode_byte_share(&byte_share).unwrap();
+        assert!(words.starts_with("w0 w0 w0 w6 w0 w2 "));
+        assert_eq!(codec.decode_byte_share(&words).unwrap(), byte_share);
+        assert!(matches!(
+            codec.encode(&[0; 9]),
+            Err(MnemonicError::TooLarge { bytes: 9, max: 8 })
+        ));
+
+        let three = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert!(matches!(WordList::new(three), Err(MnemonicError::WordListSize(3))));
+        let repeated = vec!["a".to_string(), "a".to_string()];
+        assert!(matches!(WordList::new(repeated), Err(MnemonicError::BadWord(_))));
+    }
+}
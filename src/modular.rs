@@ -0,0 +1,682 @@
+//! Modular arithmetic: quadratic residues and square roots, element orders, primitive roots,
+//! prime-order subgroups, exponentiation with secret exponents, and precomputed contexts for
+//! repeated work against one modulus.
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use thiserror::Error;
+
+use crate::factor::factorize;
+
+/// Jacobi symbol (a/n) for odd positive `n`: 0 when gcd(a, n) > 1, otherwise ±1.
+pub fn jacobi(a: &BigInt, n: &BigUint) -> i32 {
+    let n_int = BigInt::from(n.clone());
+    let mut a = a.mod_floor(&n_int).to_biguint().expect("non-negative after mod_floor");
+    let mut n = n.clone();
+    let mut result = 1;
+    while !a.is_zero() {
+        let twos = a.trailing_zeros().unwrap_or(0);
+        a >>= twos;
+        let n_mod_8 = (&n % 8u32).to_u32().unwrap_or(0);
+        if twos % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            result = -result;
+        }
+        if &a % 4u32 == BigUint::from(3u32) && &n % 4u32 == BigUint::from(3u32) {
+            result = -result;
+        }
+        std::mem::swap(&mut a, &mut n);
+        a %= &n;
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
+}
+
+/// Legendre symbol (a/p) for prime `p`: 0 if p divides a, 1 for a nonzero square mod p and
+/// -1 otherwise.
+pub fn legendre(a: &BigInt, p: &BigUint) -> i32 {
+    if p == &BigUint::from(2u32) {
+        return if a.is_odd() { 1 } else { 0 };
+    }
+    jacobi(a, p)
+}
+
+/// A square root of `a` mod prime `p` by Tonelli–Shanks, or `None` if a is not a square.
+///
+/// Returns the smaller of the two roots r and p - r.
+pub fn sqrt_mod(a: &BigUint, p: &BigUint) -> Option<BigUint> {
+    let a = a % p;
+    if a.is_zero() || p == &BigUint::from(2u32) {
+        return Some(a);
+    }
+    if legendre(&BigInt::from(a.clone()), p) != 1 {
+        return None;
+    }
+
+    // p - 1 = q·2^s with q odd
+    let p_minus_one = p - 1u32;
+    let s = p_minus_one.trailing_zeros().unwrap_or(0);
+    let q = &p_minus_one >> s;
+    let root = if s == 1 {
+        // p ≡ 3 (mod 4): a^((p+1)/4) directly
+        a.modpow(&((p + 1u32) >> 2), p)
+    } else {
+        let mut z = BigUint::from(2u32);
+        while legendre(&BigInt::from(z.clone()), p) != -1 {
+            z += 1u32;
+        }
+        let mut m = s;
+        let mut c = z.modpow(&q, p);
+        let mut t = a.modpow(&q, p);
+        let mut r = a.modpow(&((&q + 1u32) >> 1), p);
+        while !t.is_one() {
+            // Least i with t^(2^i) = 1
+            let mut i = 0;
+            let mut t_power = t.clone();
+            while !t_power.is_one() {
+                t_power = &t_power * &t_power % p;
+                i += 1;
+            }
+            let b = c.modpow(&(BigUint::one() << (m - i - 1)), p);
+            m = i;
+            c = &b * &b % p;
+            t = t * &c % p;
+            r = r * b % p;
+        }
+        r
+    };
+    Some(root.clone().min(p - root))
+}
+
+/// The x in [0, m₁·m₂·…) with x ≡ rᵢ (mod mᵢ) for every (rᵢ, mᵢ) in `residues`, or `None`
+/// if a modulus is zero or two moduli share a factor.
+///
+/// An empty system is solved by 0.
+pub fn crt(residues: &[(BigUint, BigUint)]) -> Option<BigUint> {
+    for (i, (_, m)) in residues.iter().enumerate() {
+        if m.is_zero() || residues[..i].iter().any(|(_, other)| !m.gcd(other).is_one()) {
+            return None;
+        }
+    }
+    // Garner-style accumulation: x ≡ r (mod M) extended one congruence at a time
+    let mut x = BigUint::zero();
+    let mut modulus = BigUint::one();
+    for (r, m) in residues {
+        let r = r % m;
+        let difference = (&r + m - &x % m) % m;
+        let step = difference * mod_inverse(&(&modulus % m), m).ok()? % m;
+        x += &modulus * step;
+        modulus *= m;
+    }
+    Some(x)
+}
+
+/// Why a modular inverse does not exist.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ModularError {
+    #[error("modulus must be positive")]
+    ZeroModulus,
+    #[error("{a} is not invertible modulo {modulus} (gcd {gcd})")]
+    NotInvertible {
+        a: BigUint,
+        modulus: BigUint,
+        gcd: BigUint,
+    },
+}
+
+/// (g, x, y) with g = gcd(a, b) ≥ 0 and ax + by = g.
+pub fn extended_gcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    let (mut old_r, mut r) = (a.clone(), b.clone());
+    let (mut old_x, mut x) = (BigInt::one(), BigInt::zero());
+    let (mut old_y, mut y) = (BigInt::zero(), BigInt::one());
+    while !r.is_zero() {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_x = &old_x - &quotient * &x;
+        old_x = std::mem::replace(&mut x, next_x);
+        let next_y = &old_y - &quotient * &y;
+        old_y = std::mem::replace(&mut y, next_y);
+    }
+    if old_r.is_negative() {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// a⁻¹ mod m by the extended Euclidean algorithm, for any modulus, prime or not.
+pub fn mod_inverse(a: &BigUint, modulus: &BigUint) -> Result<BigUint, ModularError> {
+    if modulus.is_zero() {
+        return Err(ModularError::ZeroModulus);
+    }
+    let m = BigInt::from(modulus.clone());
+    let (gcd, x, _) = extended_gcd(&BigInt::from(a.clone()), &m);
+    if !gcd.is_one() {
+        return Err(ModularError::NotInvertible {
+            a: a.clone(),
+            modulus: modulus.clone(),
+            gcd: gcd.magnitude().clone(),
+        });
+    }
+    Ok(x.mod_floor(&m).magnitude().clone())
+}
+
+/// The inverses of all of `values` mod `modulus`, with one extended-Euclid inversion and
+/// three multiplications per value (Montgomery's trick): the inverse of the running product
+/// is peeled back one value at a time.
+///
+/// Fails like `mod_inverse` on the first value that has no inverse.
+pub fn batch_inverse(values: &[BigUint], modulus: &BigUint) -> Result<Vec<BigUint>, ModularError> {
+    if modulus.is_zero() {
+        return Err(ModularError::ZeroModulus);
+    }
+    if values.is_empty() {
+        return Ok(vec![]);
+    }
+    let context = ModulusContext::new(modulus);
+    // prefix[i] = values[0]·…·values[i]
+    let mut prefix = Vec::with_capacity(values.len());
+    let mut product = BigUint::one() % modulus;
+    for v in values {
+        product = context.mul(&product, v);
+        prefix.push(product.clone());
+    }
+    let mut inverse = match mod_inverse(&product, modulus) {
+        Ok(inverse) => inverse,
+        Err(_) => {
+            let first = values.iter().find_map(|v| mod_inverse(v, modulus).err());
+            return Err(first.expect("a product is invertible when its factors are"));
+        }
+    };
+    let mut inverses = vec![BigUint::zero(); values.len()];
+    for i in (0..values.len()).rev() {
+        // inverse is now (values[0]·…·values[i])⁻¹
+        inverses[i] = match i {
+            0 => inverse.clone(),
+            _ => context.mul(&inverse, &prefix[i - 1]),
+        };
+        inverse = context.mul(&inverse, &values[i]);
+    }
+    Ok(inverses)
+}
+
+/// Euler's φ(n) from the factorization of `n` (φ(1) = 1).
+pub fn euler_phi(n: &BigUint) -> BigUint {
+    factorize(n)
+        .into_iter()
+        .fold(BigUint::one(), |phi, (p, k)| phi * p.pow(k - 1) * (p - 1u32))
+}
+
+/// Carmichael's λ(n), the exponent of the group of units mod `n` (λ(1) = 1).
+pub fn carmichael_lambda(n: &BigUint) -> BigUint {
+    factorize(n).into_iter().fold(BigUint::one(), |lambda, (p, k)| {
+        let prime_power = if p == BigUint::from(2u32) && k >= 3 {
+            BigUint::one() << (k - 2)
+        } else {
+            p.pow(k - 1) * (&p - 1u32)
+        };
+        lambda.lcm(&prime_power)
+    })
+}
+
+/// The least k > 0 with a^k ≡ 1 (mod n), or `None` unless gcd(a, n) = 1 and n > 1.
+///
+/// Starts from λ(n) and strips each prime factor while the power stays 1, so the cost is
+/// dominated by factoring n and λ(n); for prime n that is factoring n - 1.
+pub fn multiplicative_order(a: &BigUint, n: &BigUint) -> Option<BigUint> {
+    if n <= &BigUint::one() || !a.gcd(n).is_one() {
+        return None;
+    }
+    let lambda = carmichael_lambda(n);
+    Some(reduce_to_order(a, n, lambda))
+}
+
+/// Shrink a multiple `order` of a's order mod n down to the order itself.
+fn reduce_to_order(a: &BigUint, n: &BigUint, mut order: BigUint) -> BigUint {
+    for (q, k) in factorize(&order) {
+        for _ in 0..k {
+            let candidate = &order / &q;
+            if a.modpow(&candidate, n).is_one() {
+                order = candidate;
+            } else {
+                break;
+            }
+        }
+    }
+    order
+}
+
+/// The least primitive root mod `n`, or `None` if the units mod n are not cyclic (n other
+/// than 1, 2, 4, p^k and 2p^k).
+pub fn primitive_root(n: &BigUint) -> Option<BigUint> {
+    if n <= &BigUint::from(2u32) {
+        return if n.is_zero() { None } else { Some(n - 1u32) };
+    }
+    let phi = euler_phi(n);
+    if carmichael_lambda(n) != phi {
+        return None;
+    }
+    let factors: Vec<BigUint> = factorize(&phi).into_iter().map(|(q, _)| q).collect();
+    let mut g = BigUint::from(2u32);
+    while &g < n {
+        if g.gcd(n).is_one() && factors.iter().all(|q| !g.modpow(&(&phi / q), n).is_one()) {
+            return Some(g);
+        }
+        g += 1u32;
+    }
+    None
+}
+
+/// A generator of the subgroup of prime order `q` in the units mod prime `p`, or `None` if
+/// q does not divide p - 1.
+///
+/// Returns h^((p - 1)/q) for the least h ≥ 2 giving an element other than 1, as needed for
+/// Schnorr groups (e.g. q = (p - 1)/2 for a safe prime p).
+pub fn subgroup_generator(p: &BigUint, q: &BigUint) -> Option<BigUint> {
+    let p_minus_one = p - 1u32;
+    if q.is_zero() || !p_minus_one.is_multiple_of(q) {
+        return None;
+    }
+    let cofactor = &p_minus_one / q;
+    let mut h = BigUint::from(2u32);
+    while h < p_minus_one {
+        let g = h.modpow(&cofactor, p);
+        if !g.is_one() {
+            return Some(g);
+        }
+        h += 1u32;
+    }
+    None
+}
+
+/// Exponent bits consumed per step of `ct_modpow`.
+const CT_WINDOW: u64 = 4;
+
+/// base^exponent mod `modulus` for a secret exponent of at most `exponent_bits` bits.
+///
+/// A fixed-window ladder: every 4-bit window costs four squarings and one multiplication,
+/// all-zero windows included, so the sequence of operations depends only on `exponent_bits`.
+/// The table entry for a window is gathered by masking over all sixteen entries rather than
+/// indexing, so memory access does not follow the exponent either. The limb arithmetic of
+/// num-bigint underneath is not itself constant time; values are kept reduced mod m so
+/// their sizes vary little.
+pub fn ct_modpow(
+    base: &BigUint,
+    exponent: &BigUint,
+    modulus: &BigUint,
+    exponent_bits: u64,
+) -> BigUint {
+    assert!(!modulus.is_zero(), "modulus must be nonzero");
+    assert!(exponent.bits() <= exponent_bits, "exponent wider than exponent_bits");
+    if modulus.is_one() {
+        return BigUint::zero();
+    }
+    let width = modulus.to_u64_digits().len();
+    let limbs = |n: &BigUint| {
+        let mut digits = n.to_u64_digits();
+        digits.resize(width, 0);
+        digits
+    };
+    let base = base % modulus;
+    let mut table = vec![limbs(&BigUint::one())];
+    let mut power = BigUint::one();
+    for _ in 1..1 << CT_WINDOW {
+        power = power * &base % modulus;
+        table.push(limbs(&power));
+    }
+
+    let windows = exponent_bits.div_ceil(CT_WINDOW);
+    let digits = exponent.to_u64_digits();
+    let mut result = BigUint::one();
+    for w in (0..windows).rev() {
+        for _ in 0..CT_WINDOW {
+            result = &result * &result % modulus;
+        }
+        // Read the window bit by bit from the limbs, without branching on them
+        let mut window = 0u64;
+        for bit in (w * CT_WINDOW..(w + 1) * CT_WINDOW).rev() {
+            let limb = digits.get((bit / 64) as usize).copied().unwrap_or(0);
+            window = (window << 1) | ((limb >> (bit % 64)) & 1);
+        }
+        let mut selected = vec![0u64; width];
+        for (i, entry) in table.iter().enumerate() {
+            let difference = i as u64 ^ window;
+            // All ones when difference == 0, else all zeros
+            let mask = (((difference | difference.wrapping_neg()) >> 63) ^ 1).wrapping_neg();
+            for (acc, limb) in selected.iter_mut().zip(entry) {
+                *acc |= limb & mask;
+            }
+        }
+        result = result * BigUint::new(to_u32_digits(&selected)) % modulus;
+    }
+    result
+}
+
+/// Exponent bits consumed per step of `ModulusContext::pow`.
+const POW_WINDOW: u64 = 4;
+
+/// Arithmetic modulo a fixed modulus, with the constants for Montgomery multiplication (odd
+/// moduli) and Barrett reduction computed once, for code that works against one modulus
+/// many times over.
+///
+/// Values go in and come out as ordinary residues; the Montgomery form is kept internal to
+/// `pow`. Even moduli have no Montgomery form, so their `pow` falls back to `modpow`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModulusContext {
+    modulus: BigUint,
+    /// The modulus as little-endian u64 limbs
+    limbs: Vec<u64>,
+    /// -m⁻¹ mod 2^64, for odd moduli
+    m_prime: u64,
+    /// R² mod m for the Montgomery radix R = 2^(64·limbs), to move values into Montgomery form
+    r_squared: Vec<u64>,
+    /// R mod m, one in Montgomery form
+    r_mod: Vec<u64>,
+    /// ⌊4^k / m⌋ for the modulus's bit length k
+    barrett_mu: BigUint,
+}
+
+impl ModulusContext {
+    /// Precompute the constants for `modulus`, which must be nonzero.
+    pub fn new(modulus: &BigUint) -> Self {
+        assert!(!modulus.is_zero(), "modulus must be nonzero");
+        let limbs = modulus.to_u64_digits();
+        let bits = modulus.bits();
+        let odd = modulus.is_odd();
+        let radix = |power: usize| {
+            let r = (BigUint::one() << (64 * limbs.len() * power)) % modulus;
+            let mut digits = r.to_u64_digits();
+            digits.resize(limbs.len(), 0);
+            digits
+        };
+        ModulusContext {
+            modulus: modulus.clone(),
+            m_prime: if odd { inverse_mod_2_64(limbs[0]).wrapping_neg() } else { 0 },
+            r_squared: if odd { radix(2) } else { vec![] },
+            r_mod: if odd { radix(1) } else { vec![] },
+            barrett_mu: (BigUint::one() << (2 * bits)) / modulus,
+            limbs,
+        }
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// x mod m: by Barrett reduction when x < m², else by division.
+    pub fn reduce(&self, x: &BigUint) -> BigUint {
+        let k = self.modulus.bits();
+        if x.bits() > 2 * k {
+            return x % &self.modulus;
+        }
+        let q = ((x >> (k.saturating_sub(1))) * &self.barrett_mu) >> (k + 1);
+        // The estimate falls short of ⌊x / m⌋ by at most two
+        let mut r = x - q * &self.modulus;
+        while r >= self.modulus {
+            r -= &self.modulus;
+        }
+        r
+    }
+
+    /// a·b mod m.
+    pub fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(&(self.reduce(a) * self.reduce(b)))
+    }
+
+    /// base^exponent mod m, by a 4-bit fixed-window ladder on Montgomery residues.
+    pub fn pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        if self.modulus.is_one() {
+            return BigUint::zero();
+        }
+        if self.modulus.is_even() {
+            return base.modpow(exponent, &self.modulus);
+        }
+        let mut table = vec![self.r_mod.clone(), self.to_montgomery(base)];
+        for i in 2..1 << POW_WINDOW {
+            table.push(self.montgomery_mul(&table[i - 1], &table[1]));
+        }
+        let mut result = self.r_mod.clone();
+        let windows = exponent.bits().div_ceil(POW_WINDOW);
+        let digits = exponent.to_u64_digits();
+        for w in (0..windows).rev() {
+            for _ in 0..POW_WINDOW {
+                result = self.montgomery_mul(&result, &result);
+            }
+            let bit = w * POW_WINDOW;
+            let limb = digits[(bit / 64) as usize] >> (bit % 64);
+            let window = (limb & ((1 << POW_WINDOW) - 1)) as usize;
+            if window != 0 {
+                result = self.montgomery_mul(&result, &table[window]);
+            }
+        }
+        self.out_of_montgomery(&result)
+    }
+
+    /// a⁻¹ mod m; see `mod_inverse`.
+    pub fn inv(&self, a: &BigUint) -> Result<BigUint, ModularError> {
+        mod_inverse(a, &self.modulus)
+    }
+
+    fn to_montgomery(&self, x: &BigUint) -> Vec<u64> {
+        let mut digits = self.reduce(x).to_u64_digits();
+        digits.resize(self.limbs.len(), 0);
+        self.montgomery_mul(&digits, &self.r_squared)
+    }
+
+    fn out_of_montgomery(&self, x: &[u64]) -> BigUint {
+        let mut one = vec![0u64; self.limbs.len()];
+        one[0] = 1;
+        BigUint::new(to_u32_digits(&self.montgomery_mul(x, &one)))
+    }
+
+    /// a·b·R⁻¹ mod m for reduced Montgomery residues. Each limb of b adds a·b_i and then the
+    /// multiple of m that clears the lowest limb, working one limb further up each time
+    /// instead of shifting.
+    fn montgomery_mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let m = &self.limbs;
+        let n = m.len();
+        let mut z = vec![0u64; 2 * n];
+        // The bit above the top limb, which can be set until the final subtraction
+        let mut high = false;
+        for (i, &b_i) in b.iter().enumerate() {
+            let z = &mut z[i..];
+            let c1 = add_mul(&mut z[..n], a, b_i);
+            let u = z[0].wrapping_mul(self.m_prime);
+            let c2 = add_mul(&mut z[..n], m, u);
+            let (sum, o1) = c1.overflowing_add(high as u64);
+            let (sum, o2) = sum.overflowing_add(c2);
+            z[n] = sum;
+            high = o1 | o2;
+        }
+        let mut t = z.split_off(n);
+        // t < 2m, so one subtraction at most
+        if high || !limbs_less(&t, m) {
+            let mut borrow = false;
+            for (limb, &m_j) in t.iter_mut().zip(m) {
+                let (d, b1) = limb.overflowing_sub(m_j);
+                let (d, b2) = d.overflowing_sub(borrow as u64);
+                *limb = d;
+                borrow = b1 | b2;
+            }
+        }
+        t
+    }
+}
+
+/// z += x·y over the limbs of z, returning the carry out of the top limb.
+fn add_mul(z: &mut [u64], x: &[u64], y: u64) -> u64 {
+    let mut carry = 0u64;
+    for (z_i, &x_i) in z.iter_mut().zip(x) {
+        let v = x_i as u128 * y as u128 + *z_i as u128 + carry as u128;
+        *z_i = v as u64;
+        carry = (v >> 64) as u64;
+    }
+    carry
+}
+
+/// Whether little-endian limbs `a` are less than `b` of the same length.
+fn limbs_less(a: &[u64], b: &[u64]) -> bool {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
+/// x⁻¹ mod 2^64 for odd x, by Newton iteration (each step doubles the correct bits).
+fn inverse_mod_2_64(x: u64) -> u64 {
+    let mut inverse = 1u64;
+    for _ in 0..6 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(x.wrapping_mul(inverse)));
+    }
+    inverse
+}
+
+fn to_u32_digits(limbs: &[u64]) -> Vec<u32> {
+    limbs
+        .iter()
+        .flat_map(|&limb| [limb as u32, (limb >> 32) as u32])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: u64) -> BigUint {
+        BigUint::from(n)
+    }
+
+    #[test]
+    fn test_orders_and_roots() {
+        assert_eq!(multiplicative_order(&big(2), &big(7)), Some(big(3)));
+        assert_eq!(multiplicative_order(&big(3), &big(7)), Some(big(6)));
+        assert_eq!(multiplicative_order(&big(2), &big(15)), Some(big(4)));
+        assert_eq!(multiplicative_order(&big(3), &big(15)), None);
+        assert_eq!(carmichael_lambda(&big(8)), big(2));
+        assert_eq!(euler_phi(&big(36)), big(12));
+
+        assert_eq!(primitive_root(&big(7)), Some(big(3)));
+        assert_eq!(primitive_root(&big(23)), Some(big(5)));
+        assert_eq!(primitive_root(&big(50)), Some(big(3)));
+        assert_eq!(primitive_root(&big(8)), None);
+        // 2^89 - 1 is prime; 3 is its least primitive root
+        let m89 = (big(1) << 89u32) - 1u32;
+        assert_eq!(primitive_root(&m89), Some(big(3)));
+
+        // Safe prime 23 = 2·11 + 1: the order-11 subgroup
+        let g = subgroup_generator(&big(23), &big(11)).unwrap();
+        assert_eq!(multiplicative_order(&g, &big(23)), Some(big(11)));
+        assert_eq!(subgroup_generator(&big(23), &big(7)), None);
+    }
+
+    #[test]
+    fn test_ct_modpow() {
+        use num_bigint::RandBigInt;
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(26);
+        let modulus = (big(1) << 127u32) - 1u32;
+        for _ in 0..20 {
+            let base = rng.gen_biguint(140);
+            let exponent = rng.gen_biguint(127);
+            assert_eq!(ct_modpow(&base, &exponent, &modulus, 127), base.modpow(&exponent, &modulus));
+        }
+        // Extra leading windows are harmless, and small and even moduli work
+        assert_eq!(ct_modpow(&big(3), &big(5), &big(7), 64), big(5));
+        assert_eq!(ct_modpow(&big(3), &big(0), &big(10), 8), big(1));
+        assert_eq!(ct_modpow(&big(3), &big(4), &big(1), 8), big(0));
+    }
+
+    #[test]
+    fn test_batch_inverse() {
+        let modulus = (big(1) << 127u32) - 1u32;
+        let values: Vec<BigUint> = (1..50u64).map(|i| big(i * i * 7919) << 100u32).collect();
+        let inverses = batch_inverse(&values, &modulus).unwrap();
+        for (v, inverse) in values.iter().zip(&inverses) {
+            assert_eq!(Ok(inverse.clone()), mod_inverse(v, &modulus));
+        }
+        assert_eq!(batch_inverse(&[], &modulus), Ok(vec![]));
+        assert_eq!(batch_inverse(&[big(3)], &big(0)), Err(ModularError::ZeroModulus));
+        // 6 is the first value sharing a factor with 15
+        assert_eq!(
+            batch_inverse(&[big(2), big(6), big(5)], &big(15)),
+            Err(ModularError::NotInvertible { a: big(6), modulus: big(15), gcd: big(3) })
+        );
+    }
+
+    #[test]
+    fn test_modulus_context() {
+        use num_bigint::RandBigInt;
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(31);
+        for bits in [3, 64, 65, 127, 521, 1024] {
+            for parity in [1, 0] {
+                let top = big(1) << (bits - 1);
+                let modulus = ((rng.gen_biguint(bits) | top) >> 1u32 << 1u32) + big(parity);
+                let context = ModulusContext::new(&modulus);
+                for _ in 0..5 {
+                    let (a, b) = (rng.gen_biguint(bits + 20), rng.gen_biguint(bits));
+                    let exponent = rng.gen_biguint(bits);
+                    assert_eq!(context.pow(&a, &exponent), a.modpow(&exponent, &modulus));
+                    assert_eq!(context.mul(&a, &b), &a * &b % &modulus);
+                    assert_eq!(context.reduce(&(&a << 2000u32)), (&a << 2000u32) % &modulus);
+                }
+            }
+        }
+        let context = ModulusContext::new(&big(7));
+        assert_eq!(context.pow(&big(3), &big(0)), big(1));
+        assert_eq!(context.pow(&big(0), &big(5)), big(0));
+        assert_eq!(context.inv(&big(3)), Ok(big(5)));
+        assert_eq!(ModulusContext::new(&big(1)).pow(&big(3), &big(2)), big(0));
+        assert!(ModulusContext::new(&big(9)).inv(&big(6)).is_err());
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        let (g, x, y) = extended_gcd(&BigInt::from(240), &BigInt::from(46));
+        assert_eq!(g, BigInt::from(2));
+        assert_eq!(x * 240 + y * 46, g);
+        assert_eq!(mod_inverse(&big(3), &big(7)), Ok(big(5)));
+        // Composite moduli work too, which Fermat inversion gets wrong
+        assert_eq!(mod_inverse(&big(7), &big(15)), Ok(big(13)));
+        assert_eq!(
+            mod_inverse(&big(6), &big(15)),
+            Err(ModularError::NotInvertible { a: big(6), modulus: big(15), gcd: big(3) })
+        );
+        assert_eq!(mod_inverse(&big(1), &big(0)), Err(ModularError::ZeroModulus));
+    }
+
+    #[test]
+    fn test_crt() {
+        let system = [(big(2), big(3)), (big(3), big(5)), (big(2), big(7))];
+        assert_eq!(crt(&system), Some(big(23)));
+        assert_eq!(crt(&[(big(1), big(4)), (big(3), big(6))]), None);
+        assert_eq!(crt(&[(big(7), big(1))]), Some(big(0)));
+        assert_eq!(crt(&[]), Some(big(0)));
+    }
+
+    #[test]
+    fn test_square_roots() {
+        assert_eq!(jacobi(&BigInt::from(2), &big(15)), 1);
+        assert_eq!(legendre(&BigInt::from(-1), &big(13)), 1);
+        assert_eq!(legendre(&BigInt::from(-1), &big(7)), -1);
+        assert_eq!(legendre(&BigInt::from(14), &big(7)), 0);
+        // 41 ≡ 1 (mod 8) exercises the full Tonelli–Shanks loop
+        for p in [7u64, 13, 41, 97, 1_000_000_007] {
+            let p = big(p);
+            for a in 0u64..60 {
+                let a = big(a);
+                match sqrt_mod(&a, &p) {
+                    Some(r) => assert_eq!(&r * &r % &p, &a % &p),
+                    None => assert_eq!(legendre(&BigInt::from(a.clone()), &p), -1),
+                }
+            }
+        }
+        assert_eq!(sqrt_mod(&big(2), &big(7)), Some(big(3)));
+    }
+}
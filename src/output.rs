@@ -0,0 +1,467 @@
+//! Crash-tolerant CSV output and search checkpoints.
+//!
+//! `AppendWriter` only ever hands complete lines to the file, so a killed process leaves at
+//! most a torn final record from the OS buffer. `recover` cuts such a record off, or rolls
+//! the file back to the length recorded in the last `Checkpoint` so a resumed search does not
+//! duplicate rows written after it. A `Manifest` next to the output records how it was
+//! produced.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::form::{FormParseError, QuadraticForm};
+use crate::form_analysis::FormInvariants;
+use crate::primality::PrimalityConfig;
+use crate::search::{SearchProgress, TupleOrder};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid sync policy '{0}', expected never, always or a record count")]
+pub struct SyncPolicyError(pub String);
+
+/// When `AppendWriter` forces written records to stable storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Leave it to the OS; checkpoints still sync
+    Never,
+    /// fsync after every record
+    Always,
+    /// fsync after every N records
+    Every(u64),
+}
+
+impl FromStr for SyncPolicy {
+    type Err = SyncPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "never" => Ok(SyncPolicy::Never),
+            "always" => Ok(SyncPolicy::Always),
+            n => match n.parse::<u64>() {
+                Ok(0) => Ok(SyncPolicy::Never),
+                Ok(n) => Ok(SyncPolicy::Every(n)),
+                Err(_) => Err(SyncPolicyError(s.to_string())),
+            },
+        }
+    }
+}
+
+/// Line-buffered appending writer: bytes reach the file one complete line at a time.
+///
+/// A trailing partial line stays in memory until its newline arrives and is discarded if the
+/// writer is dropped first.
+pub struct AppendWriter {
+    file: File,
+    pending: Vec<u8>,
+    policy: SyncPolicy,
+    unsynced: u64,
+    position: u64,
+}
+
+impl AppendWriter {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: &Path, policy: SyncPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let position = file.metadata()?.len();
+        Ok(AppendWriter {
+            file,
+            pending: Vec::new(),
+            policy,
+            unsynced: 0,
+            position,
+        })
+    }
+
+    /// Length of the file once every complete line written so far has landed.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// fsync everything written so far.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+impl Write for AppendWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(last) = self.pending.iter().rposition(|&b| b == b'\n') {
+            let complete: Vec<u8> = self.pending.drain(..=last).collect();
+            self.file.write_all(&complete)?;
+            self.position += complete.len() as u64;
+            self.unsynced += complete.iter().filter(|&&b| b == b'\n').count() as u64;
+            match self.policy {
+                SyncPolicy::Always => self.sync()?,
+                SyncPolicy::Every(n) if self.unsynced >= n => self.sync()?,
+                _ => {}
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Truncate `path` to `length` if given, otherwise to just past its last newline. Returns the
+/// number of bytes removed.
+///
+/// A `length` past the end of the file means rows the checkpoint counts are gone, so resuming
+/// would silently lose them; that is an `InvalidData` error and the file is left alone.
+pub fn recover(path: &Path, length: Option<u64>) -> io::Result<u64> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let current = file.metadata()?.len();
+    let target = match length {
+        Some(length) if length > current => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checkpoint is ahead of the output: it records {} bytes, the file has {}",
+                    length, current
+                ),
+            ));
+        }
+        Some(length) => length,
+        None => end_of_last_line(&mut file, current)?,
+    };
+    if target < current {
+        file.set_len(target)?;
+        file.sync_data()?;
+    }
+    Ok(current - target)
+}
+
+/// Offset just past the last `\n` in the first `len` bytes of `file` (0 if there is none).
+fn end_of_last_line(file: &mut File, len: u64) -> io::Result<u64> {
+    const CHUNK: u64 = 8192;
+    let mut end = len;
+    let mut buffer = vec![0u8; CHUNK as usize];
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(start + i as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// Resumable state of a search written alongside its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    /// Coefficients a,b,c,d,e,f,g of the searched form
+    pub form: String,
+    pub pool_size: usize,
+    /// Order the tuples are visited in; absent from older checkpoints, which are x-major
+    pub order: TupleOrder,
+    pub progress: SearchProgress,
+    /// Output length when the checkpoint was taken; always at a record boundary
+    pub output_len: u64,
+}
+
+impl Checkpoint {
+    /// Checkpoint file used for `output`: the same path with `.checkpoint` appended.
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".checkpoint");
+        PathBuf::from(path)
+    }
+
+    /// Coefficient string identifying `form` in a checkpoint.
+    pub fn form_key(form: &QuadraticForm) -> String {
+        let coefficients: Vec<String> = form.coefficients().iter().map(|c| c.to_string()).collect();
+        coefficients.join(",")
+    }
+
+    /// Write atomically: to a temporary file that is synced and then renamed over `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = format!(
+            "seed={}\nform={}\npool_size={}\norder={}\ntuples={}\nhits={}\noutput_len={}\n",
+            self.seed,
+            self.form,
+            self.pool_size,
+            self.order,
+            self.progress.tuples,
+            self.progress.hits,
+            self.output_len
+        );
+        write_atomically(path, contents.as_bytes())
+    }
+
+    /// Read a checkpoint, or `None` if `path` does not exist.
+    pub fn load(path: &Path) -> io::Result<Option<Checkpoint>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let field = |key: &str| -> io::Result<&str> {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("checkpoint is missing '{}'", key),
+                    )
+                })
+        };
+        let number = |key: &str| -> io::Result<u64> {
+            field(key)?.trim().parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid '{}' in checkpoint", key))
+            })
+        };
+        Ok(Some(Checkpoint {
+            seed: number("seed")?,
+            form: field("form")?.to_string(),
+            pool_size: number("pool_size")? as usize,
+            order: match field("order") {
+                Ok(order) => order.parse().map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("{} in checkpoint", e))
+                })?,
+                Err(_) => TupleOrder::XMajor,
+            },
+            progress: SearchProgress {
+                tuples: number("tuples")?,
+                hits: number("hits")?,
+            },
+            output_len: number("output_len")?,
+        }))
+    }
+}
+
+/// Write `contents` atomically: to a temporary file that is synced and then renamed over
+/// `path`.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Everything needed to reproduce a search, written as JSON next to its output so a results
+/// file stays self-describing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub crate_version: String,
+    /// Commit the binary was built from, if it was built from a git checkout
+    pub git_hash: Option<String>,
+    pub seed: u64,
+    /// Coefficients a,b,c,d,e,f,g of the searched form
+    pub form: String,
+    /// Where x, y, z were drawn from, e.g. "default", "default signed" or a pool file
+    pub pool: String,
+    pub pool_size: usize,
+    pub rounds: usize,
+    pub use_bpsw: bool,
+    pub trial_division_bound: u32,
+    pub filter: Option<String>,
+    /// Tuple constraints the search skipped failing tuples by; absent from older manifests
+    #[serde(default)]
+    pub constraints: Vec<String>,
+    pub annotate: bool,
+    /// Whether tagged base-2 pseudoprimes were written alongside the primes
+    pub pseudoprimes: bool,
+    /// Whether rows carry the sums-of-squares columns; absent from older manifests
+    #[serde(default)]
+    pub squares: bool,
+    /// Whether rows carry the `r` representation-count column; absent from older manifests
+    #[serde(default)]
+    pub representations: bool,
+    /// Whether rows carry the `germain_depth` column; absent from older manifests
+    #[serde(default)]
+    pub germain_depth: bool,
+    /// Generations of a closure search, whose rows end in a `generation` column; absent from
+    /// older manifests
+    #[serde(default)]
+    pub closure_depth: Option<u32>,
+    /// Discriminant and binary class numbers of the form; absent from older manifests
+    #[serde(default)]
+    pub invariants: FormInvariants,
+}
+
+impl Manifest {
+    /// A manifest for this build, with the given search parameters.
+    pub fn new(
+        seed: u64,
+        form: &QuadraticForm,
+        pool: &str,
+        pool_size: usize,
+        primality: &PrimalityConfig,
+    ) -> Self {
+        Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("UNIVERSAL_PRIMES_GIT_HASH").map(str::to_string),
+            seed,
+            form: Checkpoint::form_key(form),
+            pool: pool.to_string(),
+            pool_size,
+            rounds: primality.rounds,
+            use_bpsw: primality.use_bpsw,
+            trial_division_bound: primality.trial_division_bound,
+            filter: None,
+            constraints: Vec::new(),
+            annotate: false,
+            pseudoprimes: false,
+            squares: false,
+            representations: false,
+            germain_depth: false,
+            closure_depth: None,
+            invariants: FormInvariants::of(form),
+        }
+    }
+
+    /// Manifest file used for `output`: the same path with `.manifest.json` appended.
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".manifest.json");
+        PathBuf::from(path)
+    }
+
+    /// The searched form.
+    pub fn form(&self) -> Result<QuadraticForm, FormParseError> {
+        self.form.parse()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        write_atomically(path, &json)
+    }
+
+    /// The first setting that changes what a row says in which `other` differs, as
+    /// (field, this value, other value). Seeds, pools and the build may differ.
+    pub fn row_mismatch(&self, other: &Manifest) -> Option<(&'static str, String, String)> {
+        let settings = |m: &Manifest| {
+            [
+                ("form", m.form.clone()),
+                ("rounds", m.rounds.to_string()),
+                ("use_bpsw", m.use_bpsw.to_string()),
+                ("trial_division_bound", m.trial_division_bound.to_string()),
+                ("filter", m.filter.clone().unwrap_or_default()),
+                ("constraints", m.constraints.join("; ")),
+                ("annotate", m.annotate.to_string()),
+                ("pseudoprimes", m.pseudoprimes.to_string()),
+                ("squares", m.squares.to_string()),
+                ("representations", m.representations.to_string()),
+                ("germain_depth", m.germain_depth.to_string()),
+                ("closure_depth", m.closure_depth.map_or_else(String::new, |d| d.to_string())),
+            ]
+        };
+        settings(self)
+            .into_iter()
+            .zip(settings(other))
+            .find(|((_, a), (_, b))| a != b)
+            .map(|((field, a), (_, b))| (field, a, b))
+    }
+
+    /// Read a manifest, or `None` if `path` does not exist.
+    pub fn load(path: &Path) -> io::Result<Option<Manifest>> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("universal-primes-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_partial_lines_never_reach_the_file() {
+        let path = scratch("append.csv");
+        let _ = fs::remove_file(&path);
+        {
+            let mut writer = AppendWriter::open(&path, SyncPolicy::Every(2)).unwrap();
+            write!(writer, "a,1\nb,").unwrap();
+            assert_eq!(writer.position(), 4);
+            write!(writer, "2\nc,3").unwrap();
+            assert_eq!(writer.position(), 8);
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,1\nb,2\n");
+
+        // Simulate a torn record left behind by the OS, then recover
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"c,").unwrap();
+        assert_eq!(recover(&path, None).unwrap(), 2);
+        assert_eq!(recover(&path, Some(4)).unwrap(), 4);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,1\n");
+
+        // A checkpoint past the end of the file is refused rather than resumed from
+        let error = recover(&path, Some(9)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,1\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path = scratch("search.checkpoint");
+        assert_eq!(Checkpoint::load(&path).unwrap(), None);
+        let checkpoint = Checkpoint {
+            seed: 7,
+            form: Checkpoint::form_key(&QuadraticForm::universal()),
+            pool_size: 35,
+            order: TupleOrder::Bands,
+            progress: SearchProgress { tuples: 4096, hits: 40 },
+            output_len: 1234,
+        };
+        checkpoint.save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap().as_ref(), Some(&checkpoint));
+        // Checkpoints from before the order line were x-major
+        let older = fs::read_to_string(&path).unwrap().replace("order=bands\n", "");
+        fs::write(&path, older).unwrap();
+        let older = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(older, Checkpoint { order: TupleOrder::XMajor, ..checkpoint });
+        assert_eq!(SyncPolicy::from_str("100"), Ok(SyncPolicy::Every(100)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let output = scratch("manifest.csv");
+        let path = Manifest::path_for(&output);
+        assert_eq!(Manifest::load(&path).unwrap(), None);
+        let form = QuadraticForm::universal();
+        let mut manifest = Manifest::new(7, &form, "default", 35, &PrimalityConfig::default());
+        manifest.filter = Some("n.bits() > 40".to_string());
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path).unwrap().as_ref(), Some(&manifest));
+        assert_eq!(manifest.form().unwrap(), form);
+        assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+        let mut other = Manifest::new(8, &form, "other.pool", 9, &PrimalityConfig::default());
+        other.filter = manifest.filter.clone();
+        assert_eq!(manifest.row_mismatch(&other), None);
+        other.pseudoprimes = true;
+        let mismatch = Some(("pseudoprimes", "false".to_string(), "true".to_string()));
+        assert_eq!(manifest.row_mismatch(&other), mismatch);
+        other.pseudoprimes = false;
+        other.closure_depth = Some(2);
+        let mismatch = Some(("closure_depth", String::new(), "2".to_string()));
+        assert_eq!(manifest.row_mismatch(&other), mismatch);
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(Manifest::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
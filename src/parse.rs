@@ -0,0 +1,265 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use num_bigint::BigUint;
+use num_traits::{Num, One, ToPrimitive, Zero};
+use thiserror::Error;
+
+use std::time::Duration;
+
+/// Largest result an expression may build, so input like `9^9^9` fails instead of hanging.
+pub const MAX_RESULT_BITS: u64 = 1 << 24;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ParseError {
+    #[error("empty input")]
+    Empty,
+    #[error("invalid number '{0}'")]
+    InvalidNumber(String),
+    #[error("invalid base64 input")]
+    InvalidBase64,
+    #[error("unexpected '{0}' in expression")]
+    UnexpectedToken(String),
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("subtraction result is negative")]
+    NegativeResult,
+    #[error("exponent too large")]
+    ExponentTooLarge,
+    #[error("result would exceed {} bits", MAX_RESULT_BITS)]
+    ResultTooLarge,
+    #[error("invalid duration '{0}', expected e.g. 90, 45s, 10m or 1h30m")]
+    InvalidDuration(String),
+}
+
+/// Parse a non-negative integer written as decimal, `0x` hex, `base64:` big-endian bytes,
+/// or a simple expression such as `2^4253-1` or `10^100+267`.
+///
+/// Expressions support `+`, `-`, `*`, `^` (right associative) and parentheses.
+pub fn parse_biguint(input: &str) -> Result<BigUint, ParseError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    if let Some(encoded) = input.strip_prefix("base64:") {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|_| ParseError::InvalidBase64)?;
+        return Ok(BigUint::from_bytes_be(&bytes));
+    }
+
+    let tokens = tokenize(input)?;
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let value = parser.expression()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+    }
+}
+
+/// Parse a duration such as `90` (seconds), `45s`, `10m`, `2h` or `1d12h`.
+pub fn parse_duration(input: &str) -> Result<Duration, ParseError> {
+    let invalid = || ParseError::InvalidDuration(input.to_string());
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut seconds = 0u64;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit = match rest.chars().next() {
+            None | Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86400,
+            Some(_) => return Err(invalid()),
+        };
+        rest = rest.get(1..).unwrap_or("");
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|v| seconds.checked_add(v))
+            .ok_or_else(invalid)?;
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(BigUint),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == '_' {
+            i += 1;
+        } else if "+-*^()".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            let radix = if c == '0' && matches!(chars.get(i + 1), Some('x') | Some('X')) {
+                i += 2;
+                16
+            } else {
+                10
+            };
+            let digits_start = i;
+            while i < chars.len() && (chars[i].is_digit(radix) || chars[i] == '_') {
+                i += 1;
+            }
+            let digits: String = chars[digits_start..i].iter().filter(|&&c| c != '_').collect();
+            let literal: String = chars[start..i].iter().collect();
+            let value = BigUint::from_str_radix(&digits, radix)
+                .map_err(|_| ParseError::InvalidNumber(literal))?;
+            tokens.push(Token::Number(value));
+        } else {
+            return Err(ParseError::UnexpectedToken(c.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over the token stream.
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<BigUint, ParseError> {
+        let mut value = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' {
+                value + rhs
+            } else if rhs > value {
+                return Err(ParseError::NegativeResult);
+            } else {
+                value - rhs
+            };
+        }
+        Ok(value)
+    }
+
+    // term := power ('*' power)*
+    fn term(&mut self) -> Result<BigUint, ParseError> {
+        let mut value = self.power()?;
+        while self.peek_op() == Some('*') {
+            self.pos += 1;
+            let rhs = self.power()?;
+            if value.bits() + rhs.bits() > MAX_RESULT_BITS && !value.is_zero() && !rhs.is_zero() {
+                return Err(ParseError::ResultTooLarge);
+            }
+            value *= rhs;
+        }
+        Ok(value)
+    }
+
+    // power := atom ('^' power)?
+    fn power(&mut self) -> Result<BigUint, ParseError> {
+        let base = self.atom()?;
+        if self.peek_op() == Some('^') {
+            self.pos += 1;
+            let exponent = self.power()?;
+            // 0 and 1 stay put whatever the exponent; anything else has about bits·e bits
+            if base <= BigUint::one() {
+                return Ok(if exponent.is_zero() { BigUint::one() } else { base });
+            }
+            let exponent = exponent.to_u32().ok_or(ParseError::ExponentTooLarge)?;
+            if base.bits().saturating_mul(exponent as u64) > MAX_RESULT_BITS {
+                return Err(ParseError::ResultTooLarge);
+            }
+            return Ok(base.pow(exponent));
+        }
+        Ok(base)
+    }
+
+    // atom := number | '(' expression ')'
+    fn atom(&mut self) -> Result<BigUint, ParseError> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::Op('(')) => {
+                self.pos += 1;
+                let value = self.expression()?;
+                if self.peek_op() != Some(')') {
+                    return match self.tokens.get(self.pos) {
+                        Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+                        None => Err(ParseError::UnexpectedEnd),
+                    };
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(parse_biguint("48883").unwrap(), BigUint::from(48883u32));
+        assert_eq!(parse_biguint("0xff").unwrap(), BigUint::from(255u32));
+        assert_eq!(parse_biguint("base64:AQA=").unwrap(), BigUint::from(256u32));
+        assert_eq!(parse_biguint("2^7-1").unwrap(), BigUint::from(127u32));
+        assert_eq!(parse_biguint("2^3^2").unwrap(), BigUint::from(512u32));
+        assert_eq!(parse_biguint("(1+2)*3").unwrap(), BigUint::from(9u32));
+        assert_eq!(
+            parse_biguint("10^100+267").unwrap(),
+            BigUint::from(10u32).pow(100) + 267u32
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_biguint(""), Err(ParseError::Empty));
+        assert_eq!(parse_biguint("1-2"), Err(ParseError::NegativeResult));
+        assert_eq!(parse_biguint("2^"), Err(ParseError::UnexpectedEnd));
+        assert!(parse_biguint("12abc").is_err());
+        assert_eq!(parse_biguint("9^9^9"), Err(ParseError::ResultTooLarge));
+        assert_eq!(parse_biguint("2^9999999999"), Err(ParseError::ExponentTooLarge));
+        assert_eq!(parse_biguint("1^(9^9^9)"), Err(ParseError::ResultTooLarge));
+        assert_eq!(parse_biguint("1^99999999999"), Ok(BigUint::one()));
+        assert_eq!(parse_biguint("0^0"), Ok(BigUint::one()));
+        let half = format!("2^{}", MAX_RESULT_BITS / 2 - 1);
+        assert!(parse_biguint(&format!("{}*{}", half, half)).is_ok());
+        assert_eq!(
+            parse_biguint(&format!("{}*{}*4", half, half)),
+            Err(ParseError::ResultTooLarge)
+        );
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("10x").is_err());
+    }
+}
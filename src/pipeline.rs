@@ -0,0 +1,214 @@
+//! An async search pipeline (feature `async`) for runs where output I/O can stall the CPUs.
+//!
+//! Three stages run concurrently, connected by bounded channels: a generator evaluating the
+//! form on each tuple, a tester handing each batch of candidates to tokio's blocking pool for
+//! primality testing and classification, and a writer draining the tested batches in order.
+//! A slow disk only holds up the writer until the channels fill, and slow testing never
+//! blocks a write that is ready. The rows are exactly those `search_from` writes.
+
+use num_bigint::BigInt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crate::form::QuadraticForm;
+use crate::pool::TupleSpace;
+use crate::search::{
+    evaluate_admitted_from, header, tuple_row, SearchConfig, SearchProgress, StopSignal,
+};
+
+/// Batch and channel sizes of the pipeline, built up from `PipelineConfig::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// Tuples per batch passed between stages
+    pub batch_size: usize,
+    /// Batches each channel holds before its sender waits, which also bounds the batches
+    /// being tested at once
+    pub capacity: usize,
+}
+
+impl PipelineConfig {
+    /// Batches of 1024 tuples, with one batch in flight per CPU.
+    pub fn new() -> Self {
+        PipelineConfig {
+            batch_size: 1024,
+            capacity: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig::new()
+    }
+}
+
+/// A tuple and its value of N, on its way to the tester.
+struct Candidate {
+    index: u64,
+    x: BigInt,
+    y: BigInt,
+    z: BigInt,
+    n: BigInt,
+}
+
+/// Tuple indices with their rows, if any.
+type TestedBatch = Vec<(u64, Option<String>)>;
+
+/// Run (or continue) a search from `start` through the pipeline, writing rows to `out`.
+///
+/// Stops like `search_from` on `max_hits`, `time_budget` or the stop signal, checked between
+/// batches, and returns the progress to resume from. There is no checkpoint callback; the
+/// header is written only when starting from the first tuple. `config.num_threads` and
+/// `low_priority` are ignored, as testing runs on tokio's blocking pool.
+pub async fn search_async<W, P>(
+    form: &QuadraticForm,
+    primes: Arc<P>,
+    out: &mut W,
+    config: &SearchConfig,
+    pipeline: &PipelineConfig,
+    start: SearchProgress,
+) -> io::Result<SearchProgress>
+where
+    W: AsyncWrite + Unpin,
+    P: TupleSpace + Send + ?Sized + 'static,
+{
+    if start.tuples == 0 {
+        out.write_all(format!("{}\n", header(config)).as_bytes()).await?;
+    }
+    let (batch_size, capacity) = (pipeline.batch_size.max(1), pipeline.capacity.max(1));
+    let (candidates, mut candidates_rx) = mpsc::channel::<Vec<Candidate>>(capacity);
+    let (tested, mut tested_rx) = mpsc::channel::<JoinHandle<TestedBatch>>(capacity);
+
+    // Generate: evaluate the form on each admitted tuple, stopping when the tester goes away
+    let total = primes.tuples();
+    let form_owned = form.clone();
+    let constraints = config.constraints.clone();
+    let generator = task::spawn_blocking(move || {
+        let mut batch = Vec::with_capacity(batch_size);
+        let tuples = evaluate_admitted_from(&form_owned, &*primes, &constraints, start.tuples);
+        for (index, x, y, z, n) in tuples {
+            let (x, y, z) = (x.into_owned(), y.into_owned(), z.into_owned());
+            batch.push(Candidate { index, x, y, z, n });
+            if batch.len() == batch_size {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                if candidates.blocking_send(full).is_err() {
+                    return;
+                }
+            }
+        }
+        if !batch.is_empty() {
+            let _ = candidates.blocking_send(batch);
+        }
+    });
+
+    // Test: one blocking task per batch, passed on in order so the writer can await them
+    let shared_config = Arc::new(config.clone());
+    let tester = task::spawn(async move {
+        while let Some(batch) = candidates_rx.recv().await {
+            let config = shared_config.clone();
+            let handle = task::spawn_blocking(move || {
+                batch
+                    .into_iter()
+                    .map(|c| (c.index, tuple_row(&config, c.index, &c.x, &c.y, &c.z, &c.n)))
+                    .collect()
+            });
+            if tested.send(handle).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // Write: the rows of each batch as it completes, in tuple order
+    let started = Instant::now();
+    let mut progress = start;
+    let mut stopped = false;
+    while let Some(handle) = tested_rx.recv().await {
+        if config.max_hits.is_some_and(|max| progress.hits >= max)
+            || config.time_budget.is_some_and(|budget| started.elapsed() >= budget)
+            || config.stop_signal.as_ref().is_some_and(StopSignal::is_stopped)
+        {
+            stopped = true;
+            break;
+        }
+        let rows = handle.await.map_err(io::Error::other)?;
+        let Some(&(last, _)) = rows.last() else { continue };
+        progress.tuples = last + 1;
+        for (index, row) in rows {
+            let Some(row) = row else { continue };
+            out.write_all(format!("{}\n", row).as_bytes()).await?;
+            progress.hits += 1;
+            if config.max_hits == Some(progress.hits) {
+                // Resuming continues just after the last hit written
+                progress.tuples = index + 1;
+                stopped = true;
+                break;
+            }
+        }
+    }
+    if !stopped {
+        // Tuples the constraints ruled out after the last batch were searched too
+        progress.tuples = total.max(start.tuples);
+    }
+    out.flush().await?;
+
+    // Closing the receiver winds the other stages down
+    drop(tested_rx);
+    tester.await.map_err(io::Error::other)?;
+    generator.await.map_err(io::Error::other)?;
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{default_pool, search};
+
+    #[test]
+    fn test_pipeline_matches_search() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..12].to_vec();
+        let config = SearchConfig::new(3);
+        let mut expected = Vec::new();
+        search(&form, &pool, &mut expected, &config).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+        let pool = Arc::new(pool);
+        let pipeline = PipelineConfig::new().batch_size(100).capacity(3);
+        let run = |config: SearchConfig, start: SearchProgress, out: &mut Vec<u8>| {
+            let search = search_async(&form, pool.clone(), out, &config, &pipeline, start);
+            runtime.block_on(search).unwrap()
+        };
+        let mut out = Vec::new();
+        let progress = run(config.clone(), SearchProgress::default(), &mut out);
+        assert_eq!(progress.tuples, 1728);
+        assert_eq!(out, expected);
+
+        // Stopping early and resuming gives the same output
+        let mut out = Vec::new();
+        let stopped = run(config.clone().stop_after_hits(7), SearchProgress::default(), &mut out);
+        assert_eq!(stopped.hits, 7);
+        assert!(stopped.tuples < 1728);
+        let resumed = run(config, stopped, &mut out);
+        assert_eq!(resumed, progress);
+        assert_eq!(out, expected);
+    }
+}
@@ -1,23 +1,39 @@
+use crate::domains;
+use crate::entropy::{EntropyRng, EntropySource};
+use crate::kdf::{self, SubKey};
+use crate::modular::{ct_modpow, subgroup_generator, ModulusContext};
+use crate::primality::{random_safe_prime, PrimalityConfig};
 use crate::prime_shamir::*;
+use crate::reader::{MalformedInput, StrictReader};
+use log::debug;
+use tracing::instrument;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use rand_distr::{Distribution, Normal};
-use sha3::{Digest, Sha3_512, Shake256};
+use sha3::Shake256;
 use sha3::digest::{Update, ExtendableOutput};
 use thiserror::Error;
-use num_bigint::{BigUint, RandBigInt, ToBigInt};
+use num_bigint::BigUint;
 use num_traits::{One, Zero};
-use num_integer::Integer;
 use std::convert::TryInto;
 use rand::Rng;
 use std::io::Read;
-use std::io;
 #[derive(Error, Debug)]
 pub enum NoiseError {
     #[error("Invalid standard deviation")]
     InvalidStdDev,
     #[error("Invalid hash output")]
     InvalidHashOutput,
+    #[error(transparent)]
+    CoordinateOverflow(#[from] CoordinateOverflow),
+}
+
+/// A coordinate too wide for the fixed pad width it must be encoded in.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Coordinate needs {needed} bytes but the pad holds {pad_length}")]
+pub struct CoordinateOverflow {
+    pub needed: usize,
+    pub pad_length: usize,
 }
 
 #[derive(Error, Debug)]
@@ -26,6 +42,10 @@ pub enum EncryptionError {
     PlaintextMappingFailed,
     #[error("Encryption process failed")]
     EncryptionFailed,
+    #[error("Cipher parameters have no ciphertext format")]
+    UnsupportedParams,
+    #[error("Pad length {pad_length} is shorter than the {minimum}-byte modulus")]
+    InvalidPadLength { pad_length: usize, minimum: usize },
 }
 
 #[derive(Error, Debug)]
@@ -40,6 +60,16 @@ pub enum DecryptionError {
     NoiseRemovalFailed(#[from] NoiseError),
     #[error("Invalid ciphertext structure")]
     InvalidCiphertext,
+    #[error("Ciphertext was encrypted under a different key")]
+    WrongKey,
+    #[error("Pad length {pad_length} is shorter than the {minimum}-byte modulus")]
+    InvalidPadLength { pad_length: usize, minimum: usize },
+    #[error(transparent)]
+    CoordinateOverflow(#[from] CoordinateOverflow),
+    #[error("Ciphertext uses pad length {found} but the key uses {expected}")]
+    PadLengthMismatch { expected: usize, found: usize },
+    #[error(transparent)]
+    Malformed(#[from] MalformedInput),
 }
 
 #[derive(Error, Debug)]
@@ -48,19 +78,52 @@ pub enum HMACError {
     SignError,
     #[error("Signature verification failed")]
     VerifyError,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    #[error("signature does not verify")]
+    Invalid,
+    #[error("signature expired at {expires} (now {now})")]
+    Expired { expires: u64, now: u64 },
+    #[error("signature is not valid before {created} (now {now})")]
+    NotYetValid { created: u64, now: u64 },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DetachedSignatureError {
+    #[error("missing {0} line")]
+    Missing(&'static str),
+    #[error("invalid {0} line")]
+    Invalid(&'static str),
 }
+/// Cryptographic strength measures of an S-box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SBoxMetrics {
+    /// Least Hamming distance from any nonzero output-bit combination to an affine function;
+    /// higher resists linear cryptanalysis (at most 112 is known for 8-bit permutations)
+    pub nonlinearity: u32,
+    /// Largest count of x with S(x ⊕ a) ⊕ S(x) = b over a ≠ 0; lower resists differential
+    /// cryptanalysis (2 is optimal, random permutations give 10 to 14)
+    pub differential_uniformity: u32,
+    /// Number of x with S(x) = x
+    pub fixed_points: u32,
+}
+
 #[derive(Debug, Clone, PartialEq)]
-struct DynamicSBox {
+pub struct DynamicSBox {
     sbox: [u8; 256],
     inverse_sbox: [u8; 256],
 }
 
 impl DynamicSBox {
     /// Generate a secure dynamic S-Box along with its inverse
-    pub fn new(rng: &mut ChaCha20Rng) -> Self {
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let mut sbox: [u8; 256] = [0; 256];
-        for i in 0..256 {
-            sbox[i] = i as u8;
+        for (i, entry) in sbox.iter_mut().enumerate() {
+            *entry = i as u8;
         }
         // Shuffle S-Box securely
         for i in (1..256).rev() {
@@ -70,13 +133,39 @@ impl DynamicSBox {
 
         // Create inverse S-Box
         let mut inverse_sbox: [u8; 256] = [0; 256];
-        for i in 0..256 {
-            inverse_sbox[sbox[i] as usize] = i as u8;
+        for (i, &value) in sbox.iter().enumerate() {
+            inverse_sbox[value as usize] = i as u8;
         }
 
         DynamicSBox { sbox, inverse_sbox }
     }
 
+    /// Generate an S-Box from an injected entropy source
+    pub fn from_entropy<E: EntropySource + ?Sized>(entropy: &mut E) -> Self {
+        DynamicSBox::new(&mut EntropyRng(entropy))
+    }
+
+    /// The S-Box bound to `private_key`, shuffled by its `SubKey::SBox` stream, so it need
+    /// not be stored beside the key
+    pub fn derive(private_key: &SpherePoint) -> Self {
+        DynamicSBox::new(&mut kdf::rng(private_key, SubKey::SBox))
+    }
+
+    /// Rebuild an S-Box from its table, or `None` if the table is not a permutation
+    pub fn from_table(sbox: [u8; 256]) -> Option<Self> {
+        let mut inverse_sbox: [u8; 256] = [0; 256];
+        for (i, &value) in sbox.iter().enumerate() {
+            inverse_sbox[value as usize] = i as u8;
+        }
+        let sbox = DynamicSBox { sbox, inverse_sbox };
+        sbox.is_bijective().then_some(sbox)
+    }
+
+    /// The substitution table
+    pub fn table(&self) -> &[u8; 256] {
+        &self.sbox
+    }
+
     /// Substitute a value using the S-Box
     pub fn substitute(&self, value: u8) -> u8 {
         self.sbox[value as usize]
@@ -86,6 +175,69 @@ impl DynamicSBox {
     pub fn inverse_substitute(&self, value: u8) -> u8 {
         self.inverse_sbox[value as usize]
     }
+
+    /// Generate up to `max_attempts` S-Boxes, returning the first that reaches
+    /// `min_nonlinearity`, or `None` if none does. Random permutations mostly land between 90
+    /// and 96: about one in ten reaches 96, one in several hundred 98, and 100 practically never.
+    pub fn new_strong<R: Rng + ?Sized>(
+        rng: &mut R,
+        min_nonlinearity: u32,
+        max_attempts: u32,
+    ) -> Option<Self> {
+        (0..max_attempts)
+            .map(|_| DynamicSBox::new(rng))
+            .find(|sbox| sbox.metrics().nonlinearity >= min_nonlinearity)
+    }
+
+    /// Nonlinearity, differential uniformity and fixed points of the S-Box
+    pub fn metrics(&self) -> SBoxMetrics {
+        // Walsh spectrum of each component b·S(x) by the fast Walsh–Hadamard transform
+        let mut max_walsh = 0;
+        for mask in 1..256usize {
+            let mut spectrum: Vec<i32> = self
+                .sbox
+                .iter()
+                .map(|&y| if (mask & y as usize).count_ones().is_multiple_of(2) { 1 } else { -1 })
+                .collect();
+            let mut half = 1;
+            while half < 256 {
+                for block in (0..256).step_by(2 * half) {
+                    for i in block..block + half {
+                        let (a, b) = (spectrum[i], spectrum[i + half]);
+                        spectrum[i] = a + b;
+                        spectrum[i + half] = a - b;
+                    }
+                }
+                half *= 2;
+            }
+            max_walsh = max_walsh.max(spectrum.iter().map(|w| w.unsigned_abs()).max().unwrap());
+        }
+
+        let mut differential_uniformity = 0;
+        for a in 1..256usize {
+            let mut counts = [0u32; 256];
+            for x in 0..256usize {
+                counts[(self.sbox[x ^ a] ^ self.sbox[x]) as usize] += 1;
+            }
+            differential_uniformity = differential_uniformity.max(*counts.iter().max().unwrap());
+        }
+
+        SBoxMetrics {
+            nonlinearity: 128 - max_walsh / 2,
+            differential_uniformity,
+            fixed_points: (0..256).filter(|&x| self.sbox[x] as usize == x).count() as u32,
+        }
+    }
+
+    /// Check that the S-Box is a permutation and the inverse table undoes it
+    pub fn is_bijective(&self) -> bool {
+        let mut seen = [false; 256];
+        for &value in &self.sbox {
+            seen[value as usize] = true;
+        }
+        seen.iter().all(|&s| s)
+            && (0..=255u8).all(|v| self.inverse_substitute(self.substitute(v)) == v)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -120,7 +272,7 @@ impl RingMetadata {
             + public.z.clone() * substituted.z.clone())
             % modulus;
 
-        &computed_ring == &self.ring_value
+        computed_ring == self.ring_value
     }
 }
 
@@ -149,13 +301,9 @@ impl SpherePoint {
             return Err(NoiseError::InvalidStdDev);
         }
 
-        let x_bytes = self.x.to_bytes_be();
-        let y_bytes = self.y.to_bytes_be();
-        let z_bytes = self.z.to_bytes_be();
-
-        let x_bytes_padded = pad_bytes(&x_bytes, pad_length);
-        let y_bytes_padded = pad_bytes(&y_bytes, pad_length);
-        let z_bytes_padded = pad_bytes(&z_bytes, pad_length);
+        let x_bytes_padded = to_fixed_width(&self.x, pad_length)?;
+        let y_bytes_padded = to_fixed_width(&self.y, pad_length)?;
+        let z_bytes_padded = to_fixed_width(&self.z, pad_length)?;
 
         let mut substituted_x = vec![0u8; pad_length];
         let mut substituted_y = vec![0u8; pad_length];
@@ -205,16 +353,24 @@ impl SpherePoint {
     }
 }
 
-/// Pad bytes to a fixed length
-fn pad_bytes(bytes: &[u8], length: usize) -> Vec<u8> {
-    let mut padded = vec![0u8; length];
-    let start = length - bytes.len();
-    padded[start..].copy_from_slice(bytes);
-    padded
+/// Big-endian bytes of `n` in exactly `pad_length` bytes, zero-filled on the left.
+///
+/// Every coordinate goes through this fixed-width encoding, so a value too wide for the pad
+/// (from a tampered ciphertext or a mismatched key) is an error rather than a slice panic.
+pub(crate) fn to_fixed_width(n: &BigUint, pad_length: usize) -> Result<Vec<u8>, CoordinateOverflow> {
+    let needed = n.bits().div_ceil(8) as usize;
+    if needed > pad_length {
+        return Err(CoordinateOverflow { needed, pad_length });
+    }
+    let mut padded = vec![0u8; pad_length];
+    if needed > 0 {
+        padded[pad_length - needed..].copy_from_slice(&n.to_bytes_be());
+    }
+    Ok(padded)
 }
 
 /// --- Plaintext Mapping ---
-fn map_plaintext_to_sphere_point(
+pub fn map_plaintext_to_sphere_point(
     plaintext: &str,
     pad_length: usize,
 ) -> Result<SpherePoint, EncryptionError> {
@@ -222,7 +378,7 @@ fn map_plaintext_to_sphere_point(
     let mut padded = plaintext_bytes.to_vec();
 
     // Pad the plaintext to a multiple of pad_length bytes for even splitting
-    while padded.len() % pad_length != 0 {
+    while !padded.len().is_multiple_of(pad_length) {
         padded.push(0);
     }
 
@@ -246,16 +402,16 @@ fn map_plaintext_to_sphere_point(
 }
 
 /// --- Plaintext Reconstruction ---
-fn map_sphere_point_to_plaintext(
+pub fn map_sphere_point_to_plaintext(
     sphere: &SpherePoint,
     pad_length: usize,
 ) -> Result<String, DecryptionError> {
     let mut bytes = Vec::new();
 
     // Convert each coordinate back to bytes
-    bytes.extend_from_slice(&pad_bytes(&sphere.x.to_bytes_be(), pad_length));
-    bytes.extend_from_slice(&pad_bytes(&sphere.y.to_bytes_be(), pad_length));
-    bytes.extend_from_slice(&pad_bytes(&sphere.z.to_bytes_be(), pad_length));
+    bytes.extend_from_slice(&to_fixed_width(&sphere.x, pad_length)?);
+    bytes.extend_from_slice(&to_fixed_width(&sphere.y, pad_length)?);
+    bytes.extend_from_slice(&to_fixed_width(&sphere.z, pad_length)?);
 
     // Remove padding (trailing zeros)
     while let Some(&last) = bytes.last() {
@@ -270,34 +426,252 @@ fn map_sphere_point_to_plaintext(
     String::from_utf8(bytes).map_err(|_| DecryptionError::PlaintextReconstructionFailed)
 }
 
+/// --- Cipher Parameters ---
+/// Ciphertext version of the original format: one substitution plus noise per byte.
+pub const CIPHERTEXT_VERSION_SINGLE_ROUND: u8 = 1;
+/// Ciphertext version with `rounds` rounds of substitution, permutation, chaining and noise.
+pub const CIPHERTEXT_VERSION_ROUNDS: u8 = 2;
+/// Like `CIPHERTEXT_VERSION_ROUNDS`, masking with keystream bytes instead of Gaussian noise.
+pub const CIPHERTEXT_VERSION_KEYSTREAM: u8 = 3;
+
+/// Where the additive mask of each round comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaskMode {
+    /// Rounded Gaussian samples (σ = 1) per byte, as in the original format. Mostly 0 or ±1,
+    /// so the masked bytes stay close to the substituted ones.
+    #[default]
+    Gaussian,
+    /// Raw bytes of the ChaCha20 keystream: uniform, and far cheaper than sampling.
+    Keystream,
+}
+
+/// Round structure of the cipher.
+///
+/// Each round substitutes every byte of x ‖ y ‖ z through the S-box, moves the bytes by a
+/// key-derived permutation, replaces each byte by its running sum with the ones before it
+/// (so a change spreads to every later position) and adds a mask. With several rounds and a
+/// fresh permutation in each, every output byte depends on every input byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CipherParams {
+    /// Number of rounds; 0 selects the original single-round format
+    pub rounds: u32,
+    /// Source of the per-round mask; the single-round format only supports `Gaussian`
+    pub mask: MaskMode,
+}
+
+impl CipherParams {
+    /// The original one-round format, without the permutation and chaining layers.
+    pub fn single_round() -> Self {
+        CipherParams {
+            rounds: 0,
+            mask: MaskMode::Gaussian,
+        }
+    }
+
+    /// The ciphertext version for these parameters, or `None` if no format supports them.
+    fn version(&self) -> Option<u8> {
+        if self.rounds > MAX_ROUNDS {
+            return None;
+        }
+        match (self.rounds, self.mask) {
+            (0, MaskMode::Gaussian) => Some(CIPHERTEXT_VERSION_SINGLE_ROUND),
+            (0, MaskMode::Keystream) => None,
+            (_, MaskMode::Gaussian) => Some(CIPHERTEXT_VERSION_ROUNDS),
+            (_, MaskMode::Keystream) => Some(CIPHERTEXT_VERSION_KEYSTREAM),
+        }
+    }
+}
+
+impl Default for CipherParams {
+    fn default() -> Self {
+        CipherParams {
+            rounds: 4,
+            mask: MaskMode::Gaussian,
+        }
+    }
+}
+
+/// The most rounds a ciphertext may have. Each round keys a permutation and a mask over the
+/// whole pad, so an unchecked count read from a ciphertext could demand terabytes.
+pub const MAX_ROUNDS: u32 = 32;
+
+/// The largest pad length a decoder accepts: 64 KiB a coordinate, a 524288-bit modulus.
+pub const MAX_PAD_LENGTH: usize = 1 << 16;
+
+/// Pad length for keys mod `modulus`: the byte length of the modulus, so every residue fits.
+pub fn pad_length_for(modulus: &BigUint) -> usize {
+    modulus.bits().div_ceil(8) as usize
+}
+
+/// Permutation and noise for one round.
+struct RoundKey {
+    permutation: Vec<usize>,
+    noise: Vec<u8>,
+}
+
+/// Derive the keys for the rounds of `params` over `length` bytes from the private key and
+/// the ciphertext's nonce, so no two messages share a permutation or a mask.
+fn round_keys(
+    private_key: &SpherePoint,
+    nonce: &[u8; 16],
+    params: CipherParams,
+    length: usize,
+) -> Result<Vec<RoundKey>, NoiseError> {
+    let mut seed = [0u8; 32];
+    let subkey = kdf::derive(private_key, SubKey::EncNoise);
+    shake256_parts(domains::PMPT_ROUND_KEYS, &[&subkey, nonce], &mut seed);
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    (0..params.rounds)
+        .map(|_| {
+            let mut permutation: Vec<usize> = (0..length).collect();
+            for i in (1..length).rev() {
+                permutation.swap(i, rng.gen_range(0..=i));
+            }
+            let noise = match params.mask {
+                MaskMode::Gaussian => (0..length)
+                    .map(|_| SpherePoint::generate_noise_byte(&mut rng, 1.0))
+                    .collect::<Result<Vec<u8>, NoiseError>>()?,
+                MaskMode::Keystream => {
+                    let mut noise = vec![0u8; length];
+                    rng.fill(&mut noise[..]);
+                    noise
+                }
+            };
+            Ok(RoundKey { permutation, noise })
+        })
+        .collect()
+}
+
+fn point_to_bytes(point: &SpherePoint, pad_length: usize) -> Result<Vec<u8>, CoordinateOverflow> {
+    let mut bytes = to_fixed_width(&point.x, pad_length)?;
+    bytes.extend_from_slice(&to_fixed_width(&point.y, pad_length)?);
+    bytes.extend_from_slice(&to_fixed_width(&point.z, pad_length)?);
+    Ok(bytes)
+}
+
+fn bytes_to_point(bytes: &[u8], pad_length: usize) -> SpherePoint {
+    SpherePoint::new(
+        BigUint::from_bytes_be(&bytes[..pad_length]),
+        BigUint::from_bytes_be(&bytes[pad_length..2 * pad_length]),
+        BigUint::from_bytes_be(&bytes[2 * pad_length..]),
+    )
+}
+
+/// Apply the rounds of `CipherParams` to `bytes`.
+fn apply_rounds(bytes: &mut Vec<u8>, sbox: &DynamicSBox, keys: &[RoundKey]) {
+    for key in keys {
+        let mut permuted = vec![0u8; bytes.len()];
+        for (i, &byte) in bytes.iter().enumerate() {
+            permuted[key.permutation[i]] = sbox.substitute(byte);
+        }
+        for i in 1..permuted.len() {
+            permuted[i] = permuted[i].wrapping_add(permuted[i - 1]);
+        }
+        for (byte, &noise) in permuted.iter_mut().zip(&key.noise) {
+            *byte = byte.wrapping_add(noise);
+        }
+        *bytes = permuted;
+    }
+}
+
+/// Undo `apply_rounds`, last round first.
+fn invert_rounds(bytes: &mut Vec<u8>, sbox: &DynamicSBox, keys: &[RoundKey]) {
+    for key in keys.iter().rev() {
+        for (byte, &noise) in bytes.iter_mut().zip(&key.noise) {
+            *byte = byte.wrapping_sub(noise);
+        }
+        for i in (1..bytes.len()).rev() {
+            bytes[i] = bytes[i].wrapping_sub(bytes[i - 1]);
+        }
+        *bytes = key
+            .permutation
+            .iter()
+            .map(|&j| sbox.inverse_substitute(bytes[j]))
+            .collect();
+    }
+}
+
 /// --- Encryption Function ---
-fn encrypt(
+/// Encrypt in the original single-round format. Its noise depends on the key alone, so equal
+/// plaintexts give equal coordinates; use `encrypt_with_params` with rounds for new data.
+pub fn encrypt<R: Rng + ?Sized>(
+    plaintext: &str,
+    public_key: &SpherePoint,
+    private_key: &SpherePoint,
+    sbox: &DynamicSBox,
+    pad_length: usize,
+    modulus: &BigUint,
+    rng: &mut R,
+) -> Result<Ciphertext, EncryptionError> {
+    encrypt_with_params(
+        plaintext,
+        public_key,
+        private_key,
+        sbox,
+        pad_length,
+        modulus,
+        CipherParams::single_round(),
+        rng,
+    )
+}
+
+/// Encrypt with the round structure in `params`, recorded in the ciphertext's version and
+/// round count, under a fresh nonce drawn from `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_with_params<R: Rng + ?Sized>(
+    plaintext: &str,
+    public_key: &SpherePoint,
+    private_key: &SpherePoint,
+    sbox: &DynamicSBox,
+    pad_length: usize,
+    modulus: &BigUint,
+    params: CipherParams,
+    rng: &mut R,
+) -> Result<Ciphertext, EncryptionError> {
+    let mut nonce = [0u8; 16];
+    rng.fill(&mut nonce);
+    encrypt_with_nonce(plaintext, public_key, private_key, sbox, pad_length, modulus, params, nonce)
+}
+
+/// `encrypt_with_params` under a given nonce, for known-answer tests. The nonce keys the
+/// rounds, so reusing one with the same key reuses every permutation and mask.
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_with_nonce(
     plaintext: &str,
     public_key: &SpherePoint,
     private_key: &SpherePoint,
     sbox: &DynamicSBox,
     pad_length: usize,
     modulus: &BigUint,
+    params: CipherParams,
+    nonce: [u8; 16],
 ) -> Result<Ciphertext, EncryptionError> {
+    let version = params.version().ok_or(EncryptionError::UnsupportedParams)?;
+    let minimum = pad_length_for(modulus);
+    if pad_length < minimum {
+        return Err(EncryptionError::InvalidPadLength { pad_length, minimum });
+    }
+
     // Step 1: Plaintext Mapping
     let mapped_point = map_plaintext_to_sphere_point(plaintext, pad_length)
         .map_err(|_| EncryptionError::PlaintextMappingFailed)?;
-    println!("Mapped Plaintext to SpherePoint: {:?}", mapped_point);
-
-    // Step 2: Deterministic Noise Generation based on private key
-    let mut hasher = Sha3_512::new();
-    Update::update(&mut hasher, &private_key.x.to_bytes_be());
-    Update::update(&mut hasher, &private_key.y.to_bytes_be());
-    Update::update(&mut hasher, &private_key.z.to_bytes_be());
-    let seed = hasher.finalize();
-    let seed_bytes: [u8; 32] = seed[0..32].try_into().unwrap();
-    let mut noise_rng = ChaCha20Rng::from_seed(seed_bytes);
-
-    // Apply substitution and add noise
-    let substituted_point = mapped_point
-        .transform_with_noise(&mut noise_rng, sbox, 1.0, pad_length)
-        .map_err(|_| EncryptionError::EncryptionFailed)?;
-    println!("Substituted and Obfuscated SpherePoint: {:?}", substituted_point);
+    debug!("Mapped Plaintext to SpherePoint: {:?}", mapped_point);
+
+    // Step 2: Substitution and deterministic noise based on the private key
+    let substituted_point = if params.rounds == 0 {
+        let mut noise_rng = kdf::rng(private_key, SubKey::EncNoise);
+        mapped_point
+            .transform_with_noise(&mut noise_rng, sbox, 1.0, pad_length)
+            .map_err(|_| EncryptionError::EncryptionFailed)?
+    } else {
+        let keys = round_keys(private_key, &nonce, params, 3 * pad_length)
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+        let mut bytes = point_to_bytes(&mapped_point, pad_length)
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+        apply_rounds(&mut bytes, sbox, &keys);
+        bytes_to_point(&bytes, pad_length)
+    };
+    debug!("Substituted and Obfuscated SpherePoint: {:?}", substituted_point);
 
     // Step 3: Ring Metadata Integration
     let ring_value = (public_key.x.clone() * substituted_point.x.clone()
@@ -305,18 +679,26 @@ fn encrypt(
         + public_key.z.clone() * substituted_point.z.clone())
         % modulus;
 
-    let ciphertext = Ciphertext {
+    // Step 4: Key Commitment
+    let fingerprint = key_fingerprint(public_key, private_key, sbox, modulus);
+    let mut ciphertext = Ciphertext {
         r: ring_value,
         x_s: substituted_point.x,
         y_s: substituted_point.y,
         z_s: substituted_point.z,
+        nonce,
+        commitment: [0; 32],
+        version,
+        rounds: params.rounds,
+        pad_length,
     };
+    ciphertext.commitment = key_commitment(&fingerprint, &ciphertext);
 
     Ok(ciphertext)
 }
 
 /// --- Decryption Function ---
-fn decrypt(
+pub fn decrypt(
     ciphertext: &Ciphertext,
     public_key: &SpherePoint,
     private_key: &SpherePoint,
@@ -324,25 +706,93 @@ fn decrypt(
     pad_length: usize,
     modulus: &BigUint,
 ) -> Result<String, DecryptionError> {
-    // Step 1: Ring Metadata Verification
+    // Step 0: Pad Length Negotiation. The key's pad must hold its modulus, the ciphertext
+    // must have been made with the same pad, and no coordinate may be wider than it
+    let minimum = pad_length_for(modulus);
+    if pad_length < minimum {
+        return Err(DecryptionError::InvalidPadLength { pad_length, minimum });
+    }
+    if ciphertext.pad_length != pad_length {
+        return Err(DecryptionError::PadLengthMismatch {
+            expected: pad_length,
+            found: ciphertext.pad_length,
+        });
+    }
+    let known_version = (CIPHERTEXT_VERSION_SINGLE_ROUND..=CIPHERTEXT_VERSION_KEYSTREAM)
+        .contains(&ciphertext.version);
+    if !known_version || ciphertext.rounds > MAX_ROUNDS {
+        return Err(DecryptionError::InvalidCiphertext);
+    }
+    if [&ciphertext.x_s, &ciphertext.y_s, &ciphertext.z_s]
+        .iter()
+        .any(|coordinate| to_fixed_width(coordinate, pad_length).is_err())
+    {
+        return Err(DecryptionError::InvalidCiphertext);
+    }
+
+    // Step 1: Key Commitment. A mismatch means the key is not the one encrypted to, or the
+    // header, nonce, ring value or coordinates were altered
+    let fingerprint = key_fingerprint(public_key, private_key, sbox, modulus);
+    if key_commitment(&fingerprint, ciphertext) != ciphertext.commitment {
+        return Err(DecryptionError::WrongKey);
+    }
+
+    // Step 2: Ring Metadata Verification
     let computed_ring = (public_key.x.clone() * ciphertext.x_s.clone()
         + public_key.y.clone() * ciphertext.y_s.clone()
         + public_key.z.clone() * ciphertext.z_s.clone())
         % modulus;
 
-    if &computed_ring != &ciphertext.r {
+    if computed_ring != ciphertext.r {
         return Err(DecryptionError::RingValidationFailed);
     }
-    println!("Ring metadata validation successful.");
+    debug!("Ring metadata validation successful.");
+
+    // Step 3: Undo the rounds, or for the original format regenerate the noise
+    let decrypted_point = match ciphertext.version {
+        CIPHERTEXT_VERSION_SINGLE_ROUND => {
+            remove_single_round(ciphertext, private_key, sbox, pad_length)?
+        }
+        CIPHERTEXT_VERSION_ROUNDS | CIPHERTEXT_VERSION_KEYSTREAM if ciphertext.rounds > 0 => {
+            let mask = if ciphertext.version == CIPHERTEXT_VERSION_KEYSTREAM {
+                MaskMode::Keystream
+            } else {
+                MaskMode::Gaussian
+            };
+            let params = CipherParams {
+                rounds: ciphertext.rounds,
+                mask,
+            };
+            let keys = round_keys(private_key, &ciphertext.nonce, params, 3 * pad_length)?;
+            let substituted_point = SpherePoint::new(
+                ciphertext.x_s.clone(),
+                ciphertext.y_s.clone(),
+                ciphertext.z_s.clone(),
+            );
+            let mut bytes = point_to_bytes(&substituted_point, pad_length)?;
+            invert_rounds(&mut bytes, sbox, &keys);
+            bytes_to_point(&bytes, pad_length)
+        }
+        _ => return Err(DecryptionError::InvalidCiphertext),
+    };
+    debug!("Decrypted SpherePoint after inverse substitution: {:?}", decrypted_point);
+
+    // Step 4: Plaintext Reconstruction
+    let plaintext = map_sphere_point_to_plaintext(&decrypted_point, pad_length)?;
+    debug!("Reconstructed Plaintext.");
 
-    // Step 2: Deterministically Regenerate Noise Using Private Key
-    let mut hasher = Sha3_512::new();
-    Update::update(&mut hasher, &private_key.x.to_bytes_be());
-    Update::update(&mut hasher, &private_key.y.to_bytes_be());
-    Update::update(&mut hasher, &private_key.z.to_bytes_be());
-    let seed = hasher.finalize();
-    let seed_bytes: [u8; 32] = seed[0..32].try_into().unwrap();
-    let mut noise_rng = ChaCha20Rng::from_seed(seed_bytes);
+    Ok(plaintext)
+}
+
+/// Remove the noise and substitution of a single-round ciphertext.
+fn remove_single_round(
+    ciphertext: &Ciphertext,
+    private_key: &SpherePoint,
+    sbox: &DynamicSBox,
+    pad_length: usize,
+) -> Result<SpherePoint, DecryptionError> {
+    // Deterministically Regenerate Noise Using Private Key
+    let mut noise_rng = kdf::rng(private_key, SubKey::EncNoise);
 
     // Generate the same noise used during encryption
     let substituted_point = SpherePoint::new(
@@ -365,9 +815,9 @@ fn decrypt(
         .collect::<Result<Vec<u8>, NoiseError>>()?;
 
     // Convert substituted sphere point to bytes
-    let x_bytes = pad_bytes(&substituted_point.x.to_bytes_be(), pad_length);
-    let y_bytes = pad_bytes(&substituted_point.y.to_bytes_be(), pad_length);
-    let z_bytes = pad_bytes(&substituted_point.z.to_bytes_be(), pad_length);
+    let x_bytes = to_fixed_width(&substituted_point.x, pad_length)?;
+    let y_bytes = to_fixed_width(&substituted_point.y, pad_length)?;
+    let z_bytes = to_fixed_width(&substituted_point.z, pad_length)?;
 
     // Apply inverse substitution after removing noise
     let mut decrypted_x_bytes = vec![0u8; pad_length];
@@ -388,26 +838,179 @@ fn decrypt(
     let decrypted_y = BigUint::from_bytes_be(&decrypted_y_bytes);
     let decrypted_z = BigUint::from_bytes_be(&decrypted_z_bytes);
 
-    let decrypted_point = SpherePoint::new(decrypted_x, decrypted_y, decrypted_z);
-    println!("Decrypted SpherePoint after inverse substitution: {:?}", decrypted_point);
-
-    // Step 3: Plaintext Reconstruction
-    let plaintext = map_sphere_point_to_plaintext(&decrypted_point, pad_length)?;
-    println!("Reconstructed Plaintext.");
-
-    Ok(plaintext)
+    Ok(SpherePoint::new(decrypted_x, decrypted_y, decrypted_z))
 }
 
 /// --- Ciphertext Structure ---
 #[derive(Debug, Clone)]
-struct Ciphertext {
-    r: BigUint, // Ring metadata
-    x_s: BigUint,
-    y_s: BigUint,
-    z_s: BigUint,
+pub struct Ciphertext {
+    pub r: BigUint, // Ring metadata
+    pub x_s: BigUint,
+    pub y_s: BigUint,
+    pub z_s: BigUint,
+    pub nonce: [u8; 16],
+    pub commitment: [u8; 32], // Key commitment over the key fingerprint and everything above
+    pub version: u8,
+    pub rounds: u32, // Rounds of `CipherParams`, 0 for the single-round version
+    pub pad_length: usize, // Bytes per coordinate
+}
+
+/// Bytes of the encoded ciphertext ahead of its values: version, rounds and pad length.
+const CIPHERTEXT_HEADER_LEN: usize = 1 + 4 + 4;
+
+impl Ciphertext {
+    /// Length of `to_bytes` for a key with `pad_length`-byte coordinates. Neither the round
+    /// count nor the mask changes it, so it is known before encrypting.
+    pub fn encoded_len(pad_length: usize) -> usize {
+        CIPHERTEXT_HEADER_LEN + 4 * pad_length + 16 + 32
+    }
+
+    /// The version, rounds and pad length (big-endian u32s), then r, x_s, y_s and z_s at the
+    /// pad width, the nonce and the commitment.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CoordinateOverflow> {
+        let mut bytes = Vec::with_capacity(Ciphertext::encoded_len(self.pad_length));
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.rounds.to_be_bytes());
+        bytes.extend_from_slice(&(self.pad_length as u32).to_be_bytes());
+        for value in [&self.r, &self.x_s, &self.y_s, &self.z_s] {
+            bytes.extend_from_slice(&to_fixed_width(value, self.pad_length)?);
+        }
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.commitment);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecryptionError> {
+        let mut reader = StrictReader::new(bytes);
+        let version = reader.u8("version")?;
+        let rounds = reader.u32_be("round count")?;
+        let rounds = reader.length("round count", rounds as u64, MAX_ROUNDS as usize)? as u32;
+        let pad_length = reader.u32_be("pad length")?;
+        let pad_length = reader.length("pad length", pad_length as u64, MAX_PAD_LENGTH)?;
+        let ciphertext = Ciphertext {
+            r: reader.biguint("ring value", pad_length)?,
+            x_s: reader.biguint("x coordinate", pad_length)?,
+            y_s: reader.biguint("y coordinate", pad_length)?,
+            z_s: reader.biguint("z coordinate", pad_length)?,
+            nonce: reader.array("nonce")?,
+            commitment: reader.array("commitment")?,
+            version,
+            rounds,
+            pad_length,
+        };
+        reader.finish()?;
+        Ok(ciphertext)
+    }
+}
+
+/// 32-byte SHAKE256 fingerprint of everything `decrypt` uses: both sphere points, the S-box
+/// and the modulus.
+pub fn key_fingerprint(
+    public_key: &SpherePoint,
+    private_key: &SpherePoint,
+    sbox: &DynamicSBox,
+    modulus: &BigUint,
+) -> [u8; 32] {
+    let mut fingerprint = [0u8; 32];
+    shake256_parts(
+        domains::PMPT_ENCRYPTION_KEY,
+        &[
+            &public_key.x.to_bytes_be(),
+            &public_key.y.to_bytes_be(),
+            &public_key.z.to_bytes_be(),
+            &private_key.x.to_bytes_be(),
+            &private_key.y.to_bytes_be(),
+            &private_key.z.to_bytes_be(),
+            &sbox.sbox,
+            &modulus.to_bytes_be(),
+        ],
+        &mut fingerprint,
+    );
+    fingerprint
+}
+
+/// The committing tag binding a ciphertext's version, round count, pad length, nonce, ring
+/// value and coordinates to one key, so none of them can be changed or downgraded unnoticed.
+/// The ring check alone cannot catch a changed body: anyone with the public key can add a
+/// vector from the kernel of `pub · (x, y, z) mod m` without changing r.
+fn key_commitment(fingerprint: &[u8; 32], ciphertext: &Ciphertext) -> [u8; 32] {
+    let mut commitment = [0u8; 32];
+    shake256_parts(
+        domains::PMPT_KEY_COMMITMENT,
+        &[
+            fingerprint,
+            &[ciphertext.version],
+            &ciphertext.rounds.to_be_bytes(),
+            &(ciphertext.pad_length as u64).to_be_bytes(),
+            &ciphertext.nonce,
+            &ciphertext.r.to_bytes_be(),
+            &ciphertext.x_s.to_bytes_be(),
+            &ciphertext.y_s.to_bytes_be(),
+            &ciphertext.z_s.to_bytes_be(),
+        ],
+        &mut commitment,
+    );
+    commitment
+}
+
+/// Fill `output` with SHAKE256 of `domain` and `parts`, each prefixed by its length so the
+/// concatenation is unambiguous.
+pub(crate) fn shake256_parts(domain: &[u8], parts: &[&[u8]], output: &mut [u8]) {
+    let mut hasher: Shake256 = domains::tagged(domain);
+    for part in parts {
+        hasher.update(&(part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher
+        .finalize_xof()
+        .read_exact(output)
+        .expect("SHAKE256 output is unbounded");
+}
+
+/// Buffer size for hashing readers.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// 64-byte SHAKE256 digest of `data`.
+fn shake256_digest(data: &[u8]) -> [u8; 64] {
+    shake256_reader(data).expect("reading a slice cannot fail")
+}
+
+/// 64-byte SHAKE256 digest of everything read from `reader`, a chunk at a time.
+fn shake256_reader<R: Read>(mut reader: R) -> std::io::Result<[u8; 64]> {
+    let mut hasher: Shake256 = domains::tagged(domains::PMPT_MESSAGE);
+    let mut buffer = vec![0u8; READ_CHUNK];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    let mut digest = [0u8; 64];
+    hasher.finalize_xof().read_exact(&mut digest)?;
+    Ok(digest)
+}
+
+/// The x, y and z bytes of the point `PmptHmac` maps the digest of `data` to, which `sign`
+/// substitutes and adds noise to.
+pub(crate) fn hmac_input_bytes(data: &[u8], pad_length: usize) -> Result<Vec<u8>, HMACError> {
+    let point = map_plaintext_to_sphere_point(
+        &String::from_utf8_lossy(&shake256_digest(data)),
+        pad_length,
+    )
+    .map_err(|_| HMACError::SignError)?;
+    let mut bytes = Vec::with_capacity(3 * pad_length);
+    for coordinate in [&point.x, &point.y, &point.z] {
+        bytes.extend(to_fixed_width(coordinate, pad_length).map_err(|_| HMACError::SignError)?);
+    }
+    Ok(bytes)
 }
 
 /// --- PMPT-HMAC Implementation ---
+/// A symmetric MAC: verifying regenerates the signing noise from the private key, so only
+/// holders of the private key can check a tag. Use `SigningKey` and `VerifyingKey` when the
+/// verifier should only have the public key.
 pub struct PmptHmac {
     public_key: SpherePoint,
     private_key: SpherePoint,
@@ -433,32 +1036,68 @@ impl PmptHmac {
         }
     }
 
+    /// Public sphere point this MAC key is paired with
+    pub fn public_key(&self) -> &SpherePoint {
+        &self.public_key
+    }
+
+    /// Modulus of the ring the key pair lives in
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// Length of a tag from `signature_bytes`: three coordinates at the pad width.
+    pub fn signature_len(&self) -> usize {
+        3 * self.pad_length
+    }
+
+    /// `signature` as x ‖ y ‖ z at the pad width, `signature_len` bytes.
+    pub fn signature_bytes(&self, signature: &SpherePoint) -> Result<Vec<u8>, HMACError> {
+        point_to_bytes(signature, self.pad_length).map_err(|_| HMACError::VerifyError)
+    }
+
+    /// The tag encoded by `signature_bytes`, or `None` if `bytes` has the wrong length.
+    pub fn signature_from_bytes(&self, bytes: &[u8]) -> Option<SpherePoint> {
+        (bytes.len() == self.signature_len()).then(|| bytes_to_point(bytes, self.pad_length))
+    }
+
     pub fn sign(&self, data: &[u8]) -> Result<SpherePoint, HMACError> {
-        // Hash the data using Shake256
-        let mut hasher = Shake256::default();
-        hasher.update(data);
-
-        let mut hash_output = [0u8; 64];
-        hasher
-            .finalize_xof()
-            .read_exact(&mut hash_output)
-            .map_err(|_| HMACError::SignError)?;
+        self.sign_digest(&shake256_digest(data))
+    }
+
+    /// Sign everything read from `reader`, hashing it incrementally; the tag is the same as
+    /// `sign` over the concatenated input.
+    pub fn sign_reader<R: Read>(&self, reader: R) -> Result<SpherePoint, HMACError> {
+        self.sign_digest(&shake256_reader(reader)?)
+    }
 
+    /// The noise bytes `sign` adds to the substituted x, y and z bytes, in that order. They
+    /// depend only on the private key, so together with the S-box they make up the MAC key.
+    pub(crate) fn noise(&self) -> Result<Vec<u8>, HMACError> {
+        let mut noise_rng = kdf::rng(&self.private_key, SubKey::Mac);
+        (0..3 * self.pad_length)
+            .map(|_| SpherePoint::generate_noise_byte(&mut noise_rng, 1.0))
+            .collect::<Result<Vec<u8>, NoiseError>>()
+            .map_err(|_| HMACError::SignError)
+    }
+
+    pub(crate) fn sbox(&self) -> &DynamicSBox {
+        &self.sbox
+    }
+
+    pub(crate) fn pad_length(&self) -> usize {
+        self.pad_length
+    }
+
+    fn sign_digest(&self, hash_output: &[u8; 64]) -> Result<SpherePoint, HMACError> {
         // Map hash output to SpherePoint
         let hash_point = map_plaintext_to_sphere_point(
-            &String::from_utf8_lossy(&hash_output),
+            &String::from_utf8_lossy(hash_output),
             self.pad_length,
         )
         .map_err(|_| HMACError::SignError)?;
 
-        // Use private key to generate noise RNG seed
-        let mut hasher = Sha3_512::new();
-        Update::update(&mut hasher, &self.private_key.x.to_bytes_be());
-        Update::update(&mut hasher, &self.private_key.y.to_bytes_be());
-        Update::update(&mut hasher, &self.private_key.z.to_bytes_be());
-        let seed = hasher.finalize();
-        let seed_bytes: [u8; 32] = seed[0..32].try_into().unwrap();
-        let mut noise_rng = ChaCha20Rng::from_seed(seed_bytes);
+        let mut noise_rng = kdf::rng(&self.private_key, SubKey::Mac);
 
         // Transform hash_point using substitution and noise
         let signature_point = hash_point
@@ -469,31 +1108,31 @@ impl PmptHmac {
     }
 
     pub fn verify(&self, data: &[u8], signature: &SpherePoint) -> Result<bool, HMACError> {
-        // Hash the data
-        let mut hasher = Shake256::default();
-        hasher.update(data);
-
-        let mut hash_output = [0u8; 64];
-        hasher
-            .finalize_xof()
-            .read_exact(&mut hash_output)
-            .map_err(|_| HMACError::VerifyError)?;
+        self.verify_digest(&shake256_digest(data), signature)
+    }
 
+    /// Verify a tag over everything read from `reader`, hashing it incrementally.
+    pub fn verify_reader<R: Read>(
+        &self,
+        reader: R,
+        signature: &SpherePoint,
+    ) -> Result<bool, HMACError> {
+        self.verify_digest(&shake256_reader(reader)?, signature)
+    }
+
+    fn verify_digest(
+        &self,
+        hash_output: &[u8; 64],
+        signature: &SpherePoint,
+    ) -> Result<bool, HMACError> {
         // Map hash output to SpherePoint
         let hash_point = map_plaintext_to_sphere_point(
-            &String::from_utf8_lossy(&hash_output),
+            &String::from_utf8_lossy(hash_output),
             self.pad_length,
         )
         .map_err(|_| HMACError::VerifyError)?;
 
-        // Use private key to regenerate noise RNG seed
-        let mut hasher = Sha3_512::new();
-        Update::update(&mut hasher, &self.private_key.x.to_bytes_be());
-        Update::update(&mut hasher, &self.private_key.y.to_bytes_be());
-        Update::update(&mut hasher, &self.private_key.z.to_bytes_be());
-        let seed = hasher.finalize();
-        let seed_bytes: [u8; 32] = seed[0..32].try_into().unwrap();
-        let mut noise_rng = ChaCha20Rng::from_seed(seed_bytes);
+        let mut noise_rng = kdf::rng(&self.private_key, SubKey::Mac);
 
         // Inverse transform the signature point
         let substituted_point = signature;
@@ -515,9 +1154,16 @@ impl PmptHmac {
             .map_err(|_| HMACError::VerifyError)?;
 
         // Convert substituted sphere point to bytes
-        let x_bytes = pad_bytes(&substituted_point.x.to_bytes_be(), self.pad_length);
-        let y_bytes = pad_bytes(&substituted_point.y.to_bytes_be(), self.pad_length);
-        let z_bytes = pad_bytes(&substituted_point.z.to_bytes_be(), self.pad_length);
+        // A tag too wide for the pad cannot have come from `sign`
+        let encode = |n: &BigUint| to_fixed_width(n, self.pad_length);
+        let (x_bytes, y_bytes, z_bytes) = match (
+            encode(&substituted_point.x),
+            encode(&substituted_point.y),
+            encode(&substituted_point.z),
+        ) {
+            (Ok(x), Ok(y), Ok(z)) => (x, y, z),
+            _ => return Ok(false),
+        };
 
         // Remove noise and apply inverse substitution
         let mut decrypted_x_bytes = vec![0u8; self.pad_length];
@@ -547,161 +1193,864 @@ impl PmptHmac {
     }
 }
 
-/// --- Miller-Rabin Primality Test ---
-fn is_prime(n: &BigUint, k: u32) -> bool {
-    if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
-        return true;
+/// --- PMPT Signatures ---
+/// Public parameters for signatures: a safe prime p = 2q + 1 and a generator g of the
+/// subgroup of prime order q.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureGroup {
+    pub p: BigUint,
+    pub q: BigUint,
+    pub g: BigUint,
+}
+
+impl SignatureGroup {
+    /// Generate a group over a random `bits`-bit safe prime.
+    #[instrument(level = "info", name = "signature_group_generation", skip(config, rng))]
+    pub fn generate<R: Rng + ?Sized>(bits: u64, config: &PrimalityConfig, rng: &mut R) -> Self {
+        let p = random_safe_prime(bits, config, rng);
+        let q = &p >> 1u32;
+        let g = subgroup_generator(&p, &q).expect("q divides p - 1");
+        SignatureGroup { p, q, g }
+    }
+
+    /// Hash `parts` under `domain` to an integer mod q, drawing 128 bits more than q has so
+    /// the reduction is unbiased in practice.
+    fn hash_to_scalar(&self, domain: &[u8], parts: &[&[u8]]) -> BigUint {
+        let mut output = vec![0u8; (self.q.bits() as usize).div_ceil(8) + 16];
+        shake256_parts(domain, parts, &mut output);
+        BigUint::from_bytes_be(&output) % &self.q
+    }
+}
+
+/// Optional attributes signed along with the data, for token-like use: a validity window in
+/// seconds since the Unix epoch and a context string naming what the signature is for.
+///
+/// They are hashed into the challenge, so a signature only verifies with the same attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureAttributes {
+    pub created: Option<u64>,
+    pub expires: Option<u64>,
+    pub context: Option<String>,
+}
+
+impl SignatureAttributes {
+    /// Canonical encoding: a presence byte per attribute, then its value as a big-endian u64
+    /// or a length-prefixed string.
+    fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for time in [self.created, self.expires] {
+            match time {
+                Some(time) => {
+                    encoded.push(1);
+                    encoded.extend_from_slice(&time.to_be_bytes());
+                }
+                None => encoded.push(0),
+            }
+        }
+        match &self.context {
+            Some(context) => {
+                encoded.push(1);
+                encoded.extend_from_slice(&(context.len() as u64).to_be_bytes());
+                encoded.extend_from_slice(context.as_bytes());
+            }
+            None => encoded.push(0),
+        }
+        encoded
+    }
+}
+
+/// A Schnorr signature (e, s) over a `SignatureGroup`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub e: BigUint,
+    pub s: BigUint,
+}
+
+/// The public half of a signing key: the public sphere point together with y = g^x mod p.
+///
+/// Verification needs nothing secret. The public point is hashed into every challenge, so a
+/// signature is bound to it as well as to y.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyingKey {
+    pub group: SignatureGroup,
+    pub public_point: SpherePoint,
+    pub y: BigUint,
+}
+
+impl VerifyingKey {
+    fn challenge(
+        &self,
+        commitment: &BigUint,
+        attributes: &SignatureAttributes,
+        data: &[u8],
+    ) -> BigUint {
+        self.group.hash_to_scalar(
+            domains::PMPT_SIGNATURE,
+            &[
+                &self.group.p.to_bytes_be(),
+                &self.group.g.to_bytes_be(),
+                &self.y.to_bytes_be(),
+                &self.public_point.x.to_bytes_be(),
+                &self.public_point.y.to_bytes_be(),
+                &self.public_point.z.to_bytes_be(),
+                &commitment.to_bytes_be(),
+                &attributes.encode(),
+                data,
+            ],
+        )
+    }
+
+    /// 32-byte SHAKE256 fingerprint of the group, y and the public point, identifying the key
+    /// in detached signatures.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut fingerprint = [0u8; 32];
+        shake256_parts(
+            domains::PMPT_VERIFYING_KEY,
+            &[
+                &self.group.p.to_bytes_be(),
+                &self.group.g.to_bytes_be(),
+                &self.y.to_bytes_be(),
+                &self.public_point.x.to_bytes_be(),
+                &self.public_point.y.to_bytes_be(),
+                &self.public_point.z.to_bytes_be(),
+            ],
+            &mut fingerprint,
+        );
+        fingerprint
+    }
+
+    /// Check a detached signature over everything read from `reader`. Signatures made with
+    /// another key (by fingerprint) are rejected without hashing the input.
+    pub fn verify_detached<R: Read>(
+        &self,
+        reader: R,
+        detached: &DetachedSignature,
+    ) -> std::io::Result<bool> {
+        if detached.fingerprint != self.fingerprint() {
+            return Ok(false);
+        }
+        let message = detached.message(&shake256_reader(reader)?);
+        Ok(self.verify(&message, &detached.signature))
     }
-    if n < &BigUint::from(2u32) || n % 2u32 == BigUint::zero() {
-        return false;
+
+    /// Check `signature` over `data`: recompute the commitment g^s · y^-e and its challenge.
+    pub fn verify(&self, data: &[u8], signature: &Signature) -> bool {
+        self.verify_with_attributes(data, signature, &SignatureAttributes::default())
     }
 
-    // Write n-1 as 2^s * d
-    let one = BigUint::one();
-    let two = &one + &one;
-    let n_minus_one = n - &one;
-    let mut d = n_minus_one.clone();
-    let mut s = 0u32;
+    /// Check `signature` over `data` and `attributes`, without looking at the times.
+    pub fn verify_with_attributes(
+        &self,
+        data: &[u8],
+        signature: &Signature,
+        attributes: &SignatureAttributes,
+    ) -> bool {
+        let SignatureGroup { p, q, g } = &self.group;
+        if signature.e >= *q || signature.s >= *q || self.y <= BigUint::one() || self.y >= *p {
+            return false;
+        }
+        // y has order q, so y^(q - e) = y^-e
+        let context = ModulusContext::new(p);
+        let commitment =
+            context.mul(&context.pow(g, &signature.s), &context.pow(&self.y, &(q - &signature.e)));
+        self.challenge(&commitment, attributes, data) == signature.e
+    }
 
-    while &d % &two == BigUint::zero() {
-        d /= &two;
-        s += 1;
+    /// Check `signature` over `data` and `attributes`, then that `now` (seconds since the Unix
+    /// epoch, from the caller's clock) lies in [created, expires).
+    pub fn verify_at(
+        &self,
+        data: &[u8],
+        signature: &Signature,
+        attributes: &SignatureAttributes,
+        now: u64,
+    ) -> Result<(), SignatureError> {
+        if !self.verify_with_attributes(data, signature, attributes) {
+            return Err(SignatureError::Invalid);
+        }
+        if let Some(created) = attributes.created.filter(|&created| now < created) {
+            return Err(SignatureError::NotYetValid { created, now });
+        }
+        if let Some(expires) = attributes.expires.filter(|&expires| now >= expires) {
+            return Err(SignatureError::Expired { expires, now });
+        }
+        Ok(())
     }
+}
 
-    let mut rng = rand::thread_rng();
-    'witness_loop: for _ in 0..k {
-        let a = rng.gen_biguint_range(&two, &(n_minus_one));
-        let mut x = a.modpow(&d, n);
-        if x == one || x == n_minus_one {
-            continue;
+/// A private scalar x derived from the private sphere point and its ring value with the public
+/// point, kept with the matching `VerifyingKey`.
+#[derive(Debug, Clone)]
+pub struct SigningKey {
+    secret: BigUint,
+    verifying_key: VerifyingKey,
+}
+
+impl SigningKey {
+    pub fn new(
+        group: SignatureGroup,
+        public_point: &SpherePoint,
+        private_point: &SpherePoint,
+        modulus: &BigUint,
+    ) -> Self {
+        let ring = RingMetadata::generate(public_point, private_point, modulus);
+        let q_minus_one = &group.q - 1u32;
+        let secret = group.hash_to_scalar(
+            domains::PMPT_SIGNING_KEY,
+            &[
+                &private_point.x.to_bytes_be(),
+                &private_point.y.to_bytes_be(),
+                &private_point.z.to_bytes_be(),
+                &ring.ring_value.to_bytes_be(),
+            ],
+        ) % &q_minus_one
+            + 1u32;
+        let y = ct_modpow(&group.g, &secret, &group.p, group.q.bits());
+        SigningKey {
+            secret,
+            verifying_key: VerifyingKey {
+                group,
+                public_point: public_point.clone(),
+                y,
+            },
         }
-        for _ in 0..(s - 1) {
-            x = x.modpow(&two, n);
-            if x == n_minus_one {
-                continue 'witness_loop;
+    }
+
+    /// The public key to hand to verifiers.
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.verifying_key
+    }
+
+    /// Sign `data` with a nonce derived deterministically from the secret and the data, so no
+    /// RNG is needed and a nonce is never reused across different messages.
+    pub fn sign(&self, data: &[u8]) -> Signature {
+        self.sign_with_attributes(data, &SignatureAttributes::default())
+    }
+
+    /// Sign `data` together with `attributes`, which must be presented again to verify.
+    pub fn sign_with_attributes(&self, data: &[u8], attributes: &SignatureAttributes) -> Signature {
+        let SignatureGroup { p, q, g } = &self.verifying_key.group;
+        let secret = self.secret.to_bytes_be();
+        let encoded = attributes.encode();
+        let mut counter = 0u64;
+        loop {
+            let k = self.verifying_key.group.hash_to_scalar(
+                domains::PMPT_SIGNATURE_NONCE,
+                &[&secret, &encoded, data, &counter.to_be_bytes()],
+            );
+            counter += 1;
+            if k.is_zero() {
+                continue;
             }
+            let commitment = ct_modpow(g, &k, p, q.bits());
+            let e = self.verifying_key.challenge(&commitment, attributes, data);
+            let s = (k + &e * &self.secret) % q;
+            return Signature { e, s };
         }
-        return false;
-    }
-    true
-}
-
-/// --- Generate Large Prime ---
-
-/// --- Main Function ---
-fn main() {
-    let mut rng = ChaCha20Rng::from_entropy();
- 
-    // Generate a large random prime
-    let secret_bits = 1024;
-    let secret = generate_large_prime(secret_bits);
-    println!("N: {}", secret);
-    let modulus_bits = secret_bits * 2;
-    let modulus = generate_large_prime(modulus_bits);                                                                          let threshold = 6;
-    let shares_count = 6;
-    let threshold = 3;
-    let shares = shamir_split_shares(&secret, threshold, shares_count, &modulus);
-    // Calculate padding length based on modulus size
-    let pad_length = ((modulus.bits() + 7) / 8) as usize; // Adjusted padding length
-    println!("Padding Length: {} bytes", pad_length);
-
-    // Create SpherePoints using DLP keys
-    let private_point = SpherePoint {
-        x: shares[0].1.clone(),
-        y: shares[1].1.clone(),
-        z: shares[2].1.clone(),
-    };
-    let public_point = SpherePoint {
-        x: shares[3].1.clone(),
-        y: shares[4].1.clone(),
-        z: shares[5].1.clone(),
-     };
-    verify_share_primality(&shares);
-    println!("Private Point: {:?}", private_point);
-    println!("Public Point: {:?}", public_point);
-    let ring_metadata = RingMetadata::generate(&public_point, &private_point, &modulus);
-    let ring_valid = ring_metadata.validate(&public_point, &private_point, &modulus);
-    let reconstructed_secret = shamir_reconstruct(&shares[..threshold], &modulus, &secret, threshold);
-    println!("Public N Reconstucted: {}", reconstructed_secret);
-    if ring_valid {
-        println!("Ring metadata validation successful (key generation step).");
-    } else {
-        panic!("Ring metadata validation failed (key generation step).");
     }
 
-    // Generate S-Box
-    let mut rng_sbox = ChaCha20Rng::from_entropy();
-    let sbox = DynamicSBox::new(&mut rng_sbox);
+    /// A detached signature over everything read from `reader`, made at `timestamp` (seconds
+    /// since the Unix epoch). The input is hashed incrementally, so it can be arbitrarily large.
+    pub fn sign_detached<R: Read>(
+        &self,
+        reader: R,
+        timestamp: u64,
+    ) -> std::io::Result<DetachedSignature> {
+        let mut detached = DetachedSignature {
+            fingerprint: self.verifying_key.fingerprint(),
+            timestamp,
+            signature: Signature {
+                e: BigUint::zero(),
+                s: BigUint::zero(),
+            },
+        };
+        let message = detached.message(&shake256_reader(reader)?);
+        detached.signature = self.sign(&message);
+        Ok(detached)
+    }
+}
 
-    // --- PMPT-HMAC Integration ---
-    let pmpt_hmac = PmptHmac::new(
-        public_point.clone(),
-        private_point.clone(),
-        sbox.clone(),
-        pad_length,
-        modulus.clone(),
-    );
+/// A signature stored apart from the data it covers, with the fingerprint of the signing key
+/// and the signing time.
+///
+/// What is signed is the SHAKE256 digest of the data followed by the fingerprint and
+/// timestamp, so neither can be altered without invalidating the signature. The text form is
+///
+/// ```text
+/// -----BEGIN PMPT SIGNATURE-----
+/// fingerprint: <64 hex digits>
+/// timestamp: <seconds since the Unix epoch>
+/// e: <decimal>
+/// s: <decimal>
+/// -----END PMPT SIGNATURE-----
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetachedSignature {
+    pub fingerprint: [u8; 32],
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+impl DetachedSignature {
+    const BEGIN: &'static str = "-----BEGIN PMPT SIGNATURE-----";
+    const END: &'static str = "-----END PMPT SIGNATURE-----";
+
+    fn message(&self, digest: &[u8; 64]) -> Vec<u8> {
+        let mut message = digest.to_vec();
+        message.extend_from_slice(&self.fingerprint);
+        message.extend_from_slice(&self.timestamp.to_be_bytes());
+        message
+    }
+}
+
+impl std::fmt::Display for DetachedSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", Self::BEGIN)?;
+        writeln!(f, "fingerprint: {}", hex::encode(self.fingerprint))?;
+        writeln!(f, "timestamp: {}", self.timestamp)?;
+        writeln!(f, "e: {}", self.signature.e)?;
+        writeln!(f, "s: {}", self.signature.s)?;
+        writeln!(f, "{}", Self::END)
+    }
+}
+
+impl std::str::FromStr for DetachedSignature {
+    type Err = DetachedSignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(Self::BEGIN) {
+            return Err(DetachedSignatureError::Missing("BEGIN"));
+        }
+        let mut field = |name: &'static str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|rest| rest.strip_prefix(':'))
+                .map(str::trim)
+                .ok_or(DetachedSignatureError::Missing(name))
+        };
+        let fingerprint = hex::decode(field("fingerprint")?)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(DetachedSignatureError::Invalid("fingerprint"))?;
+        let timestamp = field("timestamp")?
+            .parse()
+            .map_err(|_| DetachedSignatureError::Invalid("timestamp"))?;
+        let e = field("e")?
+            .parse()
+            .map_err(|_| DetachedSignatureError::Invalid("e"))?;
+        let s = field("s")?
+            .parse()
+            .map_err(|_| DetachedSignatureError::Invalid("s"))?;
+        if lines.next() != Some(Self::END) {
+            return Err(DetachedSignatureError::Missing("END"));
+        }
+        Ok(DetachedSignature {
+            fingerprint,
+            timestamp,
+            signature: Signature { e, s },
+        })
+    }
+}
 
-    let data = b"Example data for PMPT-HMAC";
-    println!("Signing data: {:?}", String::from_utf8_lossy(data));
+/// --- Key Generation ---
+/// Public/private sphere points derived from Shamir shares of a large prime secret.
+#[derive(Debug, Clone)]
+pub struct KeyPair {
+    pub public_key: SpherePoint,
+    pub private_key: SpherePoint,
+    pub modulus: BigUint,
+    pub pad_length: usize,
+}
+
+impl KeyPair {
+    /// Generate a key pair from a `secret_bits` prime split over a modulus twice that size.
+    pub fn generate<R: Rng + ?Sized>(secret_bits: usize, rng: &mut R) -> Self {
+        KeyPair::generate_with_config(secret_bits, &PrimalityConfig::default(), rng)
+    }
 
-    // Sign the data
-    let signature = pmpt_hmac.sign(data).expect("Signing failed");
-    println!("Generated Signature: {:?}", signature);
+    /// Like `generate`, testing the secret and modulus candidates as `config` prescribes.
+    #[instrument(level = "info", name = "key_generation", skip(config, rng))]
+    pub fn generate_with_config<R: Rng + ?Sized>(
+        secret_bits: usize,
+        config: &PrimalityConfig,
+        rng: &mut R,
+    ) -> Self {
+        // The secret prime is key material, so its primality tests must not leak it
+        let secret_config = PrimalityConfig {
+            constant_time: true,
+            ..*config
+        };
+        let secret = generate_large_prime_with_config(secret_bits, &secret_config, rng);
+        let modulus = generate_large_prime_with_config(secret_bits * 2, config, rng);
+        let shares = shamir_split_shares_with(&secret, 3, 6, &modulus, rng);
+
+        // Calculate padding length based on modulus size
+        let pad_length = pad_length_for(&modulus);
+
+        let private_key = SpherePoint::new(
+            shares[0].1.clone(),
+            shares[1].1.clone(),
+            shares[2].1.clone(),
+        );
+        let public_key = SpherePoint::new(
+            shares[3].1.clone(),
+            shares[4].1.clone(),
+            shares[5].1.clone(),
+        );
+
+        KeyPair {
+            public_key,
+            private_key,
+            modulus,
+            pad_length,
+        }
+    }
 
-    // Verify the signature
-    let is_valid = pmpt_hmac.verify(data, &signature).expect("Verification failed");
-    println!("Verification Result: {}", is_valid);
-    // --- PMPT Encryption and Decryption ---
-    let mut plaintext = String::new();
+    /// Like `generate_with_config`, drawing every random choice from `entropy`.
+    pub fn generate_with_entropy<E: EntropySource + ?Sized>(
+        secret_bits: usize,
+        config: &PrimalityConfig,
+        entropy: &mut E,
+    ) -> Self {
+        KeyPair::generate_with_config(secret_bits, config, &mut EntropyRng(entropy))
+    }
 
-    println!("Enter your plaintext: ");
+    /// The signing key for this pair in `group`.
+    pub fn signing_key(&self, group: SignatureGroup) -> SigningKey {
+        SigningKey::new(group, &self.public_key, &self.private_key, &self.modulus)
+    }
+}
 
-    // Read input from the user
-    io::stdin()
-        .read_line(&mut plaintext)
-        .expect("Failed to read input");
+/// --- Self Test ---
+/// Outcome of each check in `self_test`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Encrypting and decrypting a known message gives it back
+    pub round_trip: bool,
+    /// A PMPT-HMAC tag verifies over its data and not over altered data
+    pub sign_verify: bool,
+    /// Decrypting a ciphertext with an altered body fails
+    pub tamper_rejected: bool,
+    /// The S-box is a permutation with a matching inverse
+    pub sbox_bijective: bool,
+}
 
-    // Remove the trailing newline from the input
-    let plaintext = plaintext.trim();
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.round_trip && self.sign_verify && self.tamper_rejected && self.sbox_bijective
+    }
+}
 
-    // Print the input back to the user
-    println!("Original Plaintext: {}", plaintext);
+/// Fixed seed for the self-test S-box and nonce, so the test is reproducible for a given key pair.
+const SELF_TEST_SEED: u64 = 0x504d_5054;
 
+/// A power-on self test of `keys`: an encrypt/decrypt round trip, a sign/verify, rejection of
+/// a tampered ciphertext and an S-box bijection check, each reported separately.
+pub fn self_test(keys: &KeyPair) -> SelfTestReport {
+    const MESSAGE: &str = "PMPT self test";
+    let mut rng = ChaCha20Rng::seed_from_u64(SELF_TEST_SEED);
+    let sbox = DynamicSBox::new(&mut rng);
     let ciphertext = encrypt(
-        plaintext,
-        &public_point,
-        &private_point,
+        MESSAGE,
+        &keys.public_key,
+        &keys.private_key,
         &sbox,
-        pad_length,
-        &modulus,
+        keys.pad_length,
+        &keys.modulus,
+        &mut rng,
     )
-    .expect("Encryption failed");
-
-    println!("Ciphertext: {:?}", ciphertext);
-    // Perform ring check on the ciphertext
-    let substituted_point = SpherePoint {
-        x: ciphertext.x_s.clone(),
-        y: ciphertext.y_s.clone(),
-        z: ciphertext.z_s.clone(),
+    .ok();
+    let decrypt_with = |ciphertext: &Ciphertext| {
+        decrypt(
+            ciphertext,
+            &keys.public_key,
+            &keys.private_key,
+            &sbox,
+            keys.pad_length,
+            &keys.modulus,
+        )
     };
-    let ring_metadata = RingMetadata::generate(&public_point, &substituted_point, &modulus);
-    let ring_valid = ring_metadata.validate(&public_point, &substituted_point, &modulus);
-    if ring_valid {
-        println!("Ring metadata validation successful (encryption step).");
-    } else {
-        panic!("Ring metadata validation failed (encryption step).");
+    let round_trip = ciphertext
+        .as_ref()
+        .is_some_and(|ciphertext| decrypt_with(ciphertext).is_ok_and(|p| p == MESSAGE));
+    let tamper_rejected = ciphertext.is_some_and(|mut ciphertext| {
+        ciphertext.x_s += 1u32;
+        decrypt_with(&ciphertext).is_err()
+    });
+
+    let hmac = PmptHmac::new(
+        keys.public_key.clone(),
+        keys.private_key.clone(),
+        sbox.clone(),
+        keys.pad_length,
+        keys.modulus.clone(),
+    );
+    let sign_verify = hmac.sign(MESSAGE.as_bytes()).is_ok_and(|tag| {
+        matches!(hmac.verify(MESSAGE.as_bytes(), &tag), Ok(true))
+            && matches!(hmac.verify(b"PMPT self-test", &tag), Ok(false))
+    });
+
+    SelfTestReport {
+        round_trip,
+        sign_verify,
+        tamper_rejected,
+        sbox_bijective: sbox.is_bijective(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_verifies_with_public_key_only() {
+        let mut rng = ChaCha20Rng::seed_from_u64(11);
+        let config = PrimalityConfig::default();
+        let keys = KeyPair::generate_with_config(64, &config, &mut rng);
+        let group = SignatureGroup::generate(128, &config, &mut rng);
+        let signing_key = keys.signing_key(group);
+        let signature = signing_key.sign(b"message");
+
+        let verifying_key = signing_key.verifying_key().clone();
+        assert!(verifying_key.verify(b"message", &signature));
+        assert!(!verifying_key.verify(b"massage", &signature));
+        let forged = Signature {
+            e: signature.e.clone(),
+            s: (&signature.s + 1u32) % &verifying_key.group.q,
+        };
+        assert!(!verifying_key.verify(b"message", &forged));
+
+        // The same group with another key pair's public point rejects the signature
+        let other = KeyPair::generate_with_config(64, &config, &mut rng);
+        let other_key = VerifyingKey {
+            public_point: other.public_key,
+            ..verifying_key
+        };
+        assert!(!other_key.verify(b"message", &signature));
+    }
+
+    #[test]
+    fn test_detached_signature() {
+        let mut rng = ChaCha20Rng::seed_from_u64(12);
+        let config = PrimalityConfig::default();
+        let keys = KeyPair::generate_with_config(64, &config, &mut rng);
+        let signing_key = keys.signing_key(SignatureGroup::generate(128, &config, &mut rng));
+        let verifying_key = signing_key.verifying_key();
+
+        // Larger than one read chunk
+        let data: Vec<u8> = (0..3 * READ_CHUNK + 5).map(|i| i as u8).collect();
+        let detached = signing_key.sign_detached(&data[..], 1_700_000_000).unwrap();
+        let parsed: DetachedSignature = detached.to_string().parse().unwrap();
+        assert_eq!(parsed, detached);
+        assert!(verifying_key.verify_detached(&data[..], &parsed).unwrap());
+        assert!(!verifying_key.verify_detached(&data[1..], &parsed).unwrap());
+        let backdated = DetachedSignature { timestamp: 1_600_000_000, ..parsed };
+        assert!(!verifying_key.verify_detached(&data[..], &backdated).unwrap());
+
+        assert_eq!(shake256_reader(&data[..]).unwrap(), shake256_digest(&data));
+        assert_eq!(
+            "garbage".parse::<DetachedSignature>(),
+            Err(DetachedSignatureError::Missing("BEGIN"))
+        );
+    }
+
+    #[test]
+    fn test_signature_attributes() {
+        let mut rng = ChaCha20Rng::seed_from_u64(13);
+        let config = PrimalityConfig::default();
+        let keys = KeyPair::generate_with_config(64, &config, &mut rng);
+        let signing_key = keys.signing_key(SignatureGroup::generate(128, &config, &mut rng));
+        let verifying_key = signing_key.verifying_key();
+
+        let attributes = SignatureAttributes {
+            created: Some(1000),
+            expires: Some(2000),
+            context: Some("login".to_string()),
+        };
+        let signature = signing_key.sign_with_attributes(b"token", &attributes);
+        assert_eq!(verifying_key.verify_at(b"token", &signature, &attributes, 1500), Ok(()));
+        assert_eq!(
+            verifying_key.verify_at(b"token", &signature, &attributes, 2000),
+            Err(SignatureError::Expired { expires: 2000, now: 2000 })
+        );
+        assert_eq!(
+            verifying_key.verify_at(b"token", &signature, &attributes, 999),
+            Err(SignatureError::NotYetValid { created: 1000, now: 999 })
+        );
+        // Extending the expiry or changing the context breaks the signature
+        let extended = SignatureAttributes { expires: Some(3000), ..attributes.clone() };
+        assert_eq!(
+            verifying_key.verify_at(b"token", &signature, &extended, 2500),
+            Err(SignatureError::Invalid)
+        );
+        let other_context = SignatureAttributes { context: Some("admin".to_string()), ..attributes };
+        assert!(!verifying_key.verify_with_attributes(b"token", &signature, &other_context));
+        assert!(!verifying_key.verify(b"token", &signature));
+    }
+
+    #[test]
+    fn test_key_commitment() {
+        let mut rng = ChaCha20Rng::seed_from_u64(14);
+        let config = PrimalityConfig::default();
+        let keys = KeyPair::generate_with_config(64, &config, &mut rng);
+        let other = KeyPair::generate_with_config(64, &config, &mut rng);
+        let sbox = DynamicSBox::new(&mut rng);
+        let decrypt_with = |keys: &KeyPair, sbox: &DynamicSBox, ciphertext: &Ciphertext| {
+            decrypt(ciphertext, &keys.public_key, &keys.private_key, sbox, keys.pad_length, &keys.modulus)
+        };
+
+        let ciphertext = encrypt(
+            "hello",
+            &keys.public_key,
+            &keys.private_key,
+            &sbox,
+            keys.pad_length,
+            &keys.modulus,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(decrypt_with(&keys, &sbox, &ciphertext).unwrap(), "hello");
+        assert!(matches!(
+            decrypt_with(&other, &sbox, &ciphertext),
+            Err(DecryptionError::WrongKey)
+        ));
+        // The S-box is part of the key too
+        assert!(matches!(
+            decrypt_with(&keys, &DynamicSBox::new(&mut rng), &ciphertext),
+            Err(DecryptionError::WrongKey)
+        ));
+        let mut tampered = ciphertext.clone();
+        tampered.x_s += 1u32;
+        assert!(matches!(decrypt_with(&keys, &sbox, &tampered), Err(DecryptionError::WrongKey)));
+
+        // A body moved along the kernel of the public ring map keeps r, but not the commitment
+        let (public, m) = (&keys.public_key, &keys.modulus);
+        let mut shifted = ciphertext.clone();
+        shifted.x_s = (&shifted.x_s + &public.y) % m;
+        shifted.y_s = (&shifted.y_s + m - &public.x % m) % m;
+        let ring = (&public.x * &shifted.x_s + &public.y * &shifted.y_s + &public.z * &shifted.z_s)
+            % m;
+        assert_eq!(ring, ciphertext.r);
+        assert!(matches!(decrypt_with(&keys, &sbox, &shifted), Err(DecryptionError::WrongKey)));
+    }
+
+    #[test]
+    fn test_cipher_rounds() {
+        let mut rng = ChaCha20Rng::seed_from_u64(17);
+        let keys = KeyPair::generate_with_config(64, &PrimalityConfig::default(), &mut rng);
+        let sbox = DynamicSBox::new(&mut rng);
+        let mut nonces = ChaCha20Rng::seed_from_u64(19);
+        let mut encrypt_with = |plaintext: &str, params: CipherParams| {
+            let KeyPair { public_key, private_key, modulus, pad_length } = &keys;
+            let (sbox, pad_length) = (&sbox, *pad_length);
+            let rng = &mut nonces;
+            encrypt_with_params(
+                plaintext, public_key, private_key, sbox, pad_length, modulus, params, rng,
+            )
+            .unwrap()
+        };
+        let decrypt_with = |ciphertext: &Ciphertext| {
+            let KeyPair { public_key, private_key, modulus, pad_length } = &keys;
+            decrypt(ciphertext, public_key, private_key, &sbox, *pad_length, modulus)
+        };
+
+        let legacy = encrypt_with("diffusion", CipherParams::single_round());
+        assert_eq!(legacy.version, CIPHERTEXT_VERSION_SINGLE_ROUND);
+        assert_eq!(decrypt_with(&legacy).unwrap(), "diffusion");
+
+        let rounds = encrypt_with("diffusion", CipherParams::default());
+        assert_eq!((rounds.version, rounds.rounds), (CIPHERTEXT_VERSION_ROUNDS, 4));
+        assert_eq!(decrypt_with(&rounds).unwrap(), "diffusion");
+
+        // One changed plaintext byte changes most ciphertext bytes, unlike the single round
+        let changed_bytes = |a: &Ciphertext, b: &Ciphertext| {
+            let bytes = |c: &Ciphertext| {
+                let point = SpherePoint::new(c.x_s.clone(), c.y_s.clone(), c.z_s.clone());
+                point_to_bytes(&point, keys.pad_length).unwrap()
+            };
+            bytes(a).iter().zip(bytes(b)).filter(|(x, y)| **x != *y).count()
+        };
+        let legacy_changed = encrypt_with("diffusioN", CipherParams::single_round());
+        assert_eq!(changed_bytes(&legacy, &legacy_changed), 1);
+        let KeyPair { public_key, private_key, modulus, pad_length } = &keys;
+        let (params, nonce) = (CipherParams::default(), rounds.nonce);
+        let rounds_changed = encrypt_with_nonce(
+            "diffusioN", public_key, private_key, &sbox, *pad_length, modulus, params, nonce,
+        )
+        .unwrap();
+        assert!(changed_bytes(&rounds, &rounds_changed) > keys.pad_length * 3 / 2);
+
+        let keystream_params = CipherParams {
+            mask: MaskMode::Keystream,
+            ..CipherParams::default()
+        };
+        let keystream = encrypt_with("diffusion", keystream_params);
+        assert_eq!(keystream.version, CIPHERTEXT_VERSION_KEYSTREAM);
+        assert_eq!(decrypt_with(&keystream).unwrap(), "diffusion");
+        assert_ne!(keystream.x_s, rounds.x_s);
+
+        // Every message draws a fresh nonce that keys its rounds, so the same plaintext never
+        // encrypts the same way twice, and a nonce moved between ciphertexts is refused
+        let again = encrypt_with("diffusion", keystream_params);
+        assert_ne!(again.nonce, keystream.nonce);
+        assert!(changed_bytes(&again, &keystream) > keys.pad_length * 3 / 2);
+        assert_eq!(decrypt_with(&again).unwrap(), "diffusion");
+        let mut swapped = again.clone();
+        swapped.nonce = keystream.nonce;
+        assert!(matches!(decrypt_with(&swapped), Err(DecryptionError::WrongKey)));
+
+        let single_keystream = CipherParams {
+            rounds: 0,
+            mask: MaskMode::Keystream,
+        };
+        let rng = &mut rng;
+        let result = encrypt_with_params(
+            "x", public_key, private_key, &sbox, *pad_length, modulus, single_keystream, rng,
+        );
+        assert!(matches!(result, Err(EncryptionError::UnsupportedParams)));
+
+        let mut wide = rounds.clone();
+        wide.pad_length += 1;
+        assert!(matches!(
+            decrypt_with(&wide),
+            Err(DecryptionError::PadLengthMismatch { found, .. }) if found == keys.pad_length + 1
+        ));
+        let mut overflow = rounds.clone();
+        overflow.x_s = BigUint::one() << (8 * keys.pad_length);
+        assert!(matches!(decrypt_with(&overflow), Err(DecryptionError::InvalidCiphertext)));
+        let short = keys.pad_length - 1;
+        assert!(matches!(
+            encrypt_with_params(
+                "x", public_key, private_key, &sbox, short, modulus, keystream_params, rng,
+            ),
+            Err(EncryptionError::InvalidPadLength { pad_length, .. }) if pad_length == short
+        ));
+
+        let mut unknown = rounds.clone();
+        unknown.version = 4;
+        assert!(matches!(decrypt_with(&unknown), Err(DecryptionError::InvalidCiphertext)));
+
+        // The header is committed to, so it cannot be altered or downgraded
+        let mut fewer = rounds.clone();
+        fewer.rounds = 3;
+        assert!(matches!(decrypt_with(&fewer), Err(DecryptionError::WrongKey)));
+        let mut downgraded = keystream.clone();
+        downgraded.version = CIPHERTEXT_VERSION_ROUNDS;
+        assert!(matches!(decrypt_with(&downgraded), Err(DecryptionError::WrongKey)));
+        let mut endless = rounds.clone();
+        endless.rounds = u32::MAX;
+        assert!(matches!(decrypt_with(&endless), Err(DecryptionError::InvalidCiphertext)));
+        let mut bytes = rounds.to_bytes().unwrap();
+        bytes[1..5].copy_from_slice(&(MAX_ROUNDS + 1).to_be_bytes());
+        assert!(Ciphertext::from_bytes(&bytes).is_err());
+        let too_many = CipherParams { rounds: MAX_ROUNDS + 1, ..CipherParams::default() };
+        let result = encrypt_with_params(
+            "x", public_key, private_key, &sbox, *pad_length, modulus, too_many, rng,
+        );
+        assert!(matches!(result, Err(EncryptionError::UnsupportedParams)));
+    }
+
+    #[test]
+    fn test_fixed_width_encoding() {
+        assert_eq!(to_fixed_width(&BigUint::zero(), 3), Ok(vec![0, 0, 0]));
+        assert_eq!(to_fixed_width(&BigUint::from(0x0102u32), 3), Ok(vec![0, 1, 2]));
+        assert_eq!(
+            to_fixed_width(&BigUint::from(0x01_0000u32), 2),
+            Err(CoordinateOverflow { needed: 3, pad_length: 2 })
+        );
+
+        // An oversized tag is rejected instead of panicking
+        let mut rng = ChaCha20Rng::seed_from_u64(18);
+        let keys = KeyPair::generate_with_config(64, &PrimalityConfig::default(), &mut rng);
+        let hmac = PmptHmac::new(
+            keys.public_key.clone(),
+            keys.private_key.clone(),
+            DynamicSBox::new(&mut rng),
+            keys.pad_length,
+            keys.modulus.clone(),
+        );
+        let mut tag = hmac.sign(b"data").unwrap();
+        assert!(hmac.verify(b"data", &tag).unwrap());
+        let bytes = hmac.signature_bytes(&tag).unwrap();
+        assert_eq!(bytes.len(), hmac.signature_len());
+        assert_eq!(hmac.signature_from_bytes(&bytes), Some(tag.clone()));
+        assert_eq!(hmac.signature_from_bytes(&bytes[1..]), None);
+
+        // Encoded ciphertexts have the length announced for their pad, whatever the rounds
+        let sbox = DynamicSBox::new(&mut rng);
+        let (public, private, pad) = (&keys.public_key, &keys.private_key, keys.pad_length);
+        for params in [CipherParams::single_round(), CipherParams::default()] {
+            let modulus = &keys.modulus;
+            let rng = &mut rng;
+            let ciphertext =
+                encrypt_with_params("sized", public, private, &sbox, pad, modulus, params, rng);
+            let ciphertext = ciphertext.unwrap();
+            let bytes = ciphertext.to_bytes().unwrap();
+            assert_eq!(bytes.len(), Ciphertext::encoded_len(pad));
+            let decoded = Ciphertext::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), bytes);
+            let plaintext = decrypt(&decoded, public, private, &sbox, pad, &keys.modulus);
+            assert_eq!(plaintext.unwrap(), "sized");
+            assert!(matches!(
+                Ciphertext::from_bytes(&bytes[..bytes.len() - 1]),
+                Err(DecryptionError::Malformed(MalformedInput::Truncated { missing: 1, .. }))
+            ));
+        }
+
+        tag.y <<= 8 * keys.pad_length;
+        assert!(!hmac.verify(b"data", &tag).unwrap());
+        let point = SpherePoint::new(BigUint::zero(), tag.y, BigUint::zero());
+        assert!(matches!(
+            map_sphere_point_to_plaintext(&point, keys.pad_length),
+            Err(DecryptionError::CoordinateOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_self_test() {
+        let mut rng = ChaCha20Rng::seed_from_u64(15);
+        let keys = KeyPair::generate_with_config(64, &PrimalityConfig::default(), &mut rng);
+        assert!(self_test(&keys).passed());
+
+        // A pad length too short for the modulus is refused
+        let mut broken = keys.clone();
+        broken.pad_length = 1;
+        let report = self_test(&broken);
+        assert!(!report.passed() && report.sbox_bijective);
+
+        let mut sbox = DynamicSBox::new(&mut rng);
+        sbox.sbox[0] = sbox.sbox[1];
+        assert!(!sbox.is_bijective());
+    }
+
+    #[test]
+    fn test_sbox_metrics() {
+        let mut identity = [0u8; 256];
+        for (i, entry) in identity.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        let identity = DynamicSBox { sbox: identity, inverse_sbox: identity };
+        // The identity is linear: every difference maps to itself
+        assert_eq!(
+            identity.metrics(),
+            SBoxMetrics { nonlinearity: 0, differential_uniformity: 256, fixed_points: 256 }
+        );
+
+        let mut rng = ChaCha20Rng::seed_from_u64(16);
+        let strong = DynamicSBox::new_strong(&mut rng, 94, 100).unwrap();
+        let metrics = strong.metrics();
+        assert!(metrics.nonlinearity >= 94);
+        assert!(metrics.differential_uniformity <= 16);
+        assert_eq!(DynamicSBox::new_strong(&mut rng, 120, 3), None);
+        assert_eq!(DynamicSBox::new_strong(&mut rng, 0, 0), None);
+
+        // A derived S-Box is a fixed permutation of its key
+        let keys = KeyPair::generate(64, &mut rng);
+        let derived = DynamicSBox::derive(&keys.private_key);
+        assert!(derived.is_bijective());
+        assert_eq!(derived, DynamicSBox::derive(&keys.private_key));
+        assert_ne!(derived, DynamicSBox::derive(&keys.public_key));
     }
-    let decrypted_plaintext = decrypt(
-        &ciphertext,
-        &public_point,
-        &private_point,
-        &sbox,
-        pad_length,
-        &modulus,
-    )
-    .expect("Decryption failed");
-    println!("Decrypted Plaintext: {}", decrypted_plaintext);
-    assert_eq!(plaintext, decrypted_plaintext);
-    println!("Encryption and decryption are consistent.");
 }
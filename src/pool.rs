@@ -0,0 +1,347 @@
+//! Candidate pools for the search, including a compact on-disk format for very large ones.
+//!
+//! A pool file is the 8-byte magic `UPPOOL01`, the entry count as a little-endian u64, and
+//! then the primes in increasing order as LEB128 varint deltas (the first delta is from 0).
+//! Gaps between primes below 2^64 are small, so most entries take one or two bytes. Files are
+//! memory-mapped rather than read, and an index of every `BLOCK`-th entry gives random access.
+
+use memmap2::Mmap;
+use num_bigint::BigInt;
+use num_traits::Signed;
+use thiserror::Error;
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"UPPOOL01";
+const HEADER_LEN: usize = 16;
+
+/// Entries between random-access index points.
+const BLOCK: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum PoolError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("not a prime pool file")]
+    BadMagic,
+    #[error("pool file is truncated or corrupt")]
+    Corrupt,
+    #[error("pool entries must be strictly increasing, {next} follows {previous}")]
+    NotIncreasing { previous: u64, next: u64 },
+}
+
+/// Values the search draws x, y and z from, by index.
+pub trait CandidatePool: Sync {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Entry `index`, which must be less than `len()`.
+    fn get(&self, index: usize) -> Cow<'_, BigInt>;
+
+    /// Whether no entry is negative, so the search may take its unsigned fast path.
+    fn is_nonnegative(&self) -> bool;
+}
+
+impl CandidatePool for [BigInt] {
+    fn len(&self) -> usize {
+        <[BigInt]>::len(self)
+    }
+
+    fn get(&self, index: usize) -> Cow<'_, BigInt> {
+        Cow::Borrowed(&self[index])
+    }
+
+    fn is_nonnegative(&self) -> bool {
+        self.iter().all(|v| !v.is_negative())
+    }
+}
+
+impl CandidatePool for Vec<BigInt> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, index: usize) -> Cow<'_, BigInt> {
+        Cow::Borrowed(&self[index])
+    }
+
+    fn is_nonnegative(&self) -> bool {
+        self.as_slice().is_nonnegative()
+    }
+}
+
+impl<P: CandidatePool + Send + ?Sized> CandidatePool for Arc<P> {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn get(&self, index: usize) -> Cow<'_, BigInt> {
+        (**self).get(index)
+    }
+
+    fn is_nonnegative(&self) -> bool {
+        (**self).is_nonnegative()
+    }
+}
+
+/// The candidates a search draws x, y and z from: any `CandidatePool` for all three, or
+/// `VariablePools` for one each.
+pub trait TupleSpace: Sync {
+    /// Candidates for x, y and z.
+    fn dims(&self) -> [usize; 3];
+
+    /// Candidate `index` of `variable` (0 for x, 1 for y, 2 for z).
+    fn value(&self, variable: usize, index: usize) -> Cow<'_, BigInt>;
+
+    /// Whether no candidate is negative, so the search may take its unsigned fast path.
+    fn all_nonnegative(&self) -> bool;
+
+    /// Tuples in the space.
+    fn tuples(&self) -> u64 {
+        self.dims().iter().map(|&len| len as u64).product()
+    }
+}
+
+impl<P: CandidatePool + ?Sized> TupleSpace for P {
+    fn dims(&self) -> [usize; 3] {
+        [self.len(); 3]
+    }
+
+    fn value(&self, _variable: usize, index: usize) -> Cow<'_, BigInt> {
+        self.get(index)
+    }
+
+    fn all_nonnegative(&self) -> bool {
+        self.is_nonnegative()
+    }
+}
+
+/// Separate candidates for x, y and z, for forms whose variables play different roles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariablePools {
+    pub x: Vec<BigInt>,
+    pub y: Vec<BigInt>,
+    pub z: Vec<BigInt>,
+}
+
+impl VariablePools {
+    fn pool(&self, variable: usize) -> &[BigInt] {
+        match variable {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+}
+
+impl TupleSpace for VariablePools {
+    fn dims(&self) -> [usize; 3] {
+        [self.x.len(), self.y.len(), self.z.len()]
+    }
+
+    fn value(&self, variable: usize, index: usize) -> Cow<'_, BigInt> {
+        Cow::Borrowed(&self.pool(variable)[index])
+    }
+
+    fn all_nonnegative(&self) -> bool {
+        (0..3).all(|variable| self.pool(variable).is_nonnegative())
+    }
+}
+
+/// Write `primes` (strictly increasing) to a pool file at `path`. Returns the entry count.
+pub fn write_pool<I: IntoIterator<Item = u64>>(path: &Path, primes: I) -> Result<u64, PoolError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&0u64.to_le_bytes())?;
+
+    let mut count = 0u64;
+    let mut previous = 0u64;
+    let mut buffer = [0u8; 10];
+    for prime in primes {
+        if count > 0 && prime <= previous {
+            return Err(PoolError::NotIncreasing { previous, next: prime });
+        }
+        let len = encode_varint(prime - previous, &mut buffer);
+        out.write_all(&buffer[..len])?;
+        previous = prime;
+        count += 1;
+    }
+
+    // The count is only known at the end, so patch it into the header
+    let mut file = out.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(MAGIC.len() as u64))?;
+    file.write_all(&count.to_le_bytes())?;
+    file.sync_all()?;
+    Ok(count)
+}
+
+/// Sieve the primes in `lo..=hi` into a pool file at `path`. Returns the entry count.
+pub fn sieve_pool(path: &Path, lo: u64, hi: u64) -> Result<u64, PoolError> {
+    let sieve = primal::Sieve::new(hi as usize);
+    let primes = sieve
+        .primes_from(lo as usize)
+        .take_while(|&p| p as u64 <= hi)
+        .map(|p| p as u64);
+    write_pool(path, primes)
+}
+
+/// A memory-mapped pool file.
+pub struct MappedPool {
+    map: Mmap,
+    len: usize,
+    /// Byte offset of every `BLOCK`-th entry and the entry preceding it (0 for the first)
+    index: Vec<(usize, u64)>,
+}
+
+impl MappedPool {
+    /// Map `path` and index it, checking that its entries decode to the recorded count.
+    pub fn open(path: &Path) -> Result<Self, PoolError> {
+        let file = File::open(path)?;
+        // Safety: the pool is treated as read-only; truncating the file while it is mapped
+        // is a usage error, as for any memory-mapped input
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_LEN || &map[..MAGIC.len()] != MAGIC {
+            return Err(PoolError::BadMagic);
+        }
+        let len = u64::from_le_bytes(map[MAGIC.len()..HEADER_LEN].try_into().unwrap());
+        let len = usize::try_from(len).map_err(|_| PoolError::Corrupt)?;
+
+        let mut index = Vec::with_capacity(len.div_ceil(BLOCK));
+        let mut offset = HEADER_LEN;
+        let mut value = 0u64;
+        for i in 0..len {
+            if i % BLOCK == 0 {
+                index.push((offset, value));
+            }
+            let (delta, used) = decode_varint(&map[offset..]).ok_or(PoolError::Corrupt)?;
+            value = value.checked_add(delta).ok_or(PoolError::Corrupt)?;
+            offset += used;
+        }
+        if offset != map.len() {
+            return Err(PoolError::Corrupt);
+        }
+        Ok(MappedPool { map, len, index })
+    }
+
+    /// Entry `index` as a u64.
+    pub fn get_u64(&self, index: usize) -> u64 {
+        assert!(index < self.len, "pool index {} out of range", index);
+        let (mut offset, mut value) = self.index[index / BLOCK];
+        for _ in 0..=index % BLOCK {
+            // Every entry was decoded successfully when the pool was opened
+            let (delta, used) = decode_varint(&self.map[offset..]).unwrap();
+            value += delta;
+            offset += used;
+        }
+        value
+    }
+
+    /// The entries in order.
+    pub fn iter(&self) -> PoolIter<'_> {
+        PoolIter {
+            bytes: &self.map[HEADER_LEN..],
+            value: 0,
+            remaining: self.len,
+        }
+    }
+}
+
+impl CandidatePool for MappedPool {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> Cow<'_, BigInt> {
+        Cow::Owned(BigInt::from(self.get_u64(index)))
+    }
+
+    fn is_nonnegative(&self) -> bool {
+        true
+    }
+}
+
+/// Sequential decoder over a `MappedPool`.
+pub struct PoolIter<'a> {
+    bytes: &'a [u8],
+    value: u64,
+    remaining: usize,
+}
+
+impl Iterator for PoolIter<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (delta, used) = decode_varint(self.bytes)?;
+        self.bytes = &self.bytes[used..];
+        self.value += delta;
+        self.remaining -= 1;
+        Some(self.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+fn encode_varint(mut value: u64, buffer: &mut [u8; 10]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer[len] = byte;
+            return len + 1;
+        }
+        buffer[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// The varint at the start of `bytes` and its length, or `None` if it is cut off or too long.
+fn decode_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_round_trip() {
+        let path = std::env::temp_dir().join(format!("universal-primes-{}.pool", std::process::id()));
+        let count = sieve_pool(&path, 3, 20_000).unwrap();
+        let pool = MappedPool::open(&path).unwrap();
+        let expected: Vec<u64> = primal::Primes::all()
+            .skip(1)
+            .take_while(|&p| p <= 20_000)
+            .map(|p| p as u64)
+            .collect();
+        assert_eq!(count as usize, expected.len());
+        assert_eq!(pool.iter().collect::<Vec<_>>(), expected);
+        for i in [0, 1, 63, 64, 65, 1000, expected.len() - 1] {
+            assert_eq!(pool.get_u64(i), expected[i]);
+        }
+        assert!(matches!(
+            write_pool(&path, [5, 3]),
+            Err(PoolError::NotIncreasing { previous: 5, next: 3 })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
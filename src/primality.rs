@@ -0,0 +1,677 @@
+use num_bigint::{BigInt, BigUint, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use rand::Rng;
+
+use crate::modular::{crt, ct_modpow, jacobi, ModulusContext};
+use crate::sieve::is_prime_sieved;
+use crate::trial::{first_divisor, first_trial_divisor};
+use crate::wheel::{WheelCandidates, WHEEL_PRIMES};
+
+/// Miller-Rabin probable-prime test with `k` random witnesses drawn from `rng`.
+pub fn is_prime<R: Rng + ?Sized>(n: &BigUint, k: usize, rng: &mut R) -> bool {
+    if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
+        return true;
+    }
+    if n < &BigUint::from(2u32) || n % BigUint::from(2u32) == BigUint::zero() {
+        return false;
+    }
+
+    let mut d = n - BigUint::one();
+    let mut s = 0usize;
+    while &d % BigUint::from(2u32) == BigUint::zero() {
+        d /= BigUint::from(2u32);
+        s += 1;
+    }
+
+    let context = ModulusContext::new(n);
+    'witness_loop: for _ in 0..k {
+        let a = rng.gen_biguint_range(&BigUint::from(2u32), &(n - BigUint::one()));
+        let mut x = context.pow(&a, &d);
+        if x == BigUint::one() || x == n - BigUint::one() {
+            continue;
+        }
+        for _ in 0..s - 1 {
+            x = context.mul(&x, &x);
+            if x == n - BigUint::one() {
+                continue 'witness_loop;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Miller-Rabin as in `is_prime`, exponentiating with `ct_modpow`, for candidates that will
+/// become secrets: the exponent n - 1 = d·2^s is derived from the candidate itself.
+pub fn is_prime_constant_time<R: Rng + ?Sized>(n: &BigUint, k: usize, rng: &mut R) -> bool {
+    if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
+        return true;
+    }
+    if n < &BigUint::from(2u32) || !n.bit(0) {
+        return false;
+    }
+    let n_minus_one = n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    'witness_loop: for _ in 0..k {
+        let a = rng.gen_biguint_range(&BigUint::from(2u32), &n_minus_one);
+        let mut x = ct_modpow(&a, &d, n, n.bits());
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                continue 'witness_loop;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Small primes used for trial division before the expensive tests.
+const SMALL_PRIMES: [u32; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+
+/// Baillie-PSW probable-prime test: a strong base-2 Miller-Rabin test followed by a strong
+/// Lucas test with Selfridge parameters. Deterministic, with no known counterexample.
+pub fn is_bpsw_prime(n: &BigUint) -> bool {
+    if let Some(p) = first_divisor(n, &SMALL_PRIMES) {
+        return n == &BigUint::from(p);
+    }
+    if n < &BigUint::from(2u32) {
+        return false;
+    }
+    is_strong_probable_prime(n, &BigUint::from(2u32)) && is_strong_lucas_probable_prime(n)
+}
+
+/// Strong Fermat (single Miller-Rabin) test of odd `n > 2` to base `a`.
+fn is_strong_probable_prime(n: &BigUint, a: &BigUint) -> bool {
+    let one = BigUint::one();
+    let n_minus_one = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    let context = ModulusContext::new(n);
+    let mut x = context.pow(a, &d);
+    if x == one || x == n_minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = context.mul(&x, &x);
+        if x == n_minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+/// Strong Lucas probable-prime test with Selfridge's method A parameters (P = 1).
+fn is_strong_lucas_probable_prime(n: &BigUint) -> bool {
+    // Perfect squares never yield a D with (D/n) = -1
+    let root = n.sqrt();
+    if &(&root * &root) == n {
+        return false;
+    }
+
+    // Find the first D in 5, -7, 9, -11, ... with (D/n) = -1
+    let mut d = BigInt::from(5);
+    loop {
+        match jacobi(&d, n) {
+            -1 => break,
+            0 if d.magnitude() != n => return false,
+            _ => {}
+        }
+        d = if d.is_positive() { -(d + 2u32) } else { -(d - 2u32) };
+    }
+
+    let n_int = BigInt::from(n.clone());
+    let p = BigInt::one();
+    let q = (BigInt::one() - &d) / 4u32;
+    let half = |x: BigInt| -> BigInt {
+        let x = x.mod_floor(&n_int);
+        if x.is_even() {
+            x / 2u32
+        } else {
+            (x + &n_int) / 2u32
+        }
+    };
+
+    let n_plus_one = n + BigUint::one();
+    let s = n_plus_one.trailing_zeros().unwrap_or(0);
+    let k = &n_plus_one >> s;
+
+    // Binary ladder computing U_k, V_k and Q^k modulo n
+    let mut u = BigInt::one();
+    let mut v = p.clone();
+    let mut q_k = q.mod_floor(&n_int);
+    for bit in (0..k.bits() - 1).rev() {
+        u = (&u * &v).mod_floor(&n_int);
+        v = (&v * &v - &q_k * 2u32).mod_floor(&n_int);
+        q_k = (&q_k * &q_k).mod_floor(&n_int);
+        if k.bit(bit) {
+            let next_u = half(&p * &u + &v);
+            v = half(&d * &u + &p * &v);
+            u = next_u;
+            q_k = (&q_k * &q).mod_floor(&n_int);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = (&v * &v - &q_k * 2u32).mod_floor(&n_int);
+        if v.is_zero() {
+            return true;
+        }
+        q_k = (&q_k * &q_k).mod_floor(&n_int);
+    }
+    false
+}
+
+/// (k, e) with n = k·2^e + 1, k odd and k < 2^e, when `n` is a Proth number.
+pub fn proth_decomposition(n: &BigUint) -> Option<(BigUint, u64)> {
+    if n < &BigUint::from(3u32) {
+        return None;
+    }
+    let m = n - 1u32;
+    let e = m.trailing_zeros()?;
+    let k = &m >> e;
+    (k.bits() <= e).then_some((k, e))
+}
+
+/// Bases tried by `proth_test` for a quadratic non-residue.
+const PROTH_BASES: [u32; 24] = [
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// Proth's theorem: a Proth number n is prime if and only if a^((n-1)/2) ≡ -1 (mod n) for an
+/// a with Jacobi symbol (a/n) = -1, so one exponentiation settles it.
+///
+/// `None` when `n` is not a Proth number, or (as for squares) no small prime base is a
+/// non-residue.
+pub fn proth_test(n: &BigUint) -> Option<bool> {
+    proth_decomposition(n)?;
+    let n_minus_one = n - 1u32;
+    for a in PROTH_BASES {
+        match jacobi(&BigInt::from(a), n) {
+            -1 => return Some(BigUint::from(a).modpow(&(&n_minus_one >> 1u32), n) == n_minus_one),
+            0 => return Some(n == &BigUint::from(a)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Smallest prime strictly greater than `n`, stepping over the 2-3-5-7 wheel.
+pub fn next_prime(n: &BigUint) -> BigUint {
+    if let Some(&p) = WHEEL_PRIMES.iter().find(|&&p| n < &BigUint::from(p)) {
+        return BigUint::from(p);
+    }
+    WheelCandidates::ascending(&(n + 1u32))
+        .find(is_bpsw_prime)
+        .expect("there is always a larger prime")
+}
+
+/// Largest prime strictly less than `n`, or `None` when `n <= 2`, stepping down the 2-3-5-7
+/// wheel.
+pub fn prev_prime(n: &BigUint) -> Option<BigUint> {
+    if n <= &BigUint::from(11u32) {
+        return WHEEL_PRIMES
+            .iter()
+            .rev()
+            .find(|&&p| &BigUint::from(p) < n)
+            .map(|&p| BigUint::from(p));
+    }
+    // 11 is on the wheel, so the walk finds a prime before running out
+    WheelCandidates::descending(&(n - 1u32)).find(is_bpsw_prime)
+}
+
+/// A prime drawn uniformly from `[lo, hi)`, or `None` when the interval holds no prime.
+///
+/// Candidates are sampled uniformly and rejected until one passes BPSW, so every prime in
+/// the interval is equally likely (unlike rounding a random point up with `next_prime`,
+/// which favours primes after large gaps).
+pub fn random_prime_in_range<R: Rng + ?Sized>(
+    lo: &BigUint,
+    hi: &BigUint,
+    rng: &mut R,
+) -> Option<BigUint> {
+    if lo >= hi {
+        return None;
+    }
+    // Make sure the loop below terminates
+    let first = if lo.is_zero() { next_prime(lo) } else { next_prime(&(lo - 1u32)) };
+    if &first >= hi {
+        return None;
+    }
+
+    loop {
+        let candidate = rng.gen_biguint_range(lo, hi);
+        if is_bpsw_prime(&candidate) {
+            return Some(candidate);
+        }
+    }
+}
+
+/// Small odd primes whose residues are fixed up front by `random_safe_prime`.
+const SAFE_PRIME_WHEEL: [u32; 6] = [3, 5, 7, 11, 13, 17];
+
+/// Further small primes sieved out of safe-prime candidates before any probabilistic test.
+const SAFE_PRIME_SIEVE_BOUND: u32 = 2000;
+
+/// A random safe prime p = 2q + 1 (q also prime) of exactly `bits` bits (at least 5).
+///
+/// Primality of p and q is decided by `config`.
+///
+/// For every small odd prime r, p must avoid 0 (r | p) and 1 (r | q) mod r. Candidates are
+/// assembled by the Chinese remainder theorem from p ≡ 3 (mod 4) and a random allowed residue
+/// for each prime in the wheel, so they already pass that part of the sieve; the remaining
+/// small primes are sieved directly and q is tested before p. That already does the work of
+/// the 2-3-5-7 `wheel`: every candidate is odd, and its residues or the sieve keep it coprime
+/// to 3, 5 and 7.
+pub fn random_safe_prime<R: Rng + ?Sized>(
+    bits: u64,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> BigUint {
+    assert!(bits >= 5, "safe primes above 7 need at least 5 bits");
+    let lo = BigUint::one() << (bits - 1);
+    let hi = BigUint::one() << bits;
+    // Wheels too large for the range would leave no room to randomize the multiplier
+    let wheel: Vec<u32> = SAFE_PRIME_WHEEL
+        .iter()
+        .copied()
+        .scan(4u64, |modulus, r| {
+            *modulus *= r as u64;
+            Some((r, *modulus))
+        })
+        .take_while(|&(_, modulus)| BigUint::from(modulus) << 4u32 <= lo)
+        .map(|(r, _)| r)
+        .collect();
+    let sieve: Vec<u32> = primal::Primes::all()
+        .skip(1)
+        .take_while(|&r| r < SAFE_PRIME_SIEVE_BOUND as usize)
+        .map(|r| r as u32)
+        .filter(|r| !wheel.contains(r))
+        .collect();
+
+    loop {
+        let mut congruences = vec![(BigUint::from(3u32), BigUint::from(4u32))];
+        for &r in &wheel {
+            congruences.push((BigUint::from(rng.gen_range(2..r)), BigUint::from(r)));
+        }
+        let modulus: BigUint = congruences.iter().map(|(_, m)| m).product();
+        let residue = crt(&congruences).expect("wheel moduli are pairwise coprime");
+        let k_lo = (&lo - &residue).div_ceil(&modulus);
+        let k_hi = (&hi - &residue).div_ceil(&modulus);
+        let p = rng.gen_biguint_range(&k_lo, &k_hi) * &modulus + residue;
+        let q = &p >> 1u32;
+        // Below q, a residue of 0 or 1 mod r means r divides p or q
+        let mut small = sieve.iter().take_while(|&&r| BigUint::from(r) < q);
+        if small.any(|&r| matches!((&p % r).to_u32(), Some(0) | Some(1))) {
+            continue;
+        }
+        if config.is_prime(&q, rng) && config.is_prime(&p, rng) {
+            return p;
+        }
+    }
+}
+
+/// Result of the combined primality test, with a bound on how likely the verdict is wrong.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Primality {
+    pub is_prime: bool,
+    /// Upper bound on the probability that `is_prime` is wrong; 0 when the answer is certain
+    pub error_bound: f64,
+}
+
+/// BPSW, then `rounds` random Miller-Rabin witnesses.
+///
+/// Composites are always certain, as is BPSW below 2^64 where it has been verified exhaustively.
+/// Above that only the Miller-Rabin rounds are credited, giving the classic 4^-k bound.
+pub fn is_prime_with_confidence<R: Rng + ?Sized>(
+    n: &BigUint,
+    rounds: usize,
+    rng: &mut R,
+) -> Primality {
+    PrimalityConfig::with_rounds(rounds).test(n, rng)
+}
+
+/// Default number of Miller-Rabin rounds, shared by the classifiers, search and Shamir helpers.
+pub const DEFAULT_ROUNDS: usize = 20;
+
+/// How thoroughly primality is tested wherever the crate needs a verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimalityConfig {
+    /// Random Miller-Rabin witnesses per test
+    pub rounds: usize,
+    /// Run BPSW before the Miller-Rabin rounds
+    pub use_bpsw: bool,
+    /// Trial-divide by the primes below this bound first (odd numbers past 2^16); 0 disables
+    /// the pass
+    pub trial_division_bound: u32,
+    /// Test with `is_prime_constant_time`, for candidates that become key material. BPSW's
+    /// Lucas step has no ladder form here, so this skips BPSW whatever `use_bpsw` says
+    pub constant_time: bool,
+}
+
+impl Default for PrimalityConfig {
+    fn default() -> Self {
+        PrimalityConfig {
+            rounds: DEFAULT_ROUNDS,
+            use_bpsw: true,
+            trial_division_bound: 0,
+            constant_time: false,
+        }
+    }
+}
+
+impl PrimalityConfig {
+    /// The default configuration with `rounds` Miller-Rabin witnesses.
+    pub fn with_rounds(rounds: usize) -> Self {
+        PrimalityConfig {
+            rounds,
+            ..PrimalityConfig::default()
+        }
+    }
+
+    /// Run the configured tests and bound the probability that the verdict is wrong.
+    ///
+    /// An installed `sieve` bitmap settles everything up to its limit, and `proth_test` settles
+    /// Proth numbers (except in constant-time mode, where both depend on n). Trial division
+    /// settles everything below the square of its bound, and BPSW settles everything below
+    /// 2^64; otherwise only the Miller-Rabin rounds are credited (4^-k).
+    pub fn test<R: Rng + ?Sized>(&self, n: &BigUint, rng: &mut R) -> Primality {
+        let verdict = |is_prime, error_bound| Primality {
+            is_prime,
+            error_bound,
+        };
+        if n < &BigUint::from(2u32) {
+            return verdict(false, 0.0);
+        }
+        if !self.constant_time {
+            if let Some(is_prime) = is_prime_sieved(n).or_else(|| proth_test(n)) {
+                return verdict(is_prime, 0.0);
+            }
+        }
+        if self.trial_division_bound > 2 {
+            if let Some(d) = first_trial_divisor(n, self.trial_division_bound) {
+                return verdict(n == &BigUint::from(d), 0.0);
+            }
+            let bound = BigUint::from(self.trial_division_bound);
+            if n < &(&bound * &bound) {
+                return verdict(true, 0.0);
+            }
+        }
+        if self.use_bpsw && !self.constant_time {
+            if !is_bpsw_prime(n) {
+                return verdict(false, 0.0);
+            }
+            if n.bits() <= 64 {
+                return verdict(true, 0.0);
+            }
+        }
+        let passed = if self.constant_time {
+            is_prime_constant_time(n, self.rounds, rng)
+        } else {
+            is_prime(n, self.rounds, rng)
+        };
+        if !passed {
+            return verdict(false, 0.0);
+        }
+        verdict(true, 0.25f64.powi(self.rounds as i32))
+    }
+
+    pub fn is_prime<R: Rng + ?Sized>(&self, n: &BigUint, rng: &mut R) -> bool {
+        self.test(n, rng).is_prime
+    }
+}
+
+/// Outcome of a Miller-Rabin run that keeps the evidence behind the verdict.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrimalityResult {
+    /// `witness` proves `n` composite; `factor` is a nontrivial divisor when the test exposed one
+    Composite {
+        witness: BigUint,
+        factor: Option<BigUint>,
+    },
+    /// `n` survived this many random witnesses
+    ProbablyPrime { rounds: usize },
+}
+
+impl PrimalityResult {
+    pub fn is_probably_prime(&self) -> bool {
+        matches!(self, PrimalityResult::ProbablyPrime { .. })
+    }
+}
+
+/// Miller-Rabin that returns the witness of compositeness instead of a bare boolean.
+///
+/// A factor is reported when the witness shares a divisor with `n` or when the squaring
+/// chain hits a nontrivial square root of 1, where `gcd(x - 1, n)` splits `n`.
+/// Values below 2 are reported as composite with `n` itself as the witness.
+pub fn is_prime_with_witness<R: Rng + ?Sized>(
+    n: &BigUint,
+    rounds: usize,
+    rng: &mut R,
+) -> PrimalityResult {
+    let one = BigUint::one();
+    let two = BigUint::from(2u32);
+    if n < &two {
+        return PrimalityResult::Composite {
+            witness: n.clone(),
+            factor: None,
+        };
+    }
+    if n == &two || n == &BigUint::from(3u32) {
+        return PrimalityResult::ProbablyPrime { rounds };
+    }
+    if n.is_even() {
+        return PrimalityResult::Composite {
+            witness: two.clone(),
+            factor: Some(two),
+        };
+    }
+
+    let n_minus_one = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    let context = ModulusContext::new(n);
+    'witness_loop: for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&two, &n_minus_one);
+        let g = a.gcd(n);
+        if g != one {
+            return PrimalityResult::Composite {
+                witness: a,
+                factor: Some(g),
+            };
+        }
+
+        let mut x = context.pow(&a, &d);
+        if x == one || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            let y = context.mul(&x, &x);
+            if y == n_minus_one {
+                continue 'witness_loop;
+            }
+            if y == one {
+                // x is a square root of 1 other than ±1
+                return PrimalityResult::Composite {
+                    witness: a,
+                    factor: Some((&x - &one).gcd(n)),
+                };
+            }
+            x = y;
+        }
+        // a^(n-1) = x^2; if that is 1 then x is again a nontrivial root
+        let factor = if context.mul(&x, &x) == one {
+            Some((&x - &one).gcd(n))
+        } else {
+            None
+        };
+        return PrimalityResult::Composite { witness: a, factor };
+    }
+    PrimalityResult::ProbablyPrime { rounds }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sieve(limit: usize) -> Vec<bool> {
+        let mut is_prime = vec![true; limit + 1];
+        is_prime[0] = false;
+        is_prime[1] = false;
+        for i in 2..=limit {
+            if is_prime[i] {
+                for j in (i * i..=limit).step_by(i) {
+                    is_prime[j] = false;
+                }
+            }
+        }
+        is_prime
+    }
+
+    #[test]
+    fn test_bpsw_matches_sieve() {
+        let expected = sieve(20000);
+        for (n, &prime) in expected.iter().enumerate() {
+            assert_eq!(is_bpsw_prime(&BigUint::from(n)), prime, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn test_bpsw_rejects_strong_pseudoprimes() {
+        // Strong pseudoprimes to base 2 and Carmichael numbers
+        for n in [2047u64, 3277, 4033, 4681, 8321, 561, 1105, 3215031751, 3825123056546413051] {
+            assert!(!is_bpsw_prime(&BigUint::from(n)), "n = {}", n);
+        }
+        let mersenne = (BigUint::one() << 127) - BigUint::one();
+        assert!(is_bpsw_prime(&mersenne));
+    }
+
+    #[test]
+    fn test_proth_test_matches_bpsw() {
+        let mut proth = 0;
+        for n in 0u32..20000 {
+            let n = BigUint::from(n);
+            if let Some(is_prime) = proth_test(&n) {
+                assert_eq!(is_prime, is_bpsw_prime(&n), "n = {}", n);
+                proth += 1;
+            }
+        }
+        assert!(proth > 200);
+        assert_eq!(proth_decomposition(&BigUint::from(13u32)), Some((BigUint::from(3u32), 2)));
+        assert_eq!(proth_decomposition(&BigUint::from(7u32)), None);
+        // 3·2^5000 + 1 is not prime; Proth's theorem says so with one exponentiation
+        let n = (BigUint::from(3u32) << 5000u32) + 1u32;
+        assert_eq!(proth_test(&n), Some(false));
+        // 2^(2^4) + 1, a Fermat prime
+        assert_eq!(proth_test(&BigUint::from(65537u32)), Some(true));
+    }
+
+    #[test]
+    fn test_random_safe_prime() {
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(5);
+        let config = PrimalityConfig::default();
+        for bits in [5, 6, 12, 40, 128] {
+            let p = random_safe_prime(bits, &config, &mut rng);
+            assert_eq!(p.bits(), bits);
+            assert!(is_bpsw_prime(&p) && is_bpsw_prime(&(&p >> 1u32)), "{}", p);
+        }
+    }
+
+    #[test]
+    fn test_next_and_prev_prime() {
+        assert_eq!(next_prime(&BigUint::zero()), BigUint::from(2u32));
+        assert_eq!(next_prime(&BigUint::from(2u32)), BigUint::from(3u32));
+        assert_eq!(next_prime(&BigUint::from(3u32)), BigUint::from(5u32));
+        assert_eq!(next_prime(&BigUint::from(24u32)), BigUint::from(29u32));
+        assert_eq!(next_prime(&BigUint::from(48869u32)), BigUint::from(48871u32));
+        assert_eq!(prev_prime(&BigUint::from(2u32)), None);
+        assert_eq!(prev_prime(&BigUint::from(3u32)), Some(BigUint::from(2u32)));
+        assert_eq!(prev_prime(&BigUint::from(5u32)), Some(BigUint::from(3u32)));
+        assert_eq!(prev_prime(&BigUint::from(30u32)), Some(BigUint::from(29u32)));
+        assert_eq!(prev_prime(&BigUint::from(29u32)), Some(BigUint::from(23u32)));
+    }
+
+    #[test]
+    fn test_confidence_bounds() {
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let small = is_prime_with_confidence(&BigUint::from(48883u32), 20, &mut rng);
+        assert_eq!(small, Primality { is_prime: true, error_bound: 0.0 });
+        let mersenne = (BigUint::one() << 127) - BigUint::one();
+        let large = is_prime_with_confidence(&mersenne, 10, &mut rng);
+        assert!(large.is_prime);
+        assert_eq!(large.error_bound, 0.25f64.powi(10));
+        let composite = is_prime_with_confidence(&(&mersenne * 3u32), 10, &mut rng);
+        assert_eq!(composite, Primality { is_prime: false, error_bound: 0.0 });
+
+        // Without BPSW a small prime is only as certain as its rounds, unless trial
+        // division covers it
+        let mut config = PrimalityConfig {
+            rounds: 4,
+            use_bpsw: false,
+            trial_division_bound: 0,
+            constant_time: false,
+        };
+        assert_eq!(config.test(&BigUint::from(48883u32), &mut rng).error_bound, 0.25f64.powi(4));
+        config.trial_division_bound = 256;
+        assert_eq!(config.test(&BigUint::from(48883u32), &mut rng).error_bound, 0.0);
+        assert!(!config.is_prime(&BigUint::from(561u32), &mut rng));
+        assert!(config.is_prime(&BigUint::from(2u32), &mut rng));
+        config.constant_time = true;
+        assert!(!config.is_prime(&BigUint::from(561u32), &mut rng));
+        assert!(config.is_prime(&mersenne, &mut rng));
+    }
+
+    #[test]
+    fn test_witness_of_compositeness() {
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(3);
+        for n in [561u64, 1105, 2047, 3215031751, (1 << 61) * 3 + 1] {
+            let n = BigUint::from(n);
+            match is_prime_with_witness(&n, 20, &mut rng) {
+                PrimalityResult::Composite { witness, factor } => {
+                    if let Some(f) = factor {
+                        assert!(f > BigUint::one() && f < n && (&n % &f).is_zero());
+                    } else {
+                        assert_ne!(witness.modpow(&(&n - 1u32), &n), BigUint::one());
+                    }
+                }
+                other => panic!("{} reported as {:?}", n, other),
+            }
+        }
+        let mersenne = (BigUint::one() << 127) - BigUint::one();
+        assert_eq!(
+            is_prime_with_witness(&mersenne, 5, &mut rng),
+            PrimalityResult::ProbablyPrime { rounds: 5 }
+        );
+    }
+
+    #[test]
+    fn test_random_prime_in_range_is_uniform() {
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let (lo, hi) = (BigUint::from(10u32), BigUint::from(30u32));
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..6000 {
+            let p = random_prime_in_range(&lo, &hi, &mut rng).unwrap();
+            *counts.entry(p).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 6);
+        assert!(counts.values().all(|&c| (850..1150).contains(&c)), "{:?}", counts);
+
+        let empty = random_prime_in_range(&BigUint::from(24u32), &BigUint::from(29u32), &mut rng);
+        assert_eq!(empty, None);
+    }
+}
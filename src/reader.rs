@@ -0,0 +1,186 @@
+//! A strict reader for the crate's binary formats.
+//!
+//! Every decoder of untrusted bytes (Bloom filters, wrapped and session keys, hellos,
+//! ciphertexts, mnemonic share payloads) reads through `StrictReader`: each read is checked
+//! against what is left before anything is sliced or allocated, length-prefixed fields are
+//! capped, and `finish` refuses trailing bytes. Failures come back as `MalformedInput`,
+//! which each format's error type wraps, and no input makes a decoder panic, so they can be
+//! run under a fuzzer as they are.
+
+use num_bigint::BigUint;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MalformedInput {
+    #[error("input ends {missing} bytes short of the {field}")]
+    Truncated { field: &'static str, missing: usize },
+    #[error("{field} is {length} bytes, over the limit of {max}")]
+    TooLong { field: &'static str, length: usize, max: usize },
+    #[error("{0} bytes left over after the input")]
+    TrailingBytes(usize),
+    #[error("invalid {0}")]
+    Invalid(&'static str),
+}
+
+/// Reads fields from the front of a byte slice, never past its end.
+#[derive(Debug, Clone)]
+pub struct StrictReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StrictReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        StrictReader { bytes }
+    }
+
+    /// Bytes not yet read.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The next `length` bytes.
+    pub fn take(
+        &mut self,
+        field: &'static str,
+        length: usize,
+    ) -> Result<&'a [u8], MalformedInput> {
+        if length > self.bytes.len() {
+            let missing = length - self.bytes.len();
+            return Err(MalformedInput::Truncated { field, missing });
+        }
+        let (head, tail) = self.bytes.split_at(length);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn array<const N: usize>(
+        &mut self,
+        field: &'static str,
+    ) -> Result<[u8; N], MalformedInput> {
+        Ok(self.take(field, N)?.try_into().expect("took N bytes"))
+    }
+
+    pub fn u8(&mut self, field: &'static str) -> Result<u8, MalformedInput> {
+        Ok(self.array::<1>(field)?[0])
+    }
+
+    pub fn u16_be(&mut self, field: &'static str) -> Result<u16, MalformedInput> {
+        self.array(field).map(u16::from_be_bytes)
+    }
+
+    pub fn u32_be(&mut self, field: &'static str) -> Result<u32, MalformedInput> {
+        self.array(field).map(u32::from_be_bytes)
+    }
+
+    pub fn u64_be(&mut self, field: &'static str) -> Result<u64, MalformedInput> {
+        self.array(field).map(u64::from_be_bytes)
+    }
+
+    pub fn u32_le(&mut self, field: &'static str) -> Result<u32, MalformedInput> {
+        self.array(field).map(u32::from_le_bytes)
+    }
+
+    pub fn u64_le(&mut self, field: &'static str) -> Result<u64, MalformedInput> {
+        self.array(field).map(u64::from_le_bytes)
+    }
+
+    /// A length that must not exceed `max`, checked before anything of that size is read.
+    pub fn length(
+        &self,
+        field: &'static str,
+        length: u64,
+        max: usize,
+    ) -> Result<usize, MalformedInput> {
+        match usize::try_from(length) {
+            Ok(length) if length <= max => Ok(length),
+            _ => Err(MalformedInput::TooLong { field, length: length as usize, max }),
+        }
+    }
+
+    /// Bytes after a big-endian u32 length of at most `max`.
+    pub fn prefixed(
+        &mut self,
+        field: &'static str,
+        max: usize,
+    ) -> Result<&'a [u8], MalformedInput> {
+        let length = self.u32_be(field)?;
+        let length = self.length(field, length as u64, max)?;
+        self.take(field, length)
+    }
+
+    /// A big-endian integer in `length` bytes.
+    pub fn biguint(
+        &mut self,
+        field: &'static str,
+        length: usize,
+    ) -> Result<BigUint, MalformedInput> {
+        self.take(field, length).map(BigUint::from_bytes_be)
+    }
+
+    /// The rest of the input.
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+
+    /// Succeed only if the whole input has been read.
+    pub fn finish(self) -> Result<(), MalformedInput> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            left => Err(MalformedInput::TrailingBytes(left)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom::BloomFilter;
+    use crate::mnemonic::MnemonicCodec;
+    use crate::pmpt::Ciphertext;
+    use crate::session::Hello;
+    use crate::wrap::{SessionKey, WrappedKey};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_strict_reader() {
+        let mut reader = StrictReader::new(&[0, 0, 0, 2, 7, 8, 9]);
+        assert_eq!(reader.prefixed("field", 2), Ok(&[7, 8][..]));
+        assert_eq!(reader.clone().finish(), Err(MalformedInput::TrailingBytes(1)));
+        assert_eq!(
+            reader.u16_be("short"),
+            Err(MalformedInput::Truncated { field: "short", missing: 1 })
+        );
+        let mut capped = StrictReader::new(&[0, 0, 0, 3, 1, 2, 3]);
+        assert_eq!(
+            capped.prefixed("field", 2),
+            Err(MalformedInput::TooLong { field: "field", length: 3, max: 2 })
+        );
+
+        // Random bytes and prefixes of them, lengths included, never make a decoder panic
+        let mut rng = ChaCha20Rng::seed_from_u64(92);
+        let codec = MnemonicCodec::new();
+        for _ in 0..200 {
+            let length = rng.gen_range(0..400);
+            let mut bytes: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+            if length >= 4 && rng.gen_bool(0.5) {
+                // A small length up front so decoders get past their first field
+                bytes[..4].copy_from_slice(&(rng.gen_range(0..64u32)).to_be_bytes());
+            }
+            for end in [0, length / 3, length / 2, length] {
+                let input = &bytes[..end];
+                let _ = Ciphertext::from_bytes(input);
+                let _ = SessionKey::from_bytes(input);
+                let _ = WrappedKey::from_bytes(input);
+                let _ = Hello::from_bytes(input);
+                let mut filter = b"UPBLOOM2".to_vec();
+                filter.extend_from_slice(input);
+                let _ = BloomFilter::read_from(&mut filter.as_slice());
+                if let Ok(mnemonic) = codec.encode(&input[..end.min(64)]) {
+                    let _ = codec.decode_prime_share(&mnemonic);
+                    let _ = codec.decode_byte_share(&mnemonic);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Explicit representations n = F(x, y, z) by a positive-definite search form.
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::form::QuadraticForm;
+
+/// Coefficients (a, b, c, d, e, f) of the homogeneous part as signed integers.
+fn homogeneous(form: &QuadraticForm) -> [BigInt; 6] {
+    let [a, b, c, d, e, f, _] = form.coefficients();
+    [a, b, c, d, e, f].map(|c| c.clone())
+}
+
+/// Determinant of the Gram matrix [[2a, b, d], [b, 2c, e], [d, e, 2f]].
+fn gram_determinant(form: &QuadraticForm) -> BigInt {
+    let [a, b, c, d, e, f] = homogeneous(form);
+    let (a2, c2, f2) = (&a * 2u32, &c * 2u32, &f * 2u32);
+    &a2 * (&c2 * &f2 - &e * &e) - &b * (&b * &f2 - &e * &d) + &d * (&b * &e - &c2 * &d)
+}
+
+/// Whether the homogeneous part of `form` is positive definite.
+pub fn is_positive_definite(form: &QuadraticForm) -> bool {
+    let [a, b, c, ..] = homogeneous(form);
+    a.is_positive() && (&a * &c * 4u32 - &b * &b).is_positive() && gram_determinant(form).is_positive()
+}
+
+/// Find integers (x, y, z) with F(x, y, z) = n, or `None` if there are none.
+///
+/// Enumerates z outward from 0 over the ellipsoid Q ≤ n - g, bounds y for each z by
+/// completing the square, and solves the remaining quadratic in x exactly. The box holds
+/// O(n) pairs (y, z), so proving that n is *not* represented is only practical for n up to
+/// about 10^12; representable n are usually found far sooner. Forms that are not positive
+/// definite have no bounded search region and always yield `None`.
+pub fn represent(form: &QuadraticForm, n: &BigUint) -> Option<(BigInt, BigInt, BigInt)> {
+    let m = BigInt::from(n.clone()) - &form.g;
+    if !is_positive_definite(form) || m.is_negative() {
+        return None;
+    }
+    let [a, b, c, d, e, f] = homogeneous(form);
+    // Q = a(x + (by + dz)/2a)² + P(y, z)/4a with P(y, z) = αy² + βzy + γz², so the
+    // discriminant of the quadratic in x is D(y) = 4a·m - P(y, z)
+    let alpha = &a * &c * 4u32 - &b * &b;
+    let beta = &a * &e * 4u32 - &b * &d * 2u32;
+    let gamma = &a * &f * 4u32 - &d * &d;
+    let z_max = (&m * &alpha * 2u32 / gram_determinant(form)).sqrt();
+    let two_a = &a * 2u32;
+    let two_alpha = &alpha * 2u32;
+
+    let mut k = BigInt::zero();
+    while k <= z_max {
+        let signs: &[i32] = if k.is_zero() { &[1] } else { &[1, -1] };
+        for &sign in signs {
+            let z = &k * BigInt::from(sign);
+            let linear = &beta * &z;
+            let constant = &gamma * &z * &z - &a * &m * 4u32;
+            let y_discriminant = &linear * &linear - &alpha * &constant * 4u32;
+            if y_discriminant.is_negative() {
+                continue;
+            }
+            let root = y_discriminant.sqrt() + 1u32;
+            let mut y = (-&linear - &root).div_floor(&two_alpha);
+            let y_hi = (-&linear + &root).div_ceil(&two_alpha);
+            // Step D(y) by finite differences: D(y + 1) - D(y) = -α(2y + 1) - βz
+            let mut discriminant = -(&alpha * &y * &y + &linear * &y + &constant);
+            let mut step = -(&alpha * (&y * 2u32 + 1u32)) - &linear;
+            while y <= y_hi {
+                if let Some(root) = exact_sqrt(&discriminant) {
+                    let x_linear = &b * &y + &d * &z;
+                    let x = [-&x_linear + &root, -&x_linear - &root]
+                        .into_iter()
+                        .find(|numerator| numerator.is_multiple_of(&two_a));
+                    if let Some(x) = x {
+                        return Some((x / &two_a, y, z));
+                    }
+                }
+                discriminant += &step;
+                step -= &two_alpha;
+                y += 1u32;
+            }
+        }
+        k += 1u32;
+    }
+    None
+}
+
+const SQUARES_MOD_64: [bool; 64] = {
+    let mut squares = [false; 64];
+    let mut i = 0;
+    while i < 64 {
+        squares[(i * i) % 64] = true;
+        i += 1;
+    }
+    squares
+};
+
+/// The square root of `n` if it is a perfect square.
+fn exact_sqrt(n: &BigInt) -> Option<BigInt> {
+    // Only 12 of the 64 residues mod 64 are squares, which rejects most candidates cheaply
+    if n.is_negative() || !SQUARES_MOD_64[(n & BigInt::from(63u32)).to_usize()?] {
+        return None;
+    }
+    let root = n.sqrt();
+    if &root * &root == *n {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(form: &QuadraticForm, (x, y, z): &(BigInt, BigInt, BigInt)) -> BigInt {
+        let [a, b, c, d, e, f] = homogeneous(form);
+        a * x * x + b * x * y + c * y * y + d * x * z + e * y * z + f * z * z
+            + &form.g
+    }
+
+    #[test]
+    fn test_represent_universal_form() {
+        let form = QuadraticForm::universal();
+        assert!(is_positive_definite(&form));
+        for n in [107u32, 112, 1009, 48883, 1_000_003] {
+            let n = BigUint::from(n);
+            if let Some(solution) = represent(&form, &n) {
+                assert_eq!(evaluate(&form, &solution), BigInt::from(n));
+            }
+        }
+        assert!(represent(&form, &BigUint::from(112u32)).is_some());
+        assert!(represent(&form, &BigUint::from(100u32)).is_none());
+    }
+
+    #[test]
+    fn test_sum_of_three_squares() {
+        let form = QuadraticForm::new([1, 0, 1, 0, 0, 1, 0]);
+        for n in 0u32..100 {
+            let mut k = n;
+            while k % 4 == 0 && k > 0 {
+                k /= 4;
+            }
+            let expected = k % 8 != 7;
+            let solution = represent(&form, &BigUint::from(n));
+            assert_eq!(solution.is_some(), expected, "n = {}", n);
+        }
+    }
+}
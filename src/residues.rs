@@ -0,0 +1,183 @@
+//! Distribution of hit values modulo small moduli.
+//!
+//! By Dirichlet's theorem primes are equidistributed over the residues coprime to m, so a
+//! chi-square statistic against that uniform distribution measures how far the form's
+//! structure skews its prime values. Residues sharing a factor with m can only hold the
+//! prime divisors of m themselves and are reported separately.
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::ToPrimitive;
+
+use std::collections::BTreeSet;
+
+use crate::form::QuadraticForm;
+
+/// Counts of hits in each residue class mod `modulus`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResidueProfile {
+    pub modulus: u64,
+    pub counts: Vec<u64>,
+}
+
+impl ResidueProfile {
+    /// Count `values` by residue mod `modulus` (which must be at least 2).
+    pub fn new<'a, I: IntoIterator<Item = &'a BigUint>>(values: I, modulus: u64) -> Self {
+        assert!(modulus >= 2, "modulus must be at least 2");
+        let m = BigUint::from(modulus);
+        let mut counts = vec![0u64; modulus as usize];
+        for value in values {
+            counts[(value % &m).to_usize().unwrap()] += 1;
+        }
+        ResidueProfile { modulus, counts }
+    }
+
+    /// Residues coprime to the modulus.
+    pub fn units(&self) -> Vec<u64> {
+        (0..self.modulus).filter(|r| r.gcd(&self.modulus) == 1).collect()
+    }
+
+    /// Hits in residues sharing a factor with the modulus.
+    pub fn non_units(&self) -> u64 {
+        (0..self.modulus)
+            .filter(|r| r.gcd(&self.modulus) != 1)
+            .map(|r| self.counts[r as usize])
+            .sum()
+    }
+
+    /// Chi-square statistic of the unit residues against the uniform distribution, with its
+    /// degrees of freedom (φ(m) - 1).
+    pub fn chi_square(&self) -> (f64, u64) {
+        let units = self.units();
+        let total: u64 = units.iter().map(|&r| self.counts[r as usize]).sum();
+        let expected = total as f64 / units.len() as f64;
+        let statistic = if total == 0 {
+            0.0
+        } else {
+            units
+                .iter()
+                .map(|&r| (self.counts[r as usize] as f64 - expected).powi(2) / expected)
+                .sum()
+        };
+        (statistic, units.len() as u64 - 1)
+    }
+
+    /// Probability of a chi-square statistic at least this large if the hits were uniform.
+    pub fn p_value(&self) -> f64 {
+        let (statistic, degrees) = self.chi_square();
+        if degrees == 0 {
+            return 1.0;
+        }
+        upper_regularized_gamma(degrees as f64 / 2.0, statistic / 2.0)
+    }
+}
+
+/// Moduli worth profiling for `form`: 3, 4, 5 and 8, plus each coefficient's absolute value
+/// above 1.
+pub fn default_moduli(form: &QuadraticForm) -> Vec<u64> {
+    let mut moduli: BTreeSet<u64> = [3, 4, 5, 8].into_iter().collect();
+    for c in form.coefficients() {
+        if let Some(c) = c.magnitude().to_u64().filter(|&c| c > 1) {
+            moduli.insert(c);
+        }
+    }
+    moduli.into_iter().collect()
+}
+
+/// Q(s, x) = Γ(s, x) / Γ(s), by its series for x < s + 1 and Lentz's continued fraction
+/// otherwise (Numerical Recipes §6.2).
+fn upper_regularized_gamma(s: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefix = s * x.ln() - x - ln_gamma(s);
+    if x < s + 1.0 {
+        let (mut term, mut sum, mut a) = (1.0 / s, 1.0 / s, s);
+        while term.abs() > sum.abs() * EPSILON {
+            a += 1.0;
+            term *= x / a;
+            sum += term;
+        }
+        1.0 - sum * log_prefix.exp()
+    } else {
+        let mut b = x + 1.0 - s;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - s);
+            b += 2.0;
+            d = an * d + b;
+            d = if d.abs() < TINY { TINY } else { d };
+            c = b + an / c;
+            c = if c.abs() < TINY { TINY } else { c };
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        h * log_prefix.exp()
+    }
+}
+
+/// ln Γ(s) for s > 0 by the Lanczos approximation (g = 7, n = 9).
+fn ln_gamma(s: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if s < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * s).sin()).ln() - ln_gamma(1.0 - s);
+    }
+    let s = s - 1.0;
+    let t = s + 7.5;
+    let sum: f64 = COEFFICIENTS[0]
+        + COEFFICIENTS[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c / (s + i as f64 + 1.0))
+            .sum::<f64>();
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (s + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_residue_profile() {
+        let primes: Vec<BigUint> = primal::Primes::all()
+            .take_while(|&p| p < 100_000)
+            .map(BigUint::from)
+            .collect();
+        let profile = ResidueProfile::new(&primes, 4);
+        assert_eq!(profile.units(), vec![1, 3]);
+        // Only 2 is even
+        assert_eq!(profile.non_units(), 1);
+        // Primes are close to uniform mod 4, so the deviation is not significant
+        let (statistic, degrees) = profile.chi_square();
+        assert_eq!(degrees, 1);
+        assert!(statistic < 10.0 && profile.p_value() > 0.001);
+
+        // Values all ≡ 1 mod 3 are maximally biased
+        let biased: Vec<BigUint> = (0u32..300).map(|k| BigUint::from(3 * k + 1)).collect();
+        assert!(ResidueProfile::new(&biased, 3).p_value() < 1e-10);
+
+        assert!((upper_regularized_gamma(1.0, 2.0) - (-2.0f64).exp()).abs() < 1e-12);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-12);
+        assert_eq!(default_moduli(&QuadraticForm::universal()), vec![3, 4, 5, 7, 8, 11, 23, 47, 83, 107]);
+    }
+}
@@ -0,0 +1,197 @@
+//! Reading back the CSV rows written by `search::search`.
+//!
+//! Tag lists are written with `{:?}` (e.g. `["Safe", "Prime"]`), so their commas are not
+//! field separators; fields are split only on commas outside brackets.
+
+use num_bigint::{BigInt, BigUint};
+use thiserror::Error;
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead};
+
+use crate::search::CSV_HEADER;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ResultsError {
+    #[error("expected at least 8 fields, found {0}")]
+    MissingFields(usize),
+    #[error("invalid integer '{0}'")]
+    InvalidNumber(String),
+    #[error("invalid tag list '{0}'")]
+    InvalidTags(String),
+    #[error("invalid error bound '{0}'")]
+    InvalidErrorBound(String),
+}
+
+/// One hit from a results file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultRow {
+    pub x: BigInt,
+    pub y: BigInt,
+    pub z: BigInt,
+    pub n: BigInt,
+    pub classifications_n: Vec<String>,
+    pub classifications_x: Vec<String>,
+    pub classifications_y: Vec<String>,
+    pub classifications_z: Vec<String>,
+    /// Absent in files written before the error-bound column existed
+    pub error_bound: Option<f64>,
+}
+
+impl ResultRow {
+    /// Whether the row was recorded as a prime hit rather than a tagged pseudoprime.
+    pub fn is_prime_hit(&self) -> bool {
+        self.classifications_n.iter().any(|tag| tag == "Prime")
+    }
+}
+
+/// Whether `line` is the results header: a prefix of it for older files, or the header
+/// followed by annotation columns.
+pub fn is_header(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && (CSV_HEADER.starts_with(line) || line.starts_with(CSV_HEADER))
+}
+
+/// Split a results line on commas that are not inside `[...]`.
+pub fn split_fields(line: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                fields.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// Parse a tag list written either as `["Safe", "Prime"]` or as `Safe;Prime`.
+pub fn parse_tags(field: &str) -> Result<Vec<String>, ResultsError> {
+    let field = field.trim();
+    let (inner, separator) = match field.strip_prefix('[') {
+        Some(rest) => (
+            rest.strip_suffix(']')
+                .ok_or_else(|| ResultsError::InvalidTags(field.to_string()))?,
+            ',',
+        ),
+        None => (field, ';'),
+    };
+    inner
+        .split(separator)
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| {
+            let tag = tag.trim_matches('"');
+            if tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                Ok(tag.to_string())
+            } else {
+                Err(ResultsError::InvalidTags(field.to_string()))
+            }
+        })
+        .collect()
+}
+
+fn parse_number(field: &str) -> Result<BigInt, ResultsError> {
+    let field = field.trim();
+    field
+        .parse()
+        .map_err(|_| ResultsError::InvalidNumber(field.to_string()))
+}
+
+/// Parse one data row of a results file.
+pub fn parse_row(line: &str) -> Result<ResultRow, ResultsError> {
+    let fields = split_fields(line.trim_end());
+    if fields.len() < 8 {
+        return Err(ResultsError::MissingFields(fields.len()));
+    }
+    let error_bound = match fields.get(8).map(|f| f.trim()) {
+        None | Some("") => None,
+        Some(bound) => Some(
+            bound
+                .parse()
+                .map_err(|_| ResultsError::InvalidErrorBound(bound.to_string()))?,
+        ),
+    };
+    Ok(ResultRow {
+        x: parse_number(fields[0])?,
+        y: parse_number(fields[1])?,
+        z: parse_number(fields[2])?,
+        n: parse_number(fields[3])?,
+        classifications_n: parse_tags(fields[4])?,
+        classifications_x: parse_tags(fields[5])?,
+        classifications_y: parse_tags(fields[6])?,
+        classifications_z: parse_tags(fields[7])?,
+        error_bound,
+    })
+}
+
+/// Collect every row of a results file in file order, tagged pseudoprimes included.
+///
+/// A malformed row is reported as `InvalidData` with its line number.
+pub fn read_rows<B: BufRead>(input: B) -> io::Result<Vec<ResultRow>> {
+    let mut rows = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || is_header(&line) {
+            continue;
+        }
+        match parse_row(&line) {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", i + 1, e),
+                ))
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Collect the prime-hit rows of a results file in file order, skipping tagged
+/// pseudoprimes.
+///
+/// A malformed row is reported as `InvalidData` with its line number.
+pub fn read_prime_rows<B: BufRead>(input: B) -> io::Result<Vec<ResultRow>> {
+    Ok(read_rows(input)?.into_iter().filter(ResultRow::is_prime_hit).collect())
+}
+
+/// Collect |N| of every prime hit in a results file, skipping tagged pseudoprimes.
+///
+/// A malformed row is reported as `InvalidData` with its line number.
+pub fn read_hits<B: BufRead>(input: B) -> io::Result<BTreeSet<BigUint>> {
+    Ok(read_prime_rows(input)?.into_iter().map(|row| row.n.magnitude().clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_row() {
+        assert!(is_header(CSV_HEADER));
+        let row = parse_row(
+            r#"3,5,-3,-7,["Safe", "Prime"],["Germain", "Prime"],["Germain", "Safe", "Prime"],["Germain", "Prime"],0e0"#,
+        )
+        .unwrap();
+        assert_eq!(row.z, BigInt::from(-3));
+        assert_eq!(row.n, BigInt::from(-7));
+        assert_eq!(row.classifications_n, vec!["Safe", "Prime"]);
+        assert_eq!(row.classifications_y.len(), 3);
+        assert_eq!(row.error_bound, Some(0.0));
+        assert!(row.is_prime_hit());
+
+        let legacy = parse_row("3,3,3,1117,[],Germain;Prime,Germain;Prime,Germain;Prime").unwrap();
+        assert!(legacy.classifications_n.is_empty());
+        assert_eq!(legacy.error_bound, None);
+        assert_eq!(parse_row("3,3,3"), Err(ResultsError::MissingFields(3)));
+        assert!(matches!(parse_row("3,3,x,1,[],[],[],[]"), Err(ResultsError::InvalidNumber(_))));
+    }
+}
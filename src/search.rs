@@ -0,0 +1,1463 @@
+use num_bigint::{BigInt, BigUint};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use tracing::{debug, info_span, instrument};
+
+use crate::annotate::{annotation_fields, ANNOTATION_HEADER};
+use crate::classify::{
+    classify_prime_with_config, classify_with_verdict, germain_chain_depth, pseudoprime_tags,
+};
+use crate::collisions::RepresentationIndex;
+use crate::constraint::TupleConstraints;
+use crate::decompose::{squares_fields, SQUARES_HEADER};
+use crate::filter::Filter;
+use crate::form::QuadraticForm;
+use crate::pool::{CandidatePool, TupleSpace};
+use crate::primality::PrimalityConfig;
+use crate::results::{parse_row, ResultRow};
+
+pub const CSV_HEADER: &str =
+    "x,y,z,n,classifications_n,classifications_x,classifications_y,classifications_z,error_bound_n";
+
+/// N for the universal form; see `QuadraticForm::evaluate` for other forms.
+pub fn compute_n(x: &BigUint, y: &BigUint, z: &BigUint) -> BigUint {
+    QuadraticForm::universal().evaluate_unsigned(x, y, z)
+}
+
+/// The first few known primes used as the (x, y, z) candidate pool.
+pub fn default_pool() -> Vec<BigInt> {
+    [
+        3u32, 5, 7, 11, 13, 23, 47, 83, 107, 167, 227, 359, 383, 467, 479, 503, 563, 587, 719,
+        839, 863, 887, 983, 1019, 1187, 1283, 1307, 1319, 1367, 1439, 1487, 1523, 1619, 1823,
+        1907,
+    ]
+    .iter()
+    .map(|&p| BigInt::from(p))
+    .collect()
+}
+
+/// The pool together with the negation of each entry, for exploring signed variable ranges.
+pub fn signed_pool(pool: &[BigInt]) -> Vec<BigInt> {
+    pool.iter().flat_map(|v| [v.clone(), -v]).collect()
+}
+
+/// Evaluate `form` on every (x, y, z) in `pool`³, or in the product of per-variable pools,
+/// in x-major order, taking the unsigned fast path when neither the form nor the pool has
+/// negative entries.
+pub fn evaluate_all<'a, P: TupleSpace + ?Sized>(
+    form: &'a QuadraticForm,
+    pool: &'a P,
+) -> impl Iterator<Item = (Cow<'a, BigInt>, Cow<'a, BigInt>, Cow<'a, BigInt>, BigInt)> + 'a {
+    evaluate_from(form, pool, 0).map(|(_, x, y, z, n)| (x, y, z, n))
+}
+
+/// Like `evaluate_all`, but starting at tuple index `start` and yielding each tuple's index.
+pub fn evaluate_from<'a, P: TupleSpace + ?Sized>(
+    form: &'a QuadraticForm,
+    pool: &'a P,
+    start: u64,
+) -> impl Iterator<Item = (u64, Cow<'a, BigInt>, Cow<'a, BigInt>, Cow<'a, BigInt>, BigInt)> + 'a {
+    let evaluator = TupleEvaluator::new(form, pool);
+    (start..evaluator.len()).map(move |index| {
+        let (x, y, z, n) = evaluator.tuple(index);
+        (index, x, y, z, n)
+    })
+}
+
+/// Like `evaluate_from`, but skipping the tuples `constraints` rules out without evaluating
+/// them.
+pub fn evaluate_admitted_from<'a, P: TupleSpace + ?Sized>(
+    form: &'a QuadraticForm,
+    pool: &'a P,
+    constraints: &'a TupleConstraints,
+    start: u64,
+) -> impl Iterator<Item = (u64, Cow<'a, BigInt>, Cow<'a, BigInt>, Cow<'a, BigInt>, BigInt)> + 'a {
+    let evaluator = TupleEvaluator::new(form, pool);
+    (start..evaluator.len()).filter_map(move |index| {
+        let (x, y, z) = evaluator.inputs(index);
+        if !constraints.admits(&x, &y, &z) {
+            return None;
+        }
+        let n = evaluator.value(&x, &y, &z);
+        Some((index, x, y, z, n))
+    })
+}
+
+/// Random access to the x-major tuples of a `TupleSpace` and their values, so batches can be
+/// evaluated in parallel.
+struct TupleEvaluator<'a, P: ?Sized> {
+    form: &'a QuadraticForm,
+    pool: &'a P,
+    /// Whether the unsigned fast path applies
+    unsigned: bool,
+}
+
+impl<'a, P: TupleSpace + ?Sized> TupleEvaluator<'a, P> {
+    fn new(form: &'a QuadraticForm, pool: &'a P) -> Self {
+        TupleEvaluator {
+            form,
+            pool,
+            unsigned: form.is_nonnegative() && pool.all_nonnegative(),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.pool.tuples()
+    }
+
+    fn tuple(&self, index: u64) -> (Cow<'a, BigInt>, Cow<'a, BigInt>, Cow<'a, BigInt>, BigInt) {
+        let (x, y, z) = self.inputs(index);
+        let n = self.value(&x, &y, &z);
+        (x, y, z, n)
+    }
+
+    fn inputs(&self, index: u64) -> (Cow<'a, BigInt>, Cow<'a, BigInt>, Cow<'a, BigInt>) {
+        let [_, len_y, len_z] = self.pool.dims().map(|len| len as u64);
+        (
+            self.pool.value(0, (index / (len_y * len_z)) as usize),
+            self.pool.value(1, (index / len_z % len_y) as usize),
+            self.pool.value(2, (index % len_z) as usize),
+        )
+    }
+
+    fn value(&self, x: &BigInt, y: &BigInt, z: &BigInt) -> BigInt {
+        if self.unsigned {
+            BigInt::from(self.form.evaluate_unsigned(x.magnitude(), y.magnitude(), z.magnitude()))
+        } else {
+            self.form.evaluate(x, y, z)
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid tuple order '{0}', expected xyz or bands")]
+pub struct TupleOrderError(pub String);
+
+/// The order in which a search visits its tuples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TupleOrder {
+    /// x-major over the pool as given
+    #[default]
+    XMajor,
+    /// Band by band of the largest input magnitude: every tuple drawn from the k smallest
+    /// pool entries comes before any using the (k + 1)-th, so for a positive-definite form
+    /// hits turn up in roughly increasing N and `max_hits` keeps the smallest ones
+    Bands,
+}
+
+impl FromStr for TupleOrder {
+    type Err = TupleOrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "xyz" => Ok(TupleOrder::XMajor),
+            "bands" => Ok(TupleOrder::Bands),
+            _ => Err(TupleOrderError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TupleOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TupleOrder::XMajor => write!(f, "xyz"),
+            TupleOrder::Bands => write!(f, "bands"),
+        }
+    }
+}
+
+/// Where each position of a search falls in its `TupleSpace` under a `TupleOrder`.
+///
+/// Each variable's candidates are ranked by magnitude, and band k holds the tuples whose
+/// largest rank is k (3k² + 3k + 1 of them when all three pools are longer than k): first
+/// those with x of rank k, then y, then z. A position maps to the tuple's x-major index,
+/// whose witness stream it keeps, so either order writes the same rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TupleSchedule {
+    dims: [u64; 3],
+    /// Candidate indices of x, y and z by increasing magnitude, ties in pool order; `None`
+    /// for x-major
+    ranks: Option<[Vec<usize>; 3]>,
+}
+
+impl TupleSchedule {
+    pub fn new<P: TupleSpace + ?Sized>(pool: &P, order: TupleOrder) -> Self {
+        let dims = pool.dims();
+        let ranks = (order == TupleOrder::Bands).then(|| {
+            [0, 1, 2].map(|variable| {
+                let mut ranks: Vec<usize> = (0..dims[variable]).collect();
+                ranks.sort_by_cached_key(|&i| pool.value(variable, i).magnitude().clone());
+                ranks
+            })
+        });
+        TupleSchedule { dims: dims.map(|len| len as u64), ranks }
+    }
+
+    /// Tuples whose ranks are all below `k`.
+    fn below(&self, k: u64) -> u64 {
+        self.dims.iter().map(|&len| len.min(k)).product()
+    }
+
+    /// Candidate indices of the x, y and z of the tuple at `position`.
+    pub fn indices(&self, position: u64) -> (usize, usize, usize) {
+        let [len_x, len_y, len_z] = self.dims;
+        let Some(ranks) = &self.ranks else {
+            return (
+                (position / (len_y * len_z)) as usize,
+                (position / len_z % len_y) as usize,
+                (position % len_z) as usize,
+            );
+        };
+        // The band: the k with below(k) <= position < below(k + 1)
+        let (mut k, mut upper) = (0, len_x.max(len_y).max(len_z));
+        while k + 1 < upper {
+            let middle = (k + upper) / 2;
+            if self.below(middle) <= position {
+                k = middle;
+            } else {
+                upper = middle;
+            }
+        }
+        let mut offset = position - self.below(k);
+        // Ranks up to k, and strictly below k
+        let [_, upto_y, upto_z] = self.dims.map(|len| len.min(k + 1));
+        let [under_x, under_y, _] = self.dims.map(|len| len.min(k));
+        let (x, y, z) = if k < len_x && offset < upto_y * upto_z {
+            (k, offset / upto_z, offset % upto_z)
+        } else {
+            if k < len_x {
+                offset -= upto_y * upto_z;
+            }
+            if k < len_y && offset < under_x * upto_z {
+                (offset / upto_z, k, offset % upto_z)
+            } else {
+                if k < len_y {
+                    offset -= under_x * upto_z;
+                }
+                (offset / under_y, offset % under_y, k)
+            }
+        };
+        (ranks[0][x as usize], ranks[1][y as usize], ranks[2][z as usize])
+    }
+
+    /// The x-major index of the tuple at `position`.
+    pub fn x_major(&self, position: u64) -> u64 {
+        let (x, y, z) = self.indices(position);
+        let [_, len_y, len_z] = self.dims;
+        (x as u64 * len_y + y as u64) * len_z + z as u64
+    }
+}
+
+/// How far a search has got: tuples processed in the search's `TupleOrder` and rows written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchProgress {
+    pub tuples: u64,
+    pub hits: u64,
+}
+
+/// A flag raised from elsewhere, e.g. a Ctrl-C handler, to stop a search cleanly. Clones
+/// share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Signals are equal when they share a flag.
+impl PartialEq for StopSignal {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StopSignal {}
+
+/// Settings for a search run, built up from `SearchConfig::new`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// Seed of the per-tuple witness streams
+    pub seed: u64,
+    /// Primality testing applied to N and when classifying x, y, z
+    pub primality: PrimalityConfig,
+    /// Also write composite N that are base-2 pseudoprimes, tagged instead of "Prime"; they
+    /// then count as hits too
+    pub pseudoprimes: bool,
+    /// Worker threads; `None` uses the current rayon pool
+    pub num_threads: Option<usize>,
+    /// Run the workers at the lowest scheduling priority
+    pub low_priority: bool,
+    /// Stop once this many rows have been written, counting those before a resume
+    pub max_hits: Option<u64>,
+    /// Stop at the first checkpoint after this much wall-clock time
+    pub time_budget: Option<Duration>,
+    /// Stop at the first checkpoint after this is raised
+    pub stop_signal: Option<StopSignal>,
+    /// Only rows matching this filter are written (and counted as hits)
+    pub filter: Option<Filter>,
+    /// Tuples failing these are skipped before N is computed
+    pub constraints: TupleConstraints,
+    /// Append the `annotate` columns to every row
+    pub annotate: bool,
+    /// Append the `decompose` sums-of-squares columns to every row
+    pub squares: bool,
+    /// Append a `germain_depth` column with the Sophie Germain chain depth of prime hits
+    pub germain_depth: bool,
+    /// Append an `r` column with the number of pool tuples representing N, looked up here
+    pub representations: Option<Arc<RepresentationIndex>>,
+    /// The order tuples are visited and written in
+    pub order: TupleOrder,
+}
+
+impl SearchConfig {
+    pub fn new(seed: u64) -> Self {
+        SearchConfig {
+            seed,
+            primality: PrimalityConfig::default(),
+            pseudoprimes: false,
+            num_threads: None,
+            low_priority: false,
+            max_hits: None,
+            time_budget: None,
+            stop_signal: None,
+            filter: None,
+            constraints: TupleConstraints::default(),
+            annotate: false,
+            squares: false,
+            germain_depth: false,
+            representations: None,
+            order: TupleOrder::XMajor,
+        }
+    }
+
+    pub fn primality(mut self, primality: PrimalityConfig) -> Self {
+        self.primality = primality;
+        self
+    }
+
+    pub fn pseudoprimes(mut self, pseudoprimes: bool) -> Self {
+        self.pseudoprimes = pseudoprimes;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    pub fn stop_after_hits(mut self, hits: u64) -> Self {
+        self.max_hits = Some(hits);
+        self
+    }
+
+    pub fn stop_after(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn stop_on(mut self, signal: StopSignal) -> Self {
+        self.stop_signal = Some(signal);
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn constraints(mut self, constraints: TupleConstraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn annotate(mut self, annotate: bool) -> Self {
+        self.annotate = annotate;
+        self
+    }
+
+    pub fn squares(mut self, squares: bool) -> Self {
+        self.squares = squares;
+        self
+    }
+
+    pub fn germain_depth(mut self, germain_depth: bool) -> Self {
+        self.germain_depth = germain_depth;
+        self
+    }
+
+    pub fn order(mut self, order: TupleOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Add the `r` column, counted from `index`, which must be built over the searched form
+    /// and pool.
+    pub fn representations(mut self, index: Arc<RepresentationIndex>) -> Self {
+        self.representations = Some(index);
+        self
+    }
+}
+
+/// A rayon pool builder with `num_threads` workers (0 or `None` for one per CPU), each lowered
+/// to the weakest scheduling priority when `low_priority` is set.
+///
+/// Priority is only lowered on Unix, where Linux applies `setpriority` per thread; elsewhere
+/// the flag is ignored.
+pub fn thread_pool_builder(num_threads: Option<usize>, low_priority: bool) -> rayon::ThreadPoolBuilder {
+    let builder = rayon::ThreadPoolBuilder::new().num_threads(num_threads.unwrap_or(0));
+    if low_priority {
+        builder.start_handler(|_| lower_thread_priority())
+    } else {
+        builder
+    }
+}
+
+#[cfg(unix)]
+fn lower_thread_priority() {
+    // Best effort: failing to renice only means the search competes at normal priority
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS as _, 0, 19);
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_thread_priority() {}
+
+/// Tuples between calls to a search's checkpoint callback.
+pub const CHECKPOINT_INTERVAL: u64 = 4096;
+
+/// Evaluate `form` over every (x, y, z) drawn from `primes` and write each prime N as a CSV row.
+///
+/// Primality is sign-aware: a negative N is a hit when |N| is prime, and the tags of x, y, z
+/// and N always describe absolute values. With `pseudoprimes` set, composite N values that
+/// are base-2 pseudoprimes are written too, tagged instead of "Prime". Tuple `i` draws its
+/// witnesses from ChaCha20 stream `i` of the configured seed, so a resumed search writes
+/// exactly what an uninterrupted one would.
+///
+/// Returns the number of rows written.
+pub fn search<W: Write, P: TupleSpace + ?Sized>(
+    form: &QuadraticForm,
+    primes: &P,
+    out: &mut W,
+    config: &SearchConfig,
+) -> io::Result<usize> {
+    let progress = search_from(form, primes, out, config, SearchProgress::default(), |_, _| Ok(()))?;
+    Ok(progress.hits as usize)
+}
+
+/// Run (or continue) a search from `start`, calling `checkpoint` every
+/// `CHECKPOINT_INTERVAL` tuples and once at the end with the progress so far.
+///
+/// A search stopped early by `max_hits` or `time_budget` still takes its final checkpoint,
+/// so it can be resumed; the returned progress then covers fewer tuples than the space holds.
+///
+/// The header is written only when starting from the first tuple.
+#[instrument(level = "info", skip_all, fields(form = %form, start = start.tuples))]
+pub fn search_from<W, P, F>(
+    form: &QuadraticForm,
+    primes: &P,
+    out: &mut W,
+    config: &SearchConfig,
+    start: SearchProgress,
+    mut checkpoint: F,
+) -> io::Result<SearchProgress>
+where
+    W: Write,
+    P: TupleSpace + ?Sized,
+    F: FnMut(&SearchProgress, &mut W) -> io::Result<()>,
+{
+    if start.tuples == 0 {
+        writeln!(out, "{}", header(config))?;
+    }
+
+    let thread_pool = worker_pool(config)?;
+    let evaluator = TupleEvaluator::new(form, primes);
+    let schedule = TupleSchedule::new(primes, config.order);
+    let started = Instant::now();
+    let mut progress = start;
+
+    // Evaluate one checkpoint interval of tuples in parallel, then write its rows in order
+    while progress.tuples < evaluator.len() {
+        if config.max_hits.is_some_and(|max| progress.hits >= max)
+            || config.time_budget.is_some_and(|budget| started.elapsed() >= budget)
+            || config.stop_signal.as_ref().is_some_and(StopSignal::is_stopped)
+        {
+            break;
+        }
+        let end = ((progress.tuples / CHECKPOINT_INTERVAL + 1) * CHECKPOINT_INTERVAL).min(evaluator.len());
+        let _batch = info_span!("search_batch", start = progress.tuples, end).entered();
+        let evaluate_batch = || -> Vec<Option<String>> {
+            (progress.tuples..end)
+                .into_par_iter()
+                .map(|position| search_tuple(&evaluator, config, schedule.x_major(position)))
+                .collect()
+        };
+        let rows = match &thread_pool {
+            Some(thread_pool) => thread_pool.install(evaluate_batch),
+            None => evaluate_batch(),
+        };
+        let batch_start = progress.tuples;
+        progress.tuples = end;
+        for (index, row) in (batch_start..).zip(rows) {
+            let Some(row) = row else { continue };
+            writeln!(out, "{}", row)?;
+            progress.hits += 1;
+            if config.max_hits == Some(progress.hits) {
+                // Resuming continues just after the last hit written
+                progress.tuples = index + 1;
+                break;
+            }
+        }
+
+        debug!(tuples = progress.tuples, hits = progress.hits, "batch written");
+
+        if progress.tuples.is_multiple_of(CHECKPOINT_INTERVAL) {
+            checkpoint(&progress, out)?;
+        }
+    }
+
+    checkpoint(&progress, out)?;
+    Ok(progress)
+}
+
+/// A dedicated rayon pool when `config` asks for particular threads, otherwise `None` to run
+/// on the current one.
+fn worker_pool(config: &SearchConfig) -> io::Result<Option<rayon::ThreadPool>> {
+    if config.num_threads.is_some() || config.low_priority {
+        let thread_pool = thread_pool_builder(config.num_threads, config.low_priority)
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Some(thread_pool))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The results header, with the optional columns `config` adds.
+pub(crate) fn header(config: &SearchConfig) -> String {
+    let mut header = CSV_HEADER.to_string();
+    if config.annotate {
+        header = format!("{},{}", header, ANNOTATION_HEADER);
+    }
+    if config.squares {
+        header = format!("{},{}", header, SQUARES_HEADER);
+    }
+    if config.representations.is_some() {
+        header.push_str(",r");
+    }
+    if config.germain_depth {
+        header.push_str(",germain_depth");
+    }
+    header
+}
+
+/// One generation of a closure search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GenerationSummary {
+    /// 1 for the search over the starting pool
+    pub generation: u32,
+    /// Entries in the pool the generation drew from
+    pub pool_len: usize,
+    /// Tuples evaluated: those using at least one entry added by the previous generation
+    pub tuples: u64,
+    /// Rows written
+    pub hits: u64,
+    /// Distinct primes |N| not yet in the pool, added for the next generation
+    pub new_primes: usize,
+}
+
+/// The outcome of `closure_search`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClosureReport {
+    pub generations: Vec<GenerationSummary>,
+    /// The generation each pool value (by absolute value) first appeared in; the starting
+    /// pool is generation 0
+    pub generation_of: BTreeMap<BigUint, u32>,
+    /// The (x, y, z) each prime added by a generation was first produced by
+    pub parents: BTreeMap<BigUint, (BigInt, BigInt, BigInt)>,
+    /// Whether `max_hits`, `time_budget` or the stop signal ended the search early
+    pub stopped: bool,
+}
+
+/// How a value reached the pool of a closure search, as returned by `lineage_of`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lineage {
+    /// The absolute value
+    pub value: BigUint,
+    /// 0 for the starting pool
+    pub generation: u32,
+    /// The (x, y, z) that produced `value`; `None` for the starting pool
+    pub tuple: Option<(BigInt, BigInt, BigInt)>,
+    /// The lineages of |x|, |y| and |z|
+    pub parents: Vec<Lineage>,
+}
+
+impl Lineage {
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{} ", "", self.value, indent = 2 * depth)?;
+        match &self.tuple {
+            Some((x, y, z)) => {
+                writeln!(f, "(generation {}) = F({}, {}, {})", self.generation, x, y, z)?
+            }
+            None => writeln!(f, "(pool)")?,
+        }
+        for parent in &self.parents {
+            parent.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per value, each parent indented under the value it produced.
+impl fmt::Display for Lineage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl ClosureReport {
+    fn record(&mut self, generation: u32, primes: BTreeMap<BigUint, (BigInt, BigInt, BigInt)>) {
+        for (n, tuple) in primes {
+            self.generation_of.insert(n.clone(), generation);
+            self.parents.insert(n, tuple);
+        }
+    }
+
+    /// The derivation tree of `n` (by absolute value) back to the starting pool, or `None`
+    /// if the search never reached it.
+    pub fn lineage_of(&self, n: &BigUint) -> Option<Lineage> {
+        let generation = *self.generation_of.get(n)?;
+        let Some((x, y, z)) = self.parents.get(n) else {
+            return Some(Lineage { value: n.clone(), generation, tuple: None, parents: vec![] });
+        };
+        let parents = [x, y, z]
+            .iter()
+            .map(|v| self.lineage_of(v.magnitude()).expect("parents come from the pool"))
+            .collect();
+        Some(Lineage {
+            value: n.clone(),
+            generation,
+            tuple: Some((x.clone(), y.clone(), z.clone())),
+            parents,
+        })
+    }
+
+    /// Whether the last generation found no new primes, so further ones would find nothing.
+    pub fn reached_fixpoint(&self) -> bool {
+        !self.stopped && self.generations.last().is_some_and(|g| g.new_primes == 0)
+    }
+
+    /// Rows written over all generations.
+    pub fn hits(&self) -> u64 {
+        self.generations.iter().map(|g| g.hits).sum()
+    }
+}
+
+/// Search `form` over `primes`, then repeatedly append the new prime values |N| to the pool
+/// and search again, for at most `depth` generations or until no new primes turn up.
+///
+/// Each generation only evaluates the tuples that use at least one entry added by the one
+/// before it, so no tuple is searched twice. New primes are appended in increasing order,
+/// together with their negations when the pool has negative entries. Rows carry a trailing
+/// `generation` column, and `max_hits` counts rows over all generations. Closure searches
+/// take no checkpoints and cannot be resumed.
+#[instrument(level = "info", skip_all, fields(form = %form, depth))]
+pub fn closure_search<W: Write, P: CandidatePool + ?Sized>(
+    form: &QuadraticForm,
+    primes: &P,
+    out: &mut W,
+    config: &SearchConfig,
+    depth: u32,
+) -> io::Result<ClosureReport> {
+    writeln!(out, "{},generation", header(config))?;
+    let thread_pool = worker_pool(config)?;
+    let started = Instant::now();
+    let signed = !primes.is_nonnegative();
+    let mut pool: Vec<BigInt> = (0..primes.len()).map(|i| primes.get(i).into_owned()).collect();
+    let mut report = ClosureReport::default();
+    for value in &pool {
+        report.generation_of.entry(value.magnitude().clone()).or_insert(0);
+    }
+    let mut hits = 0u64;
+    let mut searched = 0u64;
+
+    for generation in 1..=depth {
+        let evaluator = TupleEvaluator::new(form, &pool);
+        let len = pool.len() as u64;
+        let mut summary =
+            GenerationSummary { generation, pool_len: pool.len(), ..Default::default() };
+        let mut new_primes = BTreeMap::new();
+        let mut tuples = fresh_tuples(searched, len).peekable();
+        while tuples.peek().is_some() {
+            if config.max_hits.is_some_and(|max| hits >= max)
+                || config.time_budget.is_some_and(|budget| started.elapsed() >= budget)
+                || config.stop_signal.as_ref().is_some_and(StopSignal::is_stopped)
+            {
+                report.stopped = true;
+                break;
+            }
+            let batch: Vec<u64> = tuples.by_ref().take(CHECKPOINT_INTERVAL as usize).collect();
+            let _batch = info_span!("closure_batch", generation, tuples = batch.len()).entered();
+            let evaluate_batch = || -> Vec<Option<String>> {
+                batch.par_iter().map(|&index| search_tuple(&evaluator, config, index)).collect()
+            };
+            let rows = match &thread_pool {
+                Some(thread_pool) => thread_pool.install(evaluate_batch),
+                None => evaluate_batch(),
+            };
+            summary.tuples += batch.len() as u64;
+            for row in rows.into_iter().flatten() {
+                writeln!(out, "{},{}", row, generation)?;
+                summary.hits += 1;
+                hits += 1;
+                let row = parse_row(&row).expect("search rows parse");
+                let n = row.n.magnitude();
+                if row.classifications_n.iter().any(|t| t == "Prime")
+                    && !report.generation_of.contains_key(n)
+                {
+                    // The first tuple in x-major order to produce a prime is its derivation
+                    new_primes.entry(n.clone()).or_insert((row.x, row.y, row.z));
+                }
+                if config.max_hits == Some(hits) {
+                    break;
+                }
+            }
+        }
+
+        debug!(generation, hits = summary.hits, new_primes = new_primes.len(), "generation done");
+        summary.new_primes = new_primes.len();
+        report.generations.push(summary);
+        if report.stopped || new_primes.is_empty() {
+            // Primes found by a stopped generation are recorded but never searched
+            report.record(generation, new_primes);
+            break;
+        }
+        searched = len;
+        for n in new_primes.keys() {
+            let n = BigInt::from(n.clone());
+            pool.push(n.clone());
+            if signed {
+                pool.push(-n);
+            }
+        }
+        report.record(generation, new_primes);
+    }
+    Ok(report)
+}
+
+/// Indices, in x-major order, of the tuples drawn from a pool of `len` entries that use at
+/// least one entry at or past `old`.
+fn fresh_tuples(old: u64, len: u64) -> impl Iterator<Item = u64> {
+    (0..len).flat_map(move |x| {
+        (0..len).flat_map(move |y| {
+            let first = if x >= old || y >= old { 0 } else { old };
+            (first..len).map(move |z| (x * len + y) * len + z)
+        })
+    })
+}
+
+/// What a full search would cost, projected from a random sample of its tuples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchEstimate {
+    /// Tuples in the whole search space
+    pub tuples: u64,
+    pub sampled: u64,
+    /// Sampled tuples that produced a row
+    pub sampled_hits: u64,
+    /// Bytes of the sampled rows, newlines included
+    pub sampled_row_bytes: u64,
+    /// Time to evaluate the sample on one thread
+    pub sample_time: Duration,
+    /// Threads the full search would run on
+    pub threads: usize,
+    pub max_hits: Option<u64>,
+    pub header_bytes: u64,
+}
+
+impl SearchEstimate {
+    pub fn per_tuple(&self) -> Duration {
+        self.sample_time / self.sampled.max(1) as u32
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        self.sampled_hits as f64 / self.sampled.max(1) as f64
+    }
+
+    /// Rows the full search would write, capped by `max_hits`.
+    pub fn projected_hits(&self) -> u64 {
+        let hits = (self.hit_rate() * self.tuples as f64).round() as u64;
+        self.max_hits.map_or(hits, |max| hits.min(max))
+    }
+
+    /// Wall-clock time of the full search, assuming the threads scale perfectly and that a
+    /// `max_hits` stop comes after the matching share of the tuples.
+    pub fn projected_runtime(&self) -> Duration {
+        let mut tuples = self.tuples as f64;
+        let expected_hits = self.hit_rate() * tuples;
+        if let Some(max) = self.max_hits.filter(|&max| expected_hits > max as f64) {
+            tuples *= max as f64 / expected_hits;
+        }
+        self.per_tuple().mul_f64(tuples / self.threads.max(1) as f64)
+    }
+
+    /// Size of the results file, header included.
+    pub fn projected_output_bytes(&self) -> u64 {
+        let row = self.sampled_row_bytes as f64 / self.sampled_hits.max(1) as f64;
+        self.header_bytes + (row * self.projected_hits() as f64).round() as u64
+    }
+}
+
+/// Evaluate `samples` tuples drawn at random (seeded by `config.seed`) on this thread and
+/// project the cost and output of the full search from them, without writing anything.
+pub fn estimate<P: TupleSpace + ?Sized>(
+    form: &QuadraticForm,
+    primes: &P,
+    config: &SearchConfig,
+    samples: u64,
+) -> SearchEstimate {
+    let evaluator = TupleEvaluator::new(form, primes);
+    let mut estimate = SearchEstimate {
+        tuples: evaluator.len(),
+        sampled: 0,
+        sampled_hits: 0,
+        sampled_row_bytes: 0,
+        sample_time: Duration::ZERO,
+        threads: config.num_threads.filter(|&n| n > 0).unwrap_or_else(rayon::current_num_threads),
+        max_hits: config.max_hits,
+        header_bytes: header(config).len() as u64 + 1,
+    };
+    if evaluator.len() == 0 {
+        return estimate;
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    let started = Instant::now();
+    for _ in 0..samples {
+        let index = rng.gen_range(0..evaluator.len());
+        estimate.sampled += 1;
+        if let Some(row) = search_tuple(&evaluator, config, index) {
+            estimate.sampled_hits += 1;
+            estimate.sampled_row_bytes += row.len() as u64 + 1;
+        }
+    }
+    estimate.sample_time = started.elapsed();
+    estimate
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid sampler '{0}', expected uniform or halton")]
+pub struct SamplerError(pub String);
+
+/// How `sample_density` picks tuples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampler {
+    /// Independent uniform draws
+    Uniform,
+    /// A Halton sequence in bases 2, 3, 5 over the x, y, z indices, randomly shifted so
+    /// that the estimate stays unbiased; it covers the cube more evenly than uniform draws
+    Halton,
+}
+
+impl FromStr for Sampler {
+    type Err = SamplerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "uniform" => Ok(Sampler::Uniform),
+            "halton" => Ok(Sampler::Halton),
+            _ => Err(SamplerError(s.to_string())),
+        }
+    }
+}
+
+/// The radical inverse of `i` in `base`: its digits mirrored about the point, in [0, 1).
+fn radical_inverse(mut i: u64, base: u64) -> f64 {
+    let (mut value, mut scale) = (0.0, 1.0 / base as f64);
+    while i > 0 {
+        value += (i % base) as f64 * scale;
+        i /= base;
+        scale /= base as f64;
+    }
+    value
+}
+
+/// Hit density over a search space, estimated from sampled tuples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DensityEstimate {
+    /// Tuples in the whole search space
+    pub tuples: u64,
+    pub samples: u64,
+    /// Sampled tuples that produced a row
+    pub hits: u64,
+}
+
+impl DensityEstimate {
+    /// The fraction of sampled tuples that were hits.
+    pub fn density(&self) -> f64 {
+        self.hits as f64 / self.samples.max(1) as f64
+    }
+
+    /// The Wilson score interval for the density at `z` standard deviations (1.96 for 95%),
+    /// which unlike the normal approximation stays inside [0, 1] and is usable with few hits.
+    pub fn confidence_interval(&self, z: f64) -> (f64, f64) {
+        if self.samples == 0 {
+            return (0.0, 1.0);
+        }
+        let n = self.samples as f64;
+        let p = self.density();
+        let denominator = 1.0 + z * z / n;
+        let centre = (p + z * z / (2.0 * n)) / denominator;
+        let spread = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+        ((centre - spread).max(0.0), (centre + spread).min(1.0))
+    }
+
+    /// Hits an exhaustive search would find, at the estimated density.
+    pub fn projected_hits(&self) -> f64 {
+        self.density() * self.tuples as f64
+    }
+}
+
+/// Estimate the hit density of the search `config` describes from `samples` tuples picked by
+/// `sampler` (seeded by `config.seed`), evaluated in parallel; rows count as hits exactly as
+/// they would in `search`, so filters apply.
+pub fn sample_density<P: TupleSpace + ?Sized>(
+    form: &QuadraticForm,
+    primes: &P,
+    config: &SearchConfig,
+    samples: u64,
+    sampler: Sampler,
+) -> DensityEstimate {
+    let evaluator = TupleEvaluator::new(form, primes);
+    let dims = primes.dims().map(|len| len as u64);
+    if evaluator.len() == 0 {
+        return DensityEstimate { tuples: 0, samples: 0, hits: 0 };
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    let indices: Vec<u64> = match sampler {
+        Sampler::Uniform => (0..samples).map(|_| rng.gen_range(0..evaluator.len())).collect(),
+        Sampler::Halton => {
+            let shift: [f64; 3] = rng.gen();
+            let coordinate = |i: u64, axis: usize| {
+                let u = (radical_inverse(i, [2, 3, 5][axis]) + shift[axis]).fract();
+                ((u * dims[axis] as f64) as u64).min(dims[axis] - 1)
+            };
+            (1..=samples)
+                .map(|i| {
+                    (coordinate(i, 0) * dims[1] + coordinate(i, 1)) * dims[2] + coordinate(i, 2)
+                })
+                .collect()
+        }
+    };
+    let hits = indices
+        .into_par_iter()
+        .filter(|&index| search_tuple(&evaluator, config, index).is_some())
+        .count() as u64;
+    DensityEstimate { tuples: evaluator.len(), samples, hits }
+}
+
+/// The CSV row for tuple `index`, or `None` if the tuple fails a constraint or its N is
+/// neither prime nor, when `pseudoprimes` is set, a tagged pseudoprime.
+fn search_tuple<P: TupleSpace + ?Sized>(
+    evaluator: &TupleEvaluator<P>,
+    config: &SearchConfig,
+    index: u64,
+) -> Option<String> {
+    let (x, y, z) = evaluator.inputs(index);
+    if !config.constraints.admits(&x, &y, &z) {
+        return None;
+    }
+    let n = evaluator.value(&x, &y, &z);
+    tuple_row(config, index, &x, &y, &z, &n)
+}
+
+/// The CSV row for tuple `index`, already evaluated to `n`; see `search_tuple`.
+pub(crate) fn tuple_row(
+    config: &SearchConfig,
+    index: u64,
+    x: &BigInt,
+    y: &BigInt,
+    z: &BigInt,
+    n: &BigInt,
+) -> Option<String> {
+    let mut rng = ChaCha20Rng::seed_from_u64(config.seed);
+    rng.set_stream(index);
+    let rng = &mut rng;
+    let primality_config = &config.primality;
+    let magnitude = n.magnitude();
+
+    // Proceed only if |N| is prime, or a pseudoprime worth cataloguing when asked for
+    let primality = primality_config.test(magnitude, rng);
+    let classifications_n = if primality.is_prime {
+        classify_with_verdict(magnitude, true, primality_config, rng)
+    } else if config.pseudoprimes {
+        pseudoprime_tags(magnitude)
+    } else {
+        return None;
+    };
+    if classifications_n.is_empty() {
+        return None;
+    }
+    let classifications_x = classify_prime_with_config(x.magnitude(), primality_config, rng);
+    let classifications_y = classify_prime_with_config(y.magnitude(), primality_config, rng);
+    let classifications_z = classify_prime_with_config(z.magnitude(), primality_config, rng);
+
+    if let Some(filter) = &config.filter {
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+        let row = ResultRow {
+            x: x.clone(),
+            y: y.clone(),
+            z: z.clone(),
+            n: n.clone(),
+            classifications_n: tags(&classifications_n),
+            classifications_x: tags(&classifications_x),
+            classifications_y: tags(&classifications_y),
+            classifications_z: tags(&classifications_z),
+            error_bound: Some(primality.error_bound),
+        };
+        if !filter.matches(&row) {
+            return None;
+        }
+    }
+
+    let mut row = format!(
+        "{},{},{},{},{:?},{:?},{:?},{:?},{:e}",
+        x, y, z, n, classifications_n, classifications_x, classifications_y, classifications_z,
+        primality.error_bound
+    );
+    if config.annotate {
+        row.push(',');
+        row.push_str(&annotation_fields(magnitude));
+    }
+    if config.squares && primality.is_prime {
+        row.push(',');
+        row.push_str(&squares_fields(magnitude));
+    } else if config.squares {
+        row.push_str(",,");
+    }
+    if let Some(index) = &config.representations {
+        row.push_str(&format!(",{}", index.count(n)));
+    }
+    if config.germain_depth && primality.is_prime {
+        row.push_str(&format!(",{}", germain_chain_depth(magnitude, primality_config, rng)));
+    } else if config.germain_depth {
+        row.push(',');
+    }
+    Some(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::VariablePools;
+
+    #[test]
+    fn test_signed_evaluation_matches_unsigned_fast_path() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..5].to_vec();
+        for (x, y, z, n) in evaluate_all(&form, &pool) {
+            assert_eq!(n, form.evaluate(&x, &y, &z));
+        }
+
+        // x² - y² takes negative values, which count as hits when |N| is prime
+        let form = QuadraticForm::new([1, 0, -1, 0, 0, 0, 0]);
+        let pool = signed_pool(&[BigInt::from(2), BigInt::from(3)]);
+        let values: Vec<BigInt> = evaluate_all(&form, &pool).map(|(_, _, _, n)| n).collect();
+        assert_eq!(values.len(), 64);
+        assert!(values.contains(&BigInt::from(-5)));
+        assert!(values.contains(&BigInt::from(5)));
+    }
+
+    #[test]
+    fn test_pseudoprime_rows_are_opt_in() {
+        // N = 561 for every tuple: a Carmichael number, so a base-2 pseudoprime
+        let form = QuadraticForm::new([561, 0, 0, 0, 0, 0, 0]);
+        let pool = signed_pool(&[BigInt::from(1)]);
+        let rows = |config: &SearchConfig| {
+            let mut out = Vec::new();
+            let hits = search(&form, &pool, &mut out, config).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            let rows: Vec<String> = out.lines().skip(1).map(String::from).collect();
+            assert_eq!(rows.len(), hits);
+            rows
+        };
+        assert!(rows(&SearchConfig::new(1)).is_empty());
+        let tagged = rows(&SearchConfig::new(1).pseudoprimes(true));
+        assert_eq!(tagged.len(), 8);
+        assert!(tagged.iter().all(|row| row.contains(",561,") && row.contains("Carmichael")));
+    }
+
+    #[test]
+    fn test_seed_reproduces_the_witness_stream() {
+        // N = 1891 = 31 * 61 for every tuple, which about a quarter of Miller-Rabin witnesses
+        // pass, so with one round and no BPSW the rows written follow the witnesses drawn
+        let form = QuadraticForm::new([1891, 0, 0, 0, 0, 0, 0]);
+        let pool = signed_pool(&[BigInt::from(1)]);
+        let primality = PrimalityConfig {
+            rounds: 1,
+            use_bpsw: false,
+            ..PrimalityConfig::default()
+        };
+        let run = |seed| {
+            let mut out = Vec::new();
+            search(&form, &pool, &mut out, &SearchConfig::new(seed).primality(primality)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let first = run(1);
+        assert_eq!(run(1), first);
+        assert_ne!(run(2), first);
+    }
+
+    #[test]
+    fn test_thread_settings_do_not_change_output() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..8].to_vec();
+        let run = |config: SearchConfig| {
+            let mut out = Vec::new();
+            search(&form, &pool, &mut out, &config).unwrap();
+            out
+        };
+        let expected = run(SearchConfig::new(3));
+        assert_eq!(run(SearchConfig::new(3).num_threads(2).low_priority(true)), expected);
+    }
+
+    #[test]
+    fn test_stop_after_hits_resumes_cleanly() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..8].to_vec();
+        let mut full = Vec::new();
+        search(&form, &pool, &mut full, &SearchConfig::new(3)).unwrap();
+
+        let mut out = Vec::new();
+        let config = SearchConfig::new(3).stop_after_hits(5);
+        let noop = |_: &SearchProgress, _: &mut Vec<u8>| Ok(());
+        let stopped = search_from(&form, &pool, &mut out, &config, SearchProgress::default(), noop).unwrap();
+        assert_eq!(stopped.hits, 5);
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 6);
+        search_from(&form, &pool, &mut out, &SearchConfig::new(3), stopped, noop).unwrap();
+        assert_eq!(out, full);
+    }
+
+    #[test]
+    fn test_band_order_finds_small_hits_first() {
+        let form = QuadraticForm::universal();
+        // Out of order, so bands have to rank the pool by magnitude
+        let mut pool = default_pool()[..9].to_vec();
+        pool.reverse();
+        let schedule = TupleSchedule::new(&pool, TupleOrder::Bands);
+        let mut seen: Vec<u64> = (0..729).map(|i| schedule.x_major(i)).collect();
+        let largest_rank = |i: u64| {
+            let (x, y, z) = schedule.indices(i);
+            [x, y, z].iter().map(|&j| 8 - j).max().unwrap()
+        };
+        assert!((1..729).all(|i| largest_rank(i - 1) <= largest_rank(i)));
+        seen.sort_unstable();
+        assert_eq!(seen, (0..729).collect::<Vec<_>>());
+
+        let run = |config: SearchConfig| {
+            let mut out = Vec::new();
+            search(&form, &pool, &mut out, &config).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            out.lines().skip(1).map(str::to_string).collect::<Vec<_>>()
+        };
+        let mut x_major = run(SearchConfig::new(3));
+        let mut bands = run(SearchConfig::new(3).order(TupleOrder::Bands));
+        let n = |line: &String| parse_row(line).unwrap().n;
+        // The first hits in band order come from the smallest inputs
+        let first = run(SearchConfig::new(3).order(TupleOrder::Bands).stop_after_hits(3));
+        let smallest = x_major.iter().map(n).min().unwrap();
+        assert!(first.iter().any(|line| n(line) == smallest));
+        // The same rows, witnesses included, only in another order
+        x_major.sort();
+        bands.sort();
+        assert_eq!(bands, x_major);
+    }
+
+    #[test]
+    fn test_variable_pools() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool();
+        let pools = VariablePools {
+            x: pool[..3].to_vec(),
+            y: pool[4..9].iter().rev().cloned().collect(),
+            z: pool[2..4].to_vec(),
+        };
+        let tuples: Vec<_> = evaluate_all(&form, &pools).collect();
+        assert_eq!(tuples.len(), 30);
+        assert_eq!((&*tuples[7].0, &*tuples[7].1, &*tuples[7].2), (&pool[0], &pool[5], &pool[3]));
+
+        // Bands cover the box once each, the largest rank never decreasing
+        let schedule = TupleSchedule::new(&pools, TupleOrder::Bands);
+        let rank = |variable: usize, index: usize| match variable {
+            1 => 4 - index,
+            _ => index,
+        };
+        let largest_rank = |i: u64| {
+            let (x, y, z) = schedule.indices(i);
+            rank(0, x).max(rank(1, y)).max(rank(2, z))
+        };
+        assert!((1..30).all(|i| largest_rank(i - 1) <= largest_rank(i)));
+        let mut seen: Vec<u64> = (0..30).map(|i| schedule.x_major(i)).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..30).collect::<Vec<_>>());
+
+        let run = |config: SearchConfig| {
+            let mut out = Vec::new();
+            search(&form, &pools, &mut out, &config).unwrap();
+            let mut rows: Vec<String> =
+                String::from_utf8(out).unwrap().lines().skip(1).map(str::to_string).collect();
+            rows.sort();
+            rows
+        };
+        let rows = run(SearchConfig::new(3));
+        assert!(!rows.is_empty());
+        assert_eq!(run(SearchConfig::new(3).order(TupleOrder::Bands)), rows);
+    }
+
+    #[test]
+    fn test_constraints_skip_tuples() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..10].to_vec();
+        let run = |config: SearchConfig| {
+            let mut out = Vec::new();
+            search(&form, &pool, &mut out, &config).unwrap();
+            let rows: Vec<ResultRow> = String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .skip(1)
+                .map(|line| parse_row(line).unwrap())
+                .collect();
+            rows
+        };
+        let constraints = TupleConstraints::parse(&["x < y < z", "(x + y) % 4 == 0"]).unwrap();
+        let constrained = run(SearchConfig::new(5).constraints(constraints.clone()));
+        let expected: Vec<ResultRow> = run(SearchConfig::new(5))
+            .into_iter()
+            .filter(|row| constraints.admits(&row.x, &row.y, &row.z))
+            .collect();
+        assert!(!constrained.is_empty());
+        assert_eq!(constrained, expected);
+    }
+
+    #[test]
+    fn test_estimate_projects_the_full_search() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..10].to_vec();
+        let config = SearchConfig::new(3).num_threads(2);
+        let mut full = Vec::new();
+        search(&form, &pool, &mut full, &config).unwrap();
+        let rows = full.iter().filter(|&&b| b == b'\n').count() as f64 - 1.0;
+
+        let estimate = estimate(&form, &pool, &config, 4000);
+        assert_eq!((estimate.tuples, estimate.sampled, estimate.threads), (1000, 4000, 2));
+        assert!((estimate.projected_hits() as f64 / rows - 1.0).abs() < 0.05);
+        assert!((estimate.projected_output_bytes() as f64 / full.len() as f64 - 1.0).abs() < 0.05);
+        assert_eq!(estimate.projected_runtime(), estimate.per_tuple() * 500);
+
+        let capped = super::estimate(&form, &pool, &config.clone().stop_after_hits(10), 100);
+        assert_eq!(capped.projected_hits(), 10);
+        assert!(capped.projected_runtime() < capped.per_tuple() * 500);
+        let empty = super::estimate(&form, &pool[..0], &config, 100);
+        assert_eq!((empty.sampled, empty.projected_output_bytes()), (0, empty.header_bytes));
+    }
+
+    #[test]
+    fn test_sampled_density_brackets_the_exhaustive_one() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..20].to_vec();
+        let config = SearchConfig::new(3);
+        let mut full = Vec::new();
+        search(&form, &pool, &mut full, &config).unwrap();
+        let exact = (full.iter().filter(|&&b| b == b'\n').count() - 1) as f64 / 8000.0;
+
+        for sampler in [Sampler::Uniform, Sampler::Halton] {
+            let estimate = sample_density(&form, &pool, &config, 2000, sampler);
+            assert_eq!((estimate.tuples, estimate.samples), (8000, 2000));
+            let (low, high) = estimate.confidence_interval(3.0);
+            assert!(low < exact && exact < high, "{:?}: {} vs {:?}", sampler, exact, (low, high));
+            assert!(high - low < 0.1);
+            assert_eq!(estimate.projected_hits(), estimate.density() * 8000.0);
+        }
+        // 30 consecutive Halton points put one point in each cell of a 2 x 3 x 5 grid
+        let cell = |i: u64, base: u64| (radical_inverse(i, base) * base as f64) as u64;
+        let cells: std::collections::HashSet<_> =
+            (1..=30u64).map(|i| (cell(i, 2), cell(i, 3), cell(i, 5))).collect();
+        assert_eq!(cells.len(), 30);
+
+        let none = DensityEstimate { tuples: 10, samples: 0, hits: 0 };
+        assert_eq!(none.confidence_interval(1.96), (0.0, 1.0));
+        let all = DensityEstimate { tuples: 10, samples: 50, hits: 50 };
+        assert_eq!(all.confidence_interval(1.96).1, 1.0);
+        assert!(all.confidence_interval(1.96).0 > 0.9);
+        assert_eq!("halton".parse(), Ok(Sampler::Halton));
+        assert!("sobol".parse::<Sampler>().is_err());
+    }
+
+    #[test]
+    fn test_representation_column() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..12].to_vec();
+        let index = Arc::new(RepresentationIndex::build(&form, &pool));
+        let mut out = Vec::new();
+        search(&form, &pool, &mut out, &SearchConfig::new(3).representations(index.clone()))
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert!(lines.next().unwrap().ends_with(",error_bound_n,r"));
+        let mut collisions = 0;
+        for line in lines {
+            let row = crate::results::parse_row(line).unwrap();
+            let r: usize = line.rsplit(',').next().unwrap().parse().unwrap();
+            assert_eq!(r, index.count(&row.n));
+            assert!(r >= 1);
+            collisions += usize::from(r > 1);
+        }
+        assert!(collisions > 0);
+    }
+
+    #[test]
+    fn test_germain_depth_column() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..12].to_vec();
+        let (mut plain, mut out) = (Vec::new(), Vec::new());
+        search(&form, &pool, &mut plain, &SearchConfig::new(3)).unwrap();
+        search(&form, &pool, &mut out, &SearchConfig::new(3).germain_depth(true)).unwrap();
+        let (plain, out) = (String::from_utf8(plain).unwrap(), String::from_utf8(out).unwrap());
+        let mut lines = out.lines();
+        assert!(lines.next().unwrap().ends_with(",germain_depth"));
+        let mut deep = 0;
+        for (line, plain) in lines.zip(plain.lines().skip(1)) {
+            // The other columns are unchanged by the extra tests
+            let (rest, depth) = line.rsplit_once(',').unwrap();
+            assert_eq!(rest, plain);
+            let row = parse_row(line).unwrap();
+            if !row.is_prime_hit() {
+                assert_eq!(depth, "");
+                continue;
+            }
+            let depth: u32 = depth.parse().unwrap();
+            assert_eq!(depth > 0, row.classifications_n.iter().any(|t| t == "Germain"));
+            deep += usize::from(depth >= 2);
+        }
+        assert!(deep > 0);
+    }
+
+    #[test]
+    fn test_stop_signal_stops_at_a_checkpoint() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..20].to_vec();
+        let mut full = Vec::new();
+        search(&form, &pool, &mut full, &SearchConfig::new(3)).unwrap();
+
+        // Raised during the first batch, as a Ctrl-C handler would
+        let signal = StopSignal::new();
+        let config = SearchConfig::new(3).stop_on(signal.clone());
+        assert_eq!(config.clone(), config);
+        let mut out = Vec::new();
+        let mut checkpoints = Vec::new();
+        let stop = |progress: &SearchProgress, _: &mut Vec<u8>| {
+            checkpoints.push(*progress);
+            signal.stop();
+            Ok(())
+        };
+        let stopped = search_from(&form, &pool, &mut out, &config, SearchProgress::default(), stop)
+            .unwrap();
+        assert_eq!(stopped.tuples, CHECKPOINT_INTERVAL);
+        assert_eq!(checkpoints, [stopped, stopped]);
+        let noop = |_: &SearchProgress, _: &mut Vec<u8>| Ok(());
+        search_from(&form, &pool, &mut out, &SearchConfig::new(3), stopped, noop).unwrap();
+        assert_eq!(out, full);
+    }
+
+    #[test]
+    fn test_closure_search_feeds_hits_back() {
+        let form = QuadraticForm::universal();
+        let pool = default_pool()[..3].to_vec();
+        let config = SearchConfig::new(3);
+        let mut plain = Vec::new();
+        search(&form, &pool, &mut plain, &config).unwrap();
+        let plain = String::from_utf8(plain).unwrap();
+
+        let mut out = Vec::new();
+        let report = closure_search(&form, &pool, &mut out, &config, 2).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next().unwrap(), format!("{},generation", CSV_HEADER));
+
+        // The first generation is the plain search
+        let first = &report.generations[0];
+        assert_eq!((first.generation, first.pool_len, first.tuples), (1, 3, 27));
+        for expected in plain.lines().skip(1) {
+            assert_eq!(lines.next().unwrap(), format!("{},1", expected));
+        }
+        // The second only draws tuples with a new entry, each from the grown pool
+        let second = &report.generations[1];
+        assert_eq!(second.pool_len, 3 + first.new_primes);
+        assert_eq!(second.tuples, (second.pool_len as u64).pow(3) - 27);
+        let rest: Vec<&str> = lines.collect();
+        assert_eq!(rest.len() as u64, second.hits);
+        for line in rest {
+            assert!(line.ends_with(",2"));
+            let row = parse_row(line).unwrap();
+            let generations: Vec<u32> = [&row.x, &row.y, &row.z]
+                .iter()
+                .map(|v| report.generation_of[v.magnitude()])
+                .collect();
+            assert!(generations.contains(&1));
+        }
+        assert_eq!(report.hits(), first.hits + second.hits);
+        assert_eq!(report.generation_of.values().filter(|&&g| g == 1).count(), first.new_primes);
+        assert!(!report.stopped && !report.reached_fixpoint());
+
+        let mut out = Vec::new();
+        let capped = closure_search(&form, &pool, &mut out, &config.stop_after_hits(2), 3).unwrap();
+        assert!(capped.stopped);
+        assert_eq!(capped.hits(), 2);
+
+        let (deepest, _) = report.generation_of.iter().rev().find(|(_, &g)| g == 2).unwrap();
+        let lineage = report.lineage_of(deepest).unwrap();
+        assert_eq!((lineage.generation, lineage.parents.len()), (2, 3));
+        let (x, y, z) = lineage.tuple.clone().unwrap();
+        assert_eq!(deepest, form.evaluate(&x, &y, &z).magnitude());
+        assert!(lineage.parents.iter().any(|p| p.generation == 1));
+        let text = lineage.to_string();
+        let root = format!("{} (generation 2) = F({}, {}, {})\n", deepest, x, y, z);
+        assert!(text.starts_with(&root));
+        // Every generation 1 parent expands to three pool values one level further in
+        let grandparents = text.lines().filter(|l| l.starts_with("    ") && l.ends_with(" (pool)"));
+        let from_generation_1 = lineage.parents.iter().filter(|p| p.generation == 1).count();
+        assert_eq!(grandparents.count(), 3 * from_generation_1);
+        assert_eq!(report.lineage_of(&BigUint::from(3u32)).unwrap().to_string(), "3 (pool)\n");
+        assert_eq!(report.lineage_of(&BigUint::from(4u32)), None);
+
+        let tuples: Vec<u64> = fresh_tuples(2, 4).collect();
+        assert_eq!(tuples.len(), 64 - 8);
+        assert!(tuples.windows(2).all(|w| w[0] < w[1]));
+        assert!(tuples.iter().all(|&i| i / 16 >= 2 || i / 4 % 4 >= 2 || i % 4 >= 2));
+    }
+}
@@ -0,0 +1,324 @@
+//! Request handling behind the REST service of the `server` binary, which exposes primality,
+//! classification, and Shamir splitting over JSON.
+//!
+//! Numbers travel as decimal strings. Each client IP gets a token bucket so one caller
+//! cannot monopolise the machine. The binary only owns the socket; everything a request
+//! body and path decide lives here.
+
+use num_bigint::BigUint;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::classify::classify_prime_with_rounds;
+use crate::primality::{is_bpsw_prime, PrimalityConfig};
+use crate::prime_shamir::{generate_large_prime, shamir_split_shares};
+
+/// The response body, or an HTTP status and message for the error body.
+pub type HandlerResult = Result<String, (u16, String)>;
+
+/// Bounds on the work a single request may ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Miller-Rabin rounds when a request does not specify any
+    pub rounds: usize,
+    /// Upper bound on the rounds a request may ask for
+    pub max_rounds: usize,
+    /// Largest accepted input, in bits
+    pub max_bits: u64,
+}
+
+#[derive(Deserialize)]
+struct NumberRequest {
+    n: String,
+    rounds: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ClassifyResponse {
+    n: String,
+    classifications: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct IsPrimeResponse {
+    n: String,
+    is_prime: bool,
+    rounds: usize,
+}
+
+#[derive(Deserialize)]
+struct SplitRequest {
+    secret: String,
+    threshold: usize,
+    shares: usize,
+    modulus: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ShareJson {
+    x: usize,
+    y: String,
+}
+
+#[derive(Serialize)]
+struct SplitResponse {
+    modulus: String,
+    shares: Vec<ShareJson>,
+}
+
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Token bucket per client address.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+/// Tokens left and time of last use per client, and when idle clients were last evicted.
+struct Buckets {
+    clients: HashMap<IpAddr, (f64, Instant)>,
+    pruned: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        RateLimiter {
+            rate,
+            burst,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take one token for `client`, returning false when its bucket is empty
+    pub fn allow(&self, client: IpAddr) -> bool {
+        self.allow_at(client, Instant::now())
+    }
+
+    fn allow_at(&self, client: IpAddr, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        // A bucket idle long enough to refill is the same as none, so evict those once per
+        // refill period, keeping the map to the clients seen recently
+        if now.duration_since(buckets.pruned).as_secs_f64() * self.rate >= self.burst {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.clients.retain(|_, (tokens, last)| {
+                *tokens + now.duration_since(*last).as_secs_f64() * rate < burst
+            });
+            buckets.pruned = now;
+        }
+        let (tokens, last) = buckets.clients.entry(client).or_insert((self.burst, now));
+        let elapsed = now.duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.burst);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn parse_number(n: &str, max_bits: u64) -> Result<BigUint, (u16, String)> {
+    let n = n
+        .trim()
+        .parse::<BigUint>()
+        .map_err(|_| (400, format!("'{}' is not a non-negative decimal integer", n)))?;
+    if n.bits() > max_bits {
+        return Err((413, format!("input exceeds {} bits", max_bits)));
+    }
+    Ok(n)
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("invalid JSON body: {}", e)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> HandlerResult {
+    serde_json::to_string(value).map_err(|e| (500, e.to_string()))
+}
+
+fn requested_rounds(rounds: Option<usize>, limits: &Limits) -> Result<usize, (u16, String)> {
+    let rounds = rounds.unwrap_or(limits.rounds);
+    if rounds == 0 || rounds > limits.max_rounds {
+        return Err((400, format!("rounds must be between 1 and {}", limits.max_rounds)));
+    }
+    Ok(rounds)
+}
+
+fn handle_classify(body: &str, limits: &Limits) -> HandlerResult {
+    let request: NumberRequest = parse_body(body)?;
+    let rounds = requested_rounds(request.rounds, limits)?;
+    let n = parse_number(&request.n, limits.max_bits)?;
+    let mut rng = ChaCha20Rng::from_entropy();
+    to_json(&ClassifyResponse {
+        classifications: classify_prime_with_rounds(&n, rounds, &mut rng),
+        n: n.to_string(),
+    })
+}
+
+fn handle_is_prime(body: &str, limits: &Limits) -> HandlerResult {
+    let request: NumberRequest = parse_body(body)?;
+    let rounds = requested_rounds(request.rounds, limits)?;
+    let n = parse_number(&request.n, limits.max_bits)?;
+    let mut rng = ChaCha20Rng::from_entropy();
+    to_json(&IsPrimeResponse {
+        is_prime: PrimalityConfig::with_rounds(rounds).is_prime(&n, &mut rng),
+        n: n.to_string(),
+        rounds,
+    })
+}
+
+fn handle_shamir_split(body: &str, limits: &Limits) -> HandlerResult {
+    let request: SplitRequest = parse_body(body)?;
+    if request.threshold < 2 || request.shares < request.threshold || request.shares > 255 {
+        return Err((400, "require 2 <= threshold <= shares <= 255".to_string()));
+    }
+    let secret = parse_number(&request.secret, limits.max_bits)?;
+    let modulus = match request.modulus {
+        Some(m) => parse_number(&m, limits.max_bits)?,
+        None => {
+            let mut rng = ChaCha20Rng::from_entropy();
+            generate_large_prime((secret.bits() as usize * 2).max(64), &mut rng)
+        }
+    };
+    if modulus <= secret {
+        return Err((400, "modulus must be larger than the secret".to_string()));
+    }
+    // Shares are evaluated at x = 1..=shares and recombined by inverting differences of
+    // those points, which only works in a prime field with more elements than shares
+    if modulus <= BigUint::from(request.shares) || !is_bpsw_prime(&modulus) {
+        return Err((400, "modulus must be a prime larger than shares".to_string()));
+    }
+    let shares = shamir_split_shares(&secret, request.threshold, request.shares, &modulus);
+    to_json(&SplitResponse {
+        modulus: modulus.to_string(),
+        shares: shares
+            .into_iter()
+            .map(|(x, y)| ShareJson { x, y: y.to_string() })
+            .collect(),
+    })
+}
+
+/// Answer a POST of `body` to `path`.
+pub fn handle(path: &str, body: &str, limits: &Limits) -> HandlerResult {
+    match path {
+        "/classify" => handle_classify(body, limits),
+        "/is_prime" => handle_is_prime(body, limits),
+        "/shamir/split" => handle_shamir_split(body, limits),
+        other => Err((404, format!("no endpoint at {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    use std::time::Duration;
+
+    const LIMITS: Limits = Limits {
+        rounds: 20,
+        max_rounds: 128,
+        max_bits: 8192,
+    };
+
+    fn status(result: HandlerResult) -> u16 {
+        result.err().map_or(200, |(status, _)| status)
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1.0, 2.0);
+        let [a, b, c]: [IpAddr; 3] = [1, 2, 3].map(|host| [10, 0, 0, host].into());
+        let start = Instant::now();
+        let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+        let clients = || limiter.buckets.lock().unwrap().clients.len();
+
+        // A burst drains the bucket, which refills at the sustained rate, per client
+        assert!(limiter.allow_at(a, at(0.0)));
+        assert!(limiter.allow_at(a, at(0.0)));
+        assert!(!limiter.allow_at(a, at(0.5)));
+        assert!(limiter.allow_at(b, at(0.5)));
+        assert!(limiter.allow_at(a, at(1.0)));
+        assert!(!limiter.allow_at(a, at(1.0)));
+        assert_eq!(clients(), 2);
+
+        // Once a refill period has passed, buckets that would be full again are dropped, and
+        // one still short of tokens is kept
+        assert!(limiter.allow_at(b, at(5.0)));
+        assert_eq!(clients(), 1);
+        assert!(limiter.allow_at(a, at(6.0)));
+        assert!(limiter.allow_at(a, at(6.0)));
+        assert!(limiter.allow_at(c, at(7.5)));
+        assert_eq!(clients(), 2);
+        assert!(limiter.allow_at(a, at(7.5)));
+        assert!(!limiter.allow_at(a, at(7.5)));
+    }
+
+    #[test]
+    fn test_request_errors() {
+        let huge = format!(r#"{{"n": "{}"}}"#, BigUint::from(1u32) << 8192u32);
+        assert_eq!(status(handle("/is_prime", &huge, &LIMITS)), 413);
+        assert_eq!(status(handle("/classify", &huge, &LIMITS)), 413);
+        assert_eq!(status(handle("/is_prime", "{", &LIMITS)), 400);
+        assert_eq!(status(handle("/is_prime", r#"{"n": "-7"}"#, &LIMITS)), 400);
+        assert_eq!(status(handle("/is_prime", r#"{"n": "7", "rounds": 0}"#, &LIMITS)), 400);
+        assert_eq!(status(handle("/is_prime", r#"{"n": "7", "rounds": 129}"#, &LIMITS)), 400);
+        assert_eq!(status(handle("/factor", r#"{"n": "7"}"#, &LIMITS)), 404);
+
+        let answer = handle("/is_prime", r#"{"n": " 97 ", "rounds": 5}"#, &LIMITS).unwrap();
+        let answer: Value = serde_json::from_str(&answer).unwrap();
+        assert_eq!(answer["n"], "97");
+        assert_eq!(answer["is_prime"], true);
+        assert_eq!(answer["rounds"], 5);
+    }
+
+    #[test]
+    fn test_shamir_split_modulus() {
+        let split = |threshold: usize, shares: usize, modulus: &str| {
+            let body = format!(
+                r#"{{"secret": "42", "threshold": {}, "shares": {}, "modulus": "{}"}}"#,
+                threshold, shares, modulus
+            );
+            handle("/shamir/split", &body, &LIMITS)
+        };
+        // Composite moduli, and prime ones no larger than the secret or the share count, are
+        // refused
+        assert_eq!(status(split(3, 5, "1001")), 400);
+        assert_eq!(status(split(3, 5, "561")), 400);
+        assert_eq!(status(split(3, 5, "43")), 200);
+        assert_eq!(status(split(3, 5, "41")), 400);
+        assert_eq!(status(split(3, 60, "59")), 400);
+        assert_eq!(status(split(1, 5, "1009")), 400);
+        assert_eq!(status(split(3, 256, "1009")), 400);
+
+        let answer: Value = serde_json::from_str(&split(3, 5, "1009").unwrap()).unwrap();
+        assert_eq!(answer["modulus"], "1009");
+        assert_eq!(answer["shares"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_classify_pseudoprime_returns() {
+        // The Cipolla pseudoprime (2^127 - 1)(2^127 + 1)/3 once sent classification into an
+        // unbounded factorisation; it must now come back like any other composite
+        let n: BigUint = ((BigUint::from(1u32) << 254u32) - 1u32) / 3u32;
+        let body = format!(r#"{{"n": "{}"}}"#, n);
+        let answer: Value = serde_json::from_str(&handle("/classify", &body, &LIMITS).unwrap())
+            .unwrap();
+        assert_eq!(answer["n"], n.to_string());
+        assert!(!answer["classifications"].as_array().unwrap().contains(&"Prime".into()));
+    }
+}
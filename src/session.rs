@@ -0,0 +1,417 @@
+//! A two-party handshake ending in a `Session` that seals and opens messages.
+//!
+//! Both parties share a `SignatureGroup`, whose prime p is the modulus context of the
+//! session. Each sends a `Hello` holding its public sphere point, a fresh nonce and an
+//! ephemeral Diffie–Hellman share g^e mod p. From the peer's hello each side computes the
+//! shared value g^(e·e') and the ring relation of the two public points, their dot product
+//! mod p; the transcript hash binds both hellos and the ring value, and HKDF-SHA3-256 over
+//! the shared value yields one key per direction. Messages are sealed with a SHAKE256
+//! keystream and tag, under a nonce derived from a per-direction message counter; the
+//! receiver keeps a sliding window of counters and refuses any it has seen or that fall
+//! behind the window, so a recorded message cannot be played back.
+//!
+//! The hellos are not authenticated. To rule out a man in the middle, each party signs
+//! `Session::transcript` with its `SigningKey` and checks the peer's signature.
+
+use hkdf::Hkdf;
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::One;
+use rand::Rng;
+use sha3::Sha3_256;
+use thiserror::Error;
+
+use crate::domains;
+use crate::modular::{ct_modpow, ModulusContext};
+use crate::pmpt::{shake256_parts, to_fixed_width, RingMetadata, SignatureGroup, SpherePoint};
+use crate::pmpt::MAX_PAD_LENGTH;
+use crate::reader::{MalformedInput, StrictReader};
+
+/// Bytes of a hello nonce.
+pub const NONCE_LEN: usize = 32;
+/// Bytes of a message counter, nonce and tag.
+const COUNTER_LEN: usize = 8;
+const MESSAGE_NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    #[error("malformed hello: {0}")]
+    Malformed(#[from] MalformedInput),
+    #[error("peer's key share is not in the group")]
+    InvalidShare,
+    #[error("peer's hello echoes our own")]
+    Reflected,
+    #[error("message is too short to hold a counter and tag")]
+    Truncated,
+    #[error("message {0} was already received")]
+    Replayed(u64),
+    #[error("message {counter} is too far behind message {highest} to check for replay")]
+    Stale { counter: u64, highest: u64 },
+    #[error("no message counters left; start a new session")]
+    CounterExhausted,
+    #[error("message failed authentication")]
+    AuthenticationFailed,
+}
+
+/// Which side of the handshake a party is on; the two must differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Initiator,
+    Responder,
+}
+
+/// What each party sends the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub public_point: SpherePoint,
+    pub nonce: [u8; NONCE_LEN],
+    /// g^e mod p for the sender's ephemeral exponent e
+    pub share: BigUint,
+}
+
+impl Hello {
+    /// The nonce, then the share and the three coordinates, each with a big-endian u32
+    /// length prefix.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.nonce.to_vec();
+        let point = &self.public_point;
+        for value in [&self.share, &point.x, &point.y, &point.z] {
+            let value = value.to_bytes_be();
+            bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&value);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SessionError> {
+        let mut reader = StrictReader::new(bytes);
+        let nonce = reader.array("nonce")?;
+        let mut value = |field| {
+            reader.prefixed(field, MAX_PAD_LENGTH).map(BigUint::from_bytes_be)
+        };
+        let share = value("key share")?;
+        let public_point = SpherePoint::new(value("x")?, value("y")?, value("z")?);
+        reader.finish()?;
+        Ok(Hello { public_point, nonce, share })
+    }
+}
+
+/// One party's half-finished handshake: its hello is out, the peer's is awaited.
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    role: Role,
+    group: SignatureGroup,
+    context: ModulusContext,
+    ephemeral: BigUint,
+    hello: Hello,
+}
+
+impl Handshake {
+    /// Start a handshake as `role` in `group`, presenting `public_point`.
+    pub fn new<R: Rng + ?Sized>(
+        role: Role,
+        group: SignatureGroup,
+        public_point: &SpherePoint,
+        rng: &mut R,
+    ) -> Self {
+        let context = ModulusContext::new(&group.p);
+        let q_minus_one = &group.q - 1u32;
+        let ephemeral = rng.gen_biguint_below(&q_minus_one) + 1u32;
+        let share = ct_modpow(&group.g, &ephemeral, &group.p, group.q.bits());
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce);
+        Handshake {
+            role,
+            group,
+            context,
+            ephemeral,
+            hello: Hello { public_point: public_point.clone(), nonce, share },
+        }
+    }
+
+    /// The hello to send to the peer.
+    pub fn hello(&self) -> &Hello {
+        &self.hello
+    }
+
+    /// Finish with the peer's hello, checking its share lies in the order-q subgroup.
+    pub fn finish(self, peer: &Hello) -> Result<Session, SessionError> {
+        let p = self.context.modulus();
+        if peer.share <= BigUint::one()
+            || &peer.share >= p
+            || !self.context.pow(&peer.share, &self.group.q).is_one()
+        {
+            return Err(SessionError::InvalidShare);
+        }
+        if peer.nonce == self.hello.nonce || peer.share == self.hello.share {
+            return Err(SessionError::Reflected);
+        }
+        let shared = ct_modpow(&peer.share, &self.ephemeral, p, self.group.q.bits());
+        let ring = RingMetadata::generate(&self.hello.public_point, &peer.public_point, p);
+
+        let (initiator, responder) = match self.role {
+            Role::Initiator => (&self.hello, peer),
+            Role::Responder => (peer, &self.hello),
+        };
+        let width = (p.bits() as usize).div_ceil(8);
+        let fixed = |value: &BigUint| {
+            to_fixed_width(value, width).map_err(|_| MalformedInput::Invalid("key share"))
+        };
+        let mut parts = Vec::new();
+        for hello in [initiator, responder] {
+            let point = &hello.public_point;
+            parts.push(hello.nonce.to_vec());
+            parts.push(fixed(&hello.share)?);
+            parts.extend([&point.x, &point.y, &point.z].map(|c| c.to_bytes_be()));
+        }
+        parts.push(fixed(&ring.ring_value)?);
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        let mut transcript = [0u8; 32];
+        shake256_parts(domains::SESSION_TRANSCRIPT, &parts, &mut transcript);
+
+        let hkdf = Hkdf::<Sha3_256>::new(Some(domains::SESSION_KEYS), &fixed(&shared)?);
+        let key = |label: &[u8]| {
+            let mut key = [0u8; 32];
+            hkdf.expand_multi_info(&[label, &transcript], &mut key)
+                .expect("32 bytes is within HKDF's output limit");
+            key
+        };
+        let to_responder = key(b"initiator-to-responder");
+        let to_initiator = key(b"responder-to-initiator");
+        let (send_key, recv_key) = match self.role {
+            Role::Initiator => (to_responder, to_initiator),
+            Role::Responder => (to_initiator, to_responder),
+        };
+        Ok(Session {
+            role: self.role,
+            send_key,
+            recv_key,
+            transcript,
+            ring_value: ring.ring_value,
+            sent: 0,
+            window: ReplayWindow { size: DEFAULT_REPLAY_WINDOW, highest: None, seen: 0 },
+        })
+    }
+}
+
+/// Counters more than this far behind the highest one seen are refused by default.
+pub const DEFAULT_REPLAY_WINDOW: u32 = 64;
+/// The largest replay window a session can keep.
+pub const MAX_REPLAY_WINDOW: u32 = 128;
+
+/// Which of the last `size` counters below the highest have been accepted.
+#[derive(Debug, Clone)]
+struct ReplayWindow {
+    size: u32,
+    /// The highest counter accepted so far, if any
+    highest: Option<u64>,
+    /// Bit i is set when counter highest - i has been accepted
+    seen: u128,
+}
+
+impl ReplayWindow {
+    /// Refuse `counter` if it was accepted before or falls behind the window.
+    fn check(&self, counter: u64) -> Result<(), SessionError> {
+        let Some(highest) = self.highest else {
+            return Ok(());
+        };
+        if counter > highest {
+            return Ok(());
+        }
+        let behind = highest - counter;
+        if behind >= self.size as u64 {
+            return Err(SessionError::Stale { counter, highest });
+        }
+        if self.seen >> behind & 1 == 1 {
+            return Err(SessionError::Replayed(counter));
+        }
+        Ok(())
+    }
+
+    /// Record `counter`, which `check` has passed.
+    fn accept(&mut self, counter: u64) {
+        match self.highest {
+            Some(highest) if counter <= highest => self.seen |= 1 << (highest - counter),
+            Some(highest) => {
+                let ahead = counter - highest;
+                self.seen = if ahead >= 128 { 0 } else { self.seen << ahead };
+                self.seen |= 1;
+                self.highest = Some(counter);
+            }
+            None => {
+                self.seen = 1;
+                self.highest = Some(counter);
+            }
+        }
+    }
+}
+
+/// An established channel: one key for each direction, a counter for messages sent and a
+/// replay window over those received.
+#[derive(Debug, Clone)]
+pub struct Session {
+    role: Role,
+    send_key: [u8; 32],
+    recv_key: [u8; 32],
+    transcript: [u8; 32],
+    ring_value: BigUint,
+    sent: u64,
+    window: ReplayWindow,
+}
+
+/// The nonce of message `counter` under `key`.
+fn message_nonce(key: &[u8; 32], counter: u64) -> [u8; MESSAGE_NONCE_LEN] {
+    let mut nonce = [0u8; MESSAGE_NONCE_LEN];
+    shake256_parts(domains::SESSION_NONCE, &[key, &counter.to_be_bytes()], &mut nonce);
+    nonce
+}
+
+fn apply_keystream(key: &[u8; 32], nonce: &[u8], data: &mut [u8]) {
+    let mut keystream = vec![0u8; data.len()];
+    shake256_parts(domains::SESSION_STREAM, &[key, nonce], &mut keystream);
+    for (byte, mask) in data.iter_mut().zip(keystream) {
+        *byte ^= mask;
+    }
+}
+
+fn tag(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let mut tag = [0u8; TAG_LEN];
+    shake256_parts(domains::SESSION_TAG, &[key, nonce, ciphertext], &mut tag);
+    tag
+}
+
+impl Session {
+    /// Accept counters up to `size` behind the highest seen, for transports that reorder;
+    /// 0 accepts only counters above every earlier one. At most `MAX_REPLAY_WINDOW`.
+    pub fn replay_window(mut self, size: u32) -> Self {
+        self.window.size = size.min(MAX_REPLAY_WINDOW);
+        self
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Hash of both hellos and the ring value, the same on both sides; sign it to
+    /// authenticate the handshake.
+    pub fn transcript(&self) -> &[u8; 32] {
+        &self.transcript
+    }
+
+    /// The dot product of the two public points mod p.
+    pub fn ring_value(&self) -> &BigUint {
+        &self.ring_value
+    }
+
+    /// Messages sent so far, which is also the counter of the next one.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Seal `plaintext` for the peer, as counter ‖ ciphertext ‖ tag. The counter is a
+    /// big-endian u64, one more than the last message's, and fixes the message nonce.
+    pub fn send(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, SessionError> {
+        let counter = self.sent;
+        self.sent = counter.checked_add(1).ok_or(SessionError::CounterExhausted)?;
+        let nonce = message_nonce(&self.send_key, counter);
+        let mut message = counter.to_be_bytes().to_vec();
+        let mut ciphertext = plaintext.to_vec();
+        apply_keystream(&self.send_key, &nonce, &mut ciphertext);
+        let tag = tag(&self.send_key, &nonce, &ciphertext);
+        message.extend_from_slice(&ciphertext);
+        message.extend_from_slice(&tag);
+        Ok(message)
+    }
+
+    /// Open a message from the peer, refusing a counter already accepted or too far behind.
+    pub fn recv(&mut self, message: &[u8]) -> Result<Vec<u8>, SessionError> {
+        if message.len() < COUNTER_LEN + TAG_LEN {
+            return Err(SessionError::Truncated);
+        }
+        let (counter, rest) = message.split_at(COUNTER_LEN);
+        let counter = u64::from_be_bytes(counter.try_into().unwrap());
+        self.window.check(counter)?;
+        let (ciphertext, received) = rest.split_at(rest.len() - TAG_LEN);
+        let nonce = message_nonce(&self.recv_key, counter);
+        let expected = tag(&self.recv_key, &nonce, ciphertext);
+        // Compare without an early exit
+        let difference = expected.iter().zip(received).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if difference != 0 {
+            return Err(SessionError::AuthenticationFailed);
+        }
+        // Only an authentic message moves the window
+        self.window.accept(counter);
+        let mut plaintext = ciphertext.to_vec();
+        apply_keystream(&self.recv_key, &nonce, &mut plaintext);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmpt::KeyPair;
+    use crate::primality::PrimalityConfig;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_handshake_and_messages() {
+        let mut rng = ChaCha20Rng::seed_from_u64(40);
+        let group = SignatureGroup::generate(128, &PrimalityConfig::default(), &mut rng);
+        let alice = KeyPair::generate(64, &mut rng);
+        let bob = KeyPair::generate(64, &mut rng);
+        let start = |role, keys: &KeyPair, rng: &mut ChaCha20Rng| {
+            Handshake::new(role, group.clone(), &keys.public_key, rng)
+        };
+        let initiator = start(Role::Initiator, &alice, &mut rng);
+        let responder = start(Role::Responder, &bob, &mut rng);
+        let hello = Hello::from_bytes(&initiator.hello().to_bytes()).unwrap();
+        assert_eq!(&hello, initiator.hello());
+        let reply = responder.hello().clone();
+        let mut a = initiator.finish(&reply).unwrap();
+        let mut b = responder.clone().finish(&hello).unwrap().replay_window(4);
+        assert_eq!(a.transcript(), b.transcript());
+        assert_eq!(a.ring_value(), b.ring_value());
+
+        let message = a.send(b"over the wire").unwrap();
+        let mut tampered = message.clone();
+        tampered[20] ^= 1;
+        assert_eq!(b.recv(&tampered), Err(SessionError::AuthenticationFailed));
+        assert_eq!(b.recv(&message[..39]), Err(SessionError::Truncated));
+        assert_eq!(b.recv(&message).unwrap(), b"over the wire");
+        let reply = b.send(b"and back").unwrap();
+        assert_eq!(a.recv(&reply).unwrap(), b"and back");
+        // A party cannot open its own messages
+        let echo = a.clone().send(b"echo").unwrap();
+        assert_eq!(a.clone().recv(&echo), Err(SessionError::AuthenticationFailed));
+
+        // Counters rise by one; replays and counters behind the window are refused, while
+        // late messages inside it are still accepted once
+        assert_eq!(b.recv(&message), Err(SessionError::Replayed(0)));
+        let later: Vec<Vec<u8>> = (1..8).map(|_| a.send(b"later").unwrap()).collect();
+        assert_eq!(a.sent(), 8);
+        assert_eq!(u64::from_be_bytes(later[6][..8].try_into().unwrap()), 7);
+        b.recv(&later[6]).unwrap();
+        assert_eq!(b.recv(&later[2]), Err(SessionError::Stale { counter: 3, highest: 7 }));
+        assert_eq!(b.recv(&later[4]).unwrap(), b"later");
+        assert_eq!(b.recv(&later[4]), Err(SessionError::Replayed(5)));
+        let mut forged = later[3].clone();
+        forged[..8].copy_from_slice(&6u64.to_be_bytes());
+        assert_eq!(b.recv(&forged), Err(SessionError::AuthenticationFailed));
+        assert_eq!(b.recv(&later[5]).unwrap(), b"later");
+
+        // Shares outside the subgroup and reflected hellos are refused
+        let mut bad = hello.clone();
+        bad.share = &group.p - 1u32;
+        assert_eq!(responder.clone().finish(&bad).unwrap_err(), SessionError::InvalidShare);
+        let own = responder.hello().clone();
+        assert_eq!(responder.finish(&own).unwrap_err(), SessionError::Reflected);
+        let truncated = MalformedInput::Truncated { field: "x", missing: 4 };
+        assert_eq!(Hello::from_bytes(&[0; 36]), Err(SessionError::Malformed(truncated)));
+        let mut trailing = hello.to_bytes();
+        trailing.push(0);
+        let trailing = Hello::from_bytes(&trailing);
+        assert_eq!(trailing, Err(MalformedInput::TrailingBytes(1).into()));
+    }
+}
@@ -0,0 +1,425 @@
+//! Share files: Shamir shares of a whole file over GF(2^8), with the headers needed to put
+//! them back together and check the result.
+//!
+//! A share file is armored text:
+//!
+//! ```text
+//! -----BEGIN SHAMIR SHARE-----
+//! scheme: shamir-gf256
+//! index: <1..=shares>
+//! threshold: <t>
+//! shares: <n>
+//! label: <text>              (optional)
+//! custodian: <text>          (optional)
+//! created: <unix seconds>    (optional)
+//! expires: <unix seconds>    (optional)
+//! quorum: <text>             (optional)
+//! sha3-256: <hex digest of the secret>
+//! mac: <hex tag over the headers, keyed with the secret>
+//! data: <base64 share bytes>
+//! -----END SHAMIR SHARE-----
+//! ```
+//!
+//! The digest lets `combine_at` tell a recovered secret from garbage made of mismatched or
+//! tampered shares. The optional lines are escrow policy; each share's MAC covers its index,
+//! threshold and policy, keyed with the secret itself, so once the secret is recovered an
+//! edited label or a pushed-back expiry is detected.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::Rng;
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::path::Path;
+
+use crate::domains;
+use crate::gf256::{self, ByteShare, Gf256Error};
+use crate::pmpt::shake256_parts;
+
+/// The only scheme written so far.
+pub const SCHEME: &str = "shamir-gf256";
+
+#[derive(Error, Debug)]
+pub enum ShareFileError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("missing {0} line")]
+    Missing(&'static str),
+    #[error("invalid {0} line")]
+    Invalid(&'static str),
+    #[error("unknown sharing scheme {0}")]
+    UnknownScheme(String),
+    #[error("shares disagree on {0}; they come from different splits")]
+    Mismatch(&'static str),
+    #[error("{have} distinct shares given, {need} needed")]
+    TooFew { have: usize, need: usize },
+    #[error(transparent)]
+    Sharing(#[from] Gf256Error),
+    #[error("recovered data does not match the SHA3-256 digest; a share is corrupt")]
+    Integrity,
+    #[error("the headers of share {0} were altered after splitting")]
+    BadMac(u8),
+    #[error("shares expired at {expires} (now {now})")]
+    Expired { expires: u64, now: u64 },
+}
+
+/// Escrow policy recorded in every share of a split; times are seconds since the Unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SharePolicy {
+    /// What the secret is, for the people holding the shares
+    pub label: Option<String>,
+    pub created: Option<u64>,
+    /// After this the shares are refused
+    pub expires: Option<u64>,
+    /// Who has to come together to recover the secret, in words
+    pub quorum: Option<String>,
+}
+
+impl SharePolicy {
+    /// Text fields have to fit on their header line.
+    fn check(&self) -> Result<(), ShareFileError> {
+        for (name, text) in [("label", &self.label), ("quorum", &self.quorum)] {
+            if text.as_deref().is_some_and(|text| text.trim() != text || text.contains('\n')) {
+                return Err(ShareFileError::Invalid(name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One share of a file with its headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFile {
+    pub index: u8,
+    pub threshold: u8,
+    pub shares: u8,
+    pub policy: SharePolicy,
+    /// Who holds this share
+    pub custodian: Option<String>,
+    /// SHA3-256 of the whole secret
+    pub digest: [u8; 32],
+    /// SHAKE256 over the secret and the headers above
+    pub mac: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+impl ShareFile {
+    const BEGIN: &'static str = "-----BEGIN SHAMIR SHARE-----";
+    const END: &'static str = "-----END SHAMIR SHARE-----";
+
+    /// Split `secret` into `shares` share files, any `threshold` of which recover it.
+    pub fn split<R: Rng + ?Sized>(
+        secret: &[u8],
+        threshold: usize,
+        shares: usize,
+        rng: &mut R,
+    ) -> Result<Vec<ShareFile>, ShareFileError> {
+        ShareFile::split_with_policy(secret, threshold, shares, &SharePolicy::default(), &[], rng)
+    }
+
+    /// Like `split`, recording `policy` in every share and `custodians[i]` in share i + 1;
+    /// `custodians` is either empty or has one entry per share.
+    pub fn split_with_policy<R: Rng + ?Sized>(
+        secret: &[u8],
+        threshold: usize,
+        shares: usize,
+        policy: &SharePolicy,
+        custodians: &[String],
+        rng: &mut R,
+    ) -> Result<Vec<ShareFile>, ShareFileError> {
+        policy.check()?;
+        let custodian_ok = |c: &String| c.trim() == c && !c.is_empty() && !c.contains('\n');
+        if !(custodians.is_empty() || custodians.len() == shares)
+            || !custodians.iter().all(custodian_ok)
+        {
+            return Err(ShareFileError::Invalid("custodian"));
+        }
+        let digest: [u8; 32] = Sha3_256::digest(secret).into();
+        Ok(gf256::split(secret, threshold, shares, rng)?
+            .into_iter()
+            .map(|share| {
+                let mut file = ShareFile {
+                    index: share.index,
+                    threshold: threshold as u8,
+                    shares: shares as u8,
+                    policy: policy.clone(),
+                    custodian: custodians.get(share.index as usize - 1).cloned(),
+                    digest,
+                    mac: [0; 32],
+                    data: share.data,
+                };
+                file.mac = file.expected_mac(secret);
+                file
+            })
+            .collect())
+    }
+
+    /// The MAC of these headers under `secret`.
+    fn expected_mac(&self, secret: &[u8]) -> [u8; 32] {
+        let text = |text: &Option<String>| match text {
+            Some(text) => [&[1][..], text.as_bytes()].concat(),
+            None => vec![0],
+        };
+        let time = |time: Option<u64>| match time {
+            Some(time) => [&[1][..], &time.to_be_bytes()].concat(),
+            None => vec![0],
+        };
+        let mut mac = [0u8; 32];
+        shake256_parts(
+            domains::SHARE_MAC,
+            &[
+                secret,
+                &[self.index, self.threshold, self.shares],
+                &text(&self.policy.label),
+                &text(&self.custodian),
+                &time(self.policy.created),
+                &time(self.policy.expires),
+                &text(&self.policy.quorum),
+                &self.digest,
+            ],
+            &mut mac,
+        );
+        mac
+    }
+
+    /// Recover the secret from share files of one split, checking it against their digest,
+    /// each share's MAC, and the expiry against `now` (seconds since the Unix epoch, from the
+    /// caller's clock). Repeated shares are ignored; at least `threshold` distinct ones are
+    /// needed.
+    pub fn combine_at(files: &[ShareFile], now: u64) -> Result<Vec<u8>, ShareFileError> {
+        let first = files.first().ok_or(ShareFileError::TooFew { have: 0, need: 1 })?;
+        let mut distinct: Vec<ByteShare> = Vec::new();
+        for file in files {
+            if file.threshold != first.threshold || file.shares != first.shares {
+                return Err(ShareFileError::Mismatch("threshold"));
+            }
+            if file.digest != first.digest {
+                return Err(ShareFileError::Mismatch("sha3-256"));
+            }
+            if file.policy != first.policy {
+                return Err(ShareFileError::Mismatch("policy"));
+            }
+            match distinct.iter().find(|share| share.index == file.index) {
+                Some(share) if share.data != file.data => {
+                    return Err(ShareFileError::Mismatch("data"));
+                }
+                Some(_) => {}
+                None => distinct.push(ByteShare { index: file.index, data: file.data.clone() }),
+            }
+        }
+        let need = first.threshold as usize;
+        if distinct.len() < need {
+            return Err(ShareFileError::TooFew { have: distinct.len(), need });
+        }
+        let secret = gf256::combine(&distinct)?;
+        if Sha3_256::digest(&secret).as_slice() != first.digest {
+            return Err(ShareFileError::Integrity);
+        }
+        if let Some(file) = files.iter().find(|file| file.mac != file.expected_mac(&secret)) {
+            return Err(ShareFileError::BadMac(file.index));
+        }
+        if let Some(expires) = first.policy.expires.filter(|&expires| now >= expires) {
+            return Err(ShareFileError::Expired { expires, now });
+        }
+        Ok(secret)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ShareFileError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl std::fmt::Display for ShareFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", Self::BEGIN)?;
+        writeln!(f, "scheme: {}", SCHEME)?;
+        writeln!(f, "index: {}", self.index)?;
+        writeln!(f, "threshold: {}", self.threshold)?;
+        writeln!(f, "shares: {}", self.shares)?;
+        if let Some(label) = &self.policy.label {
+            writeln!(f, "label: {}", label)?;
+        }
+        if let Some(custodian) = &self.custodian {
+            writeln!(f, "custodian: {}", custodian)?;
+        }
+        if let Some(created) = self.policy.created {
+            writeln!(f, "created: {}", created)?;
+        }
+        if let Some(expires) = self.policy.expires {
+            writeln!(f, "expires: {}", expires)?;
+        }
+        if let Some(quorum) = &self.policy.quorum {
+            writeln!(f, "quorum: {}", quorum)?;
+        }
+        writeln!(f, "sha3-256: {}", hex::encode(self.digest))?;
+        writeln!(f, "mac: {}", hex::encode(self.mac))?;
+        writeln!(f, "data: {}", STANDARD.encode(&self.data))?;
+        writeln!(f, "{}", Self::END)
+    }
+}
+
+/// The value of the next line if it is the `name` field.
+fn optional_field<'a, I: Iterator<Item = &'a str>>(
+    lines: &mut Peekable<I>,
+    name: &str,
+) -> Option<&'a str> {
+    let value = lines.peek()?.strip_prefix(name)?.strip_prefix(':')?.trim();
+    lines.next();
+    Some(value)
+}
+
+impl std::str::FromStr for ShareFile {
+    type Err = ShareFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty()).peekable();
+        if lines.next() != Some(Self::BEGIN) {
+            return Err(ShareFileError::Missing("BEGIN"));
+        }
+        let field = |lines: &mut Peekable<_>, name: &'static str| {
+            optional_field(lines, name).ok_or(ShareFileError::Missing(name))
+        };
+        let time = |lines: &mut Peekable<_>, name: &'static str| {
+            optional_field(lines, name)
+                .map(|value| value.parse().map_err(|_| ShareFileError::Invalid(name)))
+                .transpose()
+        };
+        let scheme = field(&mut lines, "scheme")?;
+        if scheme != SCHEME {
+            return Err(ShareFileError::UnknownScheme(scheme.to_string()));
+        }
+        let number = |lines: &mut Peekable<_>, name: &'static str| -> Result<u8, _> {
+            field(lines, name)?.parse().map_err(|_| ShareFileError::Invalid(name))
+        };
+        let index = number(&mut lines, "index")?;
+        let threshold = number(&mut lines, "threshold")?;
+        let shares = number(&mut lines, "shares")?;
+        if index == 0 || index > shares || threshold < 2 || threshold > shares {
+            return Err(ShareFileError::Invalid("index"));
+        }
+        let label = optional_field(&mut lines, "label").map(String::from);
+        let custodian = optional_field(&mut lines, "custodian").map(String::from);
+        let created = time(&mut lines, "created")?;
+        let expires = time(&mut lines, "expires")?;
+        let quorum = optional_field(&mut lines, "quorum").map(String::from);
+        let mut hash = |name: &'static str| {
+            hex::decode(field(&mut lines, name)?)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or(ShareFileError::Invalid(name))
+        };
+        let digest = hash("sha3-256")?;
+        let mac = hash("mac")?;
+        let data = STANDARD
+            .decode(field(&mut lines, "data")?)
+            .map_err(|_| ShareFileError::Invalid("data"))?;
+        if lines.next() != Some(Self::END) {
+            return Err(ShareFileError::Missing("END"));
+        }
+        Ok(ShareFile {
+            index,
+            threshold,
+            shares,
+            policy: SharePolicy { label, created, expires, quorum },
+            custodian,
+            digest,
+            mac,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_share_files() {
+        let mut rng = ChaCha20Rng::seed_from_u64(11);
+        let secret: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let files = ShareFile::split(&secret, 3, 5, &mut rng).unwrap();
+        let parsed: Vec<ShareFile> =
+            files.iter().map(|file| file.to_string().parse().unwrap()).collect();
+        assert_eq!(parsed, files);
+        assert_eq!(ShareFile::combine_at(&parsed[2..], 0).unwrap(), secret);
+        let picked = [files[4].clone(), files[0].clone(), files[4].clone(), files[2].clone()];
+        assert_eq!(ShareFile::combine_at(&picked, 0).unwrap(), secret);
+
+        assert!(matches!(
+            ShareFile::combine_at(&files[..1], 0),
+            Err(ShareFileError::TooFew { have: 1, need: 3 })
+        ));
+        assert!(matches!(
+            ShareFile::combine_at(&[files[0].clone(), files[0].clone(), files[1].clone()], 0),
+            Err(ShareFileError::TooFew { have: 2, need: 3 })
+        ));
+        let other = ShareFile::split(b"another secret", 3, 5, &mut rng).unwrap();
+        let mixed = [files[0].clone(), files[1].clone(), other[2].clone()];
+        let mismatch = ShareFile::combine_at(&mixed, 0);
+        assert!(matches!(mismatch, Err(ShareFileError::Mismatch("sha3-256"))));
+        let mut tampered = files[..3].to_vec();
+        tampered[1].data[10] ^= 1;
+        assert!(matches!(ShareFile::combine_at(&tampered, 0), Err(ShareFileError::Integrity)));
+
+        let text = files[0].to_string();
+        let unknown = text.replace(SCHEME, "shamir-prime");
+        assert!(matches!(unknown.parse::<ShareFile>(), Err(ShareFileError::UnknownScheme(_))));
+        let truncated = &text[..text.find("data").unwrap()];
+        assert!(matches!(truncated.parse::<ShareFile>(), Err(ShareFileError::Missing("data"))));
+        let bad_index = text.replace("index: 1", "index: 9");
+        assert!(matches!(bad_index.parse::<ShareFile>(), Err(ShareFileError::Invalid("index"))));
+    }
+
+    #[test]
+    fn test_share_policy() {
+        let mut rng = ChaCha20Rng::seed_from_u64(12);
+        let policy = SharePolicy {
+            label: Some("release signing key".to_string()),
+            created: Some(1000),
+            expires: Some(2000),
+            quorum: Some("two officers and the auditor".to_string()),
+        };
+        let custodians: Vec<String> = ["alice", "bob", "carol"].map(String::from).to_vec();
+        let files =
+            ShareFile::split_with_policy(b"key", 2, 3, &policy, &custodians, &mut rng).unwrap();
+        let text = files[1].to_string();
+        assert!(text.contains("\nlabel: release signing key\ncustodian: bob\ncreated: 1000\n"));
+        let parsed: ShareFile = text.parse().unwrap();
+        assert_eq!(parsed, files[1]);
+        assert_eq!(ShareFile::combine_at(&files[1..], 1999).unwrap(), b"key");
+        assert!(matches!(
+            ShareFile::combine_at(&files[1..], 2000),
+            Err(ShareFileError::Expired { expires: 2000, now: 2000 })
+        ));
+
+        // Pushing back the expiry on every share, or renaming a custodian, breaks the MAC
+        let mut extended = files.clone();
+        for file in &mut extended {
+            file.policy.expires = Some(3000);
+        }
+        assert!(matches!(ShareFile::combine_at(&extended, 2500), Err(ShareFileError::BadMac(1))));
+        let renamed: ShareFile = text.replace("custodian: bob", "custodian: eve").parse().unwrap();
+        let mixed = [files[0].clone(), renamed];
+        assert!(matches!(ShareFile::combine_at(&mixed, 0), Err(ShareFileError::BadMac(2))));
+        let mut relabelled = files.clone();
+        relabelled[0].policy.label = None;
+        let mismatch = ShareFile::combine_at(&relabelled, 0);
+        assert!(matches!(mismatch, Err(ShareFileError::Mismatch("policy"))));
+
+        let multiline = SharePolicy { label: Some("a\nb".to_string()), ..SharePolicy::default() };
+        let split = ShareFile::split_with_policy(b"key", 2, 3, &multiline, &[], &mut rng);
+        assert!(matches!(split, Err(ShareFileError::Invalid("label"))));
+        let split = ShareFile::split_with_policy(b"key", 2, 3, &policy, &custodians[..2], &mut rng);
+        assert!(matches!(split, Err(ShareFileError::Invalid("custodian"))));
+    }
+}
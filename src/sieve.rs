@@ -0,0 +1,249 @@
+//! Bit-packed prime bitmaps for O(1) primality of small numbers.
+//!
+//! A `PrimeBitmap` stores one bit per odd number up to its limit, so sieving to 10^10 takes
+//! 625 MB. It is built by the sieve of Eratosthenes or, optionally, the sieve of Atkin. Once
+//! installed with `install`, `PrimalityConfig::test` (and through it the classifiers and the
+//! search) answers from the bitmap whenever n is in range.
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use thiserror::Error;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("unknown sieve '{0}', expected eratosthenes or atkin")]
+pub struct SieveBackendError(pub String);
+
+/// Algorithm used to fill a `PrimeBitmap`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SieveBackend {
+    #[default]
+    Eratosthenes,
+    Atkin,
+}
+
+impl FromStr for SieveBackend {
+    type Err = SieveBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "eratosthenes" => Ok(SieveBackend::Eratosthenes),
+            "atkin" => Ok(SieveBackend::Atkin),
+            _ => Err(SieveBackendError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SieveBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SieveBackend::Eratosthenes => write!(f, "eratosthenes"),
+            SieveBackend::Atkin => write!(f, "atkin"),
+        }
+    }
+}
+
+/// Largest supported limit; the sieves' arithmetic stays well inside u64 below it.
+pub const MAX_LIMIT: u64 = 1_000_000_000_000;
+
+/// Primality of every integer up to `limit`; bit i stands for the odd number 2i + 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimeBitmap {
+    limit: u64,
+    words: Vec<u64>,
+}
+
+impl PrimeBitmap {
+    /// Sieve the primes up to and including `limit` with `backend`.
+    pub fn new(limit: u64, backend: SieveBackend) -> Self {
+        assert!(limit <= MAX_LIMIT, "sieve limit above {}", MAX_LIMIT);
+        let bits = limit.div_ceil(2);
+        let mut bitmap = PrimeBitmap {
+            limit,
+            words: vec![0; bits.div_ceil(64) as usize],
+        };
+        match backend {
+            SieveBackend::Eratosthenes => bitmap.eratosthenes(),
+            SieveBackend::Atkin => bitmap.atkin(),
+        }
+        bitmap
+    }
+
+    fn set(&mut self, n: u64) {
+        let i = n / 2;
+        self.words[(i / 64) as usize] |= 1 << (i % 64);
+    }
+
+    fn clear(&mut self, n: u64) {
+        let i = n / 2;
+        self.words[(i / 64) as usize] &= !(1 << (i % 64));
+    }
+
+    fn flip(&mut self, n: u64) {
+        let i = n / 2;
+        self.words[(i / 64) as usize] ^= 1 << (i % 64);
+    }
+
+    fn bit(&self, n: u64) -> bool {
+        let i = n / 2;
+        self.words[(i / 64) as usize] >> (i % 64) & 1 == 1
+    }
+
+    fn eratosthenes(&mut self) {
+        for word in &mut self.words {
+            *word = !0;
+        }
+        if self.limit >= 1 {
+            self.clear(1);
+        }
+        let mut p = 3;
+        while p * p <= self.limit {
+            if self.bit(p) {
+                // Odd multiples only, from p² on
+                for multiple in (p * p..=self.limit).step_by(2 * p as usize) {
+                    self.clear(multiple);
+                }
+            }
+            p += 2;
+        }
+        self.clear_past_limit();
+    }
+
+    /// Atkin and Bernstein's sieve: flip n once for each solution of the quadratic form
+    /// chosen by n mod 12, then clear multiples of the squares of primes.
+    fn atkin(&mut self) {
+        let limit = self.limit;
+        let mut x = 1u64;
+        // 3x² - (x - 1)², the smallest value with this x, bounds x
+        while 2 * x * x + 2 * x - 1 <= limit {
+            let mut y = 1u64;
+            while 3 * x * x + y * y <= limit || y < x {
+                let n = 4 * x * x + y * y;
+                if n <= limit && (n % 12 == 1 || n % 12 == 5) {
+                    self.flip(n);
+                }
+                let n = 3 * x * x + y * y;
+                if n <= limit && n % 12 == 7 {
+                    self.flip(n);
+                }
+                if x > y {
+                    let n = 3 * x * x - y * y;
+                    if n <= limit && n % 12 == 11 {
+                        self.flip(n);
+                    }
+                }
+                y += 1;
+            }
+            x += 1;
+        }
+        let mut r = 5;
+        while r * r <= limit {
+            if self.bit(r) {
+                // Even multiples of r² are not in the bitmap
+                for multiple in (r * r..=limit).step_by(2 * (r * r) as usize) {
+                    self.clear(multiple);
+                }
+            }
+            r += 2;
+        }
+        if limit >= 3 {
+            self.set(3);
+        }
+        self.clear_past_limit();
+    }
+
+    /// Clear the padding bits of the last word, which stand for numbers past the limit.
+    fn clear_past_limit(&mut self) {
+        let used = self.limit.div_ceil(2);
+        let total = self.words.len() as u64 * 64;
+        for i in used..total {
+            self.words[(i / 64) as usize] &= !(1 << (i % 64));
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Whether `n` is prime, or `None` past the limit.
+    pub fn contains(&self, n: u64) -> Option<bool> {
+        if n > self.limit {
+            None
+        } else if n.is_multiple_of(2) {
+            Some(n == 2)
+        } else {
+            Some(self.bit(n))
+        }
+    }
+
+    /// `contains` for a `BigUint`, which is past the limit if it does not fit a u64.
+    pub fn contains_biguint(&self, n: &BigUint) -> Option<bool> {
+        self.contains(n.to_u64()?)
+    }
+
+    /// Number of primes up to the limit.
+    pub fn count(&self) -> u64 {
+        let odd: u64 = self.words.iter().map(|w| u64::from(w.count_ones())).sum();
+        odd + u64::from(self.limit >= 2)
+    }
+
+    /// The primes up to the limit, ascending.
+    pub fn primes(&self) -> impl Iterator<Item = u64> + '_ {
+        let two = (self.limit >= 2).then_some(2);
+        let odd = self.words.iter().enumerate().flat_map(|(w, &word)| {
+            (0..64)
+                .filter(move |b| word >> b & 1 == 1)
+                .map(move |b| 2 * (w as u64 * 64 + b) + 1)
+        });
+        two.into_iter().chain(odd)
+    }
+}
+
+static INSTALLED: OnceLock<PrimeBitmap> = OnceLock::new();
+
+/// Make `bitmap` the one `is_prime_sieved` consults. Only the first call succeeds; later
+/// ones hand their bitmap back.
+pub fn install(bitmap: PrimeBitmap) -> Result<(), PrimeBitmap> {
+    INSTALLED.set(bitmap)
+}
+
+/// Primality of `n` from the installed bitmap, or `None` if there is none or `n` is past
+/// its limit.
+pub fn is_prime_sieved(n: &BigUint) -> Option<bool> {
+    INSTALLED.get()?.contains_biguint(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmaps_match_primal() {
+        for limit in [0u64, 1, 2, 3, 4, 5, 64, 127, 128, 129, 1000, 100_003] {
+            let expected: Vec<u64> = primal::Primes::all()
+                .map(|p| p as u64)
+                .take_while(|&p| p <= limit)
+                .collect();
+            for backend in [SieveBackend::Eratosthenes, SieveBackend::Atkin] {
+                let bitmap = PrimeBitmap::new(limit, backend);
+                let primes: Vec<u64> = bitmap.primes().collect();
+                assert_eq!(primes, expected, "{} to {}", backend, limit);
+                assert_eq!(bitmap.count(), expected.len() as u64);
+            }
+        }
+        let bitmap = PrimeBitmap::new(100, "atkin".parse().unwrap());
+        assert_eq!(bitmap.contains(97), Some(true));
+        assert_eq!(bitmap.contains(91), Some(false));
+        assert_eq!(bitmap.contains(101), None);
+
+        // A local bitmap, since installing one would change the path every other test takes
+        let bitmap = PrimeBitmap::new(1000, SieveBackend::Eratosthenes);
+        assert_eq!(bitmap.contains_biguint(&BigUint::from(997u32)), Some(true));
+        assert_eq!(bitmap.contains_biguint(&BigUint::from(999u32)), Some(false));
+        assert_eq!(bitmap.contains_biguint(&BigUint::from(1009u32)), None);
+        assert_eq!(bitmap.contains_biguint(&(BigUint::from(1u32) << 64u32)), None);
+    }
+}
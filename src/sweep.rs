@@ -0,0 +1,263 @@
+use num_bigint::BigInt;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::form::QuadraticForm;
+use crate::form_analysis::reduce_form;
+use crate::primality::is_bpsw_prime;
+use crate::search::evaluate_all;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RangeError {
+    #[error("invalid coefficient range '{0}', expected v, lo..hi or lo..=hi")]
+    Invalid(String),
+    #[error("empty coefficient range '{0}'")]
+    Empty(String),
+    #[error("too many forms to sweep, the coefficient ranges span more than {} forms", u64::MAX)]
+    TooManyForms,
+}
+
+/// Inclusive range of values one coefficient takes during a sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoefficientRange {
+    pub lo: i64,
+    pub hi: i64,
+}
+
+impl CoefficientRange {
+    pub fn fixed(value: i64) -> Self {
+        CoefficientRange { lo: value, hi: value }
+    }
+
+    /// Number of values in the range, or None for the full i64 range, which has 2⁶⁴.
+    pub fn count(&self) -> Option<u64> {
+        self.hi.abs_diff(self.lo).checked_add(1)
+    }
+}
+
+impl FromStr for CoefficientRange {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RangeError::Invalid(s.to_string());
+        let parse = |v: &str| v.trim().parse::<i64>().map_err(|_| invalid());
+        let (lo, hi) = if let Some((lo, hi)) = s.split_once("..=") {
+            (parse(lo)?, parse(hi)?)
+        } else if let Some((lo, hi)) = s.split_once("..") {
+            let hi = parse(hi)?.checked_sub(1).ok_or_else(invalid)?;
+            (parse(lo)?, hi)
+        } else {
+            let v = parse(s)?;
+            (v, v)
+        };
+        if lo > hi {
+            return Err(RangeError::Empty(s.to_string()));
+        }
+        Ok(CoefficientRange { lo, hi })
+    }
+}
+
+/// Bounds on all seven coefficients (a, b, c, d, e, f, g) of the swept forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoefficientBounds {
+    pub ranges: [CoefficientRange; 7],
+}
+
+impl CoefficientBounds {
+    /// Bounds that pin every coefficient to the given form.
+    pub fn fixed(coefficients: [i64; 7]) -> Self {
+        CoefficientBounds {
+            ranges: coefficients.map(CoefficientRange::fixed),
+        }
+    }
+
+    /// Number of forms the sweep will visit, or `TooManyForms` if that does not fit in a u64.
+    pub fn form_count(&self) -> Result<u64, RangeError> {
+        self.ranges.iter().try_fold(1u64, |total, range| {
+            range
+                .count()
+                .and_then(|count| total.checked_mul(count))
+                .ok_or(RangeError::TooManyForms)
+        })
+    }
+
+    /// Coefficient tuple for the `index`-th form in mixed-radix order. Only called once
+    /// `form_count` has succeeded, so every range count fits in a u64.
+    fn coefficients_at(&self, mut index: u64) -> [i64; 7] {
+        let mut coefficients = [0i64; 7];
+        for (slot, range) in coefficients.iter_mut().zip(self.ranges.iter()).rev() {
+            let count = range.count().expect("range counted by form_count");
+            *slot = range.lo.wrapping_add((index % count) as i64);
+            index /= count;
+        }
+        coefficients
+    }
+}
+
+/// How productive one form was over the sweep's (x, y, z) pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormDensity {
+    pub form: QuadraticForm,
+    pub hits: u64,
+    pub tuples: u64,
+}
+
+impl FormDensity {
+    pub fn density(&self) -> f64 {
+        if self.tuples == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.tuples as f64
+        }
+    }
+}
+
+/// Count values of `form` over every (x, y, z) in `pool`³ whose absolute value is prime.
+///
+/// Uses the deterministic BPSW test so sweeps are reproducible without a seed.
+pub fn hit_count(form: &QuadraticForm, pool: &[BigInt]) -> u64 {
+    evaluate_all(form, pool)
+        .filter(|(_, _, _, n)| is_bpsw_prime(n.magnitude()))
+        .count() as u64
+}
+
+/// Measure the hit density of every form within `bounds` over the same pool, in parallel,
+/// most productive forms first.
+pub fn sweep_forms(
+    bounds: &CoefficientBounds,
+    pool: &[BigInt],
+) -> Result<Vec<FormDensity>, RangeError> {
+    let forms = (0..bounds.form_count()?)
+        .into_par_iter()
+        .map(|index| QuadraticForm::new(bounds.coefficients_at(index)))
+        .collect();
+    Ok(measure(forms, pool))
+}
+
+/// Like `sweep_forms`, but skipping every form GL₃(Z)-equivalent to one earlier in the
+/// sweep. Equivalent forms take the same values, so only their hit counts over a finite pool
+/// differ. Forms that cannot be reduced (not positive definite) are all kept.
+///
+/// Returns the results and the number of forms skipped.
+pub fn sweep_distinct_forms(
+    bounds: &CoefficientBounds,
+    pool: &[BigInt],
+) -> Result<(Vec<FormDensity>, u64), RangeError> {
+    let reduced: Vec<(QuadraticForm, Option<QuadraticForm>)> = (0..bounds.form_count()?)
+        .into_par_iter()
+        .map(|index| {
+            let form = QuadraticForm::new(bounds.coefficients_at(index));
+            let canonical = reduce_form(&form);
+            (form, canonical)
+        })
+        .collect();
+    let total = reduced.len() as u64;
+    let mut seen = HashSet::new();
+    let forms: Vec<QuadraticForm> = reduced
+        .into_iter()
+        .filter(|(_, canonical)| canonical.as_ref().is_none_or(|c| seen.insert(c.clone())))
+        .map(|(form, _)| form)
+        .collect();
+    let skipped = total - forms.len() as u64;
+    Ok((measure(forms, pool), skipped))
+}
+
+fn measure(forms: Vec<QuadraticForm>, pool: &[BigInt]) -> Vec<FormDensity> {
+    let tuples = (pool.len() as u64).pow(3);
+    let mut results: Vec<FormDensity> = forms
+        .into_par_iter()
+        .map(|form| {
+            let hits = hit_count(&form, pool);
+            FormDensity { form, hits, tuples }
+        })
+        .collect();
+    results.sort_by_key(|r| std::cmp::Reverse(r.hits));
+    results
+}
+
+pub const SWEEP_CSV_HEADER: &str = "a,b,c,d,e,f,g,hits,tuples,density";
+
+/// Write sweep results as CSV rows.
+pub fn write_sweep<W: Write>(results: &[FormDensity], out: &mut W) -> io::Result<()> {
+    writeln!(out, "{}", SWEEP_CSV_HEADER)?;
+    for result in results {
+        let coefficients: Vec<String> = result
+            .form
+            .coefficients()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        writeln!(
+            out,
+            "{},{},{},{:.6}",
+            coefficients.join(","),
+            result.hits,
+            result.tuples,
+            result.density()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_parsing() {
+        assert_eq!("3".parse(), Ok(CoefficientRange::fixed(3)));
+        assert_eq!("1..4".parse(), Ok(CoefficientRange { lo: 1, hi: 3 }));
+        assert_eq!("1..=4".parse(), Ok(CoefficientRange { lo: 1, hi: 4 }));
+        assert_eq!("-3..=3".parse::<CoefficientRange>().map(|r| r.count()), Ok(Some(7)));
+        let full: CoefficientRange = "-9223372036854775808..=9223372036854775807".parse().unwrap();
+        assert_eq!(full.count(), None);
+        assert!("4..=1".parse::<CoefficientRange>().is_err());
+        assert!("x".parse::<CoefficientRange>().is_err());
+    }
+
+    #[test]
+    fn test_sweep_visits_every_form() {
+        let mut bounds = CoefficientBounds::fixed([5, 7, 11, 23, 47, 83, 107]);
+        bounds.ranges[6] = CoefficientRange { lo: 106, hi: 107 };
+        let pool: Vec<BigInt> = [3u32, 5, 7].iter().map(|&p| BigInt::from(p)).collect();
+        let results = sweep_forms(&bounds, &pool).unwrap();
+        assert_eq!(results.len(), 2);
+        let universal = results
+            .iter()
+            .find(|r| r.form == QuadraticForm::universal())
+            .unwrap();
+        assert_eq!(universal.hits, hit_count(&QuadraticForm::universal(), &pool));
+        assert_eq!(universal.tuples, 27);
+    }
+
+    #[test]
+    fn test_sweep_skips_equivalent_forms() {
+        // x² + c·y² + f·z² for c, f in 1..=2; swapping y and z makes x² + 2y² + z² and
+        // x² + y² + 2z² equivalent
+        let mut bounds = CoefficientBounds::fixed([1, 0, 1, 0, 0, 1, 0]);
+        bounds.ranges[2] = CoefficientRange { lo: 1, hi: 2 };
+        bounds.ranges[5] = CoefficientRange { lo: 1, hi: 2 };
+        let pool: Vec<BigInt> = [3u32, 5, 7].iter().map(|&p| BigInt::from(p)).collect();
+        let (results, skipped) = sweep_distinct_forms(&bounds, &pool).unwrap();
+        assert_eq!((results.len(), skipped), (3, 1));
+        assert_eq!(sweep_forms(&bounds, &pool).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_form_count_overflow() {
+        let mut bounds = CoefficientBounds::fixed([0; 7]);
+        assert_eq!(bounds.form_count(), Ok(1));
+        bounds.ranges[0] = CoefficientRange { lo: 0, hi: 1 << 32 };
+        bounds.ranges[1] = CoefficientRange { lo: 0, hi: 1 << 32 };
+        assert_eq!(bounds.form_count(), Err(RangeError::TooManyForms));
+        assert_eq!(sweep_forms(&bounds, &[]), Err(RangeError::TooManyForms));
+        assert_eq!(sweep_distinct_forms(&bounds, &[]), Err(RangeError::TooManyForms));
+        bounds.ranges[1] = CoefficientRange::fixed(0);
+        bounds.ranges[2] = CoefficientRange { lo: i64::MIN, hi: i64::MAX };
+        assert_eq!(bounds.form_count(), Err(RangeError::TooManyForms));
+    }
+}
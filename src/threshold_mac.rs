@@ -0,0 +1,282 @@
+//! Threshold PMPT-HMAC tags: the MAC key is dealt out in shares, each signer turns its share
+//! into a partial tag for a message, and `combine_partial_tags` adds partials from a quorum
+//! into the tag `PmptHmac::sign` would have made. The key is never put back together.
+//!
+//! A PMPT-HMAC tag byte is S[h] + N mod 256, where h is a byte of the hashed message, S the
+//! secret S-box and N a noise byte fixed by the private point. The dealer Shamir-shares every
+//! entry of S and every noise byte over the integers mod 521, the first prime above 2·255. As
+//! the message bytes are public, each tag byte is linear in the shares: a signer's partial
+//! is its Lagrange coefficient times its share of S[h] + N, and the partials of a quorum sum
+//! to S[h] + N exactly, with no reduction mod 521 to undo. The sums reveal one bit per byte
+//! beyond the tag, whether S[h] + N carried past 255.
+
+use num_bigint::BigUint;
+use rand::Rng;
+use thiserror::Error;
+
+use crate::pmpt::{hmac_input_bytes, HMACError, PmptHmac, SpherePoint};
+
+/// The prime the key shares live modulo.
+pub const FIELD: u32 = 521;
+
+#[derive(Error, Debug)]
+pub enum ThresholdMacError {
+    #[error("need 2 <= threshold <= shares <= 520, got threshold {threshold} of {shares}")]
+    InvalidThreshold { threshold: usize, shares: usize },
+    #[error("signer {0} is not in the signing set")]
+    NotASigner(u16),
+    #[error("signer {0} appears more than once")]
+    DuplicateSigner(u16),
+    #[error("no partial tag from signer {0}")]
+    MissingPartial(u16),
+    #[error("{have} signers, {need} needed")]
+    TooFew { have: usize, need: usize },
+    #[error("partial tags were made for different signing sets or keys")]
+    Mismatch,
+    #[error("partial tags do not add up to a tag; they cover different messages")]
+    Inconsistent,
+    #[error(transparent)]
+    Hmac(#[from] HMACError),
+}
+
+/// One signer's share of a PMPT-HMAC key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacKeyShare {
+    pub index: u16,
+    pub threshold: u16,
+    pub pad_length: usize,
+    /// Shares of the 256 S-box entries
+    sbox: Vec<u16>,
+    /// Shares of the noise bytes, x then y then z
+    noise: Vec<u16>,
+}
+
+/// A signer's contribution to the tag of one message, for one signing set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialTag {
+    pub index: u16,
+    pub threshold: u16,
+    /// Indices of the signers taking part, in increasing order
+    pub signers: Vec<u16>,
+    pub values: Vec<u16>,
+}
+
+fn mul_mod(a: u32, b: u32) -> u32 {
+    a * b % FIELD
+}
+
+fn inv_mod(a: u32) -> u32 {
+    // Fermat: a^(p - 2)
+    let (mut result, mut base, mut exponent) = (1, a % FIELD, FIELD - 2);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base);
+        }
+        base = mul_mod(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Shamir-share `value` over the integers mod `FIELD`, evaluating at 1 to `shares`.
+fn share_value<R: Rng + ?Sized>(
+    value: u8,
+    threshold: usize,
+    shares: usize,
+    rng: &mut R,
+) -> Vec<u16> {
+    let mut coefficients = vec![value as u32];
+    coefficients.extend((1..threshold).map(|_| rng.gen_range(0..FIELD)));
+    (1..=shares as u32)
+        .map(|x| coefficients.iter().rev().fold(0, |y, &c| (mul_mod(y, x) + c) % FIELD) as u16)
+        .collect()
+}
+
+/// Deal the key of `hmac` out as `shares` shares, any `threshold` of which can sign together.
+///
+/// The dealer holds the full key while splitting it; afterwards it can be discarded, as
+/// signing only needs the shares.
+pub fn split_mac_key<R: Rng + ?Sized>(
+    hmac: &PmptHmac,
+    threshold: usize,
+    shares: usize,
+    rng: &mut R,
+) -> Result<Vec<MacKeyShare>, ThresholdMacError> {
+    if threshold < 2 || threshold > shares || shares >= FIELD as usize {
+        return Err(ThresholdMacError::InvalidThreshold { threshold, shares });
+    }
+    let mut result: Vec<MacKeyShare> = (1..=shares as u16)
+        .map(|index| MacKeyShare {
+            index,
+            threshold: threshold as u16,
+            pad_length: hmac.pad_length(),
+            sbox: Vec::with_capacity(256),
+            noise: Vec::with_capacity(3 * hmac.pad_length()),
+        })
+        .collect();
+    for byte in 0..=255u8 {
+        let dealt = share_value(hmac.sbox().substitute(byte), threshold, shares, rng);
+        for (share, value) in result.iter_mut().zip(dealt) {
+            share.sbox.push(value);
+        }
+    }
+    for noise in hmac.noise()? {
+        for (share, value) in result.iter_mut().zip(share_value(noise, threshold, shares, rng)) {
+            share.noise.push(value);
+        }
+    }
+    Ok(result)
+}
+
+/// The Lagrange coefficient at 0 of `index` among `signers`.
+fn lagrange_at_zero(index: u16, signers: &[u16]) -> u32 {
+    let x = index as u32;
+    signers.iter().filter(|&&j| j != index).fold(1, |acc, &j| {
+        let j = j as u32;
+        mul_mod(acc, mul_mod(j, inv_mod((j + FIELD - x) % FIELD)))
+    })
+}
+
+/// `signers` sorted, checked for repeats and for holding at least `threshold` indices.
+fn signing_set(signers: &[u16], threshold: u16) -> Result<Vec<u16>, ThresholdMacError> {
+    let mut sorted = signers.to_vec();
+    sorted.sort_unstable();
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(ThresholdMacError::DuplicateSigner(pair[0]));
+    }
+    if sorted.len() < threshold as usize {
+        return Err(ThresholdMacError::TooFew { have: sorted.len(), need: threshold as usize });
+    }
+    Ok(sorted)
+}
+
+impl MacKeyShare {
+    /// This share's partial tag over `data` when signing together with `signers`, which must
+    /// include this share and be the same set for every partial being combined.
+    pub fn partial_tag(
+        &self,
+        data: &[u8],
+        signers: &[u16],
+    ) -> Result<PartialTag, ThresholdMacError> {
+        let signers = signing_set(signers, self.threshold)?;
+        if !signers.contains(&self.index) {
+            return Err(ThresholdMacError::NotASigner(self.index));
+        }
+        let lambda = lagrange_at_zero(self.index, &signers);
+        let input = hmac_input_bytes(data, self.pad_length)?;
+        let values = input
+            .iter()
+            .zip(&self.noise)
+            .map(|(&h, &noise)| mul_mod(lambda, self.sbox[h as usize] as u32 + noise as u32))
+            .map(|value| value as u16)
+            .collect();
+        Ok(PartialTag {
+            index: self.index,
+            threshold: self.threshold,
+            signers,
+            values,
+        })
+    }
+}
+
+/// The PMPT-HMAC tag assembled from the partial tags of every member of one signing set.
+pub fn combine_partial_tags(partials: &[PartialTag]) -> Result<SpherePoint, ThresholdMacError> {
+    let first = partials.first().ok_or(ThresholdMacError::TooFew { have: 0, need: 2 })?;
+    let signers = signing_set(&first.signers, first.threshold)?;
+    for partial in partials {
+        let same = partial.signers == signers && partial.threshold == first.threshold;
+        if !same || partial.values.len() != first.values.len() {
+            return Err(ThresholdMacError::Mismatch);
+        }
+    }
+    let mut indices: Vec<u16> = partials.iter().map(|partial| partial.index).collect();
+    indices.sort_unstable();
+    if let Some(pair) = indices.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(ThresholdMacError::DuplicateSigner(pair[0]));
+    }
+    if let Some(&outsider) = indices.iter().find(|index| !signers.contains(index)) {
+        return Err(ThresholdMacError::NotASigner(outsider));
+    }
+    if let Some(&missing) = signers.iter().find(|index| !indices.contains(index)) {
+        return Err(ThresholdMacError::MissingPartial(missing));
+    }
+    if first.values.len() % 3 != 0 {
+        return Err(ThresholdMacError::Mismatch);
+    }
+
+    let mut bytes = Vec::with_capacity(first.values.len());
+    for i in 0..first.values.len() {
+        let sum = partials.iter().fold(0, |sum, partial| (sum + partial.values[i] as u32) % FIELD);
+        // S[h] + N is at most 510; anything larger means the partials do not belong together
+        if sum > 510 {
+            return Err(ThresholdMacError::Inconsistent);
+        }
+        bytes.push(sum as u8);
+    }
+    let mut coordinates = bytes.chunks_exact(bytes.len() / 3).map(BigUint::from_bytes_be);
+    let (x, y, z) = (coordinates.next(), coordinates.next(), coordinates.next());
+    Ok(SpherePoint::new(x.unwrap(), y.unwrap(), z.unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmpt::{DynamicSBox, KeyPair};
+    use crate::primality::PrimalityConfig;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_threshold_tags() {
+        let mut rng = ChaCha20Rng::seed_from_u64(19);
+        let keys = KeyPair::generate_with_config(64, &PrimalityConfig::default(), &mut rng);
+        let hmac = PmptHmac::new(
+            keys.public_key.clone(),
+            keys.private_key.clone(),
+            DynamicSBox::new(&mut rng),
+            keys.pad_length,
+            keys.modulus.clone(),
+        );
+        let shares = split_mac_key(&hmac, 3, 5, &mut rng).unwrap();
+        let sign = |signers: &[u16], data: &[u8]| -> Vec<PartialTag> {
+            let holders = shares.iter().filter(|share| signers.contains(&share.index));
+            holders.map(|share| share.partial_tag(data, signers).unwrap()).collect()
+        };
+
+        for signers in [&[1, 3, 5][..], &[2, 3, 4], &[5, 4, 3, 2, 1]] {
+            let tag = combine_partial_tags(&sign(signers, b"release")).unwrap();
+            assert_eq!(tag, hmac.sign(b"release").unwrap());
+            assert!(hmac.verify(b"release", &tag).unwrap());
+        }
+
+        // Too few signers, a missing partial, mixed signing sets
+        assert!(matches!(
+            shares[0].partial_tag(b"release", &[1, 2]),
+            Err(ThresholdMacError::TooFew { have: 2, need: 3 })
+        ));
+        assert!(matches!(
+            shares[0].partial_tag(b"release", &[2, 3, 4]),
+            Err(ThresholdMacError::NotASigner(1))
+        ));
+        let partials = sign(&[1, 2, 3], b"release");
+        let missing = combine_partial_tags(&partials[..2]);
+        assert!(matches!(missing, Err(ThresholdMacError::MissingPartial(3))));
+        let mut mixed = sign(&[1, 2, 4], b"release");
+        mixed[2] = partials[2].clone();
+        assert!(matches!(combine_partial_tags(&mixed), Err(ThresholdMacError::Mismatch)));
+        let repeated = [partials[0].clone(), partials[1].clone(), partials[1].clone()];
+        let duplicate = combine_partial_tags(&repeated);
+        assert!(matches!(duplicate, Err(ThresholdMacError::DuplicateSigner(2))));
+
+        // Partials over different messages do not make a valid tag
+        let mut crossed = sign(&[1, 2, 3], b"other");
+        crossed[0] = partials[0].clone();
+        if let Ok(tag) = combine_partial_tags(&crossed) {
+            assert!(!hmac.verify(b"release", &tag).unwrap());
+        }
+        assert!(matches!(
+            split_mac_key(&hmac, 1, 5, &mut rng),
+            Err(ThresholdMacError::InvalidThreshold { threshold: 1, shares: 5 })
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+//! The extreme prime hits of a run, tracked as rows are written.
+//!
+//! Two bounded heaps hold the K largest distinct |N| and, given a bound, the K smallest above
+//! it, so a search can report them at the end in O(K) memory instead of a sort over its whole
+//! output. Each heap keeps its weakest member on top, which a better value displaces.
+
+use num_bigint::BigUint;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The `k` largest prime hits, and the `k` smallest above a bound if one is set.
+#[derive(Debug, Clone)]
+pub struct TopHits {
+    k: usize,
+    /// Min-heap: the smallest of the largest is on top
+    largest: BinaryHeap<Reverse<BigUint>>,
+    /// Max-heap: the largest of the smallest is on top
+    smallest: BinaryHeap<BigUint>,
+    above: Option<BigUint>,
+}
+
+impl TopHits {
+    pub fn new(k: usize) -> Self {
+        TopHits {
+            k,
+            largest: BinaryHeap::with_capacity(k + 1),
+            smallest: BinaryHeap::new(),
+            above: None,
+        }
+    }
+
+    /// Also track the `k` smallest hits strictly greater than `bound`.
+    pub fn smallest_above(mut self, bound: BigUint) -> Self {
+        self.smallest = BinaryHeap::with_capacity(self.k + 1);
+        self.above = Some(bound);
+        self
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// The bound of the smallest hits, if they are tracked.
+    pub fn bound(&self) -> Option<&BigUint> {
+        self.above.as_ref()
+    }
+
+    /// Offer a hit; a value already held is not counted twice.
+    pub fn record(&mut self, n: &BigUint) {
+        if self.k == 0 {
+            return;
+        }
+        let displaces_largest =
+            self.largest.len() < self.k || self.largest.peek().is_some_and(|Reverse(m)| n > m);
+        if displaces_largest && !self.largest.iter().any(|Reverse(m)| m == n) {
+            self.largest.push(Reverse(n.clone()));
+            if self.largest.len() > self.k {
+                self.largest.pop();
+            }
+        }
+        if self.above.as_ref().is_some_and(|bound| n > bound) {
+            let displaces_smallest =
+                self.smallest.len() < self.k || self.smallest.peek().is_some_and(|m| n < m);
+            if displaces_smallest && !self.smallest.iter().any(|m| m == n) {
+                self.smallest.push(n.clone());
+                if self.smallest.len() > self.k {
+                    self.smallest.pop();
+                }
+            }
+        }
+    }
+
+    /// The largest hits, largest first.
+    pub fn largest(&self) -> Vec<BigUint> {
+        let mut largest: Vec<BigUint> = self.largest.iter().map(|Reverse(n)| n.clone()).collect();
+        largest.sort_unstable_by(|a, b| b.cmp(a));
+        largest
+    }
+
+    /// The smallest hits above the bound, smallest first; empty without a bound.
+    pub fn smallest(&self) -> Vec<BigUint> {
+        let mut smallest: Vec<BigUint> = self.smallest.iter().cloned().collect();
+        smallest.sort_unstable();
+        smallest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_top_hits() {
+        let mut rng = ChaCha20Rng::seed_from_u64(96);
+        let values: Vec<u64> = (0..2000).map(|_| rng.gen_range(0..5000)).collect();
+        let mut top = TopHits::new(5).smallest_above(BigUint::from(1000u32));
+        for &n in &values {
+            top.record(&BigUint::from(n));
+        }
+
+        let mut distinct = values.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let expected_largest: Vec<BigUint> =
+            distinct.iter().rev().take(5).map(|&n| BigUint::from(n)).collect();
+        let expected_smallest: Vec<BigUint> =
+            distinct.iter().filter(|&&n| n > 1000).take(5).map(|&n| BigUint::from(n)).collect();
+        assert_eq!(top.largest(), expected_largest);
+        assert_eq!(top.smallest(), expected_smallest);
+
+        let mut unbounded = TopHits::new(3);
+        for n in [7u32, 7, 7, 2] {
+            unbounded.record(&BigUint::from(n));
+        }
+        let expected: Vec<BigUint> = vec![BigUint::from(7u32), BigUint::from(2u32)];
+        assert_eq!(unbounded.largest(), expected);
+        assert!(unbounded.smallest().is_empty());
+    }
+}
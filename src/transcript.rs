@@ -0,0 +1,258 @@
+//! An audit trail of cryptographic operations, for when key splitting is done for real.
+//!
+//! Each entry records an operation and its public parameters and is hashed together with
+//! the previous entry's hash, so editing, dropping or reordering entries breaks the chain.
+//! Secrets never enter the transcript: keys appear as fingerprints and data as digests.
+//!
+//! ```text
+//! {"entries":[{"sequence":0,"timestamp":1700000000,"operation":"key_generation",
+//!   "fields":{"modulus_bits":"128",...},"previous":"00…00","hash":"…"}]}
+//! ```
+
+use std::collections::BTreeMap;
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domains;
+use crate::pmpt::{shake256_parts, Ciphertext, KeyPair, VerifyingKey};
+use crate::primality::PrimalityConfig;
+
+#[derive(Error, Debug)]
+pub enum TranscriptError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("entry {0} does not chain from the entry before it")]
+    BrokenChain(u64),
+    #[error("entry {0} does not match its hash")]
+    HashMismatch(u64),
+}
+
+/// One recorded operation. Hashes are 32-byte SHAKE256 values in hex.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub operation: String,
+    pub fields: BTreeMap<String, String>,
+    pub previous: String,
+    pub hash: String,
+}
+
+impl TranscriptEntry {
+    fn compute_hash(&self) -> String {
+        let mut parts: Vec<&[u8]> = Vec::with_capacity(4 + 2 * self.fields.len());
+        let sequence = self.sequence.to_be_bytes();
+        let timestamp = self.timestamp.to_be_bytes();
+        parts.extend([
+            self.previous.as_bytes(),
+            &sequence[..],
+            &timestamp[..],
+            self.operation.as_bytes(),
+        ]);
+        for (name, value) in &self.fields {
+            parts.push(name.as_bytes());
+            parts.push(value.as_bytes());
+        }
+        let mut hash = [0u8; 32];
+        shake256_parts(domains::TRANSCRIPT_ENTRY, &parts, &mut hash);
+        hex::encode(hash)
+    }
+}
+
+/// A hash chain of operations, exportable as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+/// The `previous` hash of the first entry.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+fn digest_hex(data: &[u8]) -> String {
+    let mut digest = [0u8; 32];
+    shake256_parts(domains::TRANSCRIPT_DATA, &[data], &mut digest);
+    hex::encode(digest)
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Transcript::default()
+    }
+
+    /// Hash of the last entry, which commits to the whole transcript.
+    pub fn head(&self) -> &str {
+        self.entries.last().map_or(GENESIS, |entry| &entry.hash)
+    }
+
+    /// Append an operation with its fields.
+    pub fn record(
+        &mut self,
+        operation: &str,
+        fields: BTreeMap<String, String>,
+        timestamp: u64,
+    ) -> &TranscriptEntry {
+        let mut entry = TranscriptEntry {
+            sequence: self.entries.len() as u64,
+            timestamp,
+            operation: operation.to_string(),
+            fields,
+            previous: self.head().to_string(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        self.entries.push(entry);
+        self.entries.last().expect("just pushed")
+    }
+
+    /// Record the parameters of a key generation and a digest of the public key.
+    pub fn record_key_generation(
+        &mut self,
+        keys: &KeyPair,
+        secret_bits: usize,
+        config: &PrimalityConfig,
+        timestamp: u64,
+    ) -> &TranscriptEntry {
+        let public = &keys.public_key;
+        let public_digest = digest_hex(
+            &[
+                public.x.to_bytes_be(),
+                public.y.to_bytes_be(),
+                public.z.to_bytes_be(),
+                keys.modulus.to_bytes_be(),
+            ]
+            .concat(),
+        );
+        let fields = BTreeMap::from([
+            ("secret_bits".to_string(), secret_bits.to_string()),
+            ("modulus_bits".to_string(), keys.modulus.bits().to_string()),
+            ("pad_length".to_string(), keys.pad_length.to_string()),
+            ("rounds".to_string(), config.rounds.to_string()),
+            ("use_bpsw".to_string(), config.use_bpsw.to_string()),
+            ("public_key".to_string(), public_digest),
+        ]);
+        self.record("key_generation", fields, timestamp)
+    }
+
+    /// Record an encryption by its nonce, key commitment and format.
+    pub fn record_encryption(
+        &mut self,
+        ciphertext: &Ciphertext,
+        timestamp: u64,
+    ) -> &TranscriptEntry {
+        let fields = BTreeMap::from([
+            ("nonce".to_string(), hex::encode(ciphertext.nonce)),
+            ("commitment".to_string(), hex::encode(ciphertext.commitment)),
+            ("version".to_string(), ciphertext.version.to_string()),
+            ("rounds".to_string(), ciphertext.rounds.to_string()),
+            ("pad_length".to_string(), ciphertext.pad_length.to_string()),
+        ]);
+        self.record("encryption", fields, timestamp)
+    }
+
+    /// Record a signing request by the signer's fingerprint and a digest of the data.
+    pub fn record_signature(
+        &mut self,
+        key: &VerifyingKey,
+        data: &[u8],
+        timestamp: u64,
+    ) -> &TranscriptEntry {
+        let fields = BTreeMap::from([
+            ("fingerprint".to_string(), hex::encode(key.fingerprint())),
+            ("data".to_string(), digest_hex(data)),
+        ]);
+        self.record("signature", fields, timestamp)
+    }
+
+    /// Record a Shamir split by its threshold, share count and prime field.
+    pub fn record_shamir_split(
+        &mut self,
+        threshold: usize,
+        shares: usize,
+        modulus: &BigUint,
+        timestamp: u64,
+    ) -> &TranscriptEntry {
+        let fields = BTreeMap::from([
+            ("threshold".to_string(), threshold.to_string()),
+            ("shares".to_string(), shares.to_string()),
+            ("modulus".to_string(), modulus.to_string()),
+        ]);
+        self.record("shamir_split", fields, timestamp)
+    }
+
+    /// Check sequence numbers, links and hashes from the first entry on.
+    pub fn verify(&self) -> Result<(), TranscriptError> {
+        let mut previous = GENESIS;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.sequence != i as u64 || entry.previous != previous {
+                return Err(TranscriptError::BrokenChain(i as u64));
+            }
+            if entry.compute_hash() != entry.hash {
+                return Err(TranscriptError::HashMismatch(i as u64));
+            }
+            previous = &entry.hash;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("transcripts always serialize")
+    }
+
+    /// Parse and verify an exported transcript.
+    pub fn from_json(json: &str) -> Result<Self, TranscriptError> {
+        let transcript: Transcript = serde_json::from_str(json)?;
+        transcript.verify()?;
+        Ok(transcript)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmpt::{encrypt, DynamicSBox, SignatureGroup};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_transcript_chain() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(27);
+        let config = PrimalityConfig::default();
+        let keys = KeyPair::generate_with_config(64, &config, &mut rng);
+        let sbox = DynamicSBox::new(&mut rng);
+        let ciphertext = encrypt(
+            "audit",
+            &keys.public_key,
+            &keys.private_key,
+            &sbox,
+            keys.pad_length,
+            &keys.modulus,
+            &mut rng,
+        )
+        .unwrap();
+        let group = SignatureGroup::generate(64, &config, &mut rng);
+        let signing_key = keys.signing_key(group);
+
+        let mut transcript = Transcript::new();
+        transcript.record_key_generation(&keys, 64, &config, 1);
+        transcript.record_encryption(&ciphertext, 2);
+        transcript.record_signature(signing_key.verifying_key(), b"release", 3);
+        transcript.record_shamir_split(3, 5, &keys.modulus, 4);
+        assert_eq!(transcript.entries[1].fields["nonce"], hex::encode(ciphertext.nonce));
+
+        let json = transcript.to_json();
+        assert!(!json.contains(&keys.private_key.x.to_string()));
+        assert_eq!(Transcript::from_json(&json).unwrap(), transcript);
+
+        let mut edited = transcript.clone();
+        edited.entries[3].fields.insert("threshold".to_string(), "2".to_string());
+        assert!(matches!(edited.verify(), Err(TranscriptError::HashMismatch(3))));
+        let mut dropped = transcript.clone();
+        dropped.entries.remove(1);
+        assert!(matches!(dropped.verify(), Err(TranscriptError::BrokenChain(1))));
+        assert!(matches!(
+            Transcript::from_json(&edited.to_json()),
+            Err(TranscriptError::HashMismatch(3))
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+//! Batched trial division by small primes.
+//!
+//! Dividing a `BigUint` by one small prime at a time walks its digits once per prime and
+//! allocates a quotient each time. Here `LANES` divisors share one walk over the 32-bit
+//! digits, each lane carrying its own running remainder in a u64. The lanes are plain fixed
+//! width arrays updated in lockstep, which the compiler turns into vector code where the
+//! target has it; divisors left over after the last full batch take the scalar path.
+//!
+//! BPSW always screens with its handful of small primes this way. The longer pass of
+//! `first_trial_divisor` only runs when `PrimalityConfig::trial_division_bound` is set
+//! (`--trial-division-bound` on the command line); its default of 0 skips it.
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+use std::sync::OnceLock;
+
+/// Divisors tested together in one pass over the digits.
+pub const LANES: usize = 8;
+
+/// Primes below this come from a table built once; trial division beyond it takes every odd
+/// number.
+const TABLE_BOUND: u32 = 1 << 16;
+
+/// Odd divisors past the table are generated and tested this many at a time.
+const CHUNK: usize = 1 << 12;
+
+/// The remainders of the number with little-endian 32-bit `digits` modulo each divisor.
+/// Divisors must be nonzero and fit in 32 bits, so a remainder shifted up a digit fits a u64.
+fn lane_residues(digits: &[u32], divisors: &[u64; LANES]) -> [u64; LANES] {
+    let mut residues = [0u64; LANES];
+    for &digit in digits.iter().rev() {
+        for (residue, &divisor) in residues.iter_mut().zip(divisors) {
+            *residue = ((*residue << 32) | digit as u64) % divisor;
+        }
+    }
+    residues
+}
+
+/// The remainder of the number with little-endian 32-bit `digits` modulo one divisor.
+fn scalar_residue(digits: &[u32], divisor: u64) -> u64 {
+    digits.iter().rev().fold(0, |residue, &digit| ((residue << 32) | digit as u64) % divisor)
+}
+
+/// The first of `divisors` (in order) that divides `n`, if any; zero divisors are skipped.
+///
+/// Full batches of `LANES` divisors are tested together; with `batched` off every divisor
+/// takes the scalar path, which gives the same answer.
+pub fn first_divisor_with(n: &BigUint, divisors: &[u32], batched: bool) -> Option<u32> {
+    let digits = n.to_u32_digits();
+    let (batches, rest) = if batched {
+        let full = divisors.len() / LANES * LANES;
+        divisors.split_at(full)
+    } else {
+        divisors.split_at(0)
+    };
+    for batch in batches.chunks_exact(LANES) {
+        let lanes: [u64; LANES] = std::array::from_fn(|i| batch[i].max(1) as u64);
+        let residues = lane_residues(&digits, &lanes);
+        if let Some(i) = (0..LANES).find(|&i| batch[i] != 0 && residues[i] == 0) {
+            return Some(batch[i]);
+        }
+    }
+    rest.iter().copied().find(|&d| d != 0 && scalar_residue(&digits, d as u64) == 0)
+}
+
+/// The first of `divisors` (in order) that divides `n`, testing `LANES` at a time.
+pub fn first_divisor(n: &BigUint, divisors: &[u32]) -> Option<u32> {
+    first_divisor_with(n, divisors, true)
+}
+
+/// The primes below 2^16, in increasing order.
+pub fn small_primes() -> &'static [u32] {
+    static TABLE: OnceLock<Vec<u32>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        primal::Primes::all()
+            .take_while(|&p| p < TABLE_BOUND as usize)
+            .map(|p| p as u32)
+            .collect()
+    })
+}
+
+/// The first trial divisor of `n` below `bound`, trying the primes below 2^16 and then the
+/// odd numbers beyond it. A composite n has a divisor no larger than its square root, so the
+/// pass stops there, and `None` means no divisor up to `min(bound - 1, isqrt(n))`. The odd
+/// divisors are produced a chunk at a time, never all at once.
+pub fn first_trial_divisor(n: &BigUint, bound: u32) -> Option<u32> {
+    let limit = match n.bits() {
+        0..=64 => n.sqrt().to_u32().map_or(bound, |root| bound.min(root.saturating_add(1))),
+        _ => bound,
+    };
+    let primes = small_primes();
+    let table = &primes[..primes.partition_point(|&p| p < limit)];
+    if let Some(d) = first_divisor(n, table) {
+        return Some(d);
+    }
+    let mut odd = (TABLE_BOUND + 1..limit).step_by(2);
+    let mut chunk = Vec::with_capacity(CHUNK);
+    loop {
+        chunk.clear();
+        chunk.extend(odd.by_ref().take(CHUNK));
+        if chunk.is_empty() {
+            return None;
+        }
+        if let Some(d) = first_divisor(n, &chunk) {
+            return Some(d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_batched_trial_division_matches_scalar() {
+        let mut rng = ChaCha20Rng::seed_from_u64(3);
+        let divisors = &small_primes()[..168];
+        assert_eq!((divisors[0], divisors[167]), (2, 997));
+        for _ in 0..200 {
+            let n = rng.gen_biguint(200);
+            let expected = divisors.iter().copied().find(|&d| (&n % d) == BigUint::from(0u32));
+            assert_eq!(first_divisor(&n, divisors), expected);
+            assert_eq!(first_divisor_with(&n, divisors, false), expected);
+            assert_eq!(first_trial_divisor(&n, 1000), expected);
+        }
+
+        // Odd parts that are a product of primes in two batches, and a prime past the last
+        let n = BigUint::from(773u32 * 409) << 80;
+        assert_eq!(first_divisor(&n, &divisors[1..]), Some(409));
+        let n = BigUint::from(983u32) << 100;
+        assert_eq!(first_divisor(&n, &divisors[1..]), Some(983));
+        assert_eq!(first_divisor(&BigUint::from(1_000_003u32), divisors), None);
+        assert_eq!(first_divisor(&BigUint::from(0u32), &[0, 5]), Some(5));
+    }
+
+    #[test]
+    fn test_first_trial_divisor() {
+        // Past the table every odd number is tried, up to but excluding the bound
+        let n = BigUint::from(65_537u64 * 65_539);
+        assert_eq!(first_trial_divisor(&n, TABLE_BOUND + 2), Some(65_537));
+        assert_eq!(first_trial_divisor(&n, TABLE_BOUND + 1), None);
+        let n = BigUint::from(999_983u64 * 1_000_003);
+        assert_eq!(first_trial_divisor(&n, u32::MAX), Some(999_983));
+
+        // Nothing past the square root is tried, so a huge bound costs no more than isqrt(n)
+        assert_eq!(first_trial_divisor(&BigUint::from(1_000_003u32), u32::MAX), None);
+        assert_eq!(first_trial_divisor(&BigUint::from(4u32), u32::MAX), Some(2));
+        assert_eq!(first_trial_divisor(&BigUint::from(3u32), u32::MAX), None);
+        let n = (BigUint::from(1u32) << 127u32) - 1u32;
+        assert_eq!(first_trial_divisor(&n, 1 << 17), None);
+    }
+}
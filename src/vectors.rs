@@ -0,0 +1,326 @@
+//! Known-answer test vectors, so other implementations can check they agree with this one.
+//!
+//! Everything is drawn from one ChaCha20 stream seeded with the vector seed: the PMPT key
+//! pairs, the plaintexts and messages, the GF(256) sharing polynomials and the Miller-Rabin
+//! witnesses of the classifications. The same seed therefore always gives the same file.
+//! Integers are decimal strings and byte strings are hex; ciphertexts are in the
+//! `Ciphertext::to_bytes` layout and tags in the `PmptHmac::signature_bytes` one. Each
+//! S-box is the one `DynamicSBox::derive` makes from the private point.
+
+use num_bigint::BigUint;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::classify::classify_with_pseudoprimes;
+use crate::gf256::{self, ByteShare};
+use crate::jwk::mask_name;
+use crate::pmpt::{
+    decrypt, encrypt_with_nonce, encrypt_with_params, CipherParams, Ciphertext, DynamicSBox,
+    KeyPair, MaskMode, PmptHmac, SpherePoint,
+};
+use crate::primality::PrimalityConfig;
+
+/// The seed of the published vectors.
+pub const DEFAULT_SEED: u64 = 0x5645_4354_4f52_5331;
+
+/// Bits of the secret prime behind each vector key pair.
+const KEY_BITS: usize = 64;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorError {
+    #[error("{section} vector {index} does not match this implementation")]
+    Mismatch { section: &'static str, index: usize },
+    #[error("{section} vector {index} is malformed")]
+    Malformed { section: &'static str, index: usize },
+}
+
+/// A PMPT key pair and its derived S-box.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyVector {
+    pub public_key: [String; 3],
+    pub private_key: [String; 3],
+    pub modulus: String,
+    pub pad_length: usize,
+    pub sbox: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherVector {
+    pub key: KeyVector,
+    pub rounds: u32,
+    pub mask: String,
+    pub plaintext: String,
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HmacVector {
+    pub key: KeyVector,
+    pub message: String,
+    pub tag: String,
+}
+
+/// A GF(256) sharing: every share, and the shares `combine` is given to recover the secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShamirVector {
+    pub secret: String,
+    pub threshold: usize,
+    /// Index and data of each share
+    pub shares: Vec<(u8, String)>,
+    pub combine: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassificationVector {
+    pub number: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub seed: u64,
+    pub cipher: Vec<CipherVector>,
+    pub hmac: Vec<HmacVector>,
+    pub shamir: Vec<ShamirVector>,
+    pub classification: Vec<ClassificationVector>,
+}
+
+fn point_strings(point: &SpherePoint) -> [String; 3] {
+    [&point.x, &point.y, &point.z].map(|c| c.to_string())
+}
+
+impl KeyVector {
+    fn new(keys: &KeyPair) -> Self {
+        KeyVector {
+            public_key: point_strings(&keys.public_key),
+            private_key: point_strings(&keys.private_key),
+            modulus: keys.modulus.to_string(),
+            pad_length: keys.pad_length,
+            sbox: hex::encode(DynamicSBox::derive(&keys.private_key).table()),
+        }
+    }
+
+    /// The key pair and S-box, or `None` if a number does not parse or the S-box differs
+    /// from the derived one.
+    fn keys(&self) -> Option<(KeyPair, DynamicSBox)> {
+        let point = |c: &[String; 3]| -> Option<SpherePoint> {
+            let mut values = c.iter().map(|s| s.parse::<BigUint>().ok());
+            Some(SpherePoint::new(values.next()??, values.next()??, values.next()??))
+        };
+        let keys = KeyPair {
+            public_key: point(&self.public_key)?,
+            private_key: point(&self.private_key)?,
+            modulus: self.modulus.parse().ok()?,
+            pad_length: self.pad_length,
+        };
+        let sbox = DynamicSBox::derive(&keys.private_key);
+        (hex::encode(sbox.table()) == self.sbox).then_some((keys, sbox))
+    }
+}
+
+fn hmac_for(keys: &KeyPair, sbox: DynamicSBox) -> PmptHmac {
+    PmptHmac::new(
+        keys.public_key.clone(),
+        keys.private_key.clone(),
+        sbox,
+        keys.pad_length,
+        keys.modulus.clone(),
+    )
+}
+
+/// Printable ASCII of `length` characters, so plaintexts survive any JSON reader.
+fn ascii<R: Rng + ?Sized>(length: usize, rng: &mut R) -> String {
+    (0..length).map(|_| rng.gen_range(b' '..=b'~') as char).collect()
+}
+
+impl TestVectors {
+    /// The vectors for `seed`.
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let config = PrimalityConfig::default();
+
+        let params = [
+            CipherParams::single_round(),
+            CipherParams::default(),
+            CipherParams { rounds: 2, mask: MaskMode::Keystream },
+        ];
+        let mut cipher = Vec::new();
+        for params in params {
+            let keys = KeyPair::generate_with_config(KEY_BITS, &config, &mut rng);
+            let sbox = DynamicSBox::derive(&keys.private_key);
+            let length = rng.gen_range(1..keys.pad_length);
+            let plaintext = ascii(length, &mut rng);
+            let ciphertext = encrypt_with_params(
+                &plaintext,
+                &keys.public_key,
+                &keys.private_key,
+                &sbox,
+                keys.pad_length,
+                &keys.modulus,
+                params,
+                &mut rng,
+            )
+            .expect("the plaintext fits the pad");
+            cipher.push(CipherVector {
+                key: KeyVector::new(&keys),
+                rounds: params.rounds,
+                mask: mask_name(params.mask).to_string(),
+                plaintext,
+                ciphertext: hex::encode(ciphertext.to_bytes().expect("coordinates fit the pad")),
+            });
+        }
+
+        let mut hmac = Vec::new();
+        for length in [0, 32, 200] {
+            let keys = KeyPair::generate_with_config(KEY_BITS, &config, &mut rng);
+            let mac = hmac_for(&keys, DynamicSBox::derive(&keys.private_key));
+            let message: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+            let tag = mac.sign(&message).expect("vector keys sign");
+            hmac.push(HmacVector {
+                key: KeyVector::new(&keys),
+                message: hex::encode(&message),
+                tag: hex::encode(mac.signature_bytes(&tag).expect("tags fit the pad")),
+            });
+        }
+
+        let mut shamir = Vec::new();
+        for (length, threshold, count) in [(1, 2, 2), (16, 3, 5), (33, 5, 8)] {
+            let secret: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+            let shares = gf256::split(&secret, threshold, count, &mut rng).expect("valid split");
+            // The last `threshold` shares, highest index first
+            let combine = (1..=count as u8).rev().take(threshold).collect();
+            shamir.push(ShamirVector {
+                secret: hex::encode(&secret),
+                threshold,
+                shares: shares.iter().map(|s| (s.index, hex::encode(&s.data))).collect(),
+                combine,
+            });
+        }
+
+        let numbers = [
+            BigUint::from(2u32),
+            BigUint::from(11u32),
+            BigUint::from(23u32),
+            BigUint::from(97u32),
+            BigUint::from(561u32),
+            (BigUint::from(1u32) << 61u32) - 1u32,
+            (BigUint::from(1u32) << 67u32) - 1u32,
+        ];
+        let classification = numbers
+            .iter()
+            .map(|n| ClassificationVector {
+                number: n.to_string(),
+                tags: classify_with_pseudoprimes(n, &config, &mut rng)
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            })
+            .collect();
+
+        TestVectors { seed, cipher, hmac, shamir, classification }
+    }
+
+    /// Recompute every vector from its inputs: ciphertexts must decrypt and re-encrypt to
+    /// themselves, tags and shares must match, and classifications must agree.
+    pub fn check(&self) -> Result<(), VectorError> {
+        for (index, vector) in self.cipher.iter().enumerate() {
+            let malformed = VectorError::Malformed { section: "cipher", index };
+            let mismatch = VectorError::Mismatch { section: "cipher", index };
+            let (keys, sbox) = vector.key.keys().ok_or(malformed)?;
+            let bytes = hex::decode(&vector.ciphertext).map_err(|_| malformed)?;
+            let ciphertext = Ciphertext::from_bytes(&bytes).map_err(|_| malformed)?;
+            let (public, private) = (&keys.public_key, &keys.private_key);
+            let (pad_length, modulus) = (keys.pad_length, &keys.modulus);
+            let decrypted = decrypt(&ciphertext, public, private, &sbox, pad_length, modulus);
+            if decrypted.ok().as_ref() != Some(&vector.plaintext) {
+                return Err(mismatch);
+            }
+            let mask = match vector.mask.as_str() {
+                "keystream" => MaskMode::Keystream,
+                _ => MaskMode::Gaussian,
+            };
+            let params = CipherParams { rounds: ciphertext.rounds, mask };
+            let (plaintext, nonce) = (&vector.plaintext, ciphertext.nonce);
+            let again = encrypt_with_nonce(
+                plaintext, public, private, &sbox, pad_length, modulus, params, nonce,
+            );
+            if again.ok().and_then(|c| c.to_bytes().ok()) != Some(bytes) {
+                return Err(mismatch);
+            }
+        }
+
+        for (index, vector) in self.hmac.iter().enumerate() {
+            let malformed = VectorError::Malformed { section: "hmac", index };
+            let (keys, sbox) = vector.key.keys().ok_or(malformed)?;
+            let message = hex::decode(&vector.message).map_err(|_| malformed)?;
+            let mac = hmac_for(&keys, sbox);
+            let tag = mac.sign(&message).ok().and_then(|tag| mac.signature_bytes(&tag).ok());
+            if tag.map(hex::encode).as_ref() != Some(&vector.tag) {
+                return Err(VectorError::Mismatch { section: "hmac", index });
+            }
+        }
+
+        for (index, vector) in self.shamir.iter().enumerate() {
+            let malformed = VectorError::Malformed { section: "shamir", index };
+            let secret = hex::decode(&vector.secret).map_err(|_| malformed)?;
+            let mut picked = Vec::new();
+            for &wanted in &vector.combine {
+                let share = vector.shares.iter().find(|(i, _)| *i == wanted);
+                let (_, data) = share.ok_or(malformed)?;
+                let data = hex::decode(data).map_err(|_| malformed)?;
+                picked.push(ByteShare { index: wanted, data });
+            }
+            if gf256::combine(&picked).ok() != Some(secret) {
+                return Err(VectorError::Mismatch { section: "shamir", index });
+            }
+        }
+
+        let mut rng = ChaCha20Rng::seed_from_u64(self.seed);
+        let config = PrimalityConfig::default();
+        for (index, vector) in self.classification.iter().enumerate() {
+            let malformed = VectorError::Malformed { section: "classification", index };
+            let n: BigUint = vector.number.parse().map_err(|_| malformed)?;
+            let tags = classify_with_pseudoprimes(&n, &config, &mut rng);
+            if tags != vector.tags {
+                return Err(VectorError::Mismatch { section: "classification", index });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("test vectors always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_reproduce() {
+        let vectors = TestVectors::generate(7);
+        assert_eq!(TestVectors::generate(7), vectors);
+        assert_ne!(TestVectors::generate(8).cipher, vectors.cipher);
+        let parsed = TestVectors::from_json(&vectors.to_json()).unwrap();
+        assert_eq!(parsed, vectors);
+        assert_eq!(parsed.check(), Ok(()));
+        assert!(vectors.classification[4].tags.contains(&"Carmichael".to_string()));
+
+        let mut wrong = vectors.clone();
+        wrong.hmac[1].message = hex::encode(b"another message");
+        assert_eq!(wrong.check(), Err(VectorError::Mismatch { section: "hmac", index: 1 }));
+        let mut wrong = vectors.clone();
+        wrong.shamir[1].combine.pop();
+        assert_eq!(wrong.check(), Err(VectorError::Mismatch { section: "shamir", index: 1 }));
+        let mut wrong = vectors;
+        wrong.cipher[0].key.sbox.replace_range(..2, "zz");
+        assert_eq!(wrong.check(), Err(VectorError::Malformed { section: "cipher", index: 0 }));
+    }
+}
@@ -0,0 +1,168 @@
+//! Re-checking a results file before publishing it.
+
+use num_bigint::BigInt;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use std::io::{self, BufRead};
+
+use crate::classify::{classify_prime_with_config, classify_with_verdict, pseudoprime_tags};
+use crate::form::QuadraticForm;
+use crate::primality::PrimalityConfig;
+use crate::results::{is_header, parse_row, ResultRow, ResultsError};
+
+/// Miller-Rabin rounds used when re-testing hits, well above the search's default.
+pub const VERIFY_ROUNDS: usize = 64;
+
+/// Rows verified in parallel per batch.
+const VERIFY_BATCH: usize = 4096;
+
+/// Why a results row failed verification.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum VerifyFailure {
+    #[error("malformed row: {0}")]
+    Malformed(#[from] ResultsError),
+    #[error("recorded N = {recorded} but F(x, y, z) = {computed}")]
+    ValueMismatch { recorded: BigInt, computed: BigInt },
+    #[error("recorded as prime but |N| is composite")]
+    NotPrime,
+    #[error("recorded as a pseudoprime but |N| is prime")]
+    UnexpectedPrime,
+    #[error("tags for {column} were {recorded:?}, recomputed {computed:?}")]
+    TagMismatch {
+        column: &'static str,
+        recorded: Vec<String>,
+        computed: Vec<String>,
+    },
+}
+
+/// Outcome of verifying a whole file: the rows checked and every failure by line number.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifySummary {
+    pub checked: usize,
+    pub failures: Vec<(usize, VerifyFailure)>,
+}
+
+/// Recompute N from (x, y, z), re-test |N| under `config` (typically with more rounds than the
+/// search used), and reclassify N, x, y and z.
+pub fn verify_row<R: Rng + ?Sized>(
+    row: &ResultRow,
+    form: &QuadraticForm,
+    config: &PrimalityConfig,
+    rng: &mut R,
+) -> Result<(), VerifyFailure> {
+    let computed = form.evaluate(&row.x, &row.y, &row.z);
+    if computed != row.n {
+        return Err(VerifyFailure::ValueMismatch {
+            recorded: row.n.clone(),
+            computed,
+        });
+    }
+
+    let magnitude = row.n.magnitude();
+    let prime = config.is_prime(magnitude, rng);
+    match (row.is_prime_hit(), prime) {
+        (true, false) => return Err(VerifyFailure::NotPrime),
+        (false, true) => return Err(VerifyFailure::UnexpectedPrime),
+        _ => {}
+    }
+
+    let columns = [
+        ("n", &row.n, &row.classifications_n),
+        ("x", &row.x, &row.classifications_x),
+        ("y", &row.y, &row.classifications_y),
+        ("z", &row.z, &row.classifications_z),
+    ];
+    for (column, value, recorded) in columns {
+        // A composite N is only written as a tagged pseudoprime
+        let computed = if column == "n" && !prime {
+            pseudoprime_tags(magnitude)
+        } else if column == "n" {
+            classify_with_verdict(magnitude, true, config, rng)
+        } else {
+            classify_prime_with_config(value.magnitude(), config, rng)
+        };
+        let computed: Vec<String> = computed.into_iter().map(String::from).collect();
+        if &computed != recorded {
+            return Err(VerifyFailure::TagMismatch {
+                column,
+                recorded: recorded.clone(),
+                computed,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Verify every row of a results file in parallel.
+///
+/// Line numbers are 1-based. Line `i` draws its witnesses from ChaCha20 stream `i` of `seed`,
+/// so a verification run is reproducible.
+pub fn verify_results<B: BufRead>(
+    input: B,
+    form: &QuadraticForm,
+    config: &PrimalityConfig,
+    seed: u64,
+) -> io::Result<VerifySummary> {
+    let mut lines = input.lines().enumerate().peekable();
+    let mut summary = VerifySummary::default();
+
+    while lines.peek().is_some() {
+        let batch = lines
+            .by_ref()
+            .take(VERIFY_BATCH)
+            .map(|(i, line)| line.map(|line| (i, line)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let results: Vec<(usize, Result<(), VerifyFailure>)> = batch
+            .into_par_iter()
+            .filter(|(_, line)| !line.trim().is_empty() && !is_header(line))
+            .map(|(i, line)| {
+                let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                rng.set_stream(i as u64);
+                let outcome = parse_row(&line)
+                    .map_err(VerifyFailure::from)
+                    .and_then(|row| verify_row(&row, form, config, &mut rng));
+                (i + 1, outcome)
+            })
+            .collect();
+
+        for (line_number, outcome) in results {
+            summary.checked += 1;
+            if let Err(failure) = outcome {
+                summary.failures.push((line_number, failure));
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_detects_bad_rows() {
+        let input = concat!(
+            "x,y,z,n,classifications_n,classifications_x,classifications_y,classifications_z,error_bound_n\n",
+            "3,3,7,5851,[\"Prime\", \"Gaussian\"],",
+            "[\"Germain\", \"Prime\", \"Primorial\", \"Factorial\", \"Proth\", \"Cullen\", ",
+            "\"Gaussian\"],",
+            "[\"Germain\", \"Prime\", \"Primorial\", \"Factorial\", \"Proth\", \"Cullen\", ",
+            "\"Gaussian\"],",
+            "[\"Safe\", \"Prime\", \"Primorial\", \"Factorial\", \"Woodall\", \"Gaussian\"],0e0\n",
+            "3,3,83,589533,[\"Prime\"],[\"Germain\", \"Prime\"],[\"Germain\", \"Prime\"],[\"Germain\", \"Safe\", \"Prime\"],0e0\n",
+            "3,3,3\n",
+        );
+        let config = PrimalityConfig::with_rounds(VERIFY_ROUNDS);
+        let summary =
+            verify_results(input.as_bytes(), &QuadraticForm::universal(), &config, 1).unwrap();
+        assert_eq!(summary.checked, 3);
+        assert_eq!(summary.failures.len(), 2);
+        assert!(matches!(summary.failures[0], (3, VerifyFailure::ValueMismatch { .. })));
+        assert!(matches!(summary.failures[1], (4, VerifyFailure::Malformed(_))));
+    }
+}
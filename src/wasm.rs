@@ -0,0 +1,170 @@
+//! wasm-bindgen bindings so the toolkit can run in a browser demo.
+//!
+//! Big numbers cross the JS boundary as decimal strings. Every entry point that needs
+//! randomness takes an explicit `seed`, so nothing depends on `thread_rng` or the filesystem.
+
+use num_bigint::BigUint;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use wasm_bindgen::prelude::*;
+
+use crate::pmpt::{
+    decrypt, encrypt, Ciphertext, DynamicSBox, KeyPair, PmptHmac, SpherePoint, MAX_ROUNDS,
+};
+
+fn parse_decimal(n: &str) -> Result<BigUint, JsError> {
+    n.trim()
+        .parse::<BigUint>()
+        .map_err(|_| JsError::new("expected a non-negative decimal integer"))
+}
+
+fn parse_hex<const N: usize>(bytes: &str) -> Result<[u8; N], JsError> {
+    hex::decode(bytes.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| JsError::new(&format!("expected {} hex-encoded bytes", N)))
+}
+
+fn parse_point(coords: &[String]) -> Result<SpherePoint, JsError> {
+    match coords {
+        [x, y, z] => Ok(SpherePoint::new(
+            parse_decimal(x)?,
+            parse_decimal(y)?,
+            parse_decimal(z)?,
+        )),
+        _ => Err(JsError::new("expected three coordinates")),
+    }
+}
+
+/// Classification tags ("Germain", "Safe", "Prime") for a decimal number.
+#[wasm_bindgen(js_name = classifyPrime)]
+pub fn classify_prime(n: &str, seed: u64) -> Result<Vec<String>, JsError> {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let n = parse_decimal(n)?;
+    Ok(crate::classify::classify_prime(&n, &mut rng)
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+/// Miller-Rabin test with `rounds` witnesses.
+#[wasm_bindgen(js_name = isPrime)]
+pub fn is_prime(n: &str, rounds: usize, seed: u64) -> Result<bool, JsError> {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let n = parse_decimal(n)?;
+    Ok(crate::primality::is_prime(&n, rounds, &mut rng))
+}
+
+/// A random probable prime of `bits` bits, as a decimal string.
+#[wasm_bindgen(js_name = generateLargePrime)]
+pub fn generate_large_prime(bits: usize, seed: u64) -> String {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    crate::prime_shamir::generate_large_prime(bits, &mut rng).to_string()
+}
+
+/// A PMPT key pair and S-box, generated deterministically from a seed.
+#[wasm_bindgen]
+pub struct Pmpt {
+    keys: KeyPair,
+    sbox: DynamicSBox,
+}
+
+#[wasm_bindgen]
+impl Pmpt {
+    #[wasm_bindgen(constructor)]
+    pub fn new(secret_bits: usize, seed: u64) -> Pmpt {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let keys = KeyPair::generate(secret_bits, &mut rng);
+        let sbox = DynamicSBox::new(&mut rng);
+        Pmpt { keys, sbox }
+    }
+
+    fn hmac(&self) -> PmptHmac {
+        PmptHmac::new(
+            self.keys.public_key.clone(),
+            self.keys.private_key.clone(),
+            self.sbox.clone(),
+            self.keys.pad_length,
+            self.keys.modulus.clone(),
+        )
+    }
+
+    /// Encrypt to `[r, x_s, y_s, z_s, nonce, commitment, version, rounds, pad_length]`:
+    /// decimal strings, except for the nonce and key commitment in hex. The nonce is drawn
+    /// from `seed`, which should differ for every message.
+    pub fn encrypt(&self, plaintext: &str, seed: u64) -> Result<Vec<String>, JsError> {
+        let ciphertext = encrypt(
+            plaintext,
+            &self.keys.public_key,
+            &self.keys.private_key,
+            &self.sbox,
+            self.keys.pad_length,
+            &self.keys.modulus,
+            &mut ChaCha20Rng::seed_from_u64(seed),
+        )?;
+        Ok(vec![
+            ciphertext.r.to_string(),
+            ciphertext.x_s.to_string(),
+            ciphertext.y_s.to_string(),
+            ciphertext.z_s.to_string(),
+            hex::encode(ciphertext.nonce),
+            hex::encode(ciphertext.commitment),
+            ciphertext.version.to_string(),
+            ciphertext.rounds.to_string(),
+            ciphertext.pad_length.to_string(),
+        ])
+    }
+
+    /// Decrypt a `[r, x_s, y_s, z_s, nonce, commitment, version, rounds, pad_length]`
+    /// ciphertext produced by `encrypt`.
+    pub fn decrypt(&self, ciphertext: Vec<String>) -> Result<String, JsError> {
+        let ciphertext = match ciphertext.as_slice() {
+            [r, x_s, y_s, z_s, nonce, commitment, version, rounds, pad_length] => Ciphertext {
+                r: parse_decimal(r)?,
+                x_s: parse_decimal(x_s)?,
+                y_s: parse_decimal(y_s)?,
+                z_s: parse_decimal(z_s)?,
+                nonce: parse_hex(nonce)?,
+                commitment: parse_hex(commitment)?,
+                version: version
+                    .trim()
+                    .parse()
+                    .map_err(|_| JsError::new("invalid ciphertext version"))?,
+                rounds: rounds
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&rounds| rounds <= MAX_ROUNDS)
+                    .ok_or_else(|| JsError::new("invalid round count"))?,
+                pad_length: pad_length
+                    .trim()
+                    .parse()
+                    .map_err(|_| JsError::new("invalid pad length"))?,
+            },
+            _ => return Err(JsError::new("expected nine ciphertext components")),
+        };
+        Ok(decrypt(
+            &ciphertext,
+            &self.keys.public_key,
+            &self.keys.private_key,
+            &self.sbox,
+            self.keys.pad_length,
+            &self.keys.modulus,
+        )?)
+    }
+
+    /// PMPT-HMAC signature over `data` as `[x, y, z]` decimal strings.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<String>, JsError> {
+        let signature = self.hmac().sign(data)?;
+        Ok(vec![
+            signature.x.to_string(),
+            signature.y.to_string(),
+            signature.z.to_string(),
+        ])
+    }
+
+    pub fn verify(&self, data: &[u8], signature: Vec<String>) -> Result<bool, JsError> {
+        let signature = parse_point(&signature)?;
+        Ok(self.hmac().verify(data, &signature)?)
+    }
+}
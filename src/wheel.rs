@@ -0,0 +1,129 @@
+//! The 2-3-5-7 wheel: stepping through only the integers coprime to 210.
+//!
+//! 48 of every 210 integers survive, so about 77% of candidates are skipped before any
+//! primality test. The primes 2, 3, 5 and 7 themselves are off the wheel and must be handled
+//! by the caller.
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+/// 2 · 3 · 5 · 7
+pub const WHEEL_MODULUS: u32 = 210;
+
+/// Primes dividing `WHEEL_MODULUS`.
+pub const WHEEL_PRIMES: [u32; 4] = [2, 3, 5, 7];
+
+/// Residues mod 210 coprime to it, ascending.
+pub const SPOKES: [u32; 48] = spokes();
+
+const fn spokes() -> [u32; 48] {
+    let mut spokes = [0u32; 48];
+    let mut count = 0;
+    let mut r = 1;
+    while r < WHEEL_MODULUS {
+        if r % 2 != 0 && r % 3 != 0 && r % 5 != 0 && r % 7 != 0 {
+            spokes[count] = r;
+            count += 1;
+        }
+        r += 1;
+    }
+    spokes
+}
+
+/// Integers coprime to 210, walked up or down from a starting point.
+#[derive(Debug, Clone)]
+pub struct WheelCandidates {
+    /// The next value to yield, or `None` once a descending walk passes 1
+    current: Option<BigUint>,
+    /// Index of `current`'s residue in `SPOKES`
+    spoke: usize,
+    descending: bool,
+}
+
+impl WheelCandidates {
+    /// Candidates at or above `start`, ascending and unbounded.
+    pub fn ascending(start: &BigUint) -> Self {
+        let residue = (start % WHEEL_MODULUS).to_u32().expect("residue below 210");
+        let base = start - residue;
+        let (current, spoke) = match SPOKES.iter().position(|&s| s >= residue) {
+            Some(spoke) => (base + SPOKES[spoke], spoke),
+            None => (base + WHEEL_MODULUS + SPOKES[0], 0),
+        };
+        WheelCandidates {
+            current: Some(current),
+            spoke,
+            descending: false,
+        }
+    }
+
+    /// Candidates at or below `start`, descending and ending with 1.
+    pub fn descending(start: &BigUint) -> Self {
+        let residue = (start % WHEEL_MODULUS).to_u32().expect("residue below 210");
+        let base = start - residue;
+        let (current, spoke) = match SPOKES.iter().rposition(|&s| s <= residue) {
+            Some(spoke) => (Some(base + SPOKES[spoke]), spoke),
+            // Only residue 0 has no spoke below it; the last spoke of the previous turn, if any
+            None if base >= BigUint::from(WHEEL_MODULUS) => {
+                (Some(base - WHEEL_MODULUS + SPOKES[47]), 47)
+            }
+            None => (None, 0),
+        };
+        WheelCandidates {
+            current,
+            spoke,
+            descending: true,
+        }
+    }
+}
+
+impl Iterator for WheelCandidates {
+    type Item = BigUint;
+
+    fn next(&mut self) -> Option<BigUint> {
+        let current = self.current.take()?;
+        self.current = if self.descending {
+            if self.spoke > 0 {
+                self.spoke -= 1;
+                Some(&current - (SPOKES[self.spoke + 1] - SPOKES[self.spoke]))
+            } else if current > BigUint::from(SPOKES[0]) {
+                self.spoke = SPOKES.len() - 1;
+                Some(&current - (WHEEL_MODULUS + SPOKES[0] - SPOKES[47]))
+            } else {
+                None
+            }
+        } else if self.spoke + 1 < SPOKES.len() {
+            self.spoke += 1;
+            Some(&current + (SPOKES[self.spoke] - SPOKES[self.spoke - 1]))
+        } else {
+            self.spoke = 0;
+            Some(&current + (WHEEL_MODULUS + SPOKES[0] - SPOKES[47]))
+        };
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_integer::Integer;
+
+    #[test]
+    fn test_wheel_candidates() {
+        let coprime: Vec<u32> = (0..1000u32).filter(|n| n.gcd(&WHEEL_MODULUS) == 1).collect();
+        assert_eq!(SPOKES[..], coprime[..48]);
+        for start in [0u32, 1, 2, 11, 12, 209, 210, 211, 420, 500] {
+            let up: Vec<u32> = WheelCandidates::ascending(&BigUint::from(start))
+                .map(|n| n.to_u32().unwrap())
+                .take_while(|&n| n < 1000)
+                .collect();
+            let expected: Vec<u32> = coprime.iter().copied().filter(|&n| n >= start).collect();
+            assert_eq!(up, expected, "ascending from {}", start);
+
+            let down: Vec<u32> = WheelCandidates::descending(&BigUint::from(start))
+                .map(|n| n.to_u32().unwrap())
+                .collect();
+            let expected: Vec<u32> = coprime.iter().rev().copied().filter(|&n| n <= start).collect();
+            assert_eq!(down, expected, "descending from {}", start);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Wrapping PMPT session keys for recipients with conventional key pairs.
+//!
+//! A session key is everything `pmpt::decrypt` needs beyond the public key: the private
+//! sphere point, the S-box and the pad length. It is sealed under a fresh 32-byte key-
+//! encryption key (KEK) with a SHAKE256 keystream and tag, and the KEK is delivered with
+//! RSA-OAEP (feature `rsa`) or derived from an ephemeral X25519 exchange (feature `x25519`).
+
+use rand::{CryptoRng, RngCore};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+use thiserror::Error;
+
+use crate::domains;
+use crate::pmpt::{to_fixed_width, CoordinateOverflow, DynamicSBox, SpherePoint, MAX_PAD_LENGTH};
+use crate::reader::{MalformedInput, StrictReader};
+
+/// The longest encapsulated KEK accepted, enough for RSA-OAEP under a 32768-bit key.
+const MAX_ENCAPSULATED: usize = 4096;
+
+#[derive(Error, Debug)]
+pub enum WrapError {
+    #[error("malformed wrapped key: {0}")]
+    Malformed(#[from] MalformedInput),
+    #[error("wrapped key failed authentication")]
+    AuthenticationFailed,
+    #[error(transparent)]
+    CoordinateOverflow(#[from] CoordinateOverflow),
+    #[cfg(feature = "rsa")]
+    #[error(transparent)]
+    Rsa(#[from] rsa::Error),
+    #[cfg(feature = "x25519")]
+    #[error("X25519 exchange with a low-order point")]
+    LowOrderPoint,
+}
+
+/// The secret half of a PMPT key, as delivered to a recipient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionKey {
+    pub private_key: SpherePoint,
+    pub sbox: DynamicSBox,
+    pub pad_length: usize,
+}
+
+impl SessionKey {
+    /// The pad length as a big-endian u32, the three coordinates at that width, then the
+    /// S-box table.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CoordinateOverflow> {
+        let mut bytes = (self.pad_length as u32).to_be_bytes().to_vec();
+        for coordinate in [&self.private_key.x, &self.private_key.y, &self.private_key.z] {
+            bytes.extend_from_slice(&to_fixed_width(coordinate, self.pad_length)?);
+        }
+        bytes.extend_from_slice(self.sbox.table());
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WrapError> {
+        let mut reader = StrictReader::new(bytes);
+        let pad_length = reader.u32_be("pad length")?;
+        let pad_length = reader.length("pad length", pad_length as u64, MAX_PAD_LENGTH)?;
+        let private_key = SpherePoint::new(
+            reader.biguint("private key", pad_length)?,
+            reader.biguint("private key", pad_length)?,
+            reader.biguint("private key", pad_length)?,
+        );
+        let sbox = DynamicSBox::from_table(reader.array("S-box")?)
+            .ok_or(MalformedInput::Invalid("S-box"))?;
+        reader.finish()?;
+        Ok(SessionKey {
+            private_key,
+            sbox,
+            pad_length,
+        })
+    }
+}
+
+/// A sealed session key. `encapsulated` carries the KEK to the recipient: the RSA-OAEP
+/// ciphertext, the ephemeral X25519 public key, or nothing for `wrap_with_key`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrappedKey {
+    pub encapsulated: Vec<u8>,
+    pub nonce: [u8; 16],
+    pub ciphertext: Vec<u8>,
+    pub tag: [u8; 32],
+}
+
+impl WrappedKey {
+    /// `encapsulated` and `ciphertext` with big-endian u32 length prefixes, and the nonce and
+    /// tag at their fixed sizes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.encapsulated.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.encapsulated);
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&(self.ciphertext.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes.extend_from_slice(&self.tag);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WrapError> {
+        let mut reader = StrictReader::new(bytes);
+        let encapsulated = reader.prefixed("encapsulated key", MAX_ENCAPSULATED)?.to_vec();
+        let nonce = reader.array("nonce")?;
+        let ciphertext = reader.prefixed("ciphertext", 4 + 3 * MAX_PAD_LENGTH + 256)?.to_vec();
+        let tag = reader.array("tag")?;
+        reader.finish()?;
+        Ok(WrappedKey {
+            encapsulated,
+            nonce,
+            ciphertext,
+            tag,
+        })
+    }
+}
+
+/// SHAKE256 over length-prefixed `domain` and `parts`, as a reader.
+fn shake256(domain: &[u8], parts: &[&[u8]]) -> impl XofReader {
+    let mut hasher: Shake256 = domains::tagged(domain);
+    for part in parts {
+        hasher.update(&(part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize_xof()
+}
+
+fn tag(kek: &[u8; 32], nonce: &[u8; 16], encapsulated: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut tag = [0u8; 32];
+    shake256(domains::WRAP_TAG, &[kek, nonce, encapsulated, ciphertext]).read(&mut tag);
+    tag
+}
+
+fn apply_keystream(kek: &[u8; 32], nonce: &[u8; 16], data: &mut [u8]) {
+    let mut keystream = vec![0u8; data.len()];
+    shake256(domains::WRAP_STREAM, &[kek, nonce]).read(&mut keystream);
+    for (byte, mask) in data.iter_mut().zip(keystream) {
+        *byte ^= mask;
+    }
+}
+
+fn seal<R: RngCore + CryptoRng>(
+    session: &SessionKey,
+    kek: &[u8; 32],
+    encapsulated: Vec<u8>,
+    rng: &mut R,
+) -> Result<WrappedKey, WrapError> {
+    let mut nonce = [0u8; 16];
+    rng.fill_bytes(&mut nonce);
+    let mut ciphertext = session.to_bytes()?;
+    apply_keystream(kek, &nonce, &mut ciphertext);
+    let tag = tag(kek, &nonce, &encapsulated, &ciphertext);
+    Ok(WrappedKey {
+        encapsulated,
+        nonce,
+        ciphertext,
+        tag,
+    })
+}
+
+fn open(wrapped: &WrappedKey, kek: &[u8; 32]) -> Result<SessionKey, WrapError> {
+    let expected = tag(kek, &wrapped.nonce, &wrapped.encapsulated, &wrapped.ciphertext);
+    // Compare without an early exit
+    let difference = expected
+        .iter()
+        .zip(&wrapped.tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(WrapError::AuthenticationFailed);
+    }
+    let mut plaintext = wrapped.ciphertext.clone();
+    apply_keystream(kek, &wrapped.nonce, &mut plaintext);
+    SessionKey::from_bytes(&plaintext)
+}
+
+/// Seal `session` under a key-encryption key the recipient already shares.
+pub fn wrap_with_key<R: RngCore + CryptoRng>(
+    session: &SessionKey,
+    kek: &[u8; 32],
+    rng: &mut R,
+) -> Result<WrappedKey, WrapError> {
+    seal(session, kek, Vec::new(), rng)
+}
+
+pub fn unwrap_with_key(wrapped: &WrappedKey, kek: &[u8; 32]) -> Result<SessionKey, WrapError> {
+    open(wrapped, kek)
+}
+
+/// RSA-OAEP with SHA3-256, labelled with the wrap domain.
+#[cfg(feature = "rsa")]
+fn oaep() -> rsa::Oaep {
+    let label = std::str::from_utf8(domains::WRAP_RSA_OAEP).expect("domain tags are ASCII");
+    rsa::Oaep::new_with_label::<sha3::Sha3_256, _>(label)
+}
+
+/// Seal `session` under a random KEK encrypted to `recipient` with RSA-OAEP (SHA3-256).
+#[cfg(feature = "rsa")]
+pub fn wrap_rsa<R: RngCore + CryptoRng>(
+    session: &SessionKey,
+    recipient: &rsa::RsaPublicKey,
+    rng: &mut R,
+) -> Result<WrappedKey, WrapError> {
+    let mut kek = [0u8; 32];
+    rng.fill_bytes(&mut kek);
+    let encapsulated = recipient.encrypt(rng, oaep(), &kek)?;
+    seal(session, &kek, encapsulated, rng)
+}
+
+#[cfg(feature = "rsa")]
+pub fn unwrap_rsa(
+    wrapped: &WrappedKey,
+    recipient: &rsa::RsaPrivateKey,
+) -> Result<SessionKey, WrapError> {
+    let kek: [u8; 32] = recipient
+        .decrypt(oaep(), &wrapped.encapsulated)?
+        .try_into()
+        .map_err(|_| MalformedInput::Invalid("encapsulated key"))?;
+    open(wrapped, &kek)
+}
+
+/// KEK from an X25519 shared secret, bound to both public keys.
+#[cfg(feature = "x25519")]
+fn x25519_kek(
+    shared: &x25519_dalek::SharedSecret,
+    ephemeral: &x25519_dalek::PublicKey,
+    recipient: &x25519_dalek::PublicKey,
+) -> Result<[u8; 32], WrapError> {
+    if !shared.was_contributory() {
+        return Err(WrapError::LowOrderPoint);
+    }
+    let mut kek = [0u8; 32];
+    shake256(
+        domains::WRAP_X25519,
+        &[shared.as_bytes(), ephemeral.as_bytes(), recipient.as_bytes()],
+    )
+    .read(&mut kek);
+    Ok(kek)
+}
+
+/// Seal `session` under a KEK agreed between a fresh ephemeral key and `recipient`.
+#[cfg(feature = "x25519")]
+pub fn wrap_x25519<R: RngCore + CryptoRng>(
+    session: &SessionKey,
+    recipient: &x25519_dalek::PublicKey,
+    rng: &mut R,
+) -> Result<WrappedKey, WrapError> {
+    let secret = x25519_dalek::EphemeralSecret::random_from_rng(&mut *rng);
+    let ephemeral = x25519_dalek::PublicKey::from(&secret);
+    let kek = x25519_kek(&secret.diffie_hellman(recipient), &ephemeral, recipient)?;
+    seal(session, &kek, ephemeral.as_bytes().to_vec(), rng)
+}
+
+#[cfg(feature = "x25519")]
+pub fn unwrap_x25519(
+    wrapped: &WrappedKey,
+    recipient: &x25519_dalek::StaticSecret,
+) -> Result<SessionKey, WrapError> {
+    let ephemeral: [u8; 32] = wrapped
+        .encapsulated
+        .as_slice()
+        .try_into()
+        .map_err(|_| MalformedInput::Invalid("encapsulated key"))?;
+    let ephemeral = x25519_dalek::PublicKey::from(ephemeral);
+    let shared = recipient.diffie_hellman(&ephemeral);
+    let kek = x25519_kek(&shared, &ephemeral, &x25519_dalek::PublicKey::from(recipient))?;
+    open(wrapped, &kek)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn session() -> SessionKey {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(19);
+        let keys = crate::pmpt::KeyPair::generate(64, &mut rng);
+        SessionKey {
+            private_key: keys.private_key,
+            sbox: DynamicSBox::new(&mut rng),
+            pad_length: keys.pad_length,
+        }
+    }
+
+    #[test]
+    fn test_wrap_with_key() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(20);
+        let session = session();
+        let kek = [7u8; 32];
+        let wrapped = wrap_with_key(&session, &kek, &mut rng).unwrap();
+        let parsed = WrappedKey::from_bytes(&wrapped.to_bytes()).unwrap();
+        assert_eq!(unwrap_with_key(&parsed, &kek).unwrap(), session);
+        assert!(matches!(
+            unwrap_with_key(&parsed, &[8u8; 32]),
+            Err(WrapError::AuthenticationFailed)
+        ));
+        let mut tampered = parsed;
+        tampered.ciphertext[0] ^= 1;
+        assert!(matches!(unwrap_with_key(&tampered, &kek), Err(WrapError::AuthenticationFailed)));
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn test_wrap_rsa() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(21);
+        let private = rsa::RsaPrivateKey::new(&mut rng, 1024).unwrap();
+        let session = session();
+        let wrapped = wrap_rsa(&session, &private.to_public_key(), &mut rng).unwrap();
+        assert_eq!(unwrap_rsa(&wrapped, &private).unwrap(), session);
+    }
+
+    #[cfg(feature = "x25519")]
+    #[test]
+    fn test_wrap_x25519() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(22);
+        let secret = x25519_dalek::StaticSecret::random_from_rng(&mut rng);
+        let session = session();
+        let public = x25519_dalek::PublicKey::from(&secret);
+        let wrapped = wrap_x25519(&session, &public, &mut rng).unwrap();
+        assert_eq!(unwrap_x25519(&wrapped, &secret).unwrap(), session);
+        let other = x25519_dalek::StaticSecret::random_from_rng(&mut rng);
+        assert!(matches!(
+            unwrap_x25519(&wrapped, &other),
+            Err(WrapError::AuthenticationFailed)
+        ));
+    }
+}
@@ -0,0 +1,736 @@
+//! Zeta values on the critical line and the universal-prime alignment test built on them.
+//!
+//! `test_universal_prime_against_zeta` scans the same t-range for every prime, so values
+//! go through a `ZetaCache` keyed by (t, precision): an in-memory LRU, optionally backed by
+//! an append-only file of `t,precision,re,im` lines that later runs load back.
+
+use num_bigint::BigUint;
+use num_complex::Complex;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use tracing::{error, info, instrument, warn};
+
+use crate::bigfloat::BigFloat;
+
+/// Calculate the Riemann zeta function for a given complex input `s`.
+/// Uses the series definition up to `iterations` terms.
+pub fn zeta(s: Complex<f64>, iterations: usize) -> Complex<f64> {
+    let mut sum = Complex::new(0.0, 0.0);
+    for n in 1..=iterations {
+        let n_f64 = n as f64;
+        // Accumulate the series terms: 1 / n^s
+        sum += Complex::new(1.0, 0.0) / Complex::new(n_f64, 0.0).powc(s);
+    }
+    sum
+}
+
+/// B₂ₖ for k = 1..=12, as fractions.
+const BERNOULLI: [(f64, f64); 12] = [
+    (1.0, 6.0),
+    (-1.0, 30.0),
+    (1.0, 42.0),
+    (-1.0, 30.0),
+    (5.0, 66.0),
+    (-691.0, 2730.0),
+    (7.0, 6.0),
+    (-3617.0, 510.0),
+    (43867.0, 798.0),
+    (-174611.0, 330.0),
+    (854513.0, 138.0),
+    (-236364091.0, 2730.0),
+];
+
+/// ζ(s) by Euler–Maclaurin summation, accurate to about 1e-12 for s ≠ 1.
+///
+/// Sums n^-s directly below N ≈ 2|s|/π, then adds the integral, the half term and twelve
+/// Bernoulli corrections; each correction shrinks by at least 16 at that N. The cost is
+/// linear in |Im s|.
+pub fn riemann_zeta(s: Complex<f64>) -> Complex<f64> {
+    let one = Complex::new(1.0, 0.0);
+    let cutoff = (2.0 * s.norm() / std::f64::consts::PI).ceil() as u64 + 10;
+    let mut sum = Complex::new(0.0, 0.0);
+    for n in 1..cutoff {
+        sum += (-s * (n as f64).ln()).exp();
+    }
+    let big_n = cutoff as f64;
+    let n_to_minus_s = (-s * big_n.ln()).exp();
+    sum += n_to_minus_s * big_n / (s - one) + n_to_minus_s * 0.5;
+    // Tₖ = B₂ₖ / (2k)! · s(s + 1)…(s + 2k - 2) · N^(-s-2k+1)
+    let mut rising = s * n_to_minus_s / big_n;
+    let mut factorial = 2.0;
+    for (k, (numerator, denominator)) in BERNOULLI.iter().enumerate() {
+        if k > 0 {
+            let j = 2.0 * k as f64;
+            rising = rising * (s + j - 1.0) * (s + j) / (big_n * big_n);
+            factorial *= (j + 1.0) * (j + 2.0);
+        }
+        sum += rising * (numerator / denominator / factorial);
+    }
+    sum
+}
+
+/// ln Γ(z) for Re z > 0, continuous in z: Stirling's series at z + 8, shifted back.
+fn ln_gamma(z: Complex<f64>) -> Complex<f64> {
+    let mut shift = Complex::new(0.0, 0.0);
+    let mut w = z;
+    for _ in 0..8 {
+        shift += w.ln();
+        w += 1.0;
+    }
+    let w2 = w * w;
+    let series = (1.0 / 12.0 - (1.0 / 360.0 - (1.0 / 1260.0 - (1.0 / 1680.0) / w2) / w2) / w2) / w;
+    (w - 0.5) * w.ln() - w + 0.5 * (2.0 * std::f64::consts::PI).ln() + series - shift
+}
+
+/// The Riemann–Siegel theta function θ(t) = Im ln Γ(1/4 + it/2) - (t/2)·ln π, the phase
+/// that makes e^(iθ(t))·ζ(1/2 + it) real.
+pub fn riemann_siegel_theta(t: f64) -> f64 {
+    ln_gamma(Complex::new(0.25, t / 2.0)).im - t / 2.0 * std::f64::consts::PI.ln()
+}
+
+/// Number of zeros of ζ with 0 < Im s ≤ t in the critical strip, for t not itself the
+/// ordinate of a zero.
+///
+/// By the argument principle N(t) = θ(t)/π + 1 + S(t), with πS(t) the change in arg ζ along
+/// the segment from 3 + it, where Re ζ > 0, to 1/2 + it. Following Backlund, the argument is
+/// tracked in steps small enough that it turns by less than π/4 per step, halving where it
+/// turns faster.
+pub fn zero_count_upto(t: f64) -> u64 {
+    if t <= 0.0 {
+        return 0;
+    }
+    let value_at = |sigma: f64| riemann_zeta(Complex::new(sigma, t));
+    let mut sigma = 3.0;
+    let mut value = value_at(sigma);
+    let mut arg = value.arg();
+    let mut step = 0.125;
+    while sigma > 0.5 {
+        let next_sigma = (sigma - step).max(0.5);
+        let next = value_at(next_sigma);
+        let turn = (next / value).arg();
+        if turn.abs() > std::f64::consts::FRAC_PI_4 && step > 1e-9 {
+            step /= 2.0;
+            continue;
+        }
+        arg += turn;
+        sigma = next_sigma;
+        value = next;
+        step = (step * 2.0).min(0.125);
+    }
+    let count = riemann_siegel_theta(t) / std::f64::consts::PI + 1.0 + arg / std::f64::consts::PI;
+    count.round().max(0.0) as u64
+}
+
+/// The Gram point gₙ, where θ(gₙ) = nπ (g₀ ≈ 17.8456, g₁ ≈ 23.1703), by Newton's method on
+/// θ with θ'(t) ≈ ½·ln(t/2π).
+pub fn gram_point(n: u64) -> f64 {
+    let target = n as f64 * std::f64::consts::PI;
+    // θ is convex past its minimum near t = 6.3, so Newton's method from the left of g₀
+    // overshoots once and then decreases to the root
+    let mut t: f64 = 18.0;
+    for _ in 0..100 {
+        let derivative = 0.5 * (t / (2.0 * std::f64::consts::PI)).ln();
+        let correction = (riemann_siegel_theta(t) - target) / derivative;
+        t -= correction;
+        if correction.abs() < 1e-12 * t {
+            break;
+        }
+    }
+    t
+}
+
+/// The Gram points gₙ in [start, end], with their indices.
+pub fn gram_points(start: f64, end: f64) -> Vec<(u64, f64)> {
+    let first = (riemann_siegel_theta(start.max(gram_point(0))) / std::f64::consts::PI)
+        .ceil()
+        .max(0.0) as u64;
+    (first..)
+        .map(|n| (n, gram_point(n)))
+        .skip_while(|&(_, g)| g < start)
+        .take_while(|&(_, g)| g <= end)
+        .collect()
+}
+
+/// Hardy's Z(t) = e^(iθ(t))·ζ(1/2 + it), real for real t, with |Z(t)| = |ζ(1/2 + it)| and
+/// sign changes exactly at the zeros of odd order on the critical line.
+pub fn hardy_z(t: f64) -> f64 {
+    let phase = Complex::from_polar(&1.0, &riemann_siegel_theta(t));
+    (phase * riemann_zeta(Complex::new(0.5, t))).re
+}
+
+/// Sign changes of Z over `samples` equal steps from `start` to `end`.
+fn sign_changes(start: f64, end: f64, samples: usize) -> u64 {
+    let samples = samples.max(1);
+    let signs: Vec<bool> = (0..=samples)
+        .map(|i| hardy_z(start + (end - start) * i as f64 / samples as f64) > 0.0)
+        .collect();
+    signs.windows(2).filter(|w| w[0] != w[1]).count() as u64
+}
+
+/// Zeros of ζ expected in (start, end] from N(t), against the sign changes of Z(t) seen
+/// when sampling it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZeroInterval {
+    pub start: f64,
+    pub end: f64,
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl ZeroInterval {
+    /// Whether the sampling missed zeros (a pair between samples, or a zero off the line).
+    pub fn missed(&self) -> bool {
+        self.found < self.expected
+    }
+}
+
+/// Split [start, end] at the Gram points in it and compare, in each piece, the zero count
+/// from `zero_count_upto` with the sign changes of Z(t) over `samples` evenly spaced points.
+#[instrument(level = "info")]
+pub fn audit_zeros(start: f64, end: f64, samples: usize) -> Vec<ZeroInterval> {
+    let mut bounds = vec![start];
+    bounds.extend(
+        gram_points(start, end)
+            .into_iter()
+            .map(|(_, g)| g)
+            .filter(|&g| g > start && g < end),
+    );
+    bounds.push(end);
+    bounds
+        .windows(2)
+        .map(|piece| {
+            let (a, b) = (piece[0], piece[1]);
+            let interval = ZeroInterval {
+                start: a,
+                end: b,
+                expected: zero_count_upto(b) - zero_count_upto(a),
+                found: sign_changes(a, b, samples),
+            };
+            if interval.missed() {
+                warn!(start = a, end = b, interval.expected, interval.found, "zeros missed");
+            }
+            interval
+        })
+        .collect()
+}
+
+/// Whether gₙ is a good Gram point, (-1)ⁿ·Z(gₙ) > 0. Gram's law is the observation that
+/// most are, so that each Gram interval [gₙ, gₙ₊₁) holds exactly one zero.
+pub fn is_good_gram_point(n: u64) -> bool {
+    let z = hardy_z(gram_point(n));
+    if n.is_multiple_of(2) { z > 0.0 } else { z < 0.0 }
+}
+
+/// A Gram block [gⱼ, gⱼ₊ₖ): consecutive good Gram points gⱼ and gⱼ₊ₖ with only bad ones
+/// between, and the sign changes of Z found in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GramBlock {
+    pub first: u64,
+    pub length: u64,
+    pub start: f64,
+    pub end: f64,
+    pub zeros: u64,
+}
+
+impl GramBlock {
+    /// Blocks of length above 1 contain bad Gram points, i.e. Gram's law fails in them.
+    pub fn violates_gram_law(&self) -> bool {
+        self.length > 1
+    }
+
+    /// Whether the block holds fewer zeros than Gram intervals, against Rosser's rule (which
+    /// first fails near t = 7·10⁶, so at small heights this points at too coarse sampling).
+    pub fn violates_rosser_rule(&self) -> bool {
+        self.zeros < self.length
+    }
+}
+
+/// The Gram blocks lying in [start, end], sampling Z `samples` times per Gram interval to
+/// count their zeros. Blocks cut by either end of the range are left out.
+#[instrument(level = "info")]
+pub fn gram_blocks(start: f64, end: f64, samples: usize) -> Vec<GramBlock> {
+    let good: Vec<(u64, f64)> = gram_points(start, end)
+        .into_iter()
+        .filter(|&(n, _)| is_good_gram_point(n))
+        .collect();
+    good.windows(2)
+        .map(|pair| {
+            let ((first, start), (last, end)) = (pair[0], pair[1]);
+            let length = last - first;
+            let block = GramBlock {
+                first,
+                length,
+                start,
+                end,
+                zeros: sign_changes(start, end, samples * length as usize),
+            };
+            if block.violates_gram_law() {
+                info!(first, length, block.zeros, "Gram's law violated");
+            }
+            block
+        })
+        .collect()
+}
+
+/// Ordinates of the zeros of Z in [start, end]: sign changes on a grid of spacing `step`,
+/// each refined by bisection. Pairs of zeros closer than `step` are missed.
+pub fn zeta_zeros(start: f64, end: f64, step: f64) -> Vec<f64> {
+    let steps = ((end - start) / step).ceil().max(1.0) as usize;
+    let grid: Vec<(f64, f64)> = (0..=steps)
+        .map(|i| {
+            let t = (start + step * i as f64).min(end);
+            (t, hardy_z(t))
+        })
+        .collect();
+    grid.windows(2)
+        .filter(|w| (w[0].1 > 0.0) != (w[1].1 > 0.0))
+        .map(|w| {
+            let ((mut low, z_low), (mut high, _)) = (w[0], w[1]);
+            while high - low > 1e-10 * high {
+                let mid = (low + high) / 2.0;
+                if (hardy_z(mid) > 0.0) == (z_low > 0.0) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            (low + high) / 2.0
+        })
+        .collect()
+}
+
+/// Spacings of consecutive zero ordinates, unfolded by the local density ln(t/2π)/2π so
+/// that they average 1.
+pub fn unfolded_zero_spacings(zeros: &[f64]) -> Vec<f64> {
+    zeros
+        .windows(2)
+        .map(|w| {
+            let height = (w[0] + w[1]) / 2.0;
+            (w[1] - w[0]) * (height / (2.0 * std::f64::consts::PI)).ln()
+                / (2.0 * std::f64::consts::PI)
+        })
+        .collect()
+}
+
+/// Gaps between consecutive hits divided by their mean, so that they average 1.
+///
+/// The ratios are taken in `BigFloat`, so gaps between hits of any size are fine.
+pub fn normalized_hit_gaps(hits: &BTreeSet<BigUint>) -> Vec<f64> {
+    let hits: Vec<&BigUint> = hits.iter().collect();
+    let gaps: Vec<BigUint> = hits.windows(2).map(|w| w[1] - w[0]).collect();
+    if gaps.is_empty() {
+        return Vec::new();
+    }
+    let total: BigUint = gaps.iter().sum();
+    let mean = &BigFloat::from(&total) / &BigFloat::from(&BigUint::from(gaps.len()));
+    gaps.iter().map(|gap| (&BigFloat::from(gap) / &mean).to_f64()).collect()
+}
+
+/// erf(x) by Abramowitz and Stegun 7.1.26, to within 1.5·10⁻⁷.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = [1.061405429, -1.453152027, 1.421413741, -0.284496736, 0.254829592]
+        .iter()
+        .fold(0.0, |acc, c| acc * t + c)
+        * t;
+    (1.0 - poly * (-x * x).exp()).copysign(x)
+}
+
+/// Distribution function of the GUE nearest-neighbour spacing in the Wigner surmise,
+/// p(s) = (32/π²)·s²·e^(-4s²/π).
+pub fn gue_spacing_cdf(s: f64) -> f64 {
+    if s <= 0.0 {
+        return 0.0;
+    }
+    let pi = std::f64::consts::PI;
+    erf(2.0 * s / pi.sqrt()) - 4.0 * s / pi * (-4.0 * s * s / pi).exp()
+}
+
+fn sorted(sample: &[f64]) -> Vec<f64> {
+    let mut sample = sample.to_vec();
+    sample.sort_by(f64::total_cmp);
+    sample
+}
+
+/// Two-sample Kolmogorov–Smirnov distance: the largest gap between the empirical
+/// distribution functions. 1 when either sample is empty.
+pub fn ks_distance(first: &[f64], second: &[f64]) -> f64 {
+    if first.is_empty() || second.is_empty() {
+        return 1.0;
+    }
+    let (first, second) = (sorted(first), sorted(second));
+    let (mut i, mut j, mut distance) = (0, 0, 0.0f64);
+    while i < first.len() && j < second.len() {
+        let x = first[i].min(second[j]);
+        while i < first.len() && first[i] <= x {
+            i += 1;
+        }
+        while j < second.len() && second[j] <= x {
+            j += 1;
+        }
+        distance =
+            distance.max((i as f64 / first.len() as f64 - j as f64 / second.len() as f64).abs());
+    }
+    distance
+}
+
+/// Kolmogorov–Smirnov distance of a sample from the GUE spacing distribution.
+pub fn ks_distance_to_gue(sample: &[f64]) -> f64 {
+    if sample.is_empty() {
+        return 1.0;
+    }
+    let sample = sorted(sample);
+    let n = sample.len() as f64;
+    sample
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let cdf = gue_spacing_cdf(s);
+            (cdf - i as f64 / n).max((i + 1) as f64 / n - cdf)
+        })
+        .fold(0.0, f64::max)
+}
+
+/// How zero spacings and hit gaps, both normalized to mean 1, compare with each other and
+/// with GUE.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpacingComparison {
+    pub zero_spacings: usize,
+    pub hit_gaps: usize,
+    pub zeros_vs_hits: f64,
+    pub zeros_vs_gue: f64,
+    pub hits_vs_gue: f64,
+}
+
+/// Compare the unfolded spacings of `zeros` (ascending ordinates, e.g. from `zeta_zeros`)
+/// with the normalized gaps between `hits`.
+pub fn compare_spacings(zeros: &[f64], hits: &BTreeSet<BigUint>) -> SpacingComparison {
+    let spacings = unfolded_zero_spacings(zeros);
+    let gaps = normalized_hit_gaps(hits);
+    SpacingComparison {
+        zero_spacings: spacings.len(),
+        hit_gaps: gaps.len(),
+        zeros_vs_hits: ks_distance(&spacings, &gaps),
+        zeros_vs_gue: ks_distance_to_gue(&spacings),
+        hits_vs_gue: ks_distance_to_gue(&gaps),
+    }
+}
+
+/// Values kept in memory by `ZetaCache::default()` and the shared cache.
+pub const DEFAULT_CACHE_CAPACITY: usize = 1 << 20;
+
+/// Cache key: the bits of t and the number of series terms.
+type Key = (u64, usize);
+
+/// Memoised ζ(1/2 + it) values with least-recently-used eviction.
+#[derive(Debug)]
+pub struct ZetaCache {
+    capacity: usize,
+    /// Value and last-use stamp per key
+    entries: HashMap<Key, (Complex<f64>, u64)>,
+    /// Keys by last-use stamp, oldest first
+    recency: BTreeMap<u64, Key>,
+    clock: u64,
+    file: Option<BufWriter<File>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl Default for ZetaCache {
+    fn default() -> Self {
+        ZetaCache::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl ZetaCache {
+    /// An in-memory cache holding at most `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        ZetaCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            file: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// A cache backed by `path`: the most recent `capacity` values in it are loaded, and
+    /// every value computed from now on is appended to it. Malformed lines are skipped.
+    pub fn open(path: &Path, capacity: usize) -> io::Result<Self> {
+        let mut cache = ZetaCache::new(capacity);
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                let fields: Vec<&str> = line.split(',').collect();
+                let [t, precision, re, im] = fields[..] else {
+                    continue;
+                };
+                if let (Ok(t), Ok(precision), Ok(re), Ok(im)) =
+                    (t.parse::<f64>(), precision.parse(), re.parse(), im.parse())
+                {
+                    cache.insert((t.to_bits(), precision), Complex::new(re, im));
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        cache.file = Some(BufWriter::new(file));
+        Ok(cache)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn touch(&mut self, key: Key) -> Option<Complex<f64>> {
+        let (value, stamp) = self.entries.get_mut(&key)?;
+        self.recency.remove(stamp);
+        self.clock += 1;
+        *stamp = self.clock;
+        self.recency.insert(self.clock, key);
+        Some(*value)
+    }
+
+    fn insert(&mut self, key: Key, value: Complex<f64>) {
+        if self.touch(key).is_some() {
+            self.entries.get_mut(&key).expect("just touched").0 = value;
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(key, (value, self.clock));
+        self.recency.insert(self.clock, key);
+    }
+
+    /// ζ(1/2 + it) summed to `precision` terms, from the cache when possible.
+    pub fn critical_line(&mut self, t: f64, precision: usize) -> io::Result<Complex<f64>> {
+        let key = (t.to_bits(), precision);
+        if let Some(value) = self.touch(key) {
+            self.hits += 1;
+            return Ok(value);
+        }
+        self.misses += 1;
+        let value = zeta(Complex::new(0.5, t), precision);
+        self.insert(key, value);
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{},{},{},{}", t, precision, value.re, value.im)?;
+        }
+        Ok(value)
+    }
+
+    /// Write buffered values to the backing file, if any.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ZetaCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// The in-memory cache shared by `test_universal_prime_against_zeta` calls.
+fn shared_cache() -> &'static Mutex<ZetaCache> {
+    static SHARED: OnceLock<Mutex<ZetaCache>> = OnceLock::new();
+    SHARED.get_or_init(|| Mutex::new(ZetaCache::default()))
+}
+
+/// Test if the Universal Prime `N` aligns with a zero of the zeta function along the critical line.
+/// Returns true if `zeta(s) \\approx 0` for some `s` with Re(s) = 0.5.
+///
+/// Values are shared between calls through an in-memory cache.
+pub fn test_universal_prime_against_zeta(n: &BigUint, iterations: usize, tolerance: f64) -> bool {
+    let mut cache = shared_cache().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    test_universal_prime_against_zeta_with_cache(n, iterations, tolerance, &mut cache)
+}
+
+/// `test_universal_prime_against_zeta` with values taken from and added to `cache`.
+#[instrument(level = "info", name = "zeta_scan", skip(n, cache), fields(n = %BigFloat::from(n)))]
+pub fn test_universal_prime_against_zeta_with_cache(
+    n: &BigUint,
+    iterations: usize,
+    tolerance: f64,
+    cache: &mut ZetaCache,
+) -> bool {
+    // Real part of s on the critical line
+    let real_part = 0.5;
+    let step = 0.01; // Step size for incrementing the imaginary part
+    let max_imaginary = 1000.0; // Limit the range of the imaginary axis
+
+    // Iterate over a range of imaginary parts to search for a zero; t is derived from the
+    // step index so every call hits the same cache keys
+    let steps = (max_imaginary / step) as u64;
+    for i in 0..=steps {
+        let imaginary_part = i as f64 * step;
+        let zeta_value = match cache.critical_line(imaginary_part, iterations) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("failed to write the zeta cache: {}", e);
+                return false;
+            }
+        };
+
+        // Check if the zeta value is within the specified tolerance
+        if zeta_value.norm() < tolerance {
+            info!(
+                "potential zero found: s = {} + {}i, zeta(s) = {}",
+                real_part, imaginary_part, zeta_value
+            );
+            return true;
+        }
+    }
+
+    info!(hits = cache.hits, misses = cache.misses, "no zeros found near the critical line");
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::ToBigUint;
+
+    #[test]
+    #[ignore = "scans 10^5 points of the critical line at 10^4 terms each"]
+    fn test_small_universal_prime() {
+        let n = 17u32.to_biguint().unwrap(); // Small prime
+        let result = test_universal_prime_against_zeta(&n, 10000, 1e-6);
+        assert!(!result, "Expected no alignment for small prime");
+    }
+
+    #[test]
+    #[ignore = "scans 10^5 points of the critical line at 10^4 terms each"]
+    fn test_large_universal_prime() {
+        let n = 48883u32.to_biguint().unwrap(); // Example Universal Prime
+        let result = test_universal_prime_against_zeta(&n, 10000, 1e-1);
+        assert!(result, "Expected alignment for known Universal Prime");
+    }
+
+    #[test]
+    fn test_riemann_zeta() {
+        let close = |a: Complex<f64>, b: Complex<f64>| (a - b).norm() < 1e-10;
+        // ζ(2) = π²/6 and ζ(-1) = -1/12
+        let pi = std::f64::consts::PI;
+        assert!(close(riemann_zeta(Complex::new(2.0, 0.0)), Complex::new(pi * pi / 6.0, 0.0)));
+        assert!(close(riemann_zeta(Complex::new(-1.0, 0.0)), Complex::new(-1.0 / 12.0, 0.0)));
+        // ζ(1/2 + i·14.134725141734693) is the first zero
+        assert!(riemann_zeta(Complex::new(0.5, 14.134725141734693)).norm() < 1e-9);
+        assert!((riemann_zeta(Complex::new(0.5, 0.0)).re + 1.4603545088095868).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_zero_counts_and_gram_points() {
+        assert!(riemann_siegel_theta(17.8455995405).abs() < 1e-8);
+        assert!((gram_point(0) - 17.8455995405).abs() < 1e-8);
+        assert!((gram_point(1) - 23.1702827012).abs() < 1e-8);
+        let points: Vec<u64> = gram_points(20.0, 40.0).iter().map(|&(n, _)| n).collect();
+        assert_eq!(points, [1, 2, 3, 4, 5]);
+
+        assert_eq!(zero_count_upto(14.0), 0);
+        assert_eq!(zero_count_upto(15.0), 1);
+        assert_eq!(zero_count_upto(100.0), 29);
+        assert_eq!(zero_count_upto(1000.0), 649);
+
+        let audit = audit_zeros(10.0, 60.0, 50);
+        let expected: u64 = audit.iter().map(|i| i.expected).sum();
+        assert_eq!(expected, 13);
+        assert!(audit.iter().all(|i| !i.missed()));
+        // One sample per piece cannot see anything
+        assert!(audit_zeros(10.0, 60.0, 1).iter().any(|i| i.missed()));
+    }
+
+    #[test]
+    fn test_gram_blocks() {
+        assert!(hardy_z(0.0) < 0.0);
+        assert!(hardy_z(14.134725141734693).abs() < 1e-9);
+        assert!(hardy_z(20.0) > 0.0);
+        // Gram's law first fails at g₁₂₆ ≈ 282.455, and next at g₁₃₄
+        assert!((0..126).all(is_good_gram_point));
+        assert!(!is_good_gram_point(126));
+        let blocks = gram_blocks(250.0, 300.0, 20);
+        let violations: Vec<_> = blocks
+            .iter()
+            .filter(|b| b.violates_gram_law())
+            .map(|b| (b.first, b.length, b.zeros))
+            .collect();
+        assert_eq!(violations, [(125, 2, 2), (133, 2, 2)]);
+        assert!(blocks.iter().all(|b| !b.violates_rosser_rule()));
+        assert!(blocks.windows(2).all(|w| w[0].end == w[1].start));
+    }
+
+    #[test]
+    fn test_spacing_statistics() {
+        assert!((erf(0.5) - 0.5204998778).abs() < 2e-7);
+        assert!((erf(-1.5) + 0.9661051465).abs() < 2e-7);
+        assert_eq!(gue_spacing_cdf(0.0), 0.0);
+        assert!((gue_spacing_cdf(10.0) - 1.0).abs() < 1e-7);
+
+        let zeros = zeta_zeros(10.0, 1000.0, 0.05);
+        assert_eq!(zeros.len(), 649);
+        assert!((zeros[0] - 14.134725141734693).abs() < 1e-8);
+        let spacings = unfolded_zero_spacings(&zeros);
+        assert_eq!(ks_distance(&spacings, &spacings), 0.0);
+
+        let primes: BTreeSet<BigUint> =
+            primal::Primes::all().take(2000).map(BigUint::from).collect();
+        let comparison = compare_spacings(&zeros, &primes);
+        assert_eq!((comparison.zero_spacings, comparison.hit_gaps), (648, 1999));
+        // Zeros repel like GUE eigenvalues; prime gaps are closer to Poisson
+        assert!(comparison.zeros_vs_gue < 0.1, "{:?}", comparison);
+        assert!(comparison.hits_vs_gue > 0.2, "{:?}", comparison);
+        assert!(comparison.zeros_vs_hits > 0.2, "{:?}", comparison);
+        // Gaps between 2000-bit hits normalize just like small ones
+        let shifted: BTreeSet<BigUint> = primes.iter().map(|p| (p << 2000u32) + 1u32).collect();
+        let (small, large) = (normalized_hit_gaps(&primes), normalized_hit_gaps(&shifted));
+        assert!(small.iter().zip(&large).all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_zeta_cache() {
+        let mut cache = ZetaCache::new(2);
+        let first = cache.critical_line(14.13, 50).unwrap();
+        assert_eq!(first, zeta(Complex::new(0.5, 14.13), 50));
+        assert_eq!(cache.critical_line(14.13, 50).unwrap(), first);
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+        cache.critical_line(21.02, 50).unwrap();
+        cache.critical_line(14.13, 50).unwrap();
+        // 14.13 was used last, so 21.02 is evicted
+        cache.critical_line(25.01, 50).unwrap();
+        assert_eq!(cache.len(), 2);
+        cache.critical_line(14.13, 50).unwrap();
+        assert_eq!((cache.hits, cache.misses), (3, 3));
+        cache.critical_line(14.13, 60).unwrap();
+        assert_eq!(cache.misses, 4);
+
+        let path = std::env::temp_dir().join(format!("zeta-cache-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut cache = ZetaCache::open(&path, 16).unwrap();
+            for t in [1.5, 2.5, 3.5] {
+                cache.critical_line(t, 40).unwrap();
+            }
+        }
+        let mut reopened = ZetaCache::open(&path, 16).unwrap();
+        assert_eq!(reopened.len(), 3);
+        assert_eq!(reopened.critical_line(2.5, 40).unwrap(), zeta(Complex::new(0.5, 2.5), 40));
+        assert_eq!((reopened.hits, reopened.misses), (1, 0));
+        std::fs::remove_file(&path).unwrap();
+    }
+}